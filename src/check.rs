@@ -0,0 +1,265 @@
+//! Batch mode, `any_ls --check [--format text|sarif] FILE...`, reporting the
+//! diagnostics of files without an editor, e.g. in CI.
+
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity, Url};
+
+use crate::config::Config;
+use crate::handlers::{AnyHandler, DocumentContext};
+use crate::sarif;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// `path:line:column: severity: message`, one diagnostic per line.
+    Text,
+    /// A SARIF 2.1.0 log, for code scanning services.
+    Sarif,
+}
+
+#[derive(Debug)]
+pub struct FileDiagnostics {
+    /// The path as given on the command line.
+    pub path: PathBuf,
+    pub diagnostics: Vec<Diagnostic>,
+    /// Errors of the handlers that failed to check the file.
+    pub errors: Vec<String>,
+}
+
+/// The language id of files with extension `extension`, as editors send
+/// it, for extensions that differ from it.
+fn extension_filetype(extension: &str) -> Option<&'static str> {
+    let filetype = match extension {
+        "py" | "pyi" => "python",
+        "js" | "mjs" | "cjs" => "javascript",
+        "jsx" => "javascriptreact",
+        "ts" | "mts" | "cts" => "typescript",
+        "tsx" => "typescriptreact",
+        "yml" => "yaml",
+        "md" | "mkd" => "markdown",
+        "bash" => "sh",
+        "rs" => "rust",
+        "rb" => "ruby",
+        "pl" | "pm" => "perl",
+        "ex" | "exs" => "elixir",
+        "ml" | "mli" => "ocaml",
+        "rkt" => "racket",
+        "scm" | "ss" => "scheme",
+        "sc" => "scala",
+        "f90" | "f95" | "f03" | "f08" => "fortran-free-form",
+        "f" | "for" | "f77" => "fortran",
+        "rst" => "restructuredtext",
+        "sol" => "solidity",
+        "sv" | "svh" => "systemverilog",
+        "v" | "vh" => "verilog",
+        "bzl" | "star" => "starlark",
+        "gql" => "graphql",
+        "gradle" => "groovy",
+        "htm" => "html",
+        "jade" => "pug",
+        "vert" | "frag" | "comp" => "glsl",
+        "rest" => "http",
+        "service" | "socket" | "timer" | "mount" | "target" | "path" => "systemd",
+        "txt" => "plaintext",
+        _ => return None,
+    };
+    Some(filetype)
+}
+
+/// The filetype an editor would use for `path`, from its name or extension.
+fn filetype(path: &Path) -> String {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    match name.as_str() {
+        "justfile" | ".justfile" => "just".to_string(),
+        _ => {
+            let extension = path
+                .extension()
+                .map(|ext| ext.to_string_lossy().to_lowercase())
+                .unwrap_or_default();
+            extension_filetype(&extension)
+                .map(str::to_string)
+                .unwrap_or(extension)
+        }
+    }
+}
+
+/// Diagnostics of every file, failing if a file can't be read.
+async fn check(
+    handler: &mut AnyHandler,
+    paths: &[PathBuf],
+) -> Result<Vec<FileDiagnostics>, String> {
+    let mut files = Vec::new();
+    for path in paths {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("Could not read '{}': {e}", path.display()))?;
+        let uri = std::fs::canonicalize(path)
+            .ok()
+            .and_then(|path| Url::from_file_path(path).ok())
+            .ok_or_else(|| format!("Invalid path '{}'", path.display()))?;
+        let context = DocumentContext::new(uri, Vec::new());
+        let filetype = filetype(path);
+        if !handler.document_supported(&filetype, &context, &contents) {
+            eprintln!("No handler for '{}'", path.display());
+            continue;
+        }
+
+        let (diagnostics, errors) = match handler
+            .update_document_diagnostics(&filetype, &context, &contents)
+            .await
+        {
+            Ok(document) => (document.diagnostics, document.errors),
+            Err(err) => (Vec::new(), vec![err.to_string()]),
+        };
+        for err in &errors {
+            eprintln!("{}: {err}", path.display());
+        }
+        files.push(FileDiagnostics {
+            path: path.clone(),
+            diagnostics,
+            errors,
+        });
+    }
+    Ok(files)
+}
+
+fn print_text(files: &[FileDiagnostics]) {
+    for file in files {
+        for diagnostic in &file.diagnostics {
+            let severity = match diagnostic.severity {
+                Some(DiagnosticSeverity::ERROR) => "error",
+                Some(DiagnosticSeverity::INFORMATION) => "info",
+                Some(DiagnosticSeverity::HINT) => "hint",
+                _ => "warning",
+            };
+            println!(
+                "{}:{}:{}: {severity}: {}",
+                file.path.display(),
+                diagnostic.range.start.line + 1,
+                diagnostic.range.start.character + 1,
+                diagnostic.message
+            );
+        }
+    }
+}
+
+/// Runs the batch mode with the arguments following `--check`. Exits with 1
+/// if there are diagnostics, and 2 on usage errors or if a handler failed,
+/// as the files weren't fully checked.
+pub async fn run(mut args: impl Iterator<Item = String>) -> ExitCode {
+    let mut format = Format::Text;
+    let mut paths = Vec::new();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => match args.next().as_deref() {
+                Some("text") => format = Format::Text,
+                Some("sarif") => format = Format::Sarif,
+                other => {
+                    eprintln!("Unknown format: {}", other.unwrap_or_default());
+                    return ExitCode::from(2);
+                }
+            },
+            _ => paths.push(PathBuf::from(arg)),
+        }
+    }
+    if paths.is_empty() {
+        eprintln!("Usage: any_ls --check [--format text|sarif] FILE...");
+        return ExitCode::from(2);
+    }
+
+    let mut handler = AnyHandler::new(&Config::default());
+    let files = match check(&mut handler, &paths).await {
+        Ok(files) => files,
+        Err(err) => {
+            eprintln!("{err}");
+            return ExitCode::from(2);
+        }
+    };
+
+    match format {
+        Format::Text => print_text(&files),
+        Format::Sarif => println!("{:#}", sarif::report(&files)),
+    }
+    if files.iter().any(|file| !file.errors.is_empty()) {
+        ExitCode::from(2)
+    } else if files.iter().all(|file| file.diagnostics.is_empty()) {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check, filetype};
+    use crate::handlers::mock::Mock;
+    use crate::handlers::{AnyHandler, HandlerKind};
+    use crate::sarif;
+    use std::path::Path;
+    use tower_lsp::lsp_types::{Diagnostic, Position, Range};
+
+    #[test]
+    fn test_filetype() {
+        assert_eq!(filetype(Path::new("sub/Justfile")), "just");
+        assert_eq!(filetype(Path::new("build.just")), "just");
+        assert_eq!(filetype(Path::new("script.SH")), "sh");
+        assert_eq!(filetype(Path::new("src/main.py")), "python");
+        assert_eq!(filetype(Path::new("app.JS")), "javascript");
+        assert_eq!(filetype(Path::new("index.ts")), "typescript");
+        assert_eq!(filetype(Path::new(".github/ci.yml")), "yaml");
+        assert_eq!(filetype(Path::new("README.md")), "markdown");
+        assert_eq!(filetype(Path::new("docs/index.rst")), "restructuredtext");
+        assert_eq!(filetype(Path::new("data.toml")), "toml");
+        assert_eq!(filetype(Path::new("Makefile")), "");
+    }
+
+    #[tokio::test]
+    async fn test_check_sarif() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("justfile");
+        std::fs::write(&path, "default:\n  echo ok\n\na:::b\n").unwrap();
+
+        let mock = Mock {
+            filetypes: vec!["just"],
+            diagnostics: vec![Diagnostic::new_simple(
+                Range::new(Position::new(3, 1), Position::new(3, 2)),
+                "Unknown start of token".to_string(),
+            )],
+            ..Default::default()
+        };
+        let mut handler = AnyHandler::from_handlers(vec![HandlerKind::Mock(Box::new(mock))]);
+        let files = check(&mut handler, &[path]).await.unwrap();
+        let report = sarif::report(&files);
+        assert_eq!(report["version"], "2.1.0");
+        assert_eq!(report["runs"][0]["results"].as_array().unwrap().len(), 1);
+        assert_eq!(
+            report["runs"][0]["invocations"][0]["executionSuccessful"],
+            true
+        );
+    }
+
+    #[tokio::test]
+    async fn test_check_handler_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("justfile");
+        std::fs::write(&path, "default:\n  echo ok\n").unwrap();
+
+        let mock = Mock {
+            filetypes: vec!["just"],
+            timed_out: Some("just"),
+            ..Default::default()
+        };
+        let mut handler = AnyHandler::from_handlers(vec![HandlerKind::Mock(Box::new(mock))]);
+        let files = check(&mut handler, &[path]).await.unwrap();
+        assert_eq!(files[0].errors, ["'just' timed out"]);
+
+        let report = sarif::report(&files);
+        let invocation = &report["runs"][0]["invocations"][0];
+        assert_eq!(invocation["executionSuccessful"], false);
+        let notification = &invocation["toolExecutionNotifications"][0];
+        assert_eq!(notification["level"], "error");
+        assert_eq!(notification["message"]["text"], "'just' timed out");
+    }
+}
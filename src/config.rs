@@ -0,0 +1,232 @@
+use encoding_rs::Encoding;
+use globset::Glob;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use tower_lsp::lsp_types::DiagnosticSeverity;
+
+use crate::handlers::{
+    CsvConfig, GenericHandler, GenericHandlerConfig, JustConfig, TempFileStrategy, BUILTIN_HANDLERS,
+};
+
+/// Server settings, sent by the client as `initializationOptions`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Linters run as external commands, see `GenericHandlerConfig`.
+    pub generic: Vec<GenericHandlerConfig>,
+    /// `"per_request"` (default) or `"reuse"`, see `TempFileStrategy`.
+    pub temp_files: TempFileStrategy,
+    /// Names of handlers to turn off, e.g. `"Just"` or the `name` of a
+    /// generic handler.
+    pub disabled: Vec<String>,
+    /// Settings of the justfile handler, see `JustConfig`.
+    pub just: JustConfig,
+    /// Format documents before they are saved, for clients supporting
+    /// `textDocument/willSaveWaitUntil`.
+    pub format_on_save: bool,
+    /// Diagnostics published per document, further ones are summarized.
+    /// 1000 when unset.
+    pub max_diagnostics_per_document: Option<usize>,
+    /// Severities of diagnostics by `"<source>/<code>"`, e.g.
+    /// `{"eslint/no-console": "hint", "cspell/*": "off"}`. Keys are globs,
+    /// the longest matching key applies.
+    pub severity_overrides: HashMap<String, SeverityOverride>,
+    /// Parsed documents each handler keeps, e.g. justfile models, the least
+    /// recently used are parsed again. 32 when unset.
+    pub model_cache_capacity: Option<usize>,
+    /// Names of files or directories marking the root of a project, e.g.
+    /// `["Cargo.toml", "package.json"]`, where handlers stop searching
+    /// parent directories for settings files. `.git`, `.hg` and `.svn` when
+    /// unset.
+    pub root_markers: Option<Vec<String>>,
+    /// Encodings of the output of the tools of handlers by handler name,
+    /// e.g. `{"Nim": "latin1"}`, for tools not writing UTF-8. Labels are
+    /// those of the WHATWG Encoding Standard.
+    pub output_encoding: HashMap<String, String>,
+    /// Check the spelling of documents with codespell. Off by default as it
+    /// runs for documents of every filetype.
+    pub codespell: bool,
+    /// Other `language_id`s clients send for filetypes, e.g.
+    /// `{"tex": ["latex", "bibtex"]}`, added to the built-in ones.
+    pub filetype_aliases: HashMap<String, Vec<String>>,
+    /// Variables whose values hover shows masked, e.g. `["*_DSN"]`, for
+    /// secrets not to leak on shared screens. Globs matched ignoring case,
+    /// `"*KEY*"`, `"*SECRET*"`, `"*TOKEN*"` and `"*PASSWORD*"` when unset.
+    pub mask_secret_values: Option<Vec<String>>,
+    /// Settings of the CSV handler, see `CsvConfig`.
+    pub csv: CsvConfig,
+    /// Name of the handler formatting documents by filetype, e.g.
+    /// `{"javascript": "Biome"}`, for filetypes several handlers format.
+    /// The highest priority handler formats the others.
+    pub default_formatter: HashMap<String, String>,
+}
+
+/// What a `Config::severity_overrides` entry does with the diagnostics it
+/// matches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SeverityOverride {
+    Hint,
+    Info,
+    Warning,
+    Error,
+    /// Drops the diagnostics.
+    Off,
+}
+
+impl SeverityOverride {
+    /// The severity of matched diagnostics, `None` when they are dropped.
+    pub fn severity(self) -> Option<DiagnosticSeverity> {
+        match self {
+            SeverityOverride::Hint => Some(DiagnosticSeverity::HINT),
+            SeverityOverride::Info => Some(DiagnosticSeverity::INFORMATION),
+            SeverityOverride::Warning => Some(DiagnosticSeverity::WARNING),
+            SeverityOverride::Error => Some(DiagnosticSeverity::ERROR),
+            SeverityOverride::Off => None,
+        }
+    }
+}
+
+impl Config {
+    pub fn from_value(value: Option<Value>) -> Result<Self, String> {
+        match value {
+            None | Some(Value::Null) => Ok(Self::default()),
+            Some(value) => {
+                serde_json::from_value(value).map_err(|e| format!("Invalid settings: {e}"))
+            }
+        }
+    }
+
+    /// Problems with the settings that disable parts of them, e.g. invalid
+    /// patterns of generic handlers.
+    pub fn validate(&self) -> Vec<String> {
+        let generic = self
+            .generic
+            .iter()
+            .filter_map(|config| GenericHandler::new(config.clone()).err());
+        let overrides = self.severity_overrides.keys().filter_map(|pattern| {
+            Glob::new(pattern)
+                .err()
+                .map(|e| format!("Severity override '{pattern}': {e}"))
+        });
+        let encodings = self.output_encoding.iter().filter_map(|(name, label)| {
+            if !self.is_handler(name) {
+                Some(format!("Output encoding of unknown handler '{name}'"))
+            } else if Encoding::for_label(label.as_bytes()).is_none() {
+                Some(format!(
+                    "Handler '{name}': unknown output encoding '{label}'"
+                ))
+            } else {
+                None
+            }
+        });
+        let secret_keys = self
+            .mask_secret_values
+            .iter()
+            .flatten()
+            .filter_map(|pattern| {
+                Glob::new(pattern)
+                    .err()
+                    .map(|e| format!("Secret value pattern '{pattern}': {e}"))
+            });
+        let csv = [self.csv.delimiter, Some(self.csv.quote)]
+            .into_iter()
+            .flatten()
+            .filter(|c| !c.is_ascii())
+            .map(|c| format!("CSV delimiter and quote must be ASCII characters, not '{c}'"));
+        generic
+            .chain(overrides)
+            .chain(encodings)
+            .chain(secret_keys)
+            .chain(csv)
+            .collect()
+    }
+
+    /// Whether `name` is the name of a built-in or generic handler.
+    fn is_handler(&self, name: &str) -> bool {
+        BUILTIN_HANDLERS.contains(&name) || self.generic.iter().any(|config| config.name == name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Config, SeverityOverride};
+    use serde_json::json;
+
+    #[test]
+    fn test_validate() {
+        let config = Config::from_value(Some(json!({
+            "generic": [
+                {
+                    "name": "valid",
+                    "filetypes": ["txt"],
+                    "command": "lint",
+                    "pattern": "(?P<line>\\d+): (?P<message>.*)"
+                },
+                {
+                    "name": "invalid",
+                    "filetypes": ["txt"],
+                    "command": "lint",
+                    "pattern": "(?P<line>\\d+): .*"
+                }
+            ]
+        })))
+        .unwrap();
+
+        assert_eq!(config.generic.len(), 2);
+        assert_eq!(
+            config.validate(),
+            vec!["Handler 'invalid': `pattern` is missing the named capture group `(?P<message>...)`"]
+        );
+        assert!(Config::from_value(None).unwrap().generic.is_empty());
+
+        let config = Config::from_value(Some(json!({
+            "output_encoding": { "Nim": "latin1", "Ruff": "utf-9" }
+        })))
+        .unwrap();
+        assert_eq!(
+            config.validate(),
+            vec!["Handler 'Ruff': unknown output encoding 'utf-9'"]
+        );
+        let config = Config::from_value(Some(json!({
+            "generic": [
+                {
+                    "name": "vale",
+                    "filetypes": ["markdown"],
+                    "command": "vale",
+                    "pattern": "(?P<line>\\d+): (?P<message>.*)"
+                }
+            ],
+            "output_encoding": { "vale": "latin1", "ruff": "latin1" }
+        })))
+        .unwrap();
+        assert_eq!(
+            config.validate(),
+            vec!["Output encoding of unknown handler 'ruff'"]
+        );
+    }
+
+    #[test]
+    fn test_severity_overrides() {
+        let config = Config::from_value(Some(json!({
+            "severity_overrides": {
+                "eslint/no-console": "hint",
+                "cspell/*": "off",
+                "eslint/[": "info"
+            }
+        })))
+        .unwrap();
+
+        assert_eq!(
+            config.severity_overrides.get("cspell/*"),
+            Some(&SeverityOverride::Off)
+        );
+        assert_eq!(config.validate().len(), 1);
+        assert!(config.validate()[0].starts_with("Severity override 'eslint/['"));
+        assert!(Config::from_value(Some(json!({
+            "severity_overrides": { "eslint/*": "silent" }
+        })))
+        .is_err());
+    }
+}
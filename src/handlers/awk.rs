@@ -0,0 +1,141 @@
+use lazy_regex::regex_captures;
+use std::process::Command;
+use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity, Position, Range};
+
+use super::process::{output_with_timeout, probe, TempFileStrategy, TempFiles};
+use super::{Handler, HandlerError};
+
+/// AWK linting with `gawk --lint`.
+#[derive(Debug)]
+pub struct Awk {
+    temp_files: TempFiles,
+}
+
+impl Awk {
+    pub fn new() -> Result<Self, String> {
+        probe("gawk", &["--version"])?;
+        Ok(Self {
+            temp_files: TempFiles::with_suffix(".awk"),
+        })
+    }
+
+    /// Parses `gawk: file:line: warning: message` messages, and syntax
+    /// errors, which gawk reports as the source line followed by a caret
+    /// under the error: `gawk: file:line:     ^ syntax error`. Messages
+    /// without a line, e.g. about functions never called, are on the first
+    /// line.
+    pub fn parse(stderr: &str) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        for line in stderr.lines() {
+            let (line, column, severity, message) =
+                match regex_captures!(r#"^gawk: [^:]+:(\d+): (.*)$"#, line) {
+                    Some((_, number, rest)) => {
+                        let line = number.parse::<u32>().unwrap_or(1).saturating_sub(1);
+                        if let Some((_, severity, message)) =
+                            regex_captures!(r#"^(warning|error|fatal): (.*)$"#, rest)
+                        {
+                            (line, 0, severity, message)
+                        } else if let Some(caret) = rest.trim_start().strip_prefix('^') {
+                            let column = rest.len() - rest.trim_start().len();
+                            (line, column as u32, "error", caret.trim())
+                        } else {
+                            // The source line of a syntax error
+                            continue;
+                        }
+                    }
+                    None => match regex_captures!(r#"^gawk: (warning|error|fatal): (.*)$"#, line) {
+                        Some((_, severity, message)) => (0, 0, severity, message),
+                        None => continue,
+                    },
+                };
+            let severity = match severity {
+                "warning" => DiagnosticSeverity::WARNING,
+                _ => DiagnosticSeverity::ERROR,
+            };
+            let position = Position::new(line, column);
+            diagnostics.push(Diagnostic::new(
+                Range::new(position, position),
+                Some(severity),
+                None,
+                Some("gawk".to_string()),
+                message.to_string(),
+                None,
+                None,
+            ));
+        }
+        diagnostics
+    }
+}
+
+impl Handler for Awk {
+    fn filetype_supported(&self, filetype: &str) -> bool {
+        filetype == "awk"
+    }
+
+    fn set_temp_file_strategy(&mut self, strategy: TempFileStrategy) {
+        self.temp_files.set_strategy(strategy);
+    }
+
+    async fn update_diagnostics(
+        &mut self,
+        contents: &str,
+    ) -> Result<Vec<Diagnostic>, HandlerError> {
+        let temp_file = self.temp_files.write(contents)?;
+        // Pretty-printing parses the program without running it, which
+        // could have side effects, e.g. with `system()`
+        let output = output_with_timeout(
+            Command::new("gawk")
+                .arg("--lint")
+                .arg("--pretty-print=/dev/null")
+                .arg("-f")
+                .arg(temp_file.path()),
+        )?;
+        Ok(Self::parse(&String::from_utf8_lossy(&output.stderr)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Awk;
+    use tower_lsp::lsp_types::{DiagnosticSeverity, Position};
+
+    #[test]
+    fn test_parse_lint_warning() {
+        let stderr = "gawk: /tmp/.tmpXy12.awk:3: warning: statement has no effect\ngawk: warning: function `unused' defined but never called directly\n";
+        let diagnostics = Awk::parse(stderr);
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].range.start, Position::new(2, 0));
+        assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::WARNING));
+        assert_eq!(diagnostics[0].message, "statement has no effect");
+        assert_eq!(diagnostics[1].range.start, Position::new(0, 0));
+        assert_eq!(
+            diagnostics[1].message,
+            "function `unused' defined but never called directly"
+        );
+    }
+
+    #[test]
+    fn test_parse_uninitialized_variable() {
+        // `gawk --lint` on a program printing `total`, never set, on line 4
+        let stderr =
+            "gawk: /tmp/.tmpXy12.awk:4: warning: reference to uninitialized variable `total'\n";
+        let diagnostics = Awk::parse(stderr);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].range.start, Position::new(3, 0));
+        assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::WARNING));
+        assert_eq!(
+            diagnostics[0].message,
+            "reference to uninitialized variable `total'"
+        );
+    }
+
+    #[test]
+    fn test_parse_syntax_error() {
+        let stderr = "gawk: /tmp/.tmpXy12.awk:2: { print ( }\ngawk: /tmp/.tmpXy12.awk:2:           ^ syntax error\n";
+        let diagnostics = Awk::parse(stderr);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].range.start, Position::new(1, 10));
+        assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::ERROR));
+        assert_eq!(diagnostics[0].message, "syntax error");
+    }
+}
@@ -0,0 +1,110 @@
+use lazy_regex::regex_captures;
+use tower_lsp::lsp_types::{self, Diagnostic, DiagnosticSeverity, Position};
+
+use super::process::{output_with_timeout, probe, TempFileStrategy, TempFiles};
+use super::{Handler, HandlerError};
+
+/// Syntax checking with `bash -n`, a fallback for when shellcheck isn't
+/// installed.
+#[derive(Debug)]
+pub struct BashN {
+    temp_files: TempFiles,
+}
+
+impl BashN {
+    pub fn new() -> Result<Self, String> {
+        probe("bash", &["--version"])?;
+        Ok(Self {
+            temp_files: TempFiles::with_suffix(".sh"),
+        })
+    }
+
+    pub fn parse_stderr(contents: &str) -> Vec<Diagnostic> {
+        contents
+            .lines()
+            .filter_map(|line| {
+                let (_, line, message) = regex_captures!(r#"^.*: line (\d+): (.*)$"#, line)?;
+                // The offending source line is echoed after the error
+                if message.starts_with('`') {
+                    return None;
+                }
+                // Bash reports 1-based lines
+                let line = line.parse::<u32>().unwrap_or(1).saturating_sub(1);
+
+                Some(Diagnostic::new(
+                    lsp_types::Range {
+                        start: Position::new(line, 0),
+                        end: Position::new(line, 0),
+                    },
+                    Some(DiagnosticSeverity::ERROR),
+                    None,
+                    Some("bash".to_string()),
+                    message.to_string(),
+                    None,
+                    None,
+                ))
+            })
+            .collect()
+    }
+}
+
+impl Handler for BashN {
+    fn filetype_supported(&self, filetype: &str) -> bool {
+        matches!(filetype, "sh" | "bash")
+    }
+
+    fn priority(&self) -> i32 {
+        // Below dedicated shell linters such as shellcheck, whose errors are
+        // kept over the same ones of `bash -n`
+        -10
+    }
+
+    fn set_temp_file_strategy(&mut self, strategy: TempFileStrategy) {
+        self.temp_files.set_strategy(strategy);
+    }
+
+    async fn update_diagnostics(
+        &mut self,
+        contents: &str,
+    ) -> Result<Vec<Diagnostic>, HandlerError> {
+        let temp_file = self.temp_files.write(contents)?;
+
+        let out = output_with_timeout(
+            std::process::Command::new("bash")
+                .arg("-n")
+                .arg(temp_file.path()),
+        )?;
+
+        Ok(Self::parse_stderr(&String::from_utf8_lossy(&out.stderr)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BashN;
+
+    #[test]
+    fn test_parse_unexpected_eof() {
+        let stderr = "/tmp/.tmpAbC123: line 3: syntax error: unexpected end of file\n";
+        let diagnostics = BashN::parse_stderr(stderr);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].range.start.line, 2);
+        assert_eq!(
+            diagnostics[0].message,
+            "syntax error: unexpected end of file"
+        );
+    }
+
+    #[test]
+    fn test_parse_skips_echoed_line() {
+        let stderr = "/tmp/.tmpAbC123: line 1: syntax error near unexpected token `newline'
+/tmp/.tmpAbC123: line 1: `echo ('
+";
+        let diagnostics = BashN::parse_stderr(stderr);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].message,
+            "syntax error near unexpected token `newline'"
+        );
+    }
+}
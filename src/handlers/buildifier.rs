@@ -0,0 +1,188 @@
+use serde::Deserialize;
+use std::path::Path;
+use std::process::Command;
+use tower_lsp::lsp_types::{
+    self, Diagnostic, DiagnosticSeverity, NumberOrString, OneOf, Position, ServerCapabilities,
+};
+
+use super::process::{format_stdin, probe, run_with_stdin};
+use super::{Handler, HandlerError};
+
+/// Linting and formatting of Starlark files, like Bazel `BUILD` files, with
+/// buildifier.
+#[derive(Debug)]
+pub struct Buildifier {}
+
+#[derive(Debug, Deserialize)]
+struct Location {
+    line: u32,
+    column: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct Warning {
+    start: Location,
+    end: Location,
+    category: String,
+    message: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct File {
+    #[serde(default)]
+    warnings: Vec<Warning>,
+}
+
+/// The report of `buildifier -format=json`.
+#[derive(Debug, Deserialize)]
+struct Report {
+    #[serde(default)]
+    files: Vec<File>,
+}
+
+impl Buildifier {
+    pub fn new() -> Result<Self, String> {
+        probe("buildifier", &["--version"])?;
+        Ok(Self {})
+    }
+
+    pub fn parse_stdout(contents: &str) -> Result<Vec<Diagnostic>, HandlerError> {
+        let report: Report = serde_json::from_str(contents)
+            .map_err(|e| HandlerError::Parse(format!("Invalid buildifier output: {e}")))?;
+        // buildifier reports 1-based positions
+        let position = |location: &Location| {
+            Position::new(
+                location.line.saturating_sub(1),
+                location.column.saturating_sub(1),
+            )
+        };
+        Ok(report
+            .files
+            .into_iter()
+            .flat_map(|file| file.warnings)
+            .map(|warning| {
+                Diagnostic::new(
+                    lsp_types::Range {
+                        start: position(&warning.start),
+                        end: position(&warning.end),
+                    },
+                    Some(DiagnosticSeverity::WARNING),
+                    Some(NumberOrString::String(warning.category)),
+                    Some("buildifier".to_string()),
+                    warning.message,
+                    None,
+                    None,
+                )
+            })
+            .collect())
+    }
+}
+
+impl Handler for Buildifier {
+    fn filetype_supported(&self, filetype: &str) -> bool {
+        matches!(filetype, "starlark" | "bazel" | "bzl")
+    }
+
+    fn path_supported(&self, path: &Path) -> bool {
+        path.file_name().is_some_and(|name| {
+            matches!(
+                name.to_str(),
+                Some("BUILD" | "BUILD.bazel" | "WORKSPACE" | "WORKSPACE.bazel")
+            )
+        })
+    }
+
+    fn get_capabilities(&self) -> ServerCapabilities {
+        ServerCapabilities {
+            document_formatting_provider: Some(OneOf::Left(true)),
+            ..Default::default()
+        }
+    }
+
+    async fn update_diagnostics(
+        &mut self,
+        contents: &str,
+    ) -> Result<Vec<Diagnostic>, HandlerError> {
+        let out = run_with_stdin(
+            Command::new("buildifier")
+                .arg("-mode=check")
+                .arg("-lint=warn")
+                .arg("-format=json")
+                .arg("-"),
+            contents,
+        )?;
+        // Exits with 4 when there are warnings
+        if out.stdout.is_empty() {
+            return Err(HandlerError::Log(
+                String::from_utf8_lossy(&out.stderr).into_owned(),
+            ));
+        }
+        Self::parse_stdout(&String::from_utf8_lossy(&out.stdout))
+    }
+
+    async fn format(
+        &mut self,
+        _filetype: &str,
+        contents: &str,
+    ) -> Result<Option<String>, HandlerError> {
+        format_stdin(
+            Command::new("buildifier").arg("-mode=fix").arg("-"),
+            contents,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Buildifier;
+    use crate::handlers::Handler;
+    use tower_lsp::lsp_types::{NumberOrString, Position};
+
+    #[test]
+    fn test_parse_load_order() {
+        let stdout = r#"{
+  "success": false,
+  "files": [
+    {
+      "filename": "<stdin>",
+      "formatted": true,
+      "valid": true,
+      "warnings": [
+        {
+          "start": { "line": 2, "column": 1 },
+          "end": { "line": 2, "column": 46 },
+          "category": "out-of-order-load",
+          "actionable": true,
+          "autoFixable": true,
+          "message": "Load statement is out of its lexicographical order.",
+          "url": "https://github.com/bazelbuild/buildtools/blob/main/WARNINGS.md#out-of-order-load"
+        }
+      ]
+    }
+  ]
+}"#;
+        let diagnostics = Buildifier::parse_stdout(stdout).ok().unwrap();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].range.start, Position::new(1, 0));
+        assert_eq!(diagnostics[0].range.end, Position::new(1, 45));
+        assert_eq!(
+            diagnostics[0].code,
+            Some(NumberOrString::String("out-of-order-load".to_string()))
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "needs buildifier"]
+    async fn test_format() {
+        let mut buildifier = Buildifier::new().unwrap();
+
+        let contents = "cc_library(name='lib',srcs=['b.cc','a.cc'])\n";
+        let formatted = buildifier
+            .format("bazel", contents)
+            .await
+            .ok()
+            .unwrap()
+            .unwrap();
+        assert!(formatted.starts_with("cc_library(\n    name = \"lib\",\n"));
+    }
+}
@@ -0,0 +1,112 @@
+use lru::LruCache;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex};
+
+/// Models parsed from documents kept when no setting changes it.
+pub const DEFAULT_MODEL_CACHE_CAPACITY: usize = 32;
+
+/// Parsed models of documents by the hash of their contents, dropping the
+/// least recently used ones beyond a capacity.
+///
+/// Every request for an open document looks its model up, so the models
+/// dropped first are those of closed documents and of earlier contents of
+/// open ones.
+#[derive(Debug)]
+pub struct ModelCache<T> {
+    models: Mutex<LruCache<u64, Arc<T>>>,
+}
+
+fn capacity(capacity: usize) -> NonZeroUsize {
+    NonZeroUsize::new(capacity).unwrap_or(NonZeroUsize::MIN)
+}
+
+impl<T> ModelCache<T> {
+    /// A cache of at least one model.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            models: Mutex::new(LruCache::new(self::capacity(capacity))),
+        }
+    }
+
+    /// Changes the number of models kept, dropping the least recently used
+    /// ones beyond it.
+    pub fn resize(&self, capacity: usize) {
+        self.models
+            .lock()
+            .expect("Lock is not poisoned")
+            .resize(self::capacity(capacity));
+    }
+
+    /// The model of `contents` when it isn't cached, patched with `patch`
+    /// from the most recently used model, or else parsed with `parse`.
+    /// Documents are synced whole, so after a small edit of a document the
+    /// most recently used model is that of its previous contents, and
+    /// patching it is cheaper than parsing everything again.
+    pub fn get_or_patch(
+        &self,
+        contents: &str,
+        parse: impl FnOnce(&str) -> T,
+        patch: impl FnOnce(&T, &str) -> Option<T>,
+    ) -> Arc<T> {
+        let mut hasher = DefaultHasher::new();
+        contents.hash(&mut hasher);
+        let hash = hasher.finish();
+
+        let mut models = self.models.lock().expect("Lock is not poisoned");
+        if let Some(model) = models.get(&hash) {
+            return model.clone();
+        }
+        let model = models
+            .iter()
+            .next()
+            .and_then(|(_, latest)| patch(latest, contents))
+            .unwrap_or_else(|| parse(contents));
+        models.get_or_insert(hash, || Arc::new(model)).clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ModelCache;
+    use std::sync::Arc;
+
+    fn get_or_parse(cache: &ModelCache<String>, contents: &str) -> Arc<String> {
+        cache.get_or_patch(contents, str::to_uppercase, |_, _| None)
+    }
+
+    #[test]
+    fn test_patches_latest_model() {
+        let cache = ModelCache::new(2);
+        get_or_parse(&cache, "a");
+        let patch = |latest: &String, contents: &str| Some(format!("{latest}+{contents}"));
+        assert_eq!(*cache.get_or_patch("b", str::to_uppercase, patch), "A+b");
+        // Cached models aren't patched again
+        assert_eq!(*cache.get_or_patch("b", str::to_uppercase, patch), "A+b");
+        assert_eq!(
+            *cache.get_or_patch("c", str::to_uppercase, |_, _| None),
+            "C"
+        );
+    }
+
+    #[test]
+    fn test_evicts_least_recently_used() {
+        let cache = ModelCache::new(2);
+        let first = get_or_parse(&cache, "a");
+        let second = get_or_parse(&cache, "b");
+        // Touching the first document keeps it over the second
+        assert!(Arc::ptr_eq(&first, &get_or_parse(&cache, "a")));
+
+        get_or_parse(&cache, "c");
+        assert!(Arc::ptr_eq(&first, &get_or_parse(&cache, "a")));
+        let parsed_again = get_or_parse(&cache, "b");
+        assert!(!Arc::ptr_eq(&second, &parsed_again));
+        assert_eq!(*parsed_again, "B");
+
+        cache.resize(0);
+        get_or_parse(&cache, "d");
+        let models = cache.models.lock().unwrap();
+        assert_eq!(models.len(), 1);
+    }
+}
@@ -0,0 +1,579 @@
+use globset::GlobBuilder;
+use lazy_regex::regex;
+use serde::Deserialize;
+use std::ops::Range;
+use std::path::{Component, Path, PathBuf};
+use toml::Spanned;
+use tower_lsp::lsp_types::{self, Diagnostic, DiagnosticSeverity, Position};
+
+use super::text::{closest, offset_to_position};
+use super::{DocumentContext, Handler, HandlerError};
+
+/// Warns about unknown keys in `Cargo.toml`, e.g. `[depdencies]`, checked
+/// against the tables and keys documented in the Cargo reference. Tables
+/// with arbitrary keys, like `[features]` or `[package.metadata]`, aren't
+/// checked. Also warns about workspace members that match no package.
+#[derive(Debug)]
+pub struct CargoToml {}
+
+const TABLES: &[&str] = &[
+    "cargo-features",
+    "package",
+    "project",
+    "lib",
+    "bin",
+    "example",
+    "test",
+    "bench",
+    "dependencies",
+    "dev-dependencies",
+    "build-dependencies",
+    "target",
+    "badges",
+    "features",
+    "lints",
+    "patch",
+    "replace",
+    "profile",
+    "workspace",
+];
+
+const PACKAGE: &[&str] = &[
+    "name",
+    "version",
+    "authors",
+    "edition",
+    "rust-version",
+    "description",
+    "documentation",
+    "readme",
+    "homepage",
+    "repository",
+    "license",
+    "license-file",
+    "keywords",
+    "categories",
+    "workspace",
+    "build",
+    "links",
+    "exclude",
+    "include",
+    "publish",
+    "metadata",
+    "default-run",
+    "autolib",
+    "autobins",
+    "autoexamples",
+    "autotests",
+    "autobenches",
+    "resolver",
+];
+
+const TARGET: &[&str] = &[
+    "name",
+    "path",
+    "test",
+    "doctest",
+    "bench",
+    "doc",
+    "plugin",
+    "proc-macro",
+    "harness",
+    "edition",
+    "crate-type",
+    "required-features",
+];
+
+const DEPENDENCY_TABLES: &[&str] = &["dependencies", "dev-dependencies", "build-dependencies"];
+
+const DEPENDENCY: &[&str] = &[
+    "version",
+    "path",
+    "git",
+    "branch",
+    "tag",
+    "rev",
+    "features",
+    "optional",
+    "default-features",
+    "package",
+    "registry",
+    "workspace",
+    "public",
+    "artifact",
+    "lib",
+    "target",
+];
+
+const WORKSPACE: &[&str] = &[
+    "members",
+    "exclude",
+    "default-members",
+    "resolver",
+    "package",
+    "dependencies",
+    "lints",
+    "metadata",
+];
+
+const PROFILE: &[&str] = &[
+    "opt-level",
+    "debug",
+    "split-debuginfo",
+    "strip",
+    "debug-assertions",
+    "overflow-checks",
+    "lto",
+    "panic",
+    "incremental",
+    "codegen-units",
+    "rpath",
+    "inherits",
+    "build-override",
+    "package",
+];
+
+/// The `[workspace]` of a manifest, with the spans of its paths.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Manifest {
+    workspace: Option<Workspace>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Workspace {
+    members: Vec<Spanned<String>>,
+    exclude: Vec<Spanned<String>>,
+}
+
+fn is_glob(component: &str) -> bool {
+    component.contains(['*', '?', '[', '{'])
+}
+
+/// The directories below `directory` matching the remaining `components`
+/// of a member pattern, like the `glob` crate Cargo resolves them with.
+/// Hidden directories and `target` aren't searched by `**`.
+fn matching_directories(directory: &Path, components: &[&str], found: &mut Vec<PathBuf>) {
+    let Some((component, rest)) = components.split_first() else {
+        found.push(directory.to_path_buf());
+        return;
+    };
+    if !is_glob(component) {
+        let path = directory.join(component);
+        if path.is_dir() {
+            matching_directories(&path, rest, found);
+        }
+        return;
+    }
+    let Ok(entries) = std::fs::read_dir(directory) else {
+        return;
+    };
+    let matcher = GlobBuilder::new(component)
+        .literal_separator(true)
+        .build()
+        .map(|glob| glob.compile_matcher());
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if !path.is_dir() {
+            continue;
+        }
+        if *component == "**" {
+            if !name.starts_with('.') && name != "target" {
+                matching_directories(&path, components, found);
+            }
+        } else if matcher
+            .as_ref()
+            .is_ok_and(|matcher| matcher.is_match(&name))
+        {
+            matching_directories(&path, rest, found);
+        }
+    }
+    if *component == "**" {
+        matching_directories(directory, rest, found);
+    }
+}
+
+/// The path of `pattern` up to its first glob, without `.` components.
+fn literal_prefix(pattern: &str) -> PathBuf {
+    Path::new(pattern)
+        .components()
+        .filter(|component| *component != Component::CurDir)
+        .take_while(|component| !is_glob(&component.as_os_str().to_string_lossy()))
+        .collect()
+}
+
+/// Warnings about the members of the workspace of `contents` that match no
+/// directory with a `Cargo.toml` in `directory`, the directory of the
+/// manifest, and about members that are also excluded. Excluding some of
+/// the directories a glob matches is what `exclude` is for, so only
+/// members within an excluded path are reported. Cargo doesn't expand
+/// globs in `exclude`, nothing is excluded by them.
+fn check_workspace(contents: &str, directory: &Path) -> Vec<Diagnostic> {
+    let Some(workspace) = toml::from_str::<Manifest>(contents)
+        .ok()
+        .and_then(|manifest| manifest.workspace)
+    else {
+        return Vec::new();
+    };
+    let warning = |span: Range<usize>, message: String| {
+        Diagnostic::new(
+            lsp_types::Range::new(
+                offset_to_position(contents, span.start),
+                offset_to_position(contents, span.end),
+            ),
+            Some(DiagnosticSeverity::WARNING),
+            None,
+            Some("cargo-toml".to_string()),
+            message,
+            None,
+            None,
+        )
+    };
+
+    let mut diagnostics = Vec::new();
+    for member in &workspace.members {
+        let pattern = member.get_ref();
+        let prefix = literal_prefix(pattern);
+        if let Some(exclude) = workspace.exclude.iter().find(|exclude| {
+            let excluded = literal_prefix(exclude.get_ref());
+            !is_glob(exclude.get_ref())
+                && excluded.components().next().is_some()
+                && prefix.starts_with(excluded)
+        }) {
+            diagnostics.push(warning(
+                member.span(),
+                format!("`{pattern}` is also excluded by `{}`", exclude.get_ref()),
+            ));
+            continue;
+        }
+
+        let components: Vec<&str> = pattern
+            .split(['/', '\\'])
+            .filter(|component| !component.is_empty() && *component != ".")
+            .collect();
+        let mut found = Vec::new();
+        matching_directories(directory, &components, &mut found);
+        if !found.iter().any(|path| path.join("Cargo.toml").is_file()) {
+            diagnostics.push(warning(
+                member.span(),
+                format!("`{pattern}` matches no directory with a `Cargo.toml`"),
+            ));
+        }
+    }
+    diagnostics
+}
+
+/// Index of the first unknown segment of the dotted key `path`, with the
+/// keys allowed there.
+fn unknown_segment(path: &[&str]) -> Option<(usize, &'static [&'static str])> {
+    let known = |index: usize, keys: &'static [&'static str]| {
+        (!keys.contains(&path[index])).then_some((index, keys))
+    };
+    // Keys of `[dependencies.name]` tables, the name is at `offset`
+    let dependency = |offset: usize| {
+        if path.len() > offset + 1 {
+            known(offset + 1, DEPENDENCY)
+        } else {
+            None
+        }
+    };
+    match path {
+        [] => None,
+        [top, ..] if !TABLES.contains(top) => Some((0, TABLES)),
+        ["package" | "project", _, ..] => known(1, PACKAGE),
+        ["lib" | "bin" | "example" | "test" | "bench", _, ..] => known(1, TARGET),
+        [table, ..] if DEPENDENCY_TABLES.contains(table) => dependency(1),
+        ["target", _, _, ..] => known(2, DEPENDENCY_TABLES).or_else(|| dependency(3)),
+        ["workspace", "dependencies", ..] => dependency(2),
+        ["workspace", "package", _, ..] => known(2, PACKAGE),
+        ["workspace", _, ..] => known(1, WORKSPACE),
+        ["profile", _, "package", _, _, ..] => known(4, PROFILE),
+        ["profile", _, "build-override", _, ..] => known(3, PROFILE),
+        ["profile", _, _, ..] => known(2, PROFILE),
+        ["patch", _, ..] => dependency(2),
+        ["replace", ..] => dependency(1),
+        _ => None,
+    }
+}
+
+/// The segments of the dotted key starting at byte `start` of `line`, with
+/// their byte ranges. Quoted segments are unquoted.
+fn key_segments(line: &str, start: usize) -> Vec<(String, Range<usize>)> {
+    let mut segments = Vec::new();
+    let mut offset = start;
+    loop {
+        let rest = &line[offset..];
+        let skipped = rest.len() - rest.trim_start().len();
+        offset += skipped;
+        let rest = &line[offset..];
+        let (name, len) = match rest.chars().next() {
+            Some(quote @ ('"' | '\'')) => match rest[1..].find(quote) {
+                Some(end) => (rest[1..end + 1].to_string(), end + 2),
+                None => break,
+            },
+            _ => match regex!(r#"^[A-Za-z0-9_-]+"#).find(rest) {
+                Some(bare) => (bare.as_str().to_string(), bare.end()),
+                None => break,
+            },
+        };
+        segments.push((name, offset..offset + len));
+        offset += len;
+        let rest = &line[offset..];
+        match rest.trim_start().strip_prefix('.') {
+            Some(after) => offset = line.len() - after.len(),
+            None => break,
+        }
+    }
+    segments
+}
+
+impl CargoToml {
+    pub fn new() -> Result<Self, String> {
+        Ok(Self {})
+    }
+
+    /// Checks the key made of `table` and `key`, reporting unknown segments
+    /// of `key` only, those of `table` are reported at the header.
+    fn check(
+        line: u32,
+        text: &str,
+        table: &[String],
+        key: &[(String, Range<usize>)],
+        diagnostics: &mut Vec<Diagnostic>,
+    ) {
+        let path: Vec<&str> = table
+            .iter()
+            .map(String::as_str)
+            .chain(key.iter().map(|(name, _)| name.as_str()))
+            .collect();
+        let Some((index, keys)) = unknown_segment(&path) else {
+            return;
+        };
+        let Some((name, range)) = index.checked_sub(table.len()).map(|index| &key[index]) else {
+            return;
+        };
+
+        let message = match closest(name, keys) {
+            Some(suggestion) => format!("Unknown key '{name}', did you mean '{suggestion}'?"),
+            None => format!("Unknown key '{name}'"),
+        };
+        let character = |offset: usize| text[..offset].encode_utf16().count() as u32;
+        diagnostics.push(Diagnostic::new(
+            lsp_types::Range {
+                start: Position::new(line, character(range.start)),
+                end: Position::new(line, character(range.end)),
+            },
+            Some(DiagnosticSeverity::WARNING),
+            None,
+            Some("cargo-toml".to_string()),
+            message,
+            None,
+            None,
+        ));
+    }
+
+    pub fn parse(contents: &str) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        let mut table: Vec<String> = Vec::new();
+        let mut multiline_string: Option<&str> = None;
+        for (line, text) in contents.lines().enumerate() {
+            let line = line as u32;
+            if let Some(delimiter) = multiline_string {
+                if text.matches(delimiter).count() % 2 == 1 {
+                    multiline_string = None;
+                }
+                continue;
+            }
+
+            let trimmed = text.trim_start();
+            if let Some(header) = trimmed.strip_prefix('[') {
+                let start = text.len() - header.trim_start_matches('[').len();
+                let key = key_segments(text, start);
+                Self::check(line, text, &[], &key, &mut diagnostics);
+                table = key.into_iter().map(|(name, _)| name).collect();
+            } else if regex!(r#"^[A-Za-z0-9_"'-]"#).is_match(trimmed) {
+                let key = key_segments(text, text.len() - trimmed.len());
+                let Some((_, last)) = key.last() else {
+                    continue;
+                };
+                // Lines of multi-line arrays aren't keys
+                let Some(value) = text[last.end..].trim_start().strip_prefix('=') else {
+                    continue;
+                };
+                let value = value.trim();
+                if value.starts_with('{') {
+                    // Keys of inline tables, e.g. `serde = { version = "1" }`
+                    for inline in regex!(r#"[{,]\s*([A-Za-z0-9_-]+)\s*="#).captures_iter(value) {
+                        let name = inline.get(1).expect("Group 1 always matches");
+                        let start = text.len() - value.len() + name.start();
+                        let mut key = key.clone();
+                        key.push((name.as_str().to_string(), start..start + name.len()));
+                        Self::check(line, text, &table, &key, &mut diagnostics);
+                    }
+                } else {
+                    Self::check(line, text, &table, &key, &mut diagnostics);
+                }
+                for delimiter in ["\"\"\"", "'''"] {
+                    if value.matches(delimiter).count() % 2 == 1 {
+                        multiline_string = Some(delimiter);
+                    }
+                }
+            }
+        }
+        diagnostics
+    }
+}
+
+impl Handler for CargoToml {
+    fn filetype_supported(&self, _filetype: &str) -> bool {
+        false
+    }
+
+    fn path_supported(&self, path: &Path) -> bool {
+        path.file_name().is_some_and(|name| name == "Cargo.toml")
+    }
+
+    async fn update_diagnostics_with_context(
+        &mut self,
+        context: &DocumentContext,
+        contents: &str,
+    ) -> Result<Vec<Diagnostic>, HandlerError> {
+        let mut diagnostics = Self::parse(contents);
+        if let Some(directory) = context.directory() {
+            diagnostics.extend(check_workspace(contents, &directory));
+        }
+        Ok(diagnostics)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_workspace, CargoToml};
+    use tower_lsp::lsp_types::{Position, Range};
+
+    #[test]
+    fn test_misspelled_keys() {
+        let contents = r#"[package]
+name = "any_ls"
+verison = "0.1.0"
+
+[depdencies]
+serde = { version = "1.0", featurs = ["derive"] }
+
+[dependencies.tokio]
+version = "1"
+default-feature = false
+
+[profile.release]
+opt-level = 3
+"#;
+        let diagnostics = CargoToml::parse(contents);
+        let messages: Vec<&str> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "Unknown key 'verison', did you mean 'version'?",
+                "Unknown key 'depdencies', did you mean 'dependencies'?",
+                "Unknown key 'default-feature', did you mean 'default-features'?",
+            ]
+        );
+        assert_eq!(diagnostics[1].range.start, Position::new(4, 1));
+        assert_eq!(diagnostics[1].range.end, Position::new(4, 11));
+    }
+
+    #[test]
+    fn test_valid() {
+        let contents = r#"cargo-features = ["edition2024"]
+
+[package]
+name = "any_ls"
+description = """
+Not = a key
+"""
+metadata.docs.rs.all-features = true
+
+[[bin]]
+name = "any_ls"
+
+[dependencies]
+serde = { version = "1.0", features = ["derive"] }
+tower-lsp = "0.20.0"
+
+[target.'cfg(unix)'.dependencies]
+libc = { version = "0.2", default-features = false }
+
+[features]
+anything = []
+
+[workspace]
+members = [
+    "crates/*",
+]
+
+[profile.dev.package."*"]
+opt-level = 2
+"#;
+        assert!(CargoToml::parse(contents).is_empty());
+    }
+
+    #[test]
+    fn test_member_matching_nothing() {
+        let dir = tempfile::tempdir().unwrap();
+        for package in ["crates/core", "crates/cli", "tools/gen"] {
+            std::fs::create_dir_all(dir.path().join(package)).unwrap();
+            std::fs::write(dir.path().join(package).join("Cargo.toml"), "").unwrap();
+        }
+        // A directory without a package
+        std::fs::create_dir_all(dir.path().join("plugins/empty")).unwrap();
+        let contents = r#"[workspace]
+members = [
+    "crates/*",
+    "./tools/gen",
+    "plugins/*",
+    "examples/**",
+]
+"#;
+        let diagnostics = check_workspace(contents, dir.path());
+        let messages: Vec<&str> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "`plugins/*` matches no directory with a `Cargo.toml`",
+                "`examples/**` matches no directory with a `Cargo.toml`",
+            ]
+        );
+        assert_eq!(
+            diagnostics[0].range,
+            Range::new(Position::new(4, 4), Position::new(4, 15))
+        );
+    }
+
+    #[test]
+    fn test_member_excluded() {
+        let dir = tempfile::tempdir().unwrap();
+        for package in ["crates/core", "crates/old", "legacy/app"] {
+            std::fs::create_dir_all(dir.path().join(package)).unwrap();
+            std::fs::write(dir.path().join(package).join("Cargo.toml"), "").unwrap();
+        }
+        let contents = r#"[workspace]
+members = ["crates/*", "legacy/app"]
+exclude = ["crates/old", "./legacy"]
+"#;
+        let diagnostics = check_workspace(contents, dir.path());
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].message,
+            "`legacy/app` is also excluded by `./legacy`"
+        );
+        assert_eq!(diagnostics[0].range.start, Position::new(1, 23));
+
+        // Globs and the workspace itself exclude nothing
+        let contents = r#"[workspace]
+members = ["crates/*", "legacy/app"]
+exclude = ["*", "crates/*", "."]
+"#;
+        assert!(check_workspace(contents, dir.path()).is_empty());
+    }
+}
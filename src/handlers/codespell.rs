@@ -0,0 +1,170 @@
+use lazy_regex::regex_captures;
+use std::collections::HashMap;
+use std::process::Command;
+use tower_lsp::lsp_types::{
+    self, CodeAction, CodeActionProviderCapability, Diagnostic, DiagnosticSeverity, Position,
+    ServerCapabilities,
+};
+
+use super::process::{probe, run_with_stdin, strip_ansi};
+use super::spelling::{replace_actions, with_corrections};
+use super::{DocumentContext, Handler, HandlerError};
+
+/// Spell checking of documents of any filetype with codespell, enabled by
+/// `Config::codespell`.
+#[derive(Debug)]
+pub struct Codespell {}
+
+/// Byte offset of the `nth` occurrence of `word` in `line` as a whole word.
+fn find_word(line: &str, word: &str, nth: usize) -> Option<usize> {
+    let is_word_char = |c: Option<char>| c.is_some_and(|c| c.is_alphanumeric() || c == '_');
+    line.match_indices(word)
+        .map(|(start, _)| start)
+        .filter(|start| {
+            !is_word_char(line[..*start].chars().next_back())
+                && !is_word_char(line[start + word.len()..].chars().next())
+        })
+        .nth(nth)
+}
+
+impl Codespell {
+    pub fn new() -> Result<Self, String> {
+        probe("codespell", &["--version"])?;
+        Ok(Self {})
+    }
+
+    /// Parses the output of `codespell -`, which for stdin prints the
+    /// 1-based line number with the line, and the misspelling on the next:
+    ///
+    /// ```text
+    /// 3: I saw teh cat
+    ///     teh ==> the
+    /// ```
+    ///
+    /// The column isn't printed, the word is looked up in `contents`.
+    pub fn parse(stdout: &str, contents: &str) -> Vec<Diagnostic> {
+        let lines: Vec<&str> = contents.lines().collect();
+        // Misspellings seen per line and word, a word misspelled twice on a
+        // line is reported twice
+        let mut seen: HashMap<(u32, &str), usize> = HashMap::new();
+        let mut line = None;
+        let mut diagnostics = Vec::new();
+        for output in stdout.lines() {
+            if let Some((_, number)) = regex_captures!(r#"^(\d+): "#, output) {
+                line = number
+                    .parse::<u32>()
+                    .ok()
+                    .map(|line| line.saturating_sub(1));
+                continue;
+            }
+            let Some((_, word, corrections, reason)) =
+                regex_captures!(r#"^\s+(\S+) ==> ([^|]*?)\s*(?:\| (.*))?$"#, output)
+            else {
+                continue;
+            };
+            let Some(line) = line else {
+                continue;
+            };
+
+            let nth = seen.entry((line, word)).or_default();
+            let text = lines.get(line as usize).copied().unwrap_or_default();
+            let start = find_word(text, word, *nth)
+                .map_or(0, |start| text[..start].encode_utf16().count() as u32);
+            *nth += 1;
+
+            let corrections: Vec<String> = corrections
+                .split(',')
+                .map(str::trim)
+                .filter(|correction| !correction.is_empty())
+                .map(str::to_string)
+                .collect();
+            let suggestions = corrections
+                .iter()
+                .map(|correction| format!("`{correction}`"))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let mut message = format!("`{word}` may be misspelled, suggested: {suggestions}");
+            if !reason.is_empty() {
+                message.push_str(&format!(" ({reason})"));
+            }
+            let diagnostic = Diagnostic::new(
+                lsp_types::Range {
+                    start: Position::new(line, start),
+                    end: Position::new(line, start + word.encode_utf16().count() as u32),
+                },
+                Some(DiagnosticSeverity::INFORMATION),
+                None,
+                Some("codespell".to_string()),
+                message,
+                None,
+                None,
+            );
+            diagnostics.push(with_corrections(diagnostic, &corrections));
+        }
+        diagnostics
+    }
+}
+
+impl Handler for Codespell {
+    // Embedded regions aren't checked again, the whole document already is
+    fn filetype_supported(&self, _filetype: &str) -> bool {
+        true
+    }
+
+    fn get_capabilities(&self) -> ServerCapabilities {
+        ServerCapabilities {
+            code_action_provider: Some(CodeActionProviderCapability::Simple(true)),
+            ..Default::default()
+        }
+    }
+
+    async fn update_diagnostics(
+        &mut self,
+        contents: &str,
+    ) -> Result<Vec<Diagnostic>, HandlerError> {
+        // Exits with the number of misspellings
+        let out = run_with_stdin(Command::new("codespell").arg("-"), contents)?;
+        let stdout = strip_ansi(&String::from_utf8_lossy(&out.stdout));
+        Ok(Self::parse(&stdout, contents))
+    }
+
+    fn code_actions(
+        &self,
+        context: &DocumentContext,
+        diagnostics: &[Diagnostic],
+    ) -> Result<Vec<CodeAction>, HandlerError> {
+        Ok(replace_actions(&context.uri, "codespell", diagnostics))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Codespell;
+    use tower_lsp::lsp_types::{DiagnosticSeverity, Position, Range};
+
+    #[test]
+    fn test_parse_misspelling() {
+        let contents = "# Notes\n\nThe tehme is teh default, teh end.\n";
+        let stdout = "3: The tehme is teh default, teh end.\n\tteh ==> the\n3: The tehme is teh default, teh end.\n\tteh ==> the\n";
+        let diagnostics = Codespell::parse(stdout, contents);
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(
+            diagnostics[0].message,
+            "`teh` may be misspelled, suggested: `the`"
+        );
+        assert_eq!(
+            diagnostics[0].severity,
+            Some(DiagnosticSeverity::INFORMATION)
+        );
+        // Whole words only, not `teh` of `tehme`
+        assert_eq!(
+            diagnostics[0].range,
+            Range::new(Position::new(2, 13), Position::new(2, 16))
+        );
+        assert_eq!(diagnostics[1].range.start, Position::new(2, 26));
+        assert_eq!(
+            diagnostics[0].data,
+            Some(serde_json::json!({ "corrections": ["the"] }))
+        );
+    }
+}
@@ -0,0 +1,237 @@
+use lazy_regex::regex;
+use tower_lsp::lsp_types::{
+    self, Color, ColorInformation, ColorPresentation, ColorProviderCapability, ServerCapabilities,
+    TextEdit,
+};
+
+use super::text::offset_to_position;
+use super::{Handler, HandlerError};
+
+/// Color swatches for `#rgb`, `#rrggbb`, `#rrggbbaa`, `rgb()` and `rgba()`
+/// colors in styles, markup, configuration and scripts.
+#[derive(Debug)]
+pub struct ColorHandler {}
+
+/// Filetypes of stylesheets, the only ones with `#rgb` colors, elsewhere
+/// e.g. `#123` is more likely an issue.
+const STYLE_FILETYPES: &[&str] = &["css", "scss", "sass", "less", "stylus"];
+
+const FILETYPES: &[&str] = &[
+    "html",
+    "vue",
+    "svelte",
+    "xml",
+    "svg",
+    "javascript",
+    "javascriptreact",
+    "typescript",
+    "typescriptreact",
+    "json",
+    "jsonc",
+    "yaml",
+    "toml",
+];
+
+fn hex_channel(hex: &str) -> f32 {
+    let value = u8::from_str_radix(hex, 16).unwrap_or(0);
+    // Short form `#f80` means `#ff8800`
+    let value = if hex.len() == 1 { value * 17 } else { value };
+    f32::from(value) / 255.0
+}
+
+fn parse_hex(hex: &str) -> Color {
+    let channels: Vec<&str> = if hex.len() == 3 {
+        (0..3).map(|i| &hex[i..=i]).collect()
+    } else {
+        (0..hex.len()).step_by(2).map(|i| &hex[i..i + 2]).collect()
+    };
+    Color {
+        red: hex_channel(channels[0]),
+        green: hex_channel(channels[1]),
+        blue: hex_channel(channels[2]),
+        alpha: channels.get(3).map_or(1.0, |alpha| hex_channel(alpha)),
+    }
+}
+
+fn parse_alpha(alpha: &str) -> f32 {
+    match alpha.strip_suffix('%') {
+        Some(percent) => percent.parse::<f32>().unwrap_or(100.0) / 100.0,
+        None => alpha.parse().unwrap_or(1.0),
+    }
+    .clamp(0.0, 1.0)
+}
+
+fn to_byte(channel: f32) -> u8 {
+    (channel.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+impl ColorHandler {
+    pub fn new() -> Result<Self, String> {
+        Ok(Self {})
+    }
+
+    pub fn parse(filetype: &str, contents: &str) -> Vec<ColorInformation> {
+        let range = |start: usize, end: usize| lsp_types::Range {
+            start: offset_to_position(contents, start),
+            end: offset_to_position(contents, end),
+        };
+
+        let short = STYLE_FILETYPES.contains(&filetype);
+        let mut colors: Vec<ColorInformation> =
+            regex!(r#"#([0-9a-fA-F]{8}|[0-9a-fA-F]{6}|[0-9a-fA-F]{3})\b"#)
+                .captures_iter(contents)
+                .filter(|captures| short || captures[1].len() != 3)
+                .map(|captures| {
+                    let all = captures.get(0).expect("Whole match");
+                    ColorInformation {
+                        range: range(all.start(), all.end()),
+                        color: parse_hex(&captures[1]),
+                    }
+                })
+                .collect();
+
+        colors.extend(
+            regex!(
+                r#"rgba?\(\s*(\d{1,3})\s*,\s*(\d{1,3})\s*,\s*(\d{1,3})\s*(?:,\s*([\d.]+%?)\s*)?\)"#
+            )
+            .captures_iter(contents)
+            .map(|captures| {
+                let all = captures.get(0).expect("Whole match");
+                let channel =
+                    |i: usize| f32::from(captures[i].parse::<u8>().unwrap_or(u8::MAX)) / 255.0;
+                ColorInformation {
+                    range: range(all.start(), all.end()),
+                    color: Color {
+                        red: channel(1),
+                        green: channel(2),
+                        blue: channel(3),
+                        alpha: captures
+                            .get(4)
+                            .map_or(1.0, |alpha| parse_alpha(alpha.as_str())),
+                    },
+                }
+            }),
+        );
+
+        colors.sort_by_key(|color| (color.range.start.line, color.range.start.character));
+        colors
+    }
+
+    /// The ways `color` can be written, as hex and as `rgb()`.
+    pub fn presentations(color: Color, range: lsp_types::Range) -> Vec<ColorPresentation> {
+        let (red, green, blue) = (
+            to_byte(color.red),
+            to_byte(color.green),
+            to_byte(color.blue),
+        );
+        let labels = if color.alpha < 1.0 {
+            let alpha = (color.alpha * 100.0).round() / 100.0;
+            vec![
+                format!(
+                    "#{red:02x}{green:02x}{blue:02x}{:02x}",
+                    to_byte(color.alpha)
+                ),
+                format!("rgba({red}, {green}, {blue}, {alpha})"),
+            ]
+        } else {
+            vec![
+                format!("#{red:02x}{green:02x}{blue:02x}"),
+                format!("rgb({red}, {green}, {blue})"),
+            ]
+        };
+
+        labels
+            .into_iter()
+            .map(|label| ColorPresentation {
+                text_edit: Some(TextEdit::new(range, label.clone())),
+                label,
+                additional_text_edits: None,
+            })
+            .collect()
+    }
+}
+
+impl Handler for ColorHandler {
+    fn filetype_supported(&self, filetype: &str) -> bool {
+        STYLE_FILETYPES.contains(&filetype) || FILETYPES.contains(&filetype)
+    }
+
+    fn get_capabilities(&self) -> ServerCapabilities {
+        ServerCapabilities {
+            color_provider: Some(ColorProviderCapability::Simple(true)),
+            ..Default::default()
+        }
+    }
+
+    fn document_colors(
+        &self,
+        filetype: &str,
+        contents: &str,
+    ) -> Result<Vec<ColorInformation>, HandlerError> {
+        Ok(Self::parse(filetype, contents))
+    }
+
+    fn color_presentations(
+        &self,
+        color: Color,
+        range: lsp_types::Range,
+    ) -> Result<Vec<ColorPresentation>, HandlerError> {
+        Ok(Self::presentations(color, range))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ColorHandler;
+    use tower_lsp::lsp_types::{Color, Position, Range};
+
+    #[test]
+    fn test_parse() {
+        let contents = "a { color: #ff8800; }\nb { color: rgba(0, 0, 255, 0.5); border: #FFF }\n";
+        let colors = ColorHandler::parse("css", contents);
+
+        assert_eq!(colors.len(), 3);
+        assert_eq!(colors[0].range.start, Position::new(0, 11));
+        assert_eq!(colors[0].range.end, Position::new(0, 18));
+        assert_eq!(colors[0].color.red, 1.0);
+        assert_eq!(colors[0].color.green, 136.0 / 255.0);
+        assert_eq!(colors[0].color.blue, 0.0);
+        assert_eq!(colors[0].color.alpha, 1.0);
+
+        assert_eq!(colors[1].color.blue, 1.0);
+        assert_eq!(colors[1].color.alpha, 0.5);
+        assert_eq!(colors[2].color.green, 1.0);
+    }
+
+    #[test]
+    fn test_parse_short_hex() {
+        let contents = "Fixed in #123, see #ffcc00\n";
+        let colors = ColorHandler::parse("yaml", contents);
+        assert_eq!(colors.len(), 1);
+        assert_eq!(colors[0].range.start, Position::new(0, 19));
+        assert_eq!(ColorHandler::parse("scss", contents).len(), 2);
+    }
+
+    #[test]
+    fn test_presentations() {
+        let color = ColorHandler::parse("css", "#ff8800")[0].color;
+        let range = Range::new(Position::new(0, 0), Position::new(0, 7));
+        let labels: Vec<_> = ColorHandler::presentations(color, range)
+            .into_iter()
+            .map(|presentation| presentation.label)
+            .collect();
+        assert_eq!(labels, vec!["#ff8800", "rgb(255, 136, 0)"]);
+
+        let color = Color {
+            red: 0.0,
+            green: 0.0,
+            blue: 1.0,
+            alpha: 0.5,
+        };
+        let labels: Vec<_> = ColorHandler::presentations(color, range)
+            .into_iter()
+            .map(|presentation| presentation.label)
+            .collect();
+        assert_eq!(labels, vec!["#0000ff80", "rgba(0, 0, 255, 0.5)"]);
+    }
+}
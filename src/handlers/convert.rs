@@ -0,0 +1,314 @@
+use serde_json::{Map, Number, Value};
+use std::collections::HashMap;
+use tower_lsp::lsp_types::{
+    self, ExecuteCommandOptions, Position, ServerCapabilities, TextEdit, WorkspaceEdit,
+};
+use yaml_rust2::{Yaml, YamlEmitter, YamlLoader};
+
+use super::text::offset_to_position;
+use super::{DocumentContext, Handler, HandlerError};
+
+/// Converts JSON, YAML and TOML documents to one another with the
+/// `any_ls.convert.*` commands, replacing the document. Keys keep their
+/// order.
+#[derive(Debug)]
+pub struct Convert {}
+
+pub const TO_JSON: &str = "any_ls.convert.toJson";
+pub const TO_YAML: &str = "any_ls.convert.toYaml";
+pub const TO_TOML: &str = "any_ls.convert.toToml";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Json,
+    Yaml,
+    Toml,
+}
+
+impl Format {
+    fn of_filetype(filetype: &str) -> Option<Self> {
+        match filetype {
+            "json" | "jsonc" | "json5" => Some(Format::Json),
+            "yaml" => Some(Format::Yaml),
+            "toml" => Some(Format::Toml),
+            _ => None,
+        }
+    }
+
+    fn of_command(command: &str) -> Option<Self> {
+        match command {
+            TO_JSON => Some(Format::Json),
+            TO_YAML => Some(Format::Yaml),
+            TO_TOML => Some(Format::Toml),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Format::Json => "JSON",
+            Format::Yaml => "YAML",
+            Format::Toml => "TOML",
+        }
+    }
+}
+
+fn from_yaml(yaml: &Yaml) -> Result<Value, String> {
+    Ok(match yaml {
+        Yaml::Null => Value::Null,
+        Yaml::Boolean(boolean) => Value::Bool(*boolean),
+        Yaml::Integer(integer) => Value::from(*integer),
+        // `.inf` and `.nan` have no JSON number
+        Yaml::Real(real) => real
+            .parse()
+            .ok()
+            .and_then(Number::from_f64)
+            .map_or_else(|| Value::String(real.clone()), Value::Number),
+        Yaml::String(string) => Value::String(string.clone()),
+        Yaml::Array(items) => Value::Array(items.iter().map(from_yaml).collect::<Result<_, _>>()?),
+        Yaml::Hash(hash) => {
+            let mut object = Map::new();
+            for (key, value) in hash {
+                let key = match key {
+                    Yaml::String(key) | Yaml::Real(key) => key.clone(),
+                    Yaml::Integer(key) => key.to_string(),
+                    Yaml::Boolean(key) => key.to_string(),
+                    Yaml::Null => "null".to_string(),
+                    _ => return Err("Keys must be scalars".to_string()),
+                };
+                object.insert(key, from_yaml(value)?);
+            }
+            Value::Object(object)
+        }
+        Yaml::Alias(_) | Yaml::BadValue => return Err("Invalid YAML node".to_string()),
+    })
+}
+
+fn to_yaml(value: &Value) -> Yaml {
+    match value {
+        Value::Null => Yaml::Null,
+        Value::Bool(boolean) => Yaml::Boolean(*boolean),
+        Value::Number(number) => match number.as_i64() {
+            Some(integer) => Yaml::Integer(integer),
+            None => Yaml::Real(number.to_string()),
+        },
+        Value::String(string) => Yaml::String(string.clone()),
+        Value::Array(items) => Yaml::Array(items.iter().map(to_yaml).collect()),
+        Value::Object(object) => Yaml::Hash(
+            object
+                .iter()
+                .map(|(key, value)| (Yaml::String(key.clone()), to_yaml(value)))
+                .collect(),
+        ),
+    }
+}
+
+fn from_toml(toml: toml::Value) -> Value {
+    match toml {
+        toml::Value::String(string) => Value::String(string),
+        toml::Value::Integer(integer) => Value::from(integer),
+        // `inf` and `nan` have no JSON number
+        toml::Value::Float(float) => {
+            Number::from_f64(float).map_or_else(|| Value::String(float.to_string()), Value::Number)
+        }
+        toml::Value::Boolean(boolean) => Value::Bool(boolean),
+        toml::Value::Datetime(datetime) => Value::String(datetime.to_string()),
+        toml::Value::Array(items) => Value::Array(items.into_iter().map(from_toml).collect()),
+        toml::Value::Table(table) => Value::Object(
+            table
+                .into_iter()
+                .map(|(key, value)| (key, from_toml(value)))
+                .collect(),
+        ),
+    }
+}
+
+fn to_toml(value: &Value) -> Result<toml::Value, String> {
+    Ok(match value {
+        Value::Null => return Err("TOML has no null values".to_string()),
+        Value::Bool(boolean) => toml::Value::Boolean(*boolean),
+        Value::Number(number) => match number.as_i64() {
+            Some(integer) => toml::Value::Integer(integer),
+            None => toml::Value::Float(number.as_f64().unwrap_or(f64::NAN)),
+        },
+        Value::String(string) => toml::Value::String(string.clone()),
+        Value::Array(items) => {
+            toml::Value::Array(items.iter().map(to_toml).collect::<Result<_, _>>()?)
+        }
+        Value::Object(object) => toml::Value::Table(
+            object
+                .iter()
+                .map(|(key, value)| Ok((key.clone(), to_toml(value)?)))
+                .collect::<Result<_, String>>()?,
+        ),
+    })
+}
+
+fn parse(format: Format, contents: &str) -> Result<Value, String> {
+    match format {
+        // Also allows the comments of JSONC and JSON5
+        Format::Json => json5::from_str(contents).map_err(|e| e.to_string()),
+        Format::Yaml => {
+            let documents = YamlLoader::load_from_str(contents).map_err(|e| e.to_string())?;
+            match documents.as_slice() {
+                [] => Ok(Value::Null),
+                [document] => from_yaml(document),
+                _ => Err("Only single documents can be converted".to_string()),
+            }
+        }
+        Format::Toml => contents
+            .parse::<toml::Table>()
+            .map(|table| from_toml(toml::Value::Table(table)))
+            .map_err(|e| e.message().to_string()),
+    }
+}
+
+fn serialize(format: Format, value: &Value) -> Result<String, String> {
+    match format {
+        Format::Json => serde_json::to_string_pretty(value)
+            .map(|json| json + "\n")
+            .map_err(|e| e.to_string()),
+        Format::Yaml => {
+            let mut yaml = String::new();
+            YamlEmitter::new(&mut yaml)
+                .dump(&to_yaml(value))
+                .map_err(|e| e.to_string())?;
+            let yaml = yaml.trim_start_matches("---").trim_start();
+            Ok(format!("{yaml}\n"))
+        }
+        Format::Toml => match to_toml(value)? {
+            toml::Value::Table(table) => toml::to_string(&table).map_err(|e| e.to_string()),
+            _ => Err("Only tables can be TOML documents".to_string()),
+        },
+    }
+}
+
+/// `contents` of `filetype` in the format of `command`, `None` for other
+/// commands or filetypes.
+fn convert(command: &str, filetype: &str, contents: &str) -> Option<Result<String, String>> {
+    let source = Format::of_filetype(filetype)?;
+    let target = Format::of_command(command)?;
+    Some(
+        parse(source, contents)
+            .and_then(|value| serialize(target, &value))
+            .map_err(|e| {
+                format!(
+                    "Could not convert the {} document to {}: {e}",
+                    source.name(),
+                    target.name()
+                )
+            }),
+    )
+}
+
+impl Convert {
+    pub fn new() -> Result<Self, String> {
+        Ok(Self {})
+    }
+}
+
+impl Handler for Convert {
+    fn filetype_supported(&self, filetype: &str) -> bool {
+        Format::of_filetype(filetype).is_some()
+    }
+
+    fn get_capabilities(&self) -> ServerCapabilities {
+        ServerCapabilities {
+            execute_command_provider: Some(ExecuteCommandOptions {
+                commands: vec![
+                    TO_JSON.to_string(),
+                    TO_YAML.to_string(),
+                    TO_TOML.to_string(),
+                ],
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn execute_command(
+        &self,
+        command: &str,
+        _arguments: &[Value],
+        filetype: &str,
+        context: &DocumentContext,
+        document_contents: &str,
+    ) -> Result<Option<WorkspaceEdit>, HandlerError> {
+        let Some(converted) = convert(command, filetype, document_contents) else {
+            return Ok(None);
+        };
+        let converted = converted.map_err(HandlerError::Parse)?;
+        let edit = TextEdit::new(
+            lsp_types::Range::new(
+                Position::new(0, 0),
+                offset_to_position(document_contents, document_contents.len()),
+            ),
+            converted,
+        );
+        Ok(Some(WorkspaceEdit {
+            changes: Some(HashMap::from([(context.uri.clone(), vec![edit])])),
+            ..Default::default()
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{convert, TO_JSON, TO_TOML, TO_YAML};
+    use serde_json::{json, Value};
+
+    fn to_json(filetype: &str, contents: &str) -> String {
+        convert(TO_JSON, filetype, contents).unwrap().unwrap()
+    }
+
+    #[test]
+    fn test_yaml_to_json() {
+        let contents = "name: any_ls\nversion: 1.2\ntags: [lsp, rust]\nowner:\n  name: Ada\n  active: true\n  manager: null\n";
+        let converted = to_json("yaml", contents);
+        assert_eq!(
+            serde_json::from_str::<Value>(&converted).unwrap(),
+            json!({
+                "name": "any_ls",
+                "version": 1.2,
+                "tags": ["lsp", "rust"],
+                "owner": {"name": "Ada", "active": true, "manager": null}
+            })
+        );
+        // Keys keep the order of the document
+        assert!(converted.starts_with("{\n  \"name\": \"any_ls\",\n  \"version\""));
+
+        // And back
+        let yaml = convert(TO_YAML, "json", &converted).unwrap().unwrap();
+        assert_eq!(
+            serde_json::from_str::<Value>(&to_json("yaml", &yaml)).unwrap(),
+            serde_json::from_str::<Value>(&converted).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_toml_to_json() {
+        let contents = "title = \"Example\"\nreleased = 1979-05-27\n\n[server]\nport = 8080\nhosts = [\"alpha\", \"beta\"]\n\n[[users]]\nname = \"Ada\"\n";
+        let converted = to_json("toml", contents);
+        assert_eq!(
+            serde_json::from_str::<Value>(&converted).unwrap(),
+            json!({
+                "title": "Example",
+                "released": "1979-05-27",
+                "server": {"port": 8080, "hosts": ["alpha", "beta"]},
+                "users": [{"name": "Ada"}]
+            })
+        );
+
+        let toml = convert(TO_TOML, "json", &converted).unwrap().unwrap();
+        assert_eq!(
+            serde_json::from_str::<Value>(&to_json("toml", &toml)).unwrap(),
+            serde_json::from_str::<Value>(&converted).unwrap()
+        );
+        let error = convert(TO_TOML, "yaml", "[1, 2]\n").unwrap().unwrap_err();
+        assert_eq!(
+            error,
+            "Could not convert the YAML document to TOML: Only tables can be TOML documents"
+        );
+        assert!(convert(TO_JSON, "markdown", "# Title\n").is_none());
+    }
+}
@@ -0,0 +1,238 @@
+use csv::ReaderBuilder;
+use serde::Deserialize;
+use tower_lsp::lsp_types::{self, Diagnostic, DiagnosticSeverity, Position};
+
+use super::{DocumentContext, Handler, HandlerError};
+
+/// Settings of the CSV handler, the `csv` key of the settings.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct CsvConfig {
+    /// Separator of the fields of CSV files, `,` when unset. TSV files are
+    /// always separated by tabs.
+    pub delimiter: Option<char>,
+    /// Character quoting fields containing delimiters or newlines.
+    pub quote: char,
+    /// Whether fields can be quoted, off for files where quotes are data.
+    pub quoting: bool,
+}
+
+impl Default for CsvConfig {
+    fn default() -> Self {
+        Self {
+            delimiter: None,
+            quote: '"',
+            quoting: true,
+        }
+    }
+}
+
+/// Checks that the rows of CSV and TSV files have as many fields as their
+/// header.
+#[derive(Debug)]
+pub struct Csv {
+    delimiter: u8,
+    quote: u8,
+    quoting: bool,
+}
+
+/// The byte of an ASCII `character`, the only ones the reader separates by.
+fn ascii_byte(setting: &str, character: char) -> Result<u8, String> {
+    u8::try_from(character)
+        .ok()
+        .filter(u8::is_ascii)
+        .ok_or_else(|| format!("csv.{setting} is not an ASCII character: {character:?}"))
+}
+
+impl Csv {
+    pub fn new(config: CsvConfig) -> Result<Self, String> {
+        Ok(Self {
+            delimiter: ascii_byte("delimiter", config.delimiter.unwrap_or(','))?,
+            quote: ascii_byte("quote", config.quote)?,
+            quoting: config.quoting,
+        })
+    }
+
+    /// Errors on the first line of the rows of `contents` with more or
+    /// fewer fields than the first one, separated by `delimiter`.
+    pub fn check(&self, contents: &str, delimiter: u8) -> Vec<Diagnostic> {
+        let mut reader = ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .delimiter(delimiter)
+            .quote(self.quote)
+            .quoting(self.quoting)
+            .from_reader(contents.as_bytes());
+        let lines: Vec<&str> = contents.lines().collect();
+        // The whole first line of a row
+        let line_range = |line: u64| {
+            let line = line.saturating_sub(1) as u32;
+            let length = lines
+                .get(line as usize)
+                .map_or(0, |text| text.encode_utf16().count() as u32);
+            lsp_types::Range::new(Position::new(line, 0), Position::new(line, length))
+        };
+        let diagnostic = |line, message| {
+            Diagnostic::new(
+                line_range(line),
+                Some(DiagnosticSeverity::ERROR),
+                None,
+                Some("csv".to_string()),
+                message,
+                None,
+                None,
+            )
+        };
+
+        let mut header = None;
+        let mut diagnostics = Vec::new();
+        for record in reader.records() {
+            match record {
+                Ok(record) => {
+                    let line = record.position().map_or(1, |position| position.line());
+                    let expected = *header.get_or_insert(record.len());
+                    if record.len() != expected {
+                        diagnostics.push(diagnostic(
+                            line,
+                            format!("Row has {} fields, the header has {expected}", record.len()),
+                        ));
+                    }
+                }
+                Err(e) => {
+                    let line = e.position().map_or(1, |position| position.line());
+                    diagnostics.push(diagnostic(line, e.to_string()));
+                    break;
+                }
+            }
+        }
+        diagnostics
+    }
+}
+
+impl Handler for Csv {
+    fn filetype_supported(&self, filetype: &str) -> bool {
+        matches!(filetype, "csv" | "tsv")
+    }
+
+    async fn update_diagnostics_with_context(
+        &mut self,
+        context: &DocumentContext,
+        contents: &str,
+    ) -> Result<Vec<Diagnostic>, HandlerError> {
+        // Also for TSV files the client doesn't know as such
+        let delimiter = if context.filetype == "tsv" || context.uri.path().ends_with(".tsv") {
+            b'\t'
+        } else {
+            self.delimiter
+        };
+        Ok(self.check(contents, delimiter))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Csv, CsvConfig};
+    use crate::handlers::{AnyHandler, DocumentContext, HandlerKind};
+    use tower_lsp::lsp_types::{Position, Range, Url};
+
+    fn check(contents: &str) -> Vec<(u32, String)> {
+        Csv::new(CsvConfig::default())
+            .unwrap()
+            .check(contents, b',')
+            .into_iter()
+            .map(|diagnostic| (diagnostic.range.start.line, diagnostic.message))
+            .collect()
+    }
+
+    #[test]
+    fn test_short_row() {
+        let contents = "name,age,city\nAda,36,London\nAlan,41\n";
+        assert_eq!(
+            check(contents),
+            vec![(2, "Row has 2 fields, the header has 3".to_string())]
+        );
+        let diagnostics = Csv::new(CsvConfig::default())
+            .unwrap()
+            .check(contents, b',');
+        assert_eq!(
+            diagnostics[0].range,
+            Range::new(Position::new(2, 0), Position::new(2, 7))
+        );
+    }
+
+    #[test]
+    fn test_long_row() {
+        let contents = "name;age\nAda;36;London\n";
+        let diagnostics = Csv::new(CsvConfig::default())
+            .unwrap()
+            .check(contents, b';');
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].range.start.line, 1);
+        assert_eq!(diagnostics[0].message, "Row has 3 fields, the header has 2");
+    }
+
+    #[test]
+    fn test_non_ascii_settings() {
+        let config = CsvConfig {
+            delimiter: Some('§'),
+            ..Default::default()
+        };
+        assert!(Csv::new(config).is_err());
+        let config = CsvConfig {
+            quote: '«',
+            ..Default::default()
+        };
+        assert!(Csv::new(config).is_err());
+        let config = CsvConfig {
+            delimiter: Some(';'),
+            quote: '\'',
+            ..Default::default()
+        };
+        assert!(Csv::new(config).is_ok());
+    }
+
+    #[test]
+    fn test_quoted_fields() {
+        // A delimiter and a newline in quoted fields
+        let contents =
+            "name,address\n\"Lovelace, Ada\",\"12 St James's Square\nLondon\"\nAlan,Wilmslow\n";
+        assert_eq!(check(contents), vec![]);
+        // Rows after a multiline field are on later lines
+        let contents = "name,address\n\"Ada\",\"St James's Square\nLondon\"\nAlan\n";
+        assert_eq!(check(contents)[0].0, 3);
+
+        // Without quoting, quotes are data
+        let config = CsvConfig {
+            quoting: false,
+            ..Default::default()
+        };
+        let diagnostics = Csv::new(config)
+            .unwrap()
+            .check("name,title\n\"Ada\",\"Countess, of Lovelace\"\n", b',');
+        assert_eq!(diagnostics.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_tsv_filetype() {
+        let csv = Csv::new(CsvConfig::default()).unwrap();
+        let mut handler = AnyHandler::from_handlers(vec![HandlerKind::Csv(csv)]);
+        let contents = "name\taddress\nAda\t12 St James's Square, London\n";
+
+        // Untitled, or without a `.tsv` extension
+        for uri in ["untitled:Untitled-1", "file:///project/export.txt"] {
+            let context = DocumentContext::new(Url::parse(uri).unwrap(), vec![]);
+            let diagnostics = handler
+                .update_diagnostics("tsv", &context, contents)
+                .await
+                .ok()
+                .unwrap();
+            assert_eq!(diagnostics, vec![]);
+            let diagnostics = handler
+                .update_diagnostics("csv", &context, contents)
+                .await
+                .ok()
+                .unwrap();
+            assert_eq!(diagnostics.len(), 1);
+        }
+    }
+}
@@ -0,0 +1,449 @@
+use lazy_regex::{regex, regex_captures};
+use std::path::Path;
+use std::process::Command;
+use tower_lsp::lsp_types::{
+    self, Diagnostic, DiagnosticSeverity, Hover, HoverContents, HoverProviderCapability,
+    MarkupContent, MarkupKind, Position, ServerCapabilities,
+};
+
+use super::process::{output_with_timeout, probe, TempFileStrategy, TempFiles};
+use super::{DocumentContext, Handler, HandlerError};
+
+/// Checks of freedesktop Desktop Entry files, `*.desktop` launchers, with
+/// `desktop-file-validate` when it is installed, and the documentation of
+/// their keys on hover.
+#[derive(Debug)]
+pub struct Desktop {
+    temp_files: TempFiles,
+    /// Whether `desktop-file-validate` is available.
+    validate: bool,
+}
+
+/// The group every Desktop Entry file starts with.
+const MAIN_GROUP: &str = "Desktop Entry";
+
+const TYPES: &[&str] = &["Application", "Link", "Directory"];
+
+/// Keys of the specification, with what they do.
+const KEYS: &[(&str, &str)] = &[
+    ("Type", "The type of the entry, `Application`, `Link` or `Directory`."),
+    ("Version", "The version of the specification the entry follows, e.g. `1.5`."),
+    ("Name", "The name of the application, e.g. `Mozilla`."),
+    ("GenericName", "A generic name of the application, e.g. `Web Browser`."),
+    ("NoDisplay", "Whether the application is hidden from menus, while still handling its MIME types."),
+    ("Comment", "A tooltip of the entry, e.g. `View sites on the Internet`."),
+    ("Icon", "The icon of the entry, an absolute path or the name of an icon of the icon theme."),
+    ("Hidden", "Whether the entry is considered deleted, e.g. to override a system-wide entry."),
+    ("OnlyShowIn", "Desktop environments the entry is only shown in, separated by `;`."),
+    ("NotShowIn", "Desktop environments the entry is not shown in, separated by `;`."),
+    ("DBusActivatable", "Whether the application is started over D-Bus rather than with `Exec`."),
+    ("TryExec", "A program checked to be installed, the entry is ignored when it isn't."),
+    ("Exec", "The program run with its arguments, which may contain field codes like `%f` or `%U`."),
+    ("Path", "The working directory the program runs in."),
+    ("Terminal", "Whether the program runs in a terminal window."),
+    ("Actions", "Identifiers of additional actions, each with a `[Desktop Action id]` group."),
+    ("MimeType", "MIME types the application supports, separated by `;`."),
+    ("Categories", "Categories the entry is shown in in menus, separated by `;`."),
+    ("Implements", "D-Bus interfaces the application implements, separated by `;`."),
+    ("Keywords", "Words searches find the entry with, separated by `;`."),
+    ("StartupNotify", "Whether the application sends a \"remove\" message when started, for startup notifications."),
+    ("StartupWMClass", "The WM class or name hint of the windows of the application."),
+    ("URL", "The URL a `Link` entry opens."),
+    ("PrefersNonDefaultGPU", "Whether the application prefers to run on a more powerful discrete GPU."),
+    ("SingleMainWindow", "Whether the application has a single main window, without an option to open a new one."),
+];
+
+/// Keys of `[Desktop Action id]` groups.
+const ACTION_KEYS: &[&str] = &["Name", "Icon", "Exec"];
+
+/// A `Key[locale]=value` line.
+#[derive(Debug, PartialEq)]
+struct Entry<'a> {
+    line: u32,
+    group: Option<&'a str>,
+    key: &'a str,
+    /// E.g. `de` of `Name[de]`.
+    locale: Option<&'a str>,
+    value: &'a str,
+    /// Characters of the key, without its locale, and of the value on the
+    /// line.
+    key_range: (u32, u32),
+    value_range: (u32, u32),
+}
+
+/// A Desktop Entry file, its groups with their lines and its entries.
+#[derive(Debug, Default)]
+struct Parsed<'a> {
+    groups: Vec<(u32, &'a str)>,
+    entries: Vec<Entry<'a>>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+fn diagnostic(
+    line: u32,
+    (start, end): (u32, u32),
+    severity: DiagnosticSeverity,
+    message: String,
+) -> Diagnostic {
+    Diagnostic::new(
+        lsp_types::Range::new(Position::new(line, start), Position::new(line, end)),
+        Some(severity),
+        None,
+        Some("desktop".to_string()),
+        message,
+        None,
+        None,
+    )
+}
+
+fn parse(contents: &str) -> Parsed<'_> {
+    let mut parsed = Parsed::default();
+    let mut group = None;
+    for (number, line) in contents.lines().enumerate() {
+        let number = number as u32;
+        let character = |offset: usize| line[..offset].encode_utf16().count() as u32;
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        if let Some((_, name)) = regex_captures!(r#"^\[([^\[\]]+)\]$"#, trimmed) {
+            group = Some(name);
+            parsed.groups.push((number, name));
+            continue;
+        }
+        let Some(captures) =
+            regex!(r#"^\s*([A-Za-z0-9-]+)(\[[^\]]+\])?\s*=\s*(.*?)\s*$"#).captures(line)
+        else {
+            parsed.diagnostics.push(diagnostic(
+                number,
+                (0, character(line.len())),
+                DiagnosticSeverity::ERROR,
+                "Expected a `[Group]` header, a `Key=value` entry or a `#` comment".to_string(),
+            ));
+            continue;
+        };
+        let (key, value) = (captures.get(1).unwrap(), captures.get(3).unwrap());
+        if group.is_none() {
+            parsed.diagnostics.push(diagnostic(
+                number,
+                (character(key.start()), character(key.end())),
+                DiagnosticSeverity::ERROR,
+                format!("Entries must follow a group, e.g. `[{MAIN_GROUP}]`"),
+            ));
+        }
+        parsed.entries.push(Entry {
+            line: number,
+            group,
+            key: key.as_str(),
+            locale: captures
+                .get(2)
+                .map(|locale| locale.as_str().trim_matches(['[', ']'])),
+            value: value.as_str(),
+            key_range: (character(key.start()), character(key.end())),
+            value_range: (character(value.start()), character(value.end())),
+        });
+    }
+    parsed
+}
+
+/// Whether `program` is an existing file, or one in `PATH` for names
+/// without a directory.
+fn program_exists(program: &str) -> bool {
+    let path = Path::new(program);
+    if path.components().count() > 1 {
+        return path.is_file();
+    }
+    std::env::var_os("PATH").is_some_and(|paths| {
+        std::env::split_paths(&paths).any(|directory| directory.join(program).is_file())
+    })
+}
+
+/// The program of an `Exec` command, which may be quoted.
+fn exec_program(exec: &str) -> Option<&str> {
+    regex_captures!(r#"^\s*(?:"([^"]+)"|(\S+))"#, exec).map(|(_, quoted, plain)| {
+        if quoted.is_empty() {
+            plain
+        } else {
+            quoted
+        }
+    })
+}
+
+/// Problems of the keys and values of `parsed`.
+fn check(parsed: &Parsed) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    match parsed.groups.first() {
+        Some((_, name)) if *name == MAIN_GROUP => {}
+        Some((line, name)) => diagnostics.push(diagnostic(
+            *line,
+            (1, name.encode_utf16().count() as u32 + 1),
+            DiagnosticSeverity::ERROR,
+            format!("The first group must be `[{MAIN_GROUP}]`"),
+        )),
+        None => diagnostics.push(diagnostic(
+            0,
+            (0, 0),
+            DiagnosticSeverity::ERROR,
+            format!("Missing the `[{MAIN_GROUP}]` group"),
+        )),
+    }
+
+    let main: Vec<&Entry> = parsed
+        .entries
+        .iter()
+        .filter(|entry| entry.group == Some(MAIN_GROUP))
+        .collect();
+    if let Some((line, _)) = parsed.groups.iter().find(|(_, name)| *name == MAIN_GROUP) {
+        for key in ["Type", "Name"] {
+            // Localized values only add to the value
+            if !main
+                .iter()
+                .any(|entry| entry.key == key && entry.locale.is_none())
+            {
+                diagnostics.push(diagnostic(
+                    *line,
+                    (0, MAIN_GROUP.len() as u32 + 2),
+                    DiagnosticSeverity::ERROR,
+                    format!("Missing the required `{key}` key"),
+                ));
+            }
+        }
+    }
+
+    for entry in &parsed.entries {
+        let Some(group) = entry.group else {
+            continue;
+        };
+        // Extensions are free-form
+        if entry.key.starts_with("X-") || group.starts_with("X-") {
+            continue;
+        }
+        let known = if group == MAIN_GROUP {
+            KEYS.iter().any(|(key, _)| *key == entry.key)
+        } else if group.starts_with("Desktop Action ") {
+            ACTION_KEYS.contains(&entry.key)
+        } else {
+            true
+        };
+        if !known {
+            diagnostics.push(diagnostic(
+                entry.line,
+                entry.key_range,
+                DiagnosticSeverity::WARNING,
+                format!(
+                    "Unknown key `{}` in `[{group}]`, extensions must start with `X-`",
+                    entry.key
+                ),
+            ));
+            continue;
+        }
+        match entry.key {
+            "Type" if group == MAIN_GROUP && !TYPES.contains(&entry.value) => {
+                diagnostics.push(diagnostic(
+                    entry.line,
+                    entry.value_range,
+                    DiagnosticSeverity::ERROR,
+                    format!(
+                        "Invalid type `{}`, expected `Application`, `Link` or `Directory`",
+                        entry.value
+                    ),
+                ))
+            }
+            "TryExec" | "Exec" => {
+                let Some(program) = exec_program(entry.value) else {
+                    continue;
+                };
+                if !program_exists(program) {
+                    let message = match entry.key {
+                        "TryExec" => format!("`{program}` is not installed, the entry is ignored"),
+                        _ => format!("`{program}` is not installed"),
+                    };
+                    diagnostics.push(diagnostic(
+                        entry.line,
+                        entry.value_range,
+                        DiagnosticSeverity::HINT,
+                        message,
+                    ));
+                }
+            }
+            _ => {}
+        }
+    }
+    diagnostics
+}
+
+/// The documented key of the entry at `position` of `contents`, with the
+/// range of the key on the line.
+fn key_at(contents: &str, position: Position) -> Option<(&'static str, &'static str, u32, u32)> {
+    let parsed = parse(contents);
+    let entry = parsed.entries.iter().find(|entry| {
+        entry.line == position.line
+            && (entry.key_range.0..=entry.key_range.1).contains(&position.character)
+    })?;
+    if entry.group != Some(MAIN_GROUP) && !ACTION_KEYS.contains(&entry.key) {
+        return None;
+    }
+    KEYS.iter()
+        .find(|(key, _)| *key == entry.key)
+        .map(|(key, description)| (*key, *description, entry.key_range.0, entry.key_range.1))
+}
+
+impl Desktop {
+    pub fn new() -> Result<Self, String> {
+        Ok(Self {
+            temp_files: TempFiles::with_suffix(".desktop"),
+            validate: probe("desktop-file-validate", &["--help"]).is_ok(),
+        })
+    }
+
+    /// The diagnostics of `path: severity: message` lines of
+    /// `desktop-file-validate`. It doesn't report lines, so messages about
+    /// a key are on the first line of that key.
+    fn parse_validator(output: &str, contents: &str) -> Vec<Diagnostic> {
+        let parsed = parse(contents);
+        output
+            .lines()
+            .filter_map(|line| regex_captures!(r#"^[^:]+: (error|warning|hint): (.+)$"#, line))
+            .map(|(_, severity, message)| {
+                let severity = match severity {
+                    "error" => DiagnosticSeverity::ERROR,
+                    "warning" => DiagnosticSeverity::WARNING,
+                    _ => DiagnosticSeverity::HINT,
+                };
+                let entry = regex_captures!(r#"key "([^"]+)""#, message)
+                    .and_then(|(_, key)| parsed.entries.iter().find(|entry| entry.key == key));
+                let (line, range) =
+                    entry.map_or((0, (0, 0)), |entry| (entry.line, entry.key_range));
+                Diagnostic {
+                    source: Some("desktop-file-validate".to_string()),
+                    ..diagnostic(line, range, severity, message.to_string())
+                }
+            })
+            .collect()
+    }
+}
+
+impl Handler for Desktop {
+    fn filetype_supported(&self, filetype: &str) -> bool {
+        filetype == "desktop"
+    }
+
+    fn path_supported(&self, path: &Path) -> bool {
+        path.extension()
+            .is_some_and(|extension| extension == "desktop")
+    }
+
+    fn get_capabilities(&self) -> ServerCapabilities {
+        ServerCapabilities {
+            hover_provider: Some(HoverProviderCapability::Simple(true)),
+            ..Default::default()
+        }
+    }
+
+    fn set_temp_file_strategy(&mut self, strategy: TempFileStrategy) {
+        self.temp_files.set_strategy(strategy);
+    }
+
+    async fn update_diagnostics(
+        &mut self,
+        contents: &str,
+    ) -> Result<Vec<Diagnostic>, HandlerError> {
+        let parsed = parse(contents);
+        let mut diagnostics = check(&parsed);
+        diagnostics.extend(parsed.diagnostics);
+        if !self.validate {
+            return Ok(diagnostics);
+        }
+
+        let temp_file = self.temp_files.write(contents)?;
+        let out = output_with_timeout(Command::new("desktop-file-validate").arg(temp_file.path()))?;
+        let validated = Self::parse_validator(&String::from_utf8_lossy(&out.stdout), contents);
+        // The checks above already report most problems of their lines
+        let lines: Vec<u32> = diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.range.start.line)
+            .collect();
+        diagnostics.extend(
+            validated
+                .into_iter()
+                .filter(|diagnostic| !lines.contains(&diagnostic.range.start.line)),
+        );
+        Ok(diagnostics)
+    }
+
+    fn hover(
+        &self,
+        _filetype: &str,
+        _context: &DocumentContext,
+        contents: &str,
+        position: Position,
+    ) -> Result<Option<Hover>, HandlerError> {
+        let Some((key, description, start, end)) = key_at(contents, position) else {
+            return Ok(None);
+        };
+        Ok(Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: format!(
+                    "**{key}=**\n\n{description}\n\n[Reference](https://specifications.freedesktop.org/desktop-entry-spec/latest/recognized-keys.html)"
+                ),
+            }),
+            range: Some(lsp_types::Range::new(
+                Position::new(position.line, start),
+                Position::new(position.line, end),
+            )),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check, key_at, parse, Desktop};
+    use tower_lsp::lsp_types::{DiagnosticSeverity, Position, Range};
+
+    #[test]
+    fn test_missing_name() {
+        let contents = "# Launcher\n[Desktop Entry]\nType=Application\nName[de]=Rechner\nExec=sh -c true\nColour=blue\nX-GNOME-Autostart=true\n";
+        let diagnostics = check(&parse(contents));
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].message, "Missing the required `Name` key");
+        assert_eq!(
+            diagnostics[0].range,
+            Range::new(Position::new(1, 0), Position::new(1, 15))
+        );
+        assert_eq!(
+            diagnostics[1].message,
+            "Unknown key `Colour` in `[Desktop Entry]`, extensions must start with `X-`"
+        );
+        assert_eq!(diagnostics[1].severity, Some(DiagnosticSeverity::WARNING));
+
+        // The localized name is documented as `Name`
+        let (key, _, start, end) = key_at(contents, Position::new(3, 2)).unwrap();
+        assert_eq!((key, start, end), ("Name", 0, 4));
+    }
+
+    #[test]
+    fn test_invalid_type() {
+        let contents = "[Desktop Entry]\nType=App\nName=Calculator\nTryExec=/nonexistent/calculator\n\n[Desktop Action new]\nName=New\nExec=sh\n";
+        let diagnostics = check(&parse(contents));
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(
+            diagnostics[0].message,
+            "Invalid type `App`, expected `Application`, `Link` or `Directory`"
+        );
+        assert_eq!(
+            diagnostics[0].range,
+            Range::new(Position::new(1, 5), Position::new(1, 8))
+        );
+        assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::ERROR));
+        assert_eq!(
+            diagnostics[1].message,
+            "`/nonexistent/calculator` is not installed, the entry is ignored"
+        );
+        assert_eq!(diagnostics[1].severity, Some(DiagnosticSeverity::HINT));
+
+        let output = "/tmp/.tmpAb12.desktop: error: value \"App\" for key \"Type\" in group \"Desktop Entry\" is not a registered type value\n";
+        let validated = Desktop::parse_validator(output, contents);
+        assert_eq!(validated[0].range.start, Position::new(1, 0));
+    }
+}
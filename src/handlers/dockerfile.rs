@@ -0,0 +1,172 @@
+use tower_lsp::lsp_types::{
+    self, Hover, HoverContents, HoverProviderCapability, MarkupContent, MarkupKind, Position,
+    ServerCapabilities,
+};
+
+use super::{DocumentContext, Handler, HandlerError};
+
+/// Documentation of the instructions of Dockerfiles on hover.
+#[derive(Debug)]
+pub struct Dockerfile {}
+
+/// The instructions of Dockerfiles, with what they do.
+const INSTRUCTIONS: &[(&str, &str)] = &[
+    ("ADD", "Copies files, directories or remote URLs into the image, extracting local archives."),
+    ("ARG", "Defines a variable users can pass at build time with `--build-arg`."),
+    ("CMD", "Sets the default command of containers, or the default arguments of `ENTRYPOINT`."),
+    ("COPY", "Copies files or directories from the build context, or another stage with `--from`, into the image."),
+    ("ENTRYPOINT", "Sets the executable containers run, which `CMD` and the arguments of `docker run` are passed to."),
+    ("ENV", "Sets environment variables, for the following instructions and in containers."),
+    ("EXPOSE", "Documents the ports containers listen on, without publishing them."),
+    ("FROM", "Starts a build stage from a base image, optionally naming it with `AS`."),
+    ("HEALTHCHECK", "Sets the command checking that containers are still working."),
+    ("LABEL", "Adds metadata to the image as key-value pairs."),
+    ("MAINTAINER", "Sets the author of the image. Deprecated, use `LABEL` instead."),
+    ("ONBUILD", "Adds an instruction run when the image is used as the base of another build."),
+    ("RUN", "Runs a command in a new layer on top of the image, e.g. to install packages."),
+    ("SHELL", "Sets the shell commands of the shell form of `RUN`, `CMD` and `ENTRYPOINT` run with."),
+    ("STOPSIGNAL", "Sets the signal sent to containers to stop them."),
+    ("USER", "Sets the user and group the following instructions and containers run as."),
+    ("VOLUME", "Creates a mount point for volumes of the host or of other containers."),
+    ("WORKDIR", "Sets the working directory of the following instructions and of containers."),
+];
+
+/// The instruction keyword at `position` of `contents`, with its range on
+/// the line. Only the first word of an instruction is one, or the word
+/// after `ONBUILD`, and not words of lines continuing the previous one.
+fn instruction_at(
+    contents: &str,
+    position: Position,
+) -> Option<(&'static str, &'static str, u32, u32)> {
+    let lines: Vec<&str> = contents.lines().collect();
+    let line = *lines.get(position.line as usize)?;
+    let continued = lines[..position.line as usize]
+        .iter()
+        .rev()
+        .map(|line| line.trim())
+        // Comments and empty lines don't end continuations
+        .find(|line| !line.is_empty() && !line.starts_with('#'))
+        .is_some_and(|previous| previous.ends_with('\\'));
+    if continued {
+        return None;
+    }
+
+    let mut start = line.len() - line.trim_start().len();
+    let mut words = line[start..].split_whitespace();
+    let mut keyword = words.next()?;
+    let character = |offset: usize| line[..offset].encode_utf16().count() as u32;
+    let end = |start: usize, keyword: &str| character(start + keyword.len());
+    if keyword.eq_ignore_ascii_case("ONBUILD") && position.character >= end(start, keyword) {
+        let next = words.next()?;
+        start = line[start + keyword.len()..].find(next)? + start + keyword.len();
+        keyword = next;
+    }
+    let (start, end) = (character(start), end(start, keyword));
+    if !(start..=end).contains(&position.character) {
+        return None;
+    }
+    INSTRUCTIONS
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(keyword))
+        .map(|(name, description)| (*name, *description, start, end))
+}
+
+impl Dockerfile {
+    pub fn new() -> Result<Self, String> {
+        Ok(Self {})
+    }
+}
+
+impl Handler for Dockerfile {
+    fn filetype_supported(&self, filetype: &str) -> bool {
+        filetype == "dockerfile"
+    }
+
+    fn get_capabilities(&self) -> ServerCapabilities {
+        ServerCapabilities {
+            hover_provider: Some(HoverProviderCapability::Simple(true)),
+            ..Default::default()
+        }
+    }
+
+    fn hover(
+        &self,
+        _filetype: &str,
+        _context: &DocumentContext,
+        contents: &str,
+        position: Position,
+    ) -> Result<Option<Hover>, HandlerError> {
+        let Some((name, description, start, end)) = instruction_at(contents, position) else {
+            return Ok(None);
+        };
+        let reference = format!(
+            "https://docs.docker.com/reference/dockerfile/#{}",
+            name.to_lowercase()
+        );
+        Ok(Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: format!("**{name}**\n\n{description}\n\n[Reference]({reference})"),
+            }),
+            range: Some(lsp_types::Range::new(
+                Position::new(position.line, start),
+                Position::new(position.line, end),
+            )),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Dockerfile;
+    use crate::handlers::{DocumentContext, Handler};
+    use tower_lsp::lsp_types::{HoverContents, Position, Range, Url};
+
+    fn hover(contents: &str, position: Position) -> Option<String> {
+        let context = DocumentContext::new(Url::parse("file:///app/Dockerfile").unwrap(), vec![]);
+        let hover = Dockerfile::new()
+            .unwrap()
+            .hover("dockerfile", &context, contents, position)
+            .ok()
+            .unwrap()?;
+        let HoverContents::Markup(markup) = hover.contents else {
+            panic!("Expected markup");
+        };
+        Some(markup.value)
+    }
+
+    #[test]
+    fn test_hover_copy() {
+        let contents = "FROM rust:1.80 AS build\nWORKDIR /src\n  copy --from=deps . .\n";
+        let value = hover(contents, Position::new(2, 4)).unwrap();
+        assert!(value.starts_with("**COPY**\n\nCopies files"));
+        assert!(value.ends_with("(https://docs.docker.com/reference/dockerfile/#copy)"));
+
+        let context = DocumentContext::new(Url::parse("file:///app/Dockerfile").unwrap(), vec![]);
+        let range = Dockerfile::new()
+            .unwrap()
+            .hover("dockerfile", &context, contents, Position::new(2, 2))
+            .ok()
+            .unwrap()
+            .unwrap()
+            .range;
+        assert_eq!(
+            range,
+            Some(Range::new(Position::new(2, 2), Position::new(2, 6)))
+        );
+
+        // After `ONBUILD`
+        let value = hover("ONBUILD RUN make\n", Position::new(0, 9)).unwrap();
+        assert!(value.starts_with("**RUN**"));
+    }
+
+    #[test]
+    fn test_hover_not_instruction() {
+        let contents = "FROM alpine\nRUN apk add \\\n    curl \\\n  # copy\n    copy\n";
+        // An argument
+        assert_eq!(hover(contents, Position::new(0, 7)), None);
+        // A continued line
+        assert_eq!(hover(contents, Position::new(4, 5)), None);
+        assert_eq!(hover(contents, Position::new(9, 0)), None);
+    }
+}
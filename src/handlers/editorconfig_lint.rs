@@ -0,0 +1,292 @@
+use lazy_regex::regex_captures;
+use std::path::Path;
+use tower_lsp::lsp_types::{self, Diagnostic, DiagnosticSeverity, Position};
+
+use super::text::closest;
+use super::{Handler, HandlerError};
+
+/// Validates `.editorconfig` files: the globs of sections, and the names
+/// and values of properties.
+#[derive(Debug)]
+pub struct EditorConfigLint {}
+
+/// The values a property accepts, besides `unset` which every property does.
+enum Values {
+    OneOf(&'static [&'static str]),
+    /// A positive integer, or one of the keywords.
+    Number(&'static [&'static str]),
+    Any,
+}
+
+const PROPERTIES: &[(&str, Values)] = &[
+    ("indent_style", Values::OneOf(&["tab", "space"])),
+    ("indent_size", Values::Number(&["tab"])),
+    ("tab_width", Values::Number(&[])),
+    ("end_of_line", Values::OneOf(&["lf", "cr", "crlf"])),
+    (
+        "charset",
+        Values::OneOf(&["latin1", "utf-8", "utf-8-bom", "utf-16be", "utf-16le"]),
+    ),
+    (
+        "trim_trailing_whitespace",
+        Values::OneOf(&["true", "false"]),
+    ),
+    ("insert_final_newline", Values::OneOf(&["true", "false"])),
+    ("max_line_length", Values::Number(&["off"])),
+    ("spelling_language", Values::Any),
+];
+
+impl Values {
+    fn accepts(&self, value: &str) -> bool {
+        match self {
+            Values::OneOf(keywords) => keywords.contains(&value),
+            Values::Number(keywords) => {
+                keywords.contains(&value) || value.parse::<u32>().is_ok_and(|number| number > 0)
+            }
+            Values::Any => true,
+        }
+    }
+
+    fn describe(&self) -> String {
+        let quoted = |keywords: &[&str]| {
+            keywords
+                .iter()
+                .map(|keyword| format!("`{keyword}`"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        match self {
+            Values::OneOf(keywords) => format!("one of {}", quoted(keywords)),
+            Values::Number([]) => "a positive integer".to_string(),
+            Values::Number(keywords) => format!("a positive integer or {}", quoted(keywords)),
+            Values::Any => "any value".to_string(),
+        }
+    }
+}
+
+/// Why `glob` isn't a valid section glob, of unbalanced brackets or braces.
+fn glob_error(glob: &str) -> Option<String> {
+    if glob.is_empty() {
+        return Some("Empty section glob".to_string());
+    }
+    let mut chars = glob.chars();
+    let mut braces = 0;
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            // A class ends at the next `]`, it doesn't nest
+            '[' if !chars.clone().any(|c| c == ']') => {
+                return Some("Invalid section glob: unclosed `[`".to_string());
+            }
+            '[' => {
+                chars.by_ref().find(|c| *c == ']');
+            }
+            '{' => braces += 1,
+            '}' if braces == 0 => {
+                return Some("Invalid section glob: unmatched `}`".to_string());
+            }
+            '}' => braces -= 1,
+            _ => {}
+        }
+    }
+    (braces > 0).then(|| "Invalid section glob: unclosed `{`".to_string())
+}
+
+impl EditorConfigLint {
+    pub fn new() -> Result<Self, String> {
+        Ok(Self {})
+    }
+
+    /// A diagnostic of the characters `start..end` of `line`.
+    fn diagnostic(
+        line: u32,
+        start: u32,
+        end: u32,
+        severity: DiagnosticSeverity,
+        message: String,
+    ) -> Diagnostic {
+        Diagnostic::new(
+            lsp_types::Range {
+                start: Position::new(line, start),
+                end: Position::new(line, end),
+            },
+            Some(severity),
+            None,
+            Some("editorconfig".to_string()),
+            message,
+            None,
+            None,
+        )
+    }
+
+    pub fn parse(contents: &str) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        let mut in_section = false;
+        for (line, text) in contents.lines().enumerate() {
+            let line = line as u32;
+            let character = |part: &str| {
+                let offset = part.as_ptr() as usize - text.as_ptr() as usize;
+                text[..offset].encode_utf16().count() as u32
+            };
+            let end = |part: &str| character(part) + part.encode_utf16().count() as u32;
+            let trimmed = text.trim();
+            if trimmed.is_empty() || trimmed.starts_with(['#', ';']) {
+                continue;
+            }
+
+            if let Some((_, glob)) = regex_captures!(r#"^\[(.*)\]$"#, trimmed) {
+                in_section = true;
+                if let Some(message) = glob_error(glob) {
+                    diagnostics.push(Self::diagnostic(
+                        line,
+                        character(trimmed),
+                        end(trimmed),
+                        DiagnosticSeverity::ERROR,
+                        message,
+                    ));
+                }
+                continue;
+            }
+
+            let Some((key, value)) = trimmed.split_once('=') else {
+                diagnostics.push(Self::diagnostic(
+                    line,
+                    character(trimmed),
+                    end(trimmed),
+                    DiagnosticSeverity::ERROR,
+                    "Expected a `[section]` or a `property = value`".to_string(),
+                ));
+                continue;
+            };
+            let (key, value) = (key.trim_end(), value.trim_start());
+            let name = key.to_lowercase();
+            let lowercase_value = value.to_lowercase();
+
+            if name == "root" {
+                if in_section {
+                    diagnostics.push(Self::diagnostic(
+                        line,
+                        character(key),
+                        end(key),
+                        DiagnosticSeverity::WARNING,
+                        "`root` is only read before the first section".to_string(),
+                    ));
+                } else if !matches!(lowercase_value.as_str(), "true" | "false") {
+                    diagnostics.push(Self::diagnostic(
+                        line,
+                        character(value),
+                        end(value),
+                        DiagnosticSeverity::ERROR,
+                        format!("Invalid value `{value}` of `root`, expected `true` or `false`"),
+                    ));
+                }
+                continue;
+            }
+            if !in_section {
+                diagnostics.push(Self::diagnostic(
+                    line,
+                    character(key),
+                    end(key),
+                    DiagnosticSeverity::WARNING,
+                    format!("`{key}` is outside of a section and applies to no file"),
+                ));
+                continue;
+            }
+
+            let Some((_, values)) = PROPERTIES.iter().find(|(known, _)| *known == name) else {
+                let names: Vec<&str> = PROPERTIES.iter().map(|(name, _)| *name).collect();
+                let message = match closest(&name, &names) {
+                    Some(suggestion) => {
+                        format!("Unknown property `{key}`, did you mean `{suggestion}`?")
+                    }
+                    None => format!("Unknown property `{key}`"),
+                };
+                diagnostics.push(Self::diagnostic(
+                    line,
+                    character(key),
+                    end(key),
+                    DiagnosticSeverity::WARNING,
+                    message,
+                ));
+                continue;
+            };
+            if lowercase_value != "unset" && !values.accepts(&lowercase_value) {
+                diagnostics.push(Self::diagnostic(
+                    line,
+                    character(value),
+                    end(value),
+                    DiagnosticSeverity::ERROR,
+                    format!(
+                        "Invalid value `{value}` of `{key}`, expected {}",
+                        values.describe()
+                    ),
+                ));
+            }
+        }
+        diagnostics
+    }
+}
+
+impl Handler for EditorConfigLint {
+    fn filetype_supported(&self, filetype: &str) -> bool {
+        filetype == "editorconfig"
+    }
+
+    fn path_supported(&self, path: &Path) -> bool {
+        path.file_name().is_some_and(|name| name == ".editorconfig")
+    }
+
+    async fn update_diagnostics(
+        &mut self,
+        contents: &str,
+    ) -> Result<Vec<Diagnostic>, HandlerError> {
+        Ok(Self::parse(contents))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::EditorConfigLint;
+    use tower_lsp::lsp_types::{DiagnosticSeverity, Position, Range};
+
+    #[test]
+    fn test_unknown_property() {
+        let contents = "root = true\n\n[*]\nindent_style = space\nindnet_size = 4\nfoo_bar = 1\n";
+        let diagnostics = EditorConfigLint::parse(contents);
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(
+            diagnostics[0].message,
+            "Unknown property `indnet_size`, did you mean `indent_size`?"
+        );
+        assert_eq!(
+            diagnostics[0].range,
+            Range::new(Position::new(4, 0), Position::new(4, 11))
+        );
+        assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::WARNING));
+        assert_eq!(diagnostics[1].message, "Unknown property `foo_bar`");
+    }
+
+    #[test]
+    fn test_invalid_values() {
+        let contents = "[*.{js,py]\nindent_style = Tabs\nindent_size = foo\n\n[Makefile]\nindent_style = TAB\nindent_size = unset\nmax_line_length = off\n";
+        let diagnostics = EditorConfigLint::parse(contents);
+        let messages: Vec<&str> = diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.message.as_str())
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                "Invalid section glob: unclosed `{`",
+                "Invalid value `Tabs` of `indent_style`, expected one of `tab`, `space`",
+                "Invalid value `foo` of `indent_size`, expected a positive integer or `tab`",
+            ]
+        );
+        assert_eq!(
+            diagnostics[1].range,
+            Range::new(Position::new(1, 15), Position::new(1, 19))
+        );
+    }
+}
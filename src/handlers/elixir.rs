@@ -0,0 +1,250 @@
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity, OneOf, ServerCapabilities};
+
+use super::process::{
+    format_stdin, output_with_timeout, probe, traverse_parents, JsonArrayParser, JsonDiagnostic,
+    TempFileStrategy, TempFiles, ToolDiagnostic,
+};
+use super::{DocumentContext, Handler, HandlerError};
+
+/// Formatting of Elixir with `mix format`, and the issues of credo in mix
+/// projects.
+#[derive(Debug)]
+pub struct Elixir {
+    temp_files: TempFiles,
+}
+
+/// The report of `mix credo --format json`.
+#[derive(Debug, Deserialize)]
+struct Report {
+    issues: Vec<Issue>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Issue {
+    line_no: u32,
+    column: Option<u32>,
+    column_end: Option<u32>,
+    /// E.g. `readability`, `refactor` or `warning`.
+    category: String,
+    /// The name of the check, e.g. `Credo.Check.Refactor.Nesting`.
+    check: Option<String>,
+    message: String,
+    /// Higher is more important, negative for low priority issues.
+    priority: i32,
+}
+
+impl JsonDiagnostic for Issue {
+    fn into_diagnostic(self) -> Option<ToolDiagnostic> {
+        let severity = if self.category == "warning" {
+            "warning"
+        } else if self.priority < 0 {
+            "hint"
+        } else {
+            "information"
+        };
+        let column = self.column.unwrap_or(1);
+        Some(ToolDiagnostic {
+            line: self.line_no,
+            column,
+            end: self.column_end.map(|end| (self.line_no, end)),
+            severity: Some(severity.to_string()),
+            code: self.check,
+            message: self.message,
+        })
+    }
+}
+
+impl Elixir {
+    pub fn new() -> Result<Self, String> {
+        probe("mix", &["--version"])?;
+        Ok(Self {
+            temp_files: TempFiles::with_suffix(".ex"),
+        })
+    }
+
+    /// The directory of the mix project of `directory`, where credo and
+    /// the formatter settings are.
+    fn project(directory: &Path, root_markers: &[String]) -> Option<PathBuf> {
+        traverse_parents(directory, &["mix.exs"], root_markers, |_| true)
+            .as_deref()
+            .and_then(Path::parent)
+            .map(Path::to_path_buf)
+    }
+
+    fn parser() -> JsonArrayParser<Issue> {
+        // credo reports 1-based positions
+        JsonArrayParser::new("credo").severities(&[
+            ("warning", DiagnosticSeverity::WARNING),
+            ("information", DiagnosticSeverity::INFORMATION),
+            ("hint", DiagnosticSeverity::HINT),
+        ])
+    }
+
+    /// The diagnostics of a credo report. Compiling the project may print
+    /// to stdout before it.
+    pub fn parse(stdout: &str) -> Result<Vec<Diagnostic>, HandlerError> {
+        let start = match stdout.starts_with('{') {
+            true => 0,
+            false => stdout.find("\n{").map_or(0, |start| start + 1),
+        };
+        let report: Report = serde_json::from_str(&stdout[start..])
+            .map_err(|e| HandlerError::Parse(format!("Invalid credo output: {e}")))?;
+        Ok(Self::parser().diagnostics(report.issues))
+    }
+}
+
+impl Handler for Elixir {
+    fn filetype_supported(&self, filetype: &str) -> bool {
+        filetype == "elixir"
+    }
+
+    fn on_save_only(&self) -> bool {
+        // mix is slow to start
+        true
+    }
+
+    fn set_temp_file_strategy(&mut self, strategy: TempFileStrategy) {
+        self.temp_files.set_strategy(strategy);
+    }
+
+    fn get_capabilities(&self) -> ServerCapabilities {
+        ServerCapabilities {
+            document_formatting_provider: Some(OneOf::Left(true)),
+            ..Default::default()
+        }
+    }
+
+    async fn update_diagnostics_with_context(
+        &mut self,
+        context: &DocumentContext,
+        contents: &str,
+    ) -> Result<Vec<Diagnostic>, HandlerError> {
+        // credo is a dependency of the project
+        let Some(project) = context
+            .directory()
+            .and_then(|directory| Self::project(&directory, &context.root_markers))
+        else {
+            return Ok(vec![]);
+        };
+        let temp_file = self.temp_files.write(contents)?;
+
+        let out = output_with_timeout(
+            Command::new("mix")
+                .arg("credo")
+                .arg("--format")
+                .arg("json")
+                .arg(temp_file.path())
+                .current_dir(project),
+        )?;
+        // credo exits with an error when there are issues
+        let stdout = String::from_utf8_lossy(&out.stdout);
+        if !stdout.contains('{') {
+            return Err(HandlerError::Log(
+                String::from_utf8_lossy(&out.stderr).into_owned(),
+            ));
+        }
+        Self::parse(&stdout)
+    }
+
+    async fn format_with_context(
+        &mut self,
+        context: &DocumentContext,
+        _filetype: &str,
+        contents: &str,
+    ) -> Result<Option<String>, HandlerError> {
+        let mut command = Command::new("mix");
+        command.arg("format").arg("-");
+        // For the `.formatter.exs` of the project
+        if let Some(directory) = context.directory() {
+            let project = Self::project(&directory, &context.root_markers);
+            command.current_dir(project.unwrap_or(directory));
+        }
+        format_stdin(&mut command, contents)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Elixir;
+    use crate::handlers::{DocumentContext, Handler};
+    use tower_lsp::lsp_types::{DiagnosticSeverity, NumberOrString, Position, Range, Url};
+
+    #[test]
+    fn test_parse_refactoring_issue() {
+        let stdout = r#"Compiling 1 file (.ex)
+{
+  "issues": [
+    {
+      "category": "refactor",
+      "check": "Credo.Check.Refactor.Nesting",
+      "column": 9,
+      "column_end": 11,
+      "filename": "/tmp/.tmpAbC123.ex",
+      "line_no": 7,
+      "message": "Function body is nested too deep (max depth is 2, was 3).",
+      "priority": 12,
+      "scope": "Shop.Cart.total",
+      "trigger": "if"
+    },
+    {
+      "category": "readability",
+      "check": "Credo.Check.Readability.ModuleDoc",
+      "column": null,
+      "column_end": null,
+      "filename": "/tmp/.tmpAbC123.ex",
+      "line_no": 1,
+      "message": "Modules should have a @moduledoc tag.",
+      "priority": -1,
+      "scope": "Shop.Cart",
+      "trigger": "Shop.Cart"
+    }
+  ]
+}
+"#;
+        let diagnostics = Elixir::parse(stdout).ok().unwrap();
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(
+            diagnostics[0].range,
+            Range::new(Position::new(6, 8), Position::new(6, 10))
+        );
+        assert_eq!(
+            diagnostics[0].severity,
+            Some(DiagnosticSeverity::INFORMATION)
+        );
+        assert_eq!(
+            diagnostics[0].code,
+            Some(NumberOrString::String(
+                "Credo.Check.Refactor.Nesting".to_string()
+            ))
+        );
+        assert_eq!(
+            diagnostics[0].message,
+            "Function body is nested too deep (max depth is 2, was 3)."
+        );
+        assert_eq!(diagnostics[1].range.start, Position::new(0, 0));
+        assert_eq!(diagnostics[1].severity, Some(DiagnosticSeverity::HINT));
+    }
+
+    #[tokio::test]
+    #[ignore = "needs mix"]
+    async fn test_format() {
+        let mut elixir = Elixir::new().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let uri = Url::from_file_path(dir.path().join("cart.ex")).unwrap();
+        let context = DocumentContext::new(uri, vec![]);
+
+        let contents = "defmodule Cart do\ndef total(items), do: Enum.sum( items )\nend\n";
+        let formatted = elixir
+            .format_with_context(&context, "elixir", contents)
+            .await
+            .ok()
+            .unwrap();
+        assert_eq!(
+            formatted.as_deref(),
+            Some("defmodule Cart do\n  def total(items), do: Enum.sum(items)\nend\n")
+        );
+    }
+}
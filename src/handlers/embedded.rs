@@ -0,0 +1,149 @@
+use lazy_regex::regex_captures;
+use tower_lsp::lsp_types::{Diagnostic, Position};
+
+/// A part of a document in another language than the document, checked by
+/// the handlers of that language, e.g. a JavaScript code block of MDX.
+#[derive(Debug, PartialEq)]
+pub struct Region {
+    pub filetype: &'static str,
+    /// Line of the document the region starts on.
+    pub start_line: u32,
+    /// Indentation removed from each line of the region, which is less
+    /// than that of the fence on lines indented less.
+    pub indents: Vec<u32>,
+    pub contents: String,
+}
+
+impl Region {
+    /// Moves `diagnostics` of the region to their position in the document.
+    pub fn to_document(&self, diagnostics: &mut [Diagnostic]) {
+        let to_document = |position: &mut Position| {
+            let indent = self.indents.get(position.line as usize).copied();
+            position.line += self.start_line;
+            position.character += indent.unwrap_or_default();
+        };
+        for diagnostic in diagnostics {
+            to_document(&mut diagnostic.range.start);
+            to_document(&mut diagnostic.range.end);
+        }
+    }
+}
+
+/// The filetype of code blocks tagged `language`.
+fn fence_filetype(language: &str) -> Option<&'static str> {
+    let filetype = match language.to_lowercase().as_str() {
+        "js" | "javascript" | "mjs" => "javascript",
+        "jsx" => "javascriptreact",
+        "ts" | "typescript" => "typescript",
+        "tsx" => "typescriptreact",
+        "json" => "json",
+        "yaml" | "yml" => "yaml",
+        "toml" => "toml",
+        "sh" | "bash" => "sh",
+        "python" | "py" => "python",
+        _ => return None,
+    };
+    Some(filetype)
+}
+
+/// The fenced code blocks of Markdown in `contents` of a known language.
+/// Blocks that are never closed end with the document.
+fn code_blocks(contents: &str) -> Vec<Region> {
+    let mut regions = Vec::new();
+    let mut lines = contents.lines().enumerate();
+    while let Some((line, text)) = lines.next() {
+        let Some((_, indent, fence, info)) =
+            regex_captures!(r#"^( {0,3})(`{3,}|~{3,})\s*([^`\s]*)"#, text)
+        else {
+            continue;
+        };
+        let filetype = fence_filetype(info);
+        let fence_char = fence.chars().next().unwrap_or('`');
+        let mut block = String::new();
+        let mut indents = Vec::new();
+        for (_, text) in lines.by_ref() {
+            // Closed by a fence at least as long
+            let trimmed = text.trim_start_matches(' ');
+            if trimmed.starts_with(fence)
+                && trimmed.trim_start_matches(fence_char).trim().is_empty()
+            {
+                break;
+            }
+            // Lines of the block lose the indentation of the fence
+            let indented = (text.len() - trimmed.len()).min(indent.len());
+            block.push_str(&text[indented..]);
+            indents.push(indented as u32);
+            block.push('\n');
+        }
+        if let Some(filetype) = filetype {
+            regions.push(Region {
+                filetype,
+                start_line: line as u32 + 1,
+                indents,
+                contents: block,
+            });
+        }
+    }
+    regions
+}
+
+/// The regions of other languages embedded in documents of `filetype`,
+/// empty for filetypes embedding none.
+pub fn regions(filetype: &str, contents: &str) -> Vec<Region> {
+    match filetype {
+        // The Markdown of MDX, and its code blocks
+        "mdx" => {
+            let markdown = Region {
+                filetype: "markdown",
+                start_line: 0,
+                indents: Vec::new(),
+                contents: contents.to_string(),
+            };
+            std::iter::once(markdown)
+                .chain(code_blocks(contents))
+                .collect()
+        }
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{regions, Region};
+    use tower_lsp::lsp_types::{Diagnostic, Position, Range};
+
+    #[test]
+    fn test_mdx_code_blocks() {
+        let contents = "# Counter\n\n```jsx\nexport const Counter = () => <b>1</b>\n```\n\n- Item\n   ~~~js\n   let a = 1\n  let b = 2\n   ~~~\n\n```text\nplain\n```\n```ts\nconst x: number = 1\n";
+        let regions = regions("mdx", contents);
+        assert_eq!(regions.len(), 4);
+        assert_eq!(regions[0].filetype, "markdown");
+        assert_eq!(
+            regions[1],
+            Region {
+                filetype: "javascriptreact",
+                start_line: 3,
+                indents: vec![0],
+                contents: "export const Counter = () => <b>1</b>\n".to_string(),
+            }
+        );
+        assert_eq!(regions[2].start_line, 8);
+        assert_eq!(regions[2].indents, vec![3, 2]);
+        assert_eq!(regions[2].contents, "let a = 1\nlet b = 2\n");
+        // On the line indented less than the fence
+        let mut diagnostics = vec![Diagnostic::new_simple(
+            Range::new(Position::new(1, 4), Position::new(1, 5)),
+            "Unused".to_string(),
+        )];
+        regions[2].to_document(&mut diagnostics);
+        assert_eq!(
+            diagnostics[0].range,
+            Range::new(Position::new(9, 6), Position::new(9, 7))
+        );
+        // Unclosed at the end of the document
+        assert_eq!(regions[3].filetype, "typescript");
+        assert_eq!(regions[3].contents, "const x: number = 1\n");
+
+        assert!(super::regions("markdown", contents).is_empty());
+    }
+}
@@ -0,0 +1,108 @@
+use lazy_regex::regex_captures;
+use tower_lsp::lsp_types::Url;
+
+/// The filetype of an interpreter named in a shebang.
+fn interpreter_filetype(interpreter: &str) -> Option<&'static str> {
+    let filetype = match interpreter {
+        "sh" | "bash" | "dash" | "ksh" => "sh",
+        "zsh" => "zsh",
+        "python" | "python2" | "python3" => "python",
+        "node" | "deno" | "bun" => "javascript",
+        "Rscript" => "r",
+        "ruby" => "ruby",
+        "perl" => "perl",
+        "just" => "just",
+        _ => return None,
+    };
+    Some(filetype)
+}
+
+/// The filetype of a well-known file name, for files without a telling
+/// extension.
+fn name_filetype(name: &str) -> Option<&'static str> {
+    let lowercase = name.to_lowercase();
+    let filetype = match lowercase.as_str() {
+        "justfile" | ".justfile" => "just",
+        "makefile" | "gnumakefile" => "make",
+        "jenkinsfile" => "groovy",
+        "containerfile" => "dockerfile",
+        ".gitignore" | ".dockerignore" | ".npmignore" => "gitignore",
+        name if name.starts_with("dockerfile") || name.ends_with(".dockerfile") => "dockerfile",
+        _ => return None,
+    };
+    Some(filetype)
+}
+
+/// The filetype of a document detected from its shebang, its file name or
+/// markers in its contents, for when the language id from the client is
+/// missing or too generic.
+pub fn detect_filetype(uri: &Url, first_line: &str, contents: &str) -> Option<String> {
+    if let Some((_, interpreter)) = regex_captures!(
+        r#"^#!\s*(?:\S*/)?(?:env\s+(?:-\S+\s+)*)?([A-Za-z]+)[\d.]*(?:\s|$)"#,
+        first_line
+    ) {
+        if let Some(filetype) = interpreter_filetype(interpreter) {
+            return Some(filetype.to_string());
+        }
+    }
+
+    let name = uri
+        .path_segments()
+        .and_then(|mut segments| segments.next_back());
+    if let Some(filetype) = name.and_then(name_filetype) {
+        return Some(filetype.to_string());
+    }
+
+    let start = contents.trim_start();
+    let filetype = if start.starts_with("<?xml") {
+        "xml"
+    } else if start
+        .get(..14)
+        .is_some_and(|doctype| doctype.eq_ignore_ascii_case("<!doctype html"))
+    {
+        "html"
+    } else if start.starts_with("%YAML") {
+        "yaml"
+    } else {
+        return None;
+    };
+    Some(filetype.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::detect_filetype;
+    use tower_lsp::lsp_types::Url;
+
+    fn detect(path: &str, contents: &str) -> Option<String> {
+        let uri = Url::from_file_path(path).unwrap();
+        detect_filetype(&uri, contents.lines().next().unwrap_or_default(), contents)
+    }
+
+    #[test]
+    fn test_shebang() {
+        assert_eq!(
+            detect("/bin/deploy", "#!/bin/bash\nset -e\n").as_deref(),
+            Some("sh")
+        );
+        assert_eq!(
+            detect("/bin/deploy", "#!/usr/bin/env -S python3.12 -u\n").as_deref(),
+            Some("python")
+        );
+        assert_eq!(detect("/bin/deploy", "#!/usr/bin/env unknown\n"), None);
+        assert_eq!(detect("/bin/deploy", "echo no shebang\n"), None);
+    }
+
+    #[test]
+    fn test_file_name() {
+        assert_eq!(
+            detect("/project/Dockerfile.prod", "FROM alpine\n").as_deref(),
+            Some("dockerfile")
+        );
+        assert_eq!(detect("/project/Justfile", "").as_deref(), Some("just"));
+        assert_eq!(
+            detect("/project/data", "<?xml version=\"1.0\"?>\n<a/>\n").as_deref(),
+            Some("xml")
+        );
+    }
+}
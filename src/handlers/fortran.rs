@@ -0,0 +1,163 @@
+use lazy_regex::regex;
+use std::path::Path;
+use std::process::Command;
+use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity, OneOf, ServerCapabilities};
+
+use super::process::{
+    format_stdin, probe, run_and_parse, InputMode, RegexLineParser, Stream, TempFileStrategy,
+    TempFiles,
+};
+use super::{Handler, HandlerError};
+
+/// Fortran syntax checking with `gfortran -fsyntax-only` and formatting with
+/// fprettify. Either tool is enough for the handler to run.
+#[derive(Debug)]
+pub struct Fortran {
+    temp_files: TempFiles,
+    /// Whether `gfortran` is available.
+    gfortran: bool,
+    /// Whether `fprettify` is available.
+    fprettify: bool,
+}
+
+fn parse_severity(severity: &str) -> Option<DiagnosticSeverity> {
+    match severity {
+        "Warning" => Some(DiagnosticSeverity::WARNING),
+        _ => Some(DiagnosticSeverity::ERROR),
+    }
+}
+
+impl Fortran {
+    pub fn new() -> Result<Self, String> {
+        let gfortran = probe("gfortran", &["--version"]);
+        let fprettify = probe("fprettify", &["--version"]);
+        if let (Err(gfortran), Err(fprettify)) = (&gfortran, &fprettify) {
+            return Err(format!("{gfortran}, {fprettify}"));
+        }
+        Ok(Self {
+            // gfortran reads `.f90` files as free-form source
+            temp_files: TempFiles::with_suffix(".f90"),
+            gfortran: gfortran.is_ok(),
+            fprettify: fprettify.is_ok(),
+        })
+    }
+
+    /// Parses `path:line:column:` locations and the `Error: message` line
+    /// ending them, after the excerpt of the source unless the output is
+    /// plain.
+    fn parser() -> RegexLineParser {
+        RegexLineParser {
+            regex: regex!(
+                r#"(?m)^(?P<path>[^:\n]+):(?P<line>\d+):(?P<column>\d+):(?: |\n(?:.*\n)*?)(?P<severity>Error|Fatal Error|Warning): (?P<message>.*)$"#
+            ),
+            source: "gfortran",
+            stream: Stream::Stderr,
+            first_line: 1,
+            first_column: 1,
+            severity: parse_severity,
+        }
+    }
+}
+
+impl Handler for Fortran {
+    fn filetype_supported(&self, filetype: &str) -> bool {
+        matches!(filetype, "fortran" | "fortran-free-form")
+    }
+
+    fn set_temp_file_strategy(&mut self, strategy: TempFileStrategy) {
+        self.temp_files.set_strategy(strategy);
+    }
+
+    fn get_capabilities(&self) -> ServerCapabilities {
+        ServerCapabilities {
+            document_formatting_provider: self.fprettify.then_some(OneOf::Left(true)),
+            ..Default::default()
+        }
+    }
+
+    async fn update_diagnostics(
+        &mut self,
+        contents: &str,
+    ) -> Result<Vec<Diagnostic>, HandlerError> {
+        if !self.gfortran {
+            return Ok(Vec::new());
+        }
+        let temp_file = self.temp_files.write(contents)?;
+
+        // Module files of the checked source are written to the temporary
+        // directory instead of the server's working directory
+        let mut command = Command::new("gfortran");
+        command
+            .arg("-fsyntax-only")
+            .arg("-fdiagnostics-color=never")
+            .arg(temp_file.path());
+        if let Some(directory) = temp_file.path().parent() {
+            command.arg("-J").arg(directory);
+        }
+        let messages = run_and_parse(&mut command, InputMode::File, &Self::parser())?;
+        Ok(messages
+            .into_iter()
+            .filter(|(path, _)| {
+                path.as_deref().map(|path| Path::new(path).file_name())
+                    == Some(temp_file.path().file_name())
+            })
+            .map(|(_, diagnostic)| diagnostic)
+            .collect())
+    }
+
+    async fn format(
+        &mut self,
+        _filetype: &str,
+        contents: &str,
+    ) -> Result<Option<String>, HandlerError> {
+        if !self.fprettify {
+            return Ok(None);
+        }
+        // Formats stdin to stdout for `-`
+        format_stdin(Command::new("fprettify").arg("--silent").arg("-"), contents)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Fortran;
+    use crate::handlers::Handler;
+    use tower_lsp::lsp_types::{DiagnosticSeverity, Position};
+
+    #[test]
+    fn test_parse_syntax_error() {
+        let stderr = "/tmp/.tmpAb12.f90:3:13:
+
+    3 |   x = 1.0 +
+      |             1
+Error: Syntax error in expression at (1)
+/tmp/.tmpAb12.f90:5:7: Warning: Unused variable 'y' declared at (1) [-Wunused-variable]
+";
+        let messages = Fortran::parser().parse_text(stderr);
+        assert_eq!(messages.len(), 2);
+
+        let (path, error) = &messages[0];
+        assert_eq!(path.as_deref(), Some("/tmp/.tmpAb12.f90"));
+        assert_eq!(error.range.start, Position::new(2, 12));
+        assert_eq!(error.severity, Some(DiagnosticSeverity::ERROR));
+        assert_eq!(error.message, "Syntax error in expression at (1)");
+
+        let (_, warning) = &messages[1];
+        assert_eq!(warning.range.start, Position::new(4, 6));
+        assert_eq!(warning.severity, Some(DiagnosticSeverity::WARNING));
+    }
+
+    #[tokio::test]
+    #[ignore = "needs fprettify"]
+    async fn test_format() {
+        let mut fortran = Fortran::new().unwrap();
+        assert!(fortran.fprettify, "fprettify is not installed");
+
+        let contents = "program main\nx=1+2\nend program main\n";
+        let formatted = fortran.format("fortran", contents).await.ok().unwrap();
+        assert_eq!(
+            formatted.as_deref(),
+            Some("program main\n   x = 1 + 2\nend program main\n")
+        );
+    }
+}
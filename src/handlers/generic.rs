@@ -0,0 +1,450 @@
+use lazy_regex::{Captures, Regex};
+use serde::Deserialize;
+use std::process::Command;
+use tower_lsp::lsp_types::{
+    self, CodeAction, CodeActionProviderCapability, Diagnostic, DiagnosticSeverity, NumberOrString,
+    Position, ServerCapabilities,
+};
+
+use super::process::{
+    output_with_timeout, run_with_stdin, strip_ansi, TempFileStrategy, TempFiles,
+};
+use super::spelling::{replace_actions, with_corrections};
+use super::{DocumentContext, Handler, HandlerError};
+
+/// A user configured linter, run as an external command whose output is
+/// parsed with regexes.
+///
+/// Output is matched either with a single `pattern`, or with separate
+/// `message_pattern`, `location_pattern` and `severity_pattern` whose n-th
+/// matches are combined into the n-th diagnostic. Patterns are applied to
+/// stdout followed by stderr, so a pattern may span several lines when it
+/// enables `(?s)`, for tools printing the location on a different line
+/// than the message.
+///
+/// Named capture groups:
+/// - `line` (required in `pattern` / `location_pattern`): 1-based line.
+/// - `message` (required in `pattern` / `message_pattern`).
+/// - `column` (optional): 1-based column.
+/// - `severity` (optional, required in `severity_pattern`): `error`,
+///   `warning`, `info` or `hint`, anything else is an error.
+/// - `code` (optional): rule name or number.
+/// - `word` (optional): the text at `column` the diagnostic is about,
+///   which it then spans.
+/// - `corrections` (optional): comma separated replacements of `word`,
+///   which may be quoted, offered as quick fixes, e.g. by spell checkers
+///   like typos.
+#[derive(Debug, Clone, Deserialize)]
+pub struct GenericHandlerConfig {
+    pub name: String,
+    pub filetypes: Vec<String>,
+    pub command: String,
+    /// Arguments, `{file}` is replaced by the path of a temporary file with
+    /// the document contents. Without `{file}` the contents go to stdin.
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Extension of the `{file}` temporary file, e.g. `.py`, for tools
+    /// detecting the language from the file name.
+    #[serde(default)]
+    pub extension: Option<String>,
+    pub pattern: Option<String>,
+    pub message_pattern: Option<String>,
+    pub location_pattern: Option<String>,
+    pub severity_pattern: Option<String>,
+}
+
+#[derive(Debug)]
+enum Patterns {
+    Single(Regex),
+    Separate {
+        message: Regex,
+        location: Regex,
+        severity: Option<Regex>,
+    },
+}
+
+impl Patterns {
+    /// Whether a pattern matching the diagnostics captures `group`.
+    fn captures(&self, group: &str) -> bool {
+        let has_group = |regex: &Regex| regex.capture_names().flatten().any(|name| name == group);
+        match self {
+            Patterns::Single(pattern) => has_group(pattern),
+            Patterns::Separate {
+                message, location, ..
+            } => has_group(message) || has_group(location),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct GenericHandler {
+    config: GenericHandlerConfig,
+    patterns: Patterns,
+    temp_files: TempFiles,
+}
+
+fn compile(
+    config: &GenericHandlerConfig,
+    field: &str,
+    pattern: &str,
+    required: &[&str],
+) -> Result<Regex, String> {
+    let regex = Regex::new(pattern)
+        .map_err(|e| format!("Handler '{}': invalid `{field}`: {e}", config.name))?;
+    let names: Vec<&str> = regex.capture_names().flatten().collect();
+    for group in required {
+        if !names.contains(group) {
+            return Err(format!(
+                "Handler '{}': `{field}` is missing the named capture group `(?P<{group}>...)`",
+                config.name
+            ));
+        }
+    }
+    Ok(regex)
+}
+
+fn parse_severity(severity: Option<&str>) -> DiagnosticSeverity {
+    match severity.map(str::to_lowercase).as_deref() {
+        Some("warning" | "warn" | "w") => DiagnosticSeverity::WARNING,
+        Some("info" | "information" | "note" | "i") => DiagnosticSeverity::INFORMATION,
+        Some("hint" | "style" | "h") => DiagnosticSeverity::HINT,
+        _ => DiagnosticSeverity::ERROR,
+    }
+}
+
+/// 0-based position from the 1-based `line` and `column` groups.
+fn parse_position(captures: &Captures) -> Position {
+    let group = |name: &str| {
+        captures
+            .name(name)
+            .and_then(|value| value.as_str().parse::<u32>().ok())
+            .unwrap_or(1)
+            .saturating_sub(1)
+    };
+    Position::new(group("line"), group("column"))
+}
+
+impl GenericHandler {
+    /// Validates `config`, failing if a pattern doesn't compile or lacks
+    /// the capture groups it needs.
+    pub fn new(config: GenericHandlerConfig) -> Result<Self, String> {
+        let patterns = match (
+            &config.pattern,
+            &config.message_pattern,
+            &config.location_pattern,
+        ) {
+            (Some(pattern), None, None) => Patterns::Single(compile(
+                &config,
+                "pattern",
+                pattern,
+                &["line", "message"],
+            )?),
+            (None, Some(message), Some(location)) => Patterns::Separate {
+                message: compile(&config, "message_pattern", message, &["message"])?,
+                location: compile(&config, "location_pattern", location, &["line"])?,
+                severity: config
+                    .severity_pattern
+                    .as_deref()
+                    .map(|severity| {
+                        compile(&config, "severity_pattern", severity, &["severity"])
+                    })
+                    .transpose()?,
+            },
+            _ => {
+                return Err(format!(
+                    "Handler '{}': set either `pattern`, or both `message_pattern` and `location_pattern`",
+                    config.name
+                ))
+            }
+        };
+        let temp_files = TempFiles::with_suffix(config.extension.clone().unwrap_or_default());
+        Ok(Self {
+            config,
+            patterns,
+            temp_files,
+        })
+    }
+
+    fn diagnostic(
+        &self,
+        position: Position,
+        severity: Option<&str>,
+        code: Option<&str>,
+        word: Option<&str>,
+        corrections: Option<&str>,
+        message: &str,
+    ) -> Diagnostic {
+        let end = Position::new(
+            position.line,
+            position.character + word.map_or(0, |word| word.encode_utf16().count() as u32),
+        );
+        let diagnostic = Diagnostic::new(
+            lsp_types::Range {
+                start: position,
+                end,
+            },
+            Some(parse_severity(severity)),
+            code.map(|code| NumberOrString::String(code.to_string())),
+            Some(self.config.name.clone()),
+            message.trim().to_string(),
+            None,
+            None,
+        );
+        match corrections {
+            Some(corrections) => {
+                let corrections: Vec<String> = corrections
+                    .split(',')
+                    .map(|correction| {
+                        correction.trim_matches(|c: char| c.is_whitespace() || "`'\"".contains(c))
+                    })
+                    .filter(|correction| !correction.is_empty())
+                    .map(str::to_string)
+                    .collect();
+                with_corrections(diagnostic, &corrections)
+            }
+            None => diagnostic,
+        }
+    }
+
+    pub fn parse_output(&self, output: &str) -> Vec<Diagnostic> {
+        let output = strip_ansi(output);
+        match &self.patterns {
+            Patterns::Single(pattern) => pattern
+                .captures_iter(&output)
+                .map(|captures| {
+                    self.diagnostic(
+                        parse_position(&captures),
+                        captures.name("severity").map(|m| m.as_str()),
+                        captures.name("code").map(|m| m.as_str()),
+                        captures.name("word").map(|m| m.as_str()),
+                        captures.name("corrections").map(|m| m.as_str()),
+                        &captures["message"],
+                    )
+                })
+                .collect(),
+            Patterns::Separate {
+                message,
+                location,
+                severity,
+            } => {
+                let mut severities = severity
+                    .as_ref()
+                    .map(|severity| severity.captures_iter(&output).collect::<Vec<_>>())
+                    .unwrap_or_default()
+                    .into_iter();
+                message
+                    .captures_iter(&output)
+                    .zip(location.captures_iter(&output))
+                    .map(|(message, location)| {
+                        let severity = severities.next();
+                        let severity = severity
+                            .as_ref()
+                            .and_then(|captures| captures.name("severity"))
+                            .or_else(|| message.name("severity"))
+                            .map(|m| m.as_str());
+                        let group = |name: &str| {
+                            message
+                                .name(name)
+                                .or_else(|| location.name(name))
+                                .map(|m| m.as_str())
+                        };
+                        self.diagnostic(
+                            parse_position(&location),
+                            severity,
+                            group("code"),
+                            group("word"),
+                            group("corrections"),
+                            &message["message"],
+                        )
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+impl Handler for GenericHandler {
+    fn filetype_supported(&self, filetype: &str) -> bool {
+        self.config
+            .filetypes
+            .iter()
+            .any(|supported| supported == filetype)
+    }
+
+    fn get_capabilities(&self) -> ServerCapabilities {
+        ServerCapabilities {
+            code_action_provider: self
+                .patterns
+                .captures("corrections")
+                .then_some(CodeActionProviderCapability::Simple(true)),
+            ..Default::default()
+        }
+    }
+
+    fn set_temp_file_strategy(&mut self, strategy: TempFileStrategy) {
+        self.temp_files.set_strategy(strategy);
+    }
+
+    async fn update_diagnostics(
+        &mut self,
+        contents: &str,
+    ) -> Result<Vec<Diagnostic>, HandlerError> {
+        let mut command = Command::new(&self.config.command);
+        let out = if self.config.args.iter().any(|arg| arg.contains("{file}")) {
+            let temp_file = self.temp_files.write(contents)?;
+            let path = temp_file.path().to_string_lossy();
+            output_with_timeout(
+                command.args(
+                    self.config
+                        .args
+                        .iter()
+                        .map(|arg| arg.replace("{file}", &path)),
+                ),
+            )?
+        } else {
+            run_with_stdin(command.args(&self.config.args), contents)?
+        };
+
+        let output = format!(
+            "{}\n{}",
+            String::from_utf8_lossy(&out.stdout),
+            String::from_utf8_lossy(&out.stderr)
+        );
+        Ok(self.parse_output(&output))
+    }
+
+    fn code_actions(
+        &self,
+        context: &DocumentContext,
+        diagnostics: &[Diagnostic],
+    ) -> Result<Vec<CodeAction>, HandlerError> {
+        Ok(replace_actions(
+            &context.uri,
+            &self.config.name,
+            diagnostics,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GenericHandler, GenericHandlerConfig};
+    use crate::handlers::{DocumentContext, Handler};
+    use tower_lsp::lsp_types::{DiagnosticSeverity, NumberOrString, Position, Range, Url};
+
+    fn config() -> GenericHandlerConfig {
+        GenericHandlerConfig {
+            name: "lint".to_string(),
+            filetypes: vec!["txt".to_string()],
+            command: "lint".to_string(),
+            args: vec![],
+            extension: None,
+            pattern: None,
+            message_pattern: None,
+            location_pattern: None,
+            severity_pattern: None,
+        }
+    }
+
+    #[test]
+    fn test_single_line() {
+        let handler = GenericHandler::new(GenericHandlerConfig {
+            pattern: Some(
+                r#"(?m)^[^:]+:(?P<line>\d+):(?P<column>\d+): (?P<severity>\w+) \[(?P<code>\w+)\] (?P<message>.*)$"#
+                    .to_string(),
+            ),
+            ..config()
+        })
+        .unwrap();
+
+        let output =
+            "a.txt:3:5: warning [W001] Trailing whitespace\na.txt:10:1: error [E002] Bad\n";
+        let diagnostics = handler.parse_output(output);
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].range.start, Position::new(2, 4));
+        assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::WARNING));
+        assert_eq!(
+            diagnostics[0].code,
+            Some(NumberOrString::String("W001".to_string()))
+        );
+        assert_eq!(diagnostics[0].message, "Trailing whitespace");
+        assert_eq!(diagnostics[1].severity, Some(DiagnosticSeverity::ERROR));
+    }
+
+    #[test]
+    fn test_multi_line() {
+        let output = "error: Unknown start of token:
+ ——▶ justfile:7:13
+  │
+7 │   just something here
+  │             ^
+warning: Unused variable
+ ——▶ justfile:2:1
+";
+        let single = GenericHandler::new(GenericHandlerConfig {
+            pattern: Some(
+                r#"(?s)(?P<severity>error|warning): (?P<message>[^\n]*)\n.*?——▶ [^:]*:(?P<line>\d+):(?P<column>\d+)"#
+                    .to_string(),
+            ),
+            ..config()
+        })
+        .unwrap();
+        let separate = GenericHandler::new(GenericHandlerConfig {
+            message_pattern: Some(r#"(?m)^(?:error|warning): (?P<message>.*)$"#.to_string()),
+            location_pattern: Some(r#"——▶ [^:]*:(?P<line>\d+):(?P<column>\d+)"#.to_string()),
+            severity_pattern: Some(r#"(?m)^(?P<severity>error|warning):"#.to_string()),
+            ..config()
+        })
+        .unwrap();
+
+        for handler in [single, separate] {
+            let diagnostics = handler.parse_output(output);
+            assert_eq!(diagnostics.len(), 2);
+            assert_eq!(diagnostics[0].range.start, Position::new(6, 12));
+            assert_eq!(diagnostics[0].message, "Unknown start of token:");
+            assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::ERROR));
+            assert_eq!(diagnostics[1].range.start, Position::new(1, 0));
+            assert_eq!(diagnostics[1].severity, Some(DiagnosticSeverity::WARNING));
+        }
+    }
+
+    #[test]
+    fn test_missing_group() {
+        let err = GenericHandler::new(GenericHandlerConfig {
+            pattern: Some(r#"(?P<message>.*) at (\d+)"#.to_string()),
+            ..config()
+        })
+        .unwrap_err();
+        assert_eq!(
+            err,
+            "Handler 'lint': `pattern` is missing the named capture group `(?P<line>...)`"
+        );
+
+        assert!(GenericHandler::new(config()).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_correction_actions() {
+        // The brief format of typos
+        let handler = GenericHandler::new(GenericHandlerConfig {
+            name: "typos".to_string(),
+            pattern: Some(
+                r#"(?m)^[^:]+:(?P<line>\d+):(?P<column>\d+): (?P<message>`(?P<word>[^`]+)` -> (?P<corrections>.*))$"#
+                    .to_string(),
+            ),
+            ..config()
+        })
+        .unwrap();
+        assert!(handler.get_capabilities().code_action_provider.is_some());
+
+        let diagnostics = handler.parse_output("-:3:5: `ot` -> `to`, `of`\n");
+        assert_eq!(
+            diagnostics[0].range,
+            Range::new(Position::new(2, 4), Position::new(2, 6))
+        );
+        let context = DocumentContext::new(Url::parse("file:///tmp/a.txt").unwrap(), Vec::new());
+        let actions = handler.code_actions(&context, &diagnostics).ok().unwrap();
+        let titles: Vec<&str> = actions.iter().map(|action| action.title.as_str()).collect();
+        assert_eq!(titles, vec!["Replace with `to`", "Replace with `of`"]);
+    }
+}
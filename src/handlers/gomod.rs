@@ -0,0 +1,225 @@
+use lazy_regex::regex;
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path::Path;
+use tower_lsp::lsp_types::{self, Diagnostic, DiagnosticSeverity};
+
+use super::text::offset_to_position;
+use super::{DocumentContext, Handler, HandlerError};
+
+/// Checks `go.mod` files for an invalid `go` version, modules required more
+/// than once and `replace` directives pointing to missing directories.
+#[derive(Debug)]
+pub struct GoMod {}
+
+/// A directive of `go.mod`, either on its own line or inside a block like
+/// `require ( ... )`, with the byte ranges of its arguments.
+struct Directive<'a> {
+    verb: &'a str,
+    arguments: Vec<(&'a str, Range<usize>)>,
+}
+
+/// The directives of `contents`, without comments.
+fn directives(contents: &str) -> Vec<Directive<'_>> {
+    let mut directives = Vec::new();
+    // The verb of the block being read
+    let mut block: Option<&str> = None;
+    let mut line_start = 0;
+    for line in contents.split('\n') {
+        let code = line.split("//").next().unwrap_or_default();
+        let mut arguments: Vec<(&str, Range<usize>)> = regex!(r#""[^"]*"|`[^`]*`|[^\s"`]+"#)
+            .find_iter(code)
+            .map(|word| {
+                (
+                    word.as_str(),
+                    line_start + word.start()..line_start + word.end(),
+                )
+            })
+            .collect();
+        line_start += line.len() + 1;
+
+        if let Some(verb) = block {
+            if arguments.first().is_some_and(|(word, _)| *word == ")") {
+                block = None;
+            } else if !arguments.is_empty() {
+                directives.push(Directive { verb, arguments });
+            }
+            continue;
+        }
+        if arguments.is_empty() {
+            continue;
+        }
+        let (verb, _) = arguments.remove(0);
+        if arguments.len() == 1 && arguments[0].0 == "(" {
+            block = Some(verb);
+        } else {
+            directives.push(Directive { verb, arguments });
+        }
+    }
+    directives
+}
+
+impl GoMod {
+    pub fn new() -> Result<Self, String> {
+        Ok(Self {})
+    }
+
+    /// Diagnostics of `contents`, with local `replace` targets resolved
+    /// relative to `directory`, the directory of `go.mod`.
+    pub fn parse(contents: &str, directory: Option<&Path>) -> Vec<Diagnostic> {
+        let diagnostic = |range: &Range<usize>, severity, message| {
+            Diagnostic::new(
+                lsp_types::Range::new(
+                    offset_to_position(contents, range.start),
+                    offset_to_position(contents, range.end),
+                ),
+                Some(severity),
+                None,
+                Some("go.mod".to_string()),
+                message,
+                None,
+                None,
+            )
+        };
+
+        let mut diagnostics = Vec::new();
+        let mut required: HashMap<&str, u32> = HashMap::new();
+        for directive in directives(contents) {
+            match (directive.verb, directive.arguments.as_slice()) {
+                ("go", [(version, range)])
+                    if !regex!(r#"^1(?:\.\d+){1,2}(?:(?:rc|beta)\d+)?$"#).is_match(version) =>
+                {
+                    diagnostics.push(diagnostic(
+                        range,
+                        DiagnosticSeverity::ERROR,
+                        format!("Invalid Go version '{version}', expected e.g. '1.22'"),
+                    ));
+                }
+                ("require", [(module, range), ..]) => {
+                    let line = offset_to_position(contents, range.start).line;
+                    if let Some(first) = required.get(module) {
+                        diagnostics.push(diagnostic(
+                            range,
+                            DiagnosticSeverity::WARNING,
+                            format!(
+                                "Module '{module}' is already required on line {}",
+                                first + 1
+                            ),
+                        ));
+                    } else {
+                        required.insert(module, line);
+                    }
+                }
+                ("replace", arguments) => {
+                    // `old [version] => new [version]`, new is a directory
+                    // when it is a relative or absolute path
+                    let target = arguments
+                        .iter()
+                        .skip_while(|(word, _)| *word != "=>")
+                        .nth(1);
+                    let Some((target, range)) = target else {
+                        continue;
+                    };
+                    let is_local = target.starts_with("./")
+                        || target.starts_with("../")
+                        || Path::new(target).is_absolute();
+                    let Some(directory) = directory.filter(|_| is_local) else {
+                        continue;
+                    };
+                    if !directory.join(target).is_dir() {
+                        diagnostics.push(diagnostic(
+                            range,
+                            DiagnosticSeverity::ERROR,
+                            format!("Replacement directory '{target}' does not exist"),
+                        ));
+                    }
+                }
+                _ => {}
+            }
+        }
+        diagnostics
+    }
+}
+
+impl Handler for GoMod {
+    fn filetype_supported(&self, _filetype: &str) -> bool {
+        false
+    }
+
+    fn path_supported(&self, path: &Path) -> bool {
+        path.file_name().is_some_and(|name| name == "go.mod")
+    }
+
+    async fn update_diagnostics(
+        &mut self,
+        contents: &str,
+    ) -> Result<Vec<Diagnostic>, HandlerError> {
+        Ok(Self::parse(contents, None))
+    }
+
+    async fn update_diagnostics_with_context(
+        &mut self,
+        context: &DocumentContext,
+        contents: &str,
+    ) -> Result<Vec<Diagnostic>, HandlerError> {
+        Ok(Self::parse(contents, context.directory().as_deref()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GoMod;
+    use tower_lsp::lsp_types::{DiagnosticSeverity, Position, Range};
+
+    #[test]
+    fn test_duplicate_require() {
+        let contents = r#"module example.com/app
+
+go 1.22
+
+require github.com/pkg/errors v0.9.1
+
+require (
+	golang.org/x/sync v0.7.0 // indirect
+	github.com/pkg/errors v0.8.0
+)
+"#;
+        let diagnostics = GoMod::parse(contents, None);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].message,
+            "Module 'github.com/pkg/errors' is already required on line 5"
+        );
+        assert_eq!(
+            diagnostics[0].range,
+            Range::new(Position::new(8, 1), Position::new(8, 22))
+        );
+        assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::WARNING));
+    }
+
+    #[test]
+    fn test_missing_replace_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("lib")).unwrap();
+        let contents = r#"module example.com/app
+
+go 1.x
+
+replace (
+	example.com/lib => ./lib
+	example.com/util v1.0.0 => ../util
+	example.com/fork => github.com/me/fork v1.2.0
+)
+"#;
+        let diagnostics = GoMod::parse(contents, Some(dir.path()));
+        let messages: Vec<&str> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "Invalid Go version '1.x', expected e.g. '1.22'",
+                "Replacement directory '../util' does not exist",
+            ]
+        );
+        assert_eq!(diagnostics[1].range.start, Position::new(6, 28));
+    }
+}
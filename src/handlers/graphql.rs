@@ -0,0 +1,180 @@
+use serde::Deserialize;
+use std::process::Command;
+use tower_lsp::lsp_types::{Diagnostic, OneOf, ServerCapabilities};
+
+use super::process::{
+    format_stdin, output_with_timeout, probe, strip_ansi, JsonArrayParser, JsonDiagnostic,
+    TempFileStrategy, TempFiles, ToolDiagnostic,
+};
+use super::{Handler, HandlerError};
+
+/// GraphQL schema linting with graphql-schema-linter, and formatting with
+/// Prettier. Either tool is enough for the handler to run.
+#[derive(Debug)]
+pub struct GraphQl {
+    temp_files: TempFiles,
+    /// Whether `graphql-schema-linter` is available.
+    lint: bool,
+    /// Whether `prettier` is available.
+    format: bool,
+}
+
+/// The report of `graphql-schema-linter --format json`.
+#[derive(Debug, Deserialize)]
+struct Report {
+    errors: Vec<LintError>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LintError {
+    message: String,
+    location: Option<Location>,
+    rule: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Location {
+    line: u32,
+    column: u32,
+}
+
+impl JsonDiagnostic for LintError {
+    fn into_diagnostic(self) -> Option<ToolDiagnostic> {
+        let location = self.location?;
+        Some(ToolDiagnostic {
+            line: location.line,
+            column: location.column,
+            code: self.rule,
+            message: self.message,
+            ..Default::default()
+        })
+    }
+}
+
+impl GraphQl {
+    pub fn new() -> Result<Self, String> {
+        let lint = probe("graphql-schema-linter", &["--version"]);
+        let format = probe("prettier", &["--version"]);
+        if let (Err(lint), Err(format)) = (&lint, &format) {
+            return Err(format!("{lint}, {format}"));
+        }
+        Ok(Self {
+            temp_files: TempFiles::with_suffix(".graphql"),
+            lint: lint.is_ok(),
+            format: format.is_ok(),
+        })
+    }
+
+    fn parse_report(text: &str) -> Result<Vec<Diagnostic>, HandlerError> {
+        let report: Report = serde_json::from_str(text).map_err(|e| {
+            HandlerError::Parse(format!("Invalid graphql-schema-linter output: {e}"))
+        })?;
+        Ok(JsonArrayParser::new("graphql-schema-linter").diagnostics(report.errors))
+    }
+}
+
+impl Handler for GraphQl {
+    fn filetype_supported(&self, filetype: &str) -> bool {
+        filetype == "graphql"
+    }
+
+    fn set_temp_file_strategy(&mut self, strategy: TempFileStrategy) {
+        self.temp_files.set_strategy(strategy);
+    }
+
+    fn get_capabilities(&self) -> ServerCapabilities {
+        ServerCapabilities {
+            document_formatting_provider: self.format.then_some(OneOf::Left(true)),
+            ..Default::default()
+        }
+    }
+
+    async fn update_diagnostics(
+        &mut self,
+        contents: &str,
+    ) -> Result<Vec<Diagnostic>, HandlerError> {
+        if !self.lint {
+            return Ok(Vec::new());
+        }
+        let temp_file = self.temp_files.write(contents)?;
+
+        let out = output_with_timeout(
+            Command::new("graphql-schema-linter")
+                .arg("--format")
+                .arg("json")
+                .arg(temp_file.path()),
+        )?;
+        // Exits with an error when there are lint errors, without a report
+        // when it couldn't run
+        let stdout = String::from_utf8_lossy(&out.stdout);
+        if stdout.trim().is_empty() {
+            if out.status.success() {
+                return Ok(Vec::new());
+            }
+            return Err(HandlerError::Log(strip_ansi(&String::from_utf8_lossy(
+                &out.stderr,
+            ))));
+        }
+        Self::parse_report(&stdout)
+    }
+
+    async fn format(
+        &mut self,
+        _filetype: &str,
+        contents: &str,
+    ) -> Result<Option<String>, HandlerError> {
+        if !self.format {
+            return Ok(None);
+        }
+        format_stdin(
+            Command::new("prettier")
+                .arg("--stdin-filepath")
+                .arg("schema.graphql"),
+            contents,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GraphQl;
+    use crate::handlers::Handler;
+    use tower_lsp::lsp_types::{DiagnosticSeverity, NumberOrString, Position};
+
+    #[test]
+    fn test_parse_fields_have_descriptions() {
+        let stdout = r#"{"errors":[{"message":"The field `Query.user` is missing a description.","location":{"line":2,"column":3,"file":"/tmp/.tmpx1.graphql"},"rule":"fields-have-descriptions"}]}"#;
+        let diagnostics = GraphQl::parse_report(stdout).ok().unwrap();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].range.start, Position::new(1, 2));
+        assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::WARNING));
+        assert_eq!(
+            diagnostics[0].code,
+            Some(NumberOrString::String(
+                "fields-have-descriptions".to_string()
+            ))
+        );
+        assert_eq!(
+            diagnostics[0].message,
+            "The field `Query.user` is missing a description."
+        );
+        assert_eq!(
+            diagnostics[0].source.as_deref(),
+            Some("graphql-schema-linter")
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "needs prettier"]
+    async fn test_format() {
+        let mut graphql = GraphQl::new().unwrap();
+        assert!(graphql.format, "prettier is not installed");
+
+        let contents = "type Query{user(id:ID!):User}\n";
+        let formatted = graphql.format("graphql", contents).await.ok().unwrap();
+        assert_eq!(
+            formatted.as_deref(),
+            Some("type Query {\n  user(id: ID!): User\n}\n")
+        );
+    }
+}
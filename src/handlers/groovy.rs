@@ -0,0 +1,153 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+use std::process::Command;
+use tower_lsp::lsp_types::{self, Diagnostic, DiagnosticSeverity, NumberOrString, Position};
+
+use super::process::{probe, run_with_stdin};
+use super::{Handler, HandlerError};
+
+/// Groovy and Jenkinsfile linting with `npm-groovy-lint`.
+#[derive(Debug)]
+pub struct Groovy {}
+
+#[derive(Debug, Deserialize)]
+struct Report {
+    files: HashMap<String, FileReport>,
+}
+
+#[derive(Debug, Deserialize)]
+struct FileReport {
+    #[serde(default)]
+    errors: Vec<LintError>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LintError {
+    line: Option<u32>,
+    rule: String,
+    msg: String,
+    severity: String,
+}
+
+fn parse_severity(severity: &str) -> DiagnosticSeverity {
+    match severity {
+        "error" => DiagnosticSeverity::ERROR,
+        "warning" => DiagnosticSeverity::WARNING,
+        "info" => DiagnosticSeverity::INFORMATION,
+        _ => {
+            log::info!("Unknown severity when parsing npm-groovy-lint output: '{severity}'");
+            DiagnosticSeverity::WARNING
+        }
+    }
+}
+
+impl Groovy {
+    pub fn new() -> Result<Self, String> {
+        probe("npm-groovy-lint", &["--version"])?;
+        Ok(Self {})
+    }
+
+    pub fn parse_stdout(contents: &str) -> Result<Vec<Diagnostic>, HandlerError> {
+        let report: Report = serde_json::from_str(contents)
+            .map_err(|e| HandlerError::Parse(format!("Invalid npm-groovy-lint output: {e}")))?;
+
+        Ok(report
+            .files
+            .into_values()
+            .flat_map(|file| file.errors)
+            .map(|error| {
+                // Positions are 1-based, errors without one apply to the file
+                let line = error.line.unwrap_or(1).saturating_sub(1);
+                Diagnostic::new(
+                    lsp_types::Range {
+                        start: Position::new(line, 0),
+                        end: Position::new(line, 0),
+                    },
+                    Some(parse_severity(&error.severity)),
+                    Some(NumberOrString::String(error.rule)),
+                    Some("npm-groovy-lint".to_string()),
+                    error.msg,
+                    None,
+                    None,
+                )
+            })
+            .collect())
+    }
+}
+
+impl Handler for Groovy {
+    fn filetype_supported(&self, filetype: &str) -> bool {
+        matches!(filetype, "groovy" | "jenkinsfile")
+    }
+
+    fn path_supported(&self, path: &Path) -> bool {
+        path.file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name == "Jenkinsfile" || name.starts_with("Jenkinsfile."))
+    }
+
+    async fn update_diagnostics(
+        &mut self,
+        contents: &str,
+    ) -> Result<Vec<Diagnostic>, HandlerError> {
+        let out = run_with_stdin(
+            Command::new("npm-groovy-lint")
+                .arg("--output")
+                .arg("json")
+                .arg("-"),
+            contents,
+        )?;
+
+        // Exits with an error code when there are findings
+        Self::parse_stdout(&String::from_utf8_lossy(&out.stdout))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Groovy;
+    use crate::handlers::Handler;
+    use std::path::Path;
+    use tower_lsp::lsp_types::{DiagnosticSeverity, NumberOrString};
+
+    #[test]
+    fn test_parse_unused_import() {
+        let stdout = r#"{
+  "files": {
+    "0": {
+      "errors": [
+        {
+          "id": 0,
+          "line": 2,
+          "rule": "UnusedImport",
+          "severity": "warning",
+          "msg": "The [java.util.List] import is never referenced"
+        }
+      ]
+    }
+  },
+  "summary": { "totalFoundNumber": 1 }
+}"#;
+        let diagnostics = Groovy::parse_stdout(stdout).ok().unwrap();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].range.start.line, 1);
+        assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::WARNING));
+        assert_eq!(
+            diagnostics[0].code,
+            Some(NumberOrString::String("UnusedImport".to_string()))
+        );
+        assert_eq!(
+            diagnostics[0].message,
+            "The [java.util.List] import is never referenced"
+        );
+    }
+
+    #[test]
+    fn test_path_supported() {
+        let groovy = Groovy {};
+        assert!(groovy.path_supported(Path::new("/project/Jenkinsfile")));
+        assert!(groovy.path_supported(Path::new("/project/Jenkinsfile.release")));
+        assert!(!groovy.path_supported(Path::new("/project/justfile")));
+    }
+}
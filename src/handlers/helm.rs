@@ -0,0 +1,188 @@
+use lazy_regex::{regex, regex_captures};
+use std::path::{Component, Path, PathBuf};
+use std::process::Command;
+use tower_lsp::lsp_types::{self, Diagnostic, DiagnosticSeverity, Position};
+
+use super::process::{output_with_timeout, probe};
+use super::{DocumentContext, Handler, HandlerError};
+
+/// Helm chart linting with `helm lint`, for templates in the `templates`
+/// directory of a chart. Helm lints the whole chart from disk, so results
+/// follow the saved files.
+#[derive(Debug)]
+pub struct Helm {}
+
+fn parse_severity(severity: &str) -> DiagnosticSeverity {
+    match severity {
+        "ERROR" => DiagnosticSeverity::ERROR,
+        "WARNING" => DiagnosticSeverity::WARNING,
+        _ => DiagnosticSeverity::INFORMATION,
+    }
+}
+
+impl Helm {
+    pub fn new() -> Result<Self, String> {
+        probe("helm", &["version"])?;
+        Ok(Self {})
+    }
+
+    /// The root of the chart `path` is a template of: the closest parent
+    /// with a `Chart.yaml`, if `path` is in its `templates` directory.
+    pub fn chart_root(path: &Path) -> Option<PathBuf> {
+        let root = path
+            .ancestors()
+            .skip(1)
+            .find(|directory| directory.join("Chart.yaml").is_file())?;
+        let relative = path.strip_prefix(root).ok()?;
+        match relative.components().next() {
+            Some(Component::Normal(name)) if name == "templates" => Some(root.to_path_buf()),
+            _ => None,
+        }
+    }
+
+    /// Diagnostics of `helm lint` for the template at `template`, relative
+    /// to the chart root, e.g. `templates/deployment.yaml`.
+    pub fn parse_stdout(contents: &str, template: &str) -> Vec<Diagnostic> {
+        contents
+            .lines()
+            .filter_map(|line| {
+                let (_, severity, path, message) =
+                    regex_captures!(r#"^\[(ERROR|WARNING|INFO)\]\s+([^:]*):\s*(.*)$"#, line)?;
+
+                // Rendering errors name the template with its position,
+                // prefixed with the chart name
+                let rendered = regex!(r#"(?:^|[\s/])(templates/[^\s:]+):(\d+)(?::(\d+))?:\s*(.*)"#)
+                    .captures(message);
+                let (file, line, column, message): (&str, u32, u32, &str) = match &rendered {
+                    Some(captures) => (
+                        &captures[1],
+                        captures[2].parse().unwrap_or(1),
+                        captures
+                            .get(3)
+                            .map_or(1, |c| c.as_str().parse().unwrap_or(1)),
+                        captures.get(4).map_or(message, |c| c.as_str()),
+                    ),
+                    None => {
+                        let line = regex_captures!(r#"\bline (\d+)"#, message)
+                            .map_or(1, |(_, line)| line.parse().unwrap_or(1));
+                        (path, line, 1, message)
+                    }
+                };
+                if file != template {
+                    return None;
+                }
+
+                // helm reports 1-based positions
+                let position = Position::new(line.saturating_sub(1), column.saturating_sub(1));
+                Some(Diagnostic::new(
+                    lsp_types::Range {
+                        start: position,
+                        end: position,
+                    },
+                    Some(parse_severity(severity)),
+                    None,
+                    Some("helm".to_string()),
+                    message.to_string(),
+                    None,
+                    None,
+                ))
+            })
+            .collect()
+    }
+}
+
+impl Handler for Helm {
+    fn filetype_supported(&self, _filetype: &str) -> bool {
+        false
+    }
+
+    fn path_supported(&self, path: &Path) -> bool {
+        Self::chart_root(path).is_some()
+    }
+
+    async fn update_diagnostics(
+        &mut self,
+        _contents: &str,
+    ) -> Result<Vec<Diagnostic>, HandlerError> {
+        // Charts are linted from disk
+        Ok(Vec::new())
+    }
+
+    async fn update_diagnostics_with_context(
+        &mut self,
+        context: &DocumentContext,
+        _contents: &str,
+    ) -> Result<Vec<Diagnostic>, HandlerError> {
+        let Ok(path) = context.uri.to_file_path() else {
+            return Ok(Vec::new());
+        };
+        let Some(root) = Self::chart_root(&path) else {
+            return Ok(Vec::new());
+        };
+        let template = path
+            .strip_prefix(&root)
+            .map_err(|e| HandlerError::Log(format!("{e}")))?
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+
+        let out = output_with_timeout(
+            Command::new("helm")
+                .arg("lint")
+                .arg(&root)
+                .current_dir(&root),
+        )?;
+
+        // Exits with 1 when the chart has errors
+        if out.stdout.is_empty() {
+            return Err(HandlerError::Log(
+                String::from_utf8_lossy(&out.stderr).into_owned(),
+            ));
+        }
+        Ok(Self::parse_stdout(
+            &String::from_utf8_lossy(&out.stdout),
+            &template,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Helm;
+    use tower_lsp::lsp_types::{DiagnosticSeverity, Position};
+
+    #[test]
+    fn test_parse_render_error() {
+        let stdout = r#"==> Linting .
+[INFO] Chart.yaml: icon is recommended
+[ERROR] templates/: template: web/templates/deployment.yaml:15:20: executing "web/templates/deployment.yaml" at <.Values.image.tag>: nil pointer evaluating interface {}.tag
+[WARNING] templates/service.yaml: object name does not conform to Kubernetes naming requirements
+
+Error: 1 chart(s) linted, 1 chart(s) failed
+"#;
+        let diagnostics = Helm::parse_stdout(stdout, "templates/deployment.yaml");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].range.start, Position::new(14, 19));
+        assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::ERROR));
+        assert!(diagnostics[0]
+            .message
+            .starts_with("executing \"web/templates/deployment.yaml\" at <.Values.image.tag>"));
+
+        let diagnostics = Helm::parse_stdout(stdout, "templates/service.yaml");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::WARNING));
+    }
+
+    #[test]
+    fn test_chart_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let templates = dir.path().join("templates/config");
+        std::fs::create_dir_all(&templates).unwrap();
+        std::fs::write(dir.path().join("Chart.yaml"), "name: web\n").unwrap();
+
+        let template = templates.join("map.yaml");
+        assert_eq!(Helm::chart_root(&template).as_deref(), Some(dir.path()));
+        assert_eq!(Helm::chart_root(&dir.path().join("values.yaml")), None);
+    }
+}
@@ -0,0 +1,392 @@
+use lazy_regex::{regex, regex_captures};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use tower_lsp::lsp_types::{
+    self, Diagnostic, DiagnosticSeverity, Hover, HoverContents, HoverProviderCapability,
+    MarkupContent, MarkupKind, Position, ServerCapabilities,
+};
+
+use super::{DocumentContext, Handler, HandlerError};
+
+/// Checks of the requests of `.http` files, of the REST Client and
+/// JetBrains HTTP clients, and their URLs with variables substituted on
+/// hover.
+#[derive(Debug)]
+pub struct Http {}
+
+/// Methods of request lines. `GRAPHQL` and `WEBSOCKET` are requests of the
+/// JetBrains client.
+const METHODS: &[&str] = &[
+    "GET",
+    "POST",
+    "PUT",
+    "DELETE",
+    "PATCH",
+    "HEAD",
+    "OPTIONS",
+    "CONNECT",
+    "TRACE",
+    "GRAPHQL",
+    "WEBSOCKET",
+];
+
+/// Environments of the requests, merged with the private ones overriding
+/// them.
+const ENV_FILES: &[&str] = &["http-client.env.json", "http-client.private.env.json"];
+
+/// The request line of a request, e.g. `GET {{host}}/users HTTP/1.1`.
+#[derive(Debug, PartialEq)]
+struct RequestLine {
+    line: u32,
+    method: String,
+    url: String,
+    /// Characters of the URL on the line.
+    start: u32,
+    end: u32,
+}
+
+/// A `.http` file, its requests, `@name = value` variables and problems.
+#[derive(Debug, Default)]
+struct Parsed {
+    requests: Vec<RequestLine>,
+    variables: HashMap<String, String>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+/// Where the parser is in a request.
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    /// Before the request line, after a `###` separator.
+    Request,
+    /// In a `< {% ... %}` pre-request script.
+    Script,
+    Headers,
+    /// The body, which ends at the next separator.
+    Body,
+}
+
+fn parse(contents: &str) -> Parsed {
+    let mut parsed = Parsed::default();
+    let mut state = State::Request;
+    for (number, line) in contents.lines().enumerate() {
+        let number = number as u32;
+        let character = |offset: usize| line[..offset].encode_utf16().count() as u32;
+        let error = |start: usize, end: usize, message: String| {
+            Diagnostic::new(
+                lsp_types::Range::new(
+                    Position::new(number, character(start)),
+                    Position::new(number, character(end)),
+                ),
+                Some(DiagnosticSeverity::ERROR),
+                None,
+                Some("http".to_string()),
+                message,
+                None,
+                None,
+            )
+        };
+        let trimmed = line.trim();
+        if trimmed.starts_with("###") {
+            state = State::Request;
+            continue;
+        }
+        let comment = trimmed.starts_with('#') || trimmed.starts_with("//");
+        match state {
+            State::Script => {
+                if trimmed.contains("%}") {
+                    state = State::Request;
+                }
+            }
+            State::Request if trimmed.is_empty() || comment => {}
+            State::Request if trimmed.starts_with('@') => {
+                if let Some((_, name, value)) =
+                    regex_captures!(r#"^@([\w.-]+)\s*=\s*(.*)$"#, trimmed)
+                {
+                    parsed
+                        .variables
+                        .insert(name.to_string(), value.trim().to_string());
+                }
+            }
+            State::Request if trimmed.starts_with('<') => {
+                if trimmed.starts_with("< {%") && !trimmed.contains("%}") {
+                    state = State::Script;
+                }
+            }
+            State::Request => {
+                state = State::Headers;
+                let start = line.len() - line.trim_start().len();
+                let words: Vec<(usize, &str)> = regex!(r#"\S+"#)
+                    .find_iter(line)
+                    .map(|word| (word.start(), word.as_str()))
+                    .collect();
+                // A URL alone is a `GET` request
+                let (method, url) = match words.as_slice() {
+                    [url] => ("GET", *url),
+                    [(_, method), url] | [(_, method), url, _] => (*method, *url),
+                    _ => {
+                        parsed.diagnostics.push(error(
+                            start,
+                            line.trim_end().len(),
+                            "Malformed request line, expected `METHOD URL [HTTP/version]`"
+                                .to_string(),
+                        ));
+                        continue;
+                    }
+                };
+                if !METHODS.contains(&method) {
+                    parsed.diagnostics.push(error(
+                        start,
+                        start + method.len(),
+                        format!("Unknown HTTP method `{method}`"),
+                    ));
+                }
+                if let [_, _, (version_start, version)] = words.as_slice() {
+                    if !regex!(r#"^HTTP/\d(\.\d)?$"#).is_match(version) {
+                        parsed.diagnostics.push(error(
+                            *version_start,
+                            version_start + version.len(),
+                            format!("Invalid HTTP version `{version}`, expected e.g. `HTTP/1.1`"),
+                        ));
+                    }
+                }
+                parsed.requests.push(RequestLine {
+                    line: number,
+                    method: method.to_string(),
+                    url: url.1.to_string(),
+                    start: character(url.0),
+                    end: character(url.0 + url.1.len()),
+                });
+            }
+            // The query of the URL continued on the next lines
+            State::Headers if trimmed.starts_with(['?', '&']) => {}
+            State::Headers if trimmed.is_empty() => state = State::Body,
+            State::Headers if comment => {}
+            State::Headers => {
+                if !regex!(r#"^\s*[!#$%&'*+.^_`|~0-9A-Za-z-]+\s*:"#).is_match(line) {
+                    let start = line.len() - line.trim_start().len();
+                    parsed.diagnostics.push(error(
+                        start,
+                        line.trim_end().len(),
+                        "Header is missing a `:` between its name and value".to_string(),
+                    ));
+                }
+            }
+            State::Body => {}
+        }
+    }
+    parsed
+}
+
+/// The variables of the environments of the env files in `directory`, by
+/// environment.
+fn environments(directory: &Path) -> BTreeMap<String, HashMap<String, String>> {
+    let mut environments: BTreeMap<String, HashMap<String, String>> = BTreeMap::new();
+    for name in ENV_FILES {
+        let Some(Value::Object(file)) = std::fs::read_to_string(directory.join(name))
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+        else {
+            continue;
+        };
+        for (environment, variables) in file {
+            let Value::Object(variables) = variables else {
+                continue;
+            };
+            let resolved = environments.entry(environment).or_default();
+            for (name, value) in variables {
+                let value = match value {
+                    Value::String(value) => value,
+                    value => value.to_string(),
+                };
+                resolved.insert(name, value);
+            }
+        }
+    }
+    environments
+}
+
+/// `url` with its `{{name}}` variables replaced, from the variables of the
+/// file first. Values may use other variables, unknown ones are kept.
+fn substitute(
+    url: &str,
+    variables: &HashMap<String, String>,
+    environment: &HashMap<String, String>,
+) -> String {
+    let mut url = url.to_string();
+    // Bounded, variables may refer to each other in a cycle
+    for _ in 0..10 {
+        let substituted = regex!(r#"\{\{\s*([^{}\s]+)\s*\}\}"#).replace_all(
+            &url,
+            |captures: &lazy_regex::Captures| {
+                let name = &captures[1];
+                variables
+                    .get(name)
+                    .or_else(|| environment.get(name))
+                    .cloned()
+                    .unwrap_or_else(|| captures[0].to_string())
+            },
+        );
+        if substituted == url {
+            break;
+        }
+        url = substituted.into_owned();
+    }
+    url
+}
+
+impl Http {
+    pub fn new() -> Result<Self, String> {
+        Ok(Self {})
+    }
+}
+
+impl Handler for Http {
+    fn filetype_supported(&self, filetype: &str) -> bool {
+        filetype == "http"
+    }
+
+    fn get_capabilities(&self) -> ServerCapabilities {
+        ServerCapabilities {
+            hover_provider: Some(HoverProviderCapability::Simple(true)),
+            ..Default::default()
+        }
+    }
+
+    async fn update_diagnostics(
+        &mut self,
+        contents: &str,
+    ) -> Result<Vec<Diagnostic>, HandlerError> {
+        Ok(parse(contents).diagnostics)
+    }
+
+    fn hover(
+        &self,
+        _filetype: &str,
+        context: &DocumentContext,
+        contents: &str,
+        position: Position,
+    ) -> Result<Option<Hover>, HandlerError> {
+        let parsed = parse(contents);
+        let Some(request) = parsed.requests.iter().find(|request| {
+            request.line == position.line
+                && (request.start..=request.end).contains(&position.character)
+        }) else {
+            return Ok(None);
+        };
+        if !request.url.contains("{{") {
+            return Ok(None);
+        }
+
+        let environments = context
+            .directory()
+            .map(|directory| environments(&directory))
+            .unwrap_or_default();
+        let value = if environments.is_empty() {
+            format!(
+                "**{}** `{}`",
+                request.method,
+                substitute(&request.url, &parsed.variables, &HashMap::new())
+            )
+        } else {
+            let urls: Vec<String> = environments
+                .iter()
+                .map(|(name, environment)| {
+                    format!(
+                        "{name}: `{}`",
+                        substitute(&request.url, &parsed.variables, environment)
+                    )
+                })
+                .collect();
+            format!("**{}**\n\n{}", request.method, urls.join("\n\n"))
+        };
+        Ok(Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value,
+            }),
+            range: Some(lsp_types::Range::new(
+                Position::new(request.line, request.start),
+                Position::new(request.line, request.end),
+            )),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, Http};
+    use crate::handlers::{DocumentContext, Handler};
+    use tower_lsp::lsp_types::{HoverContents, Position, Range, Url, WorkspaceFolder};
+
+    #[test]
+    fn test_header_missing_colon() {
+        let contents = "@host = https://api.example.com\n\n### Create a user\nPOST {{host}}/users HTTP/1.1\nContent-Type: application/json\nAuthorization Bearer token\n\n{\"name\": \"Ada\"}\n";
+        let diagnostics = parse(contents).diagnostics;
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].message,
+            "Header is missing a `:` between its name and value"
+        );
+        assert_eq!(
+            diagnostics[0].range,
+            Range::new(Position::new(5, 0), Position::new(5, 26))
+        );
+    }
+
+    #[test]
+    fn test_invalid_method() {
+        let contents = "GTE https://example.com/users\n\n###\n\n# A GET request\nhttps://example.com/health\n?verbose=true\n\n###\nGET https://example.com HTTP/one\n";
+        let diagnostics = parse(contents).diagnostics;
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].message, "Unknown HTTP method `GTE`");
+        assert_eq!(
+            diagnostics[0].range,
+            Range::new(Position::new(0, 0), Position::new(0, 3))
+        );
+        assert_eq!(
+            diagnostics[1].message,
+            "Invalid HTTP version `HTTP/one`, expected e.g. `HTTP/1.1`"
+        );
+    }
+
+    #[test]
+    fn test_hover_environments() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("http-client.env.json"),
+            r#"{"dev": {"host": "localhost:8080"}, "prod": {"host": "api.example.com"}}"#,
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("http-client.private.env.json"),
+            r#"{"dev": {"host": "127.0.0.1:8080"}}"#,
+        )
+        .unwrap();
+        let uri = Url::from_file_path(dir.path().join("users.http")).unwrap();
+        let folder = WorkspaceFolder {
+            uri: Url::from_file_path(dir.path()).unwrap(),
+            name: "project".to_string(),
+        };
+        let context = DocumentContext::new(uri, vec![folder]);
+
+        let contents = "@base = https://{{host}}/v1\n\nGET {{base}}/users/{{id}}\n";
+        let hover = Http::new()
+            .unwrap()
+            .hover("http", &context, contents, Position::new(2, 6))
+            .ok()
+            .unwrap()
+            .unwrap();
+        let HoverContents::Markup(markup) = hover.contents else {
+            panic!("Expected markup");
+        };
+        assert_eq!(
+            markup.value,
+            "**GET**\n\ndev: `https://127.0.0.1:8080/v1/users/{{id}}`\n\nprod: `https://api.example.com/v1/users/{{id}}`"
+        );
+        assert_eq!(
+            hover.range,
+            Some(Range::new(Position::new(2, 4), Position::new(2, 25)))
+        );
+    }
+}
@@ -0,0 +1,276 @@
+use lazy_regex::regex_captures;
+use std::collections::HashMap;
+use tower_lsp::lsp_types::{self, Diagnostic, DiagnosticSeverity};
+
+use super::text::offset_to_position;
+use super::{Handler, HandlerError};
+
+/// Checks of interface definition files (Thrift, FlatBuffers, Protobuf) for
+/// when no dedicated tool is installed: unbalanced brackets and field ids
+/// used twice in a block.
+#[derive(Debug)]
+pub struct Idl {}
+
+/// An open bracket, with the field ids seen directly inside it.
+struct Block {
+    bracket: char,
+    offset: usize,
+    ids: HashMap<u32, usize>,
+}
+
+/// The field id declared by `statement`, if any: `1: i32 name` (Thrift),
+/// `name:int (id: 1)` (FlatBuffers) or `int32 name = 1` (Protobuf, and enum
+/// values of all three). Returns the id and its offset in `statement`.
+fn field_id(statement: &str) -> Option<(u32, usize)> {
+    let (id, offset) = if let Some((_, id)) = regex_captures!(r#"^\s*(\d+)\s*:"#, statement) {
+        (id, statement.find(id)?)
+    } else if let Some((all, id)) = regex_captures!(r#"\bid\s*:\s*(\d+)"#, statement) {
+        (id, statement.find(all)? + all.rfind(id)?)
+    } else if let Some((all, id)) =
+        regex_captures!(r#"=\s*(\d+)\s*(?:\[[^\]]*\])?$"#, statement.trim_end())
+    {
+        // FlatBuffers defaults, `name:int = 1`, and Protobuf options aren't ids
+        if statement.contains(':') || statement.trim_start().starts_with("option") {
+            return None;
+        }
+        (id, statement.find(all)? + all.rfind(id)?)
+    } else {
+        return None;
+    };
+    Some((id.parse().ok()?, offset))
+}
+
+fn closing(bracket: char) -> char {
+    match bracket {
+        '{' => '}',
+        '(' => ')',
+        _ => ']',
+    }
+}
+
+impl Idl {
+    pub fn new() -> Result<Self, String> {
+        Ok(Self {})
+    }
+
+    fn diagnostic(contents: &str, offset: usize, message: String) -> Diagnostic {
+        let position = offset_to_position(contents, offset);
+        Diagnostic::new(
+            lsp_types::Range {
+                start: position,
+                end: position,
+            },
+            Some(DiagnosticSeverity::ERROR),
+            None,
+            Some("idl".to_string()),
+            message,
+            None,
+            None,
+        )
+    }
+
+    /// Checks the field ids of the statement ending at `end`.
+    fn end_statement(
+        contents: &str,
+        blocks: &mut [Block],
+        start: usize,
+        end: usize,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) {
+        let Some(block) = blocks.last_mut().filter(|block| block.bracket == '{') else {
+            return;
+        };
+        let Some((id, offset)) = field_id(&contents[start..end]) else {
+            return;
+        };
+        if let Some(first) = block.ids.get(&id) {
+            let line = offset_to_position(contents, *first).line + 1;
+            diagnostics.push(Self::diagnostic(
+                contents,
+                start + offset,
+                format!("Duplicate field id {id}, first used on line {line}"),
+            ));
+        } else {
+            block.ids.insert(id, start + offset);
+        }
+    }
+
+    pub fn parse(contents: &str) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        let mut blocks: Vec<Block> = Vec::new();
+        let mut statement_start = 0;
+        let mut chars = contents.char_indices().peekable();
+        while let Some((offset, c)) = chars.next() {
+            match c {
+                '"' | '\'' => {
+                    while let Some((_, next)) = chars.next() {
+                        if next == '\\' {
+                            chars.next();
+                        } else if next == c || next == '\n' {
+                            break;
+                        }
+                    }
+                }
+                '/' if chars.peek().is_some_and(|(_, next)| *next == '*') => {
+                    let end = contents[offset + 2..]
+                        .find("*/")
+                        .map_or(contents.len(), |end| offset + 2 + end + 2);
+                    while chars.next_if(|(next, _)| *next < end).is_some() {}
+                }
+                '#' | '/' if c == '#' || chars.peek().is_some_and(|(_, next)| *next == '/') => {
+                    // Comments end the statement, so ids in them aren't read
+                    Self::end_statement(
+                        contents,
+                        &mut blocks,
+                        statement_start,
+                        offset,
+                        &mut diagnostics,
+                    );
+                    while chars.next_if(|(_, next)| *next != '\n').is_some() {}
+                    statement_start = chars.peek().map_or(contents.len(), |(next, _)| *next);
+                }
+                '{' | '(' | '[' => {
+                    if c == '{' {
+                        Self::end_statement(
+                            contents,
+                            &mut blocks,
+                            statement_start,
+                            offset,
+                            &mut diagnostics,
+                        );
+                        statement_start = offset + 1;
+                    }
+                    blocks.push(Block {
+                        bracket: c,
+                        offset,
+                        ids: HashMap::new(),
+                    });
+                }
+                '}' | ')' | ']' => {
+                    if c == '}' {
+                        Self::end_statement(
+                            contents,
+                            &mut blocks,
+                            statement_start,
+                            offset,
+                            &mut diagnostics,
+                        );
+                        statement_start = offset + 1;
+                    }
+                    // Close the innermost matching bracket, those opened
+                    // after it are unclosed
+                    match blocks.iter().rposition(|block| closing(block.bracket) == c) {
+                        Some(index) => {
+                            for block in blocks.drain(index + 1..) {
+                                diagnostics.push(Self::diagnostic(
+                                    contents,
+                                    block.offset,
+                                    format!("Unclosed '{}'", block.bracket),
+                                ));
+                            }
+                            blocks.pop();
+                        }
+                        None => diagnostics.push(Self::diagnostic(
+                            contents,
+                            offset,
+                            format!("Unmatched '{c}'"),
+                        )),
+                    }
+                }
+                // Separators inside parentheses, e.g. of attributes, don't
+                // end the statement
+                ';' | ',' | '\n' if blocks.last().is_none_or(|block| block.bracket == '{') => {
+                    Self::end_statement(
+                        contents,
+                        &mut blocks,
+                        statement_start,
+                        offset,
+                        &mut diagnostics,
+                    );
+                    statement_start = offset + 1;
+                }
+                _ => {}
+            }
+        }
+
+        for block in blocks {
+            diagnostics.push(Self::diagnostic(
+                contents,
+                block.offset,
+                format!("Unclosed '{}'", block.bracket),
+            ));
+        }
+        diagnostics
+    }
+}
+
+impl Handler for Idl {
+    fn filetype_supported(&self, filetype: &str) -> bool {
+        matches!(filetype, "thrift" | "fbs" | "proto")
+    }
+
+    fn priority(&self) -> i32 {
+        // Below dedicated tools such as buf or flatc
+        -10
+    }
+
+    async fn update_diagnostics(
+        &mut self,
+        contents: &str,
+    ) -> Result<Vec<Diagnostic>, HandlerError> {
+        Ok(Self::parse(contents))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Idl;
+
+    #[test]
+    fn test_duplicate_field_id() {
+        let thrift = "struct User {
+  1: i64 id,
+  2: string name, // 1: commented
+  1: string email
+}
+
+enum Role {
+  ADMIN = 1,
+  USER = 2
+}
+";
+        let diagnostics = Idl::parse(thrift);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].range.start.line, 3);
+        assert_eq!(diagnostics[0].range.start.character, 2);
+        assert_eq!(
+            diagnostics[0].message,
+            "Duplicate field id 1, first used on line 2"
+        );
+
+        let fbs = "table Monster {\n  hp:short = 1;\n  mana:short = 1;\n  name:string (id: 0);\n  pos:Vec3 (id: 0, deprecated);\n}\n";
+        let diagnostics = Idl::parse(fbs);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].range.start.line, 4);
+
+        let proto = "message A {\n  option deprecated = 1;\n  int32 a = 1;\n  message B { int32 b = 1; }\n  string c = 2 [json_name = \"x\"];\n  string d = 2;\n}\n";
+        let diagnostics = Idl::parse(proto);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].range.start.line, 5);
+    }
+
+    #[test]
+    fn test_unbalanced_brace() {
+        let diagnostics = Idl::parse("struct A {\n  1: i32 a\n\nstruct B {\n  1: i32 b\n}\n");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].range.start.line, 0);
+        assert_eq!(diagnostics[0].message, "Unclosed '{'");
+
+        let diagnostics = Idl::parse("struct A {\n  1: list<i32> a = [1, 2\n}\n}\n");
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].range.start.line, 1);
+        assert_eq!(diagnostics[0].message, "Unclosed '['");
+        assert_eq!(diagnostics[1].range.start.line, 3);
+        assert_eq!(diagnostics[1].message, "Unmatched '}'");
+    }
+}
@@ -0,0 +1,281 @@
+use globset::{GlobBuilder, GlobMatcher};
+use lazy_regex::regex_replace_all;
+use std::path::Path;
+use tower_lsp::lsp_types::{self, Diagnostic, DiagnosticSeverity, Position};
+
+use super::{DocumentContext, Handler, HandlerError};
+
+/// Validates the patterns of `.gitignore`, `.dockerignore` and `.npmignore`
+/// files, and warns about negations that have no effect.
+#[derive(Debug)]
+pub struct IgnoreFile {}
+
+/// How the patterns of an ignore file match.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Syntax {
+    /// `.gitignore` and `.npmignore`, patterns without a slash match at any
+    /// depth and files in ignored directories can't be re-included.
+    Git,
+    /// `.dockerignore`, patterns match from the root of the build context
+    /// and negations re-include files in ignored directories.
+    Docker,
+}
+
+impl Syntax {
+    fn of(path: &Path) -> Self {
+        if path.file_name().is_some_and(|name| name == ".dockerignore") {
+            Syntax::Docker
+        } else {
+            Syntax::Git
+        }
+    }
+}
+
+/// A parsed, valid pattern.
+struct Pattern {
+    line: u32,
+    negated: bool,
+    /// Only matches directories, the pattern ends with `/`.
+    directory: bool,
+    matcher: GlobMatcher,
+}
+
+impl Pattern {
+    fn matches(&self, path: &str, is_directory: bool) -> bool {
+        (is_directory || !self.directory) && self.matcher.is_match(path)
+    }
+}
+
+/// The line of the last pattern deciding whether `path` is ignored, if it
+/// ignores it.
+fn ignored_by(patterns: &[Pattern], path: &str, is_directory: bool) -> Option<u32> {
+    patterns
+        .iter()
+        .rev()
+        .find(|pattern| pattern.matches(path, is_directory))
+        .filter(|pattern| !pattern.negated)
+        .map(|pattern| pattern.line)
+}
+
+/// A path matched by `glob`, approximated by replacing wildcards.
+fn example_path(glob: &str) -> String {
+    let path = regex_replace_all!(r#"\*\*/|/\*\*$"#, glob, "");
+    let path = regex_replace_all!(r#"\[!?\]?([^\]])[^\]]*\]"#, &path, "$1");
+    path.replace(['*', '?'], "x").replace('\\', "")
+}
+
+impl IgnoreFile {
+    pub fn new() -> Result<Self, String> {
+        Ok(Self {})
+    }
+
+    fn diagnostic(
+        line: u32,
+        text: &str,
+        severity: DiagnosticSeverity,
+        message: String,
+    ) -> Diagnostic {
+        Diagnostic::new(
+            lsp_types::Range {
+                start: Position::new(line, 0),
+                end: Position::new(line, text.encode_utf16().count() as u32),
+            },
+            Some(severity),
+            None,
+            Some("ignorefile".to_string()),
+            message,
+            None,
+            None,
+        )
+    }
+
+    pub fn parse(contents: &str, syntax: Syntax) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        let mut patterns: Vec<Pattern> = Vec::new();
+        for (line, text) in contents.lines().enumerate() {
+            let line = line as u32;
+            // Trailing spaces are ignored unless escaped
+            let text = if text.ends_with("\\ ") {
+                text
+            } else {
+                text.trim_end()
+            };
+            if text.is_empty() || text.starts_with('#') {
+                continue;
+            }
+
+            let (negated, pattern) = match text.strip_prefix('!') {
+                Some(pattern) => (true, pattern),
+                None => (false, text),
+            };
+            let directory = pattern.ends_with('/');
+            let pattern = pattern.trim_end_matches('/');
+            // Patterns without a slash match at any depth, for git
+            let glob = match pattern.strip_prefix('/') {
+                Some(anchored) => anchored.to_string(),
+                None if pattern.contains('/') || syntax == Syntax::Docker => pattern.to_string(),
+                None => format!("**/{pattern}"),
+            };
+
+            let matcher = match GlobBuilder::new(&glob)
+                .literal_separator(true)
+                .backslash_escape(true)
+                .build()
+            {
+                Ok(glob) => glob.compile_matcher(),
+                Err(err) => {
+                    diagnostics.push(Self::diagnostic(
+                        line,
+                        text,
+                        DiagnosticSeverity::ERROR,
+                        format!("Invalid pattern: {}", err.kind()),
+                    ));
+                    continue;
+                }
+            };
+
+            if negated {
+                let path = example_path(glob.trim_start_matches("**/"));
+                let parents: Vec<&str> = path
+                    .match_indices('/')
+                    .map(|(index, _)| &path[..index])
+                    .collect();
+                let parent_ignored = parents
+                    .iter()
+                    .find_map(|parent| Some((parent, ignored_by(&patterns, parent, true)?)));
+                match parent_ignored {
+                    // Docker re-includes files of ignored directories
+                    Some(_) if syntax == Syntax::Docker => {}
+                    Some((parent, ignored)) => {
+                        diagnostics.push(Self::diagnostic(
+                            line,
+                            text,
+                            DiagnosticSeverity::WARNING,
+                            format!(
+                                "Negation never matches, the parent directory '{parent}' is ignored on line {}",
+                                ignored + 1
+                            ),
+                        ));
+                    }
+                    None if ignored_by(&patterns, &path, directory).is_none() => {
+                        diagnostics.push(Self::diagnostic(
+                            line,
+                            text,
+                            DiagnosticSeverity::WARNING,
+                            "Redundant negation, no earlier pattern ignores it".to_string(),
+                        ));
+                    }
+                    None => {}
+                }
+            }
+
+            patterns.push(Pattern {
+                line,
+                negated,
+                directory,
+                matcher,
+            });
+        }
+        diagnostics
+    }
+}
+
+impl Handler for IgnoreFile {
+    fn filetype_supported(&self, filetype: &str) -> bool {
+        filetype == "gitignore"
+    }
+
+    fn path_supported(&self, path: &Path) -> bool {
+        path.file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| matches!(name, ".gitignore" | ".dockerignore" | ".npmignore"))
+    }
+
+    async fn update_diagnostics(
+        &mut self,
+        contents: &str,
+    ) -> Result<Vec<Diagnostic>, HandlerError> {
+        Ok(Self::parse(contents, Syntax::Git))
+    }
+
+    async fn update_diagnostics_with_context(
+        &mut self,
+        context: &DocumentContext,
+        contents: &str,
+    ) -> Result<Vec<Diagnostic>, HandlerError> {
+        let syntax = context
+            .uri
+            .to_file_path()
+            .map_or(Syntax::Git, |path| Syntax::of(&path));
+        Ok(Self::parse(contents, syntax))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{IgnoreFile, Syntax};
+    use crate::handlers::Handler;
+    use std::path::Path;
+    use tower_lsp::lsp_types::DiagnosticSeverity;
+
+    #[test]
+    fn test_invalid_class() {
+        let diagnostics = IgnoreFile::parse("# build output\ntarget/\n*.[ch\n", Syntax::Git);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].range.start.line, 2);
+        assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::ERROR));
+        assert!(diagnostics[0].message.starts_with("Invalid pattern"));
+    }
+
+    #[test]
+    fn test_negations() {
+        let contents = "!important.log
+*.log
+!keep.log
+build/
+!build/keep.txt
+dist/*
+!dist/keep.txt
+!/src/*.rs
+";
+        let diagnostics = IgnoreFile::parse(contents, Syntax::Git);
+        let lines: Vec<u32> = diagnostics.iter().map(|d| d.range.start.line).collect();
+        assert_eq!(lines, vec![0, 4, 7]);
+        assert_eq!(
+            diagnostics[0].message,
+            "Redundant negation, no earlier pattern ignores it"
+        );
+        assert_eq!(
+            diagnostics[1].message,
+            "Negation never matches, the parent directory 'build' is ignored on line 4"
+        );
+        assert!(diagnostics
+            .iter()
+            .all(|d| d.severity == Some(DiagnosticSeverity::WARNING)));
+    }
+
+    #[test]
+    fn test_dockerignore() {
+        let contents = "*.log\n!logs/keep.log\nbuild/\n!build/keep.txt\n";
+        let lines = |syntax| {
+            IgnoreFile::parse(contents, syntax)
+                .iter()
+                .map(|d| d.range.start.line)
+                .collect::<Vec<u32>>()
+        };
+        // `*.log` only ignores logs at the root of the build context
+        assert_eq!(lines(Syntax::Docker), vec![1]);
+        assert_eq!(lines(Syntax::Git), vec![3]);
+        assert_eq!(
+            Syntax::of(Path::new("/project/.dockerignore")),
+            Syntax::Docker
+        );
+    }
+
+    #[test]
+    fn test_path_supported() {
+        let handler = IgnoreFile {};
+        assert!(handler.path_supported(Path::new("/project/.dockerignore")));
+        assert!(!handler.path_supported(Path::new("/project/ignore")));
+    }
+}
@@ -0,0 +1,330 @@
+use std::iter::Peekable;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use tower_lsp::lsp_types::{
+    self, Diagnostic, DiagnosticSeverity, Hover, HoverContents, HoverProviderCapability,
+    MarkupContent, MarkupKind, Position, ServerCapabilities,
+};
+
+use super::props::Definition;
+use super::text::{offset_to_position, position_to_offset};
+use super::{DocumentContext, Handler, HandlerError};
+
+/// Duplicate keys and hover of the values of Java `.properties` files.
+#[derive(Debug)]
+pub struct JavaProps {}
+
+/// A key and its value, with the byte range of the key.
+#[derive(Debug, PartialEq)]
+pub struct Entry {
+    pub definition: Definition,
+    pub key_range: Range<usize>,
+}
+
+/// Whitespace of a `.properties` file, which excludes line breaks.
+fn is_blank(c: char) -> bool {
+    matches!(c, ' ' | '\t' | '\u{c}')
+}
+
+/// The next character with its escape decoded, and the offset after it.
+fn decode(chars: &mut Peekable<impl Iterator<Item = (usize, char)>>) -> Option<(char, usize)> {
+    let (offset, c) = chars.next()?;
+    if c != '\\' {
+        return Some((c, offset + c.len_utf8()));
+    }
+    let Some((offset, escaped)) = chars.next() else {
+        return Some(('\\', offset + 1));
+    };
+    let decoded = match escaped {
+        't' => '\t',
+        'n' => '\n',
+        'r' => '\r',
+        'f' => '\u{c}',
+        'u' => {
+            let digits: String = (0..4)
+                .map_while(|_| chars.next_if(|(_, c)| c.is_ascii_hexdigit()))
+                .map(|(_, c)| c)
+                .collect();
+            let decoded = u32::from_str_radix(&digits, 16)
+                .ok()
+                .and_then(char::from_u32)
+                .unwrap_or(char::REPLACEMENT_CHARACTER);
+            return Some((decoded, offset + 1 + digits.len()));
+        }
+        // Any other character stands for itself, e.g. `\=` or `\#`
+        other => other,
+    };
+    Some((decoded, offset + escaped.len_utf8()))
+}
+
+/// The entry of a logical line, its characters with their byte offsets.
+fn parse_entry(line: &[(usize, char)], from_path: &Path) -> Option<Entry> {
+    let mut chars = line.iter().copied().peekable();
+    let start = chars.peek()?.0;
+    let mut name = String::new();
+    let mut end = start;
+    while let Some(&(_, c)) = chars.peek() {
+        if matches!(c, '=' | ':') || is_blank(c) {
+            break;
+        }
+        let (c, after) = decode(&mut chars)?;
+        name.push(c);
+        end = after;
+    }
+
+    // Whitespace, then at most one separator and the whitespace after it
+    while chars.next_if(|(_, c)| is_blank(*c)).is_some() {}
+    if chars.next_if(|(_, c)| matches!(c, '=' | ':')).is_some() {
+        while chars.next_if(|(_, c)| is_blank(*c)).is_some() {}
+    }
+    let mut value = String::new();
+    while let Some((c, _)) = decode(&mut chars) {
+        value.push(c);
+    }
+
+    Some(Entry {
+        definition: Definition {
+            name,
+            value,
+            from_path: from_path.to_path_buf(),
+        },
+        key_range: start..end,
+    })
+}
+
+/// The entries of the `.properties` file at `from_path` with `contents`.
+/// Lines ending in an odd number of backslashes continue on the next line,
+/// without its leading whitespace.
+pub fn entries(contents: &str, from_path: &Path) -> Vec<Entry> {
+    let mut entries = Vec::new();
+    let mut logical: Vec<(usize, char)> = Vec::new();
+    let mut line_start = 0;
+    for line in contents.split('\n') {
+        let start = line_start;
+        line_start += line.len() + 1;
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        let trimmed = line.trim_start_matches(is_blank);
+        if logical.is_empty() && (trimmed.is_empty() || trimmed.starts_with(['#', '!'])) {
+            continue;
+        }
+
+        let indent = line.len() - trimmed.len();
+        let backslashes = trimmed.chars().rev().take_while(|c| *c == '\\').count();
+        let continues = backslashes % 2 == 1;
+        let text = if continues {
+            &trimmed[..trimmed.len() - 1]
+        } else {
+            trimmed
+        };
+        logical.extend(text.char_indices().map(|(i, c)| (start + indent + i, c)));
+        if !continues {
+            entries.extend(parse_entry(&logical, from_path));
+            logical.clear();
+        }
+    }
+    // The last line may end with a backslash
+    entries.extend(parse_entry(&logical, from_path));
+    entries
+}
+
+impl JavaProps {
+    pub fn new() -> Result<Self, String> {
+        Ok(Self {})
+    }
+
+    fn path(context: &DocumentContext) -> PathBuf {
+        context.uri.to_file_path().unwrap_or_default()
+    }
+
+    fn range(contents: &str, entry: &Entry) -> lsp_types::Range {
+        lsp_types::Range::new(
+            offset_to_position(contents, entry.key_range.start),
+            offset_to_position(contents, entry.key_range.end),
+        )
+    }
+
+    /// Warnings on keys defined before, the last value is the one used.
+    pub fn duplicates(contents: &str, entries: &[Entry]) -> Vec<Diagnostic> {
+        entries
+            .iter()
+            .enumerate()
+            .filter_map(|(i, entry)| {
+                let first = entries[..i]
+                    .iter()
+                    .find(|other| other.definition.name == entry.definition.name)?;
+                let line = offset_to_position(contents, first.key_range.start).line + 1;
+                Some(Diagnostic::new(
+                    Self::range(contents, entry),
+                    Some(DiagnosticSeverity::WARNING),
+                    None,
+                    Some("properties".to_string()),
+                    format!(
+                        "Duplicate key `{}`, first defined on line {line}",
+                        entry.definition.name
+                    ),
+                    None,
+                    None,
+                ))
+            })
+            .collect()
+    }
+}
+
+impl Handler for JavaProps {
+    fn filetype_supported(&self, filetype: &str) -> bool {
+        filetype == "properties"
+    }
+
+    fn get_capabilities(&self) -> ServerCapabilities {
+        ServerCapabilities {
+            hover_provider: Some(HoverProviderCapability::Simple(true)),
+            ..Default::default()
+        }
+    }
+
+    async fn update_diagnostics(
+        &mut self,
+        contents: &str,
+    ) -> Result<Vec<Diagnostic>, HandlerError> {
+        Ok(Self::duplicates(
+            contents,
+            &entries(contents, Path::new("")),
+        ))
+    }
+
+    async fn update_diagnostics_with_context(
+        &mut self,
+        context: &DocumentContext,
+        contents: &str,
+    ) -> Result<Vec<Diagnostic>, HandlerError> {
+        let entries = entries(contents, &Self::path(context));
+        Ok(Self::duplicates(contents, &entries))
+    }
+
+    fn hover(
+        &self,
+        _filetype: &str,
+        context: &DocumentContext,
+        contents: &str,
+        position: Position,
+    ) -> Result<Option<Hover>, HandlerError> {
+        let offset = position_to_offset(contents, position);
+        let entries = entries(contents, &Self::path(context));
+        let Some(entry) = entries
+            .iter()
+            .find(|entry| entry.key_range.contains(&offset))
+        else {
+            return Ok(None);
+        };
+
+        let definition = &entry.definition;
+        let mut value = format!("`{}={}`", definition.name, definition.value);
+        // Later definitions replace the value
+        if let Some(last) = entries
+            .iter()
+            .rev()
+            .find(|other| other.definition.name == definition.name)
+            .filter(|last| last.key_range != entry.key_range)
+        {
+            let line = offset_to_position(contents, last.key_range.start).line + 1;
+            value.push_str(&format!("\n\nOverridden on line {line}"));
+        }
+        Ok(Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value,
+            }),
+            range: Some(Self::range(contents, entry)),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{entries, JavaProps};
+    use crate::handlers::{DocumentContext, Handler};
+    use std::path::Path;
+    use tower_lsp::lsp_types::{HoverContents, Position, Range, Url};
+
+    fn names_and_values(contents: &str) -> Vec<(String, String)> {
+        entries(contents, Path::new("/app/messages.properties"))
+            .into_iter()
+            .map(|entry| (entry.definition.name, entry.definition.value))
+            .collect()
+    }
+
+    #[test]
+    fn test_continued_value() {
+        let contents =
+            "# Fruits\nfruits = apple, banana, \\\n         pear, \\\n         cherry\n! done\n";
+        assert_eq!(
+            names_and_values(contents),
+            vec![(
+                "fruits".to_string(),
+                "apple, banana, pear, cherry".to_string()
+            )]
+        );
+
+        // An escaped backslash doesn't continue the line
+        let contents = "path=C:\\\\\nnext=1\n";
+        assert_eq!(
+            names_and_values(contents),
+            vec![
+                ("path".to_string(), "C:\\".to_string()),
+                ("next".to_string(), "1".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_separators_and_escapes() {
+        let contents = "greeting: Hello\nkey\\:with\\=separators = value\nempty\nspaced   value\nunicode=caf\\u00e9\\tbar\n";
+        assert_eq!(
+            names_and_values(contents),
+            vec![
+                ("greeting".to_string(), "Hello".to_string()),
+                ("key:with=separators".to_string(), "value".to_string()),
+                ("empty".to_string(), String::new()),
+                ("spaced".to_string(), "value".to_string()),
+                ("unicode".to_string(), "café\tbar".to_string()),
+            ]
+        );
+
+        let entries = entries(contents, Path::new(""));
+        assert_eq!(entries[0].key_range, 0..8);
+        assert_eq!(entries[1].key_range, 16..37);
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_keys() {
+        let contents = "title=Home\nsubtitle=Welcome\ntitle: Start\n";
+        let diagnostics = JavaProps::new()
+            .unwrap()
+            .update_diagnostics(contents)
+            .await
+            .ok()
+            .unwrap();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].message,
+            "Duplicate key `title`, first defined on line 1"
+        );
+        assert_eq!(
+            diagnostics[0].range,
+            Range::new(Position::new(2, 0), Position::new(2, 5))
+        );
+
+        let uri = Url::parse("untitled:messages.properties").unwrap();
+        let context = DocumentContext::new(uri, vec![]);
+        let hover = JavaProps::new()
+            .unwrap()
+            .hover("properties", &context, contents, Position::new(0, 2))
+            .ok()
+            .unwrap()
+            .unwrap();
+        let HoverContents::Markup(markup) = hover.contents else {
+            panic!("Expected markup");
+        };
+        assert_eq!(markup.value, "`title=Home`\n\nOverridden on line 3");
+    }
+}
@@ -0,0 +1,81 @@
+use serde::de::IgnoredAny;
+use tower_lsp::lsp_types::{self, Diagnostic, DiagnosticSeverity, Position};
+
+use super::{Handler, HandlerError};
+
+/// Validates JSON with comments and trailing commas (JSONC) and JSON5.
+#[derive(Debug)]
+pub struct Jsonc {}
+
+impl Jsonc {
+    pub fn new() -> Result<Self, String> {
+        Ok(Self {})
+    }
+
+    pub fn parse(contents: &str) -> Vec<Diagnostic> {
+        let Err(json5::Error::Message { msg, location }) = json5::from_str::<IgnoredAny>(contents)
+        else {
+            return vec![];
+        };
+
+        // The parser reports 1-based positions
+        let position = location
+            .map(|location| {
+                Position::new(
+                    location.line.saturating_sub(1) as u32,
+                    location.column.saturating_sub(1) as u32,
+                )
+            })
+            .unwrap_or_default();
+
+        vec![Diagnostic::new(
+            lsp_types::Range {
+                start: position,
+                end: position,
+            },
+            Some(DiagnosticSeverity::ERROR),
+            None,
+            Some("jsonc".to_string()),
+            msg,
+            None,
+            None,
+        )]
+    }
+}
+
+impl Handler for Jsonc {
+    fn filetype_supported(&self, filetype: &str) -> bool {
+        matches!(filetype, "jsonc" | "json5")
+    }
+
+    async fn update_diagnostics(
+        &mut self,
+        contents: &str,
+    ) -> Result<Vec<Diagnostic>, HandlerError> {
+        Ok(Self::parse(contents))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Jsonc;
+
+    #[test]
+    fn test_syntax_error() {
+        let diagnostics = Jsonc::parse("{\n  \"a\": 1,\n  \"b\" 2\n}\n");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].range.start.line, 2);
+    }
+
+    #[test]
+    fn test_comments_and_trailing_commas() {
+        let contents = r#"{
+  // A comment
+  "a": [1, 2, 3,],
+  /* Another comment */
+  "b": { "c": true, },
+}
+"#;
+        assert!(Jsonc::parse(contents).is_empty());
+    }
+}
@@ -1,11 +1,103 @@
-use lazy_regex::regex_captures;
-use std::io::Write;
-use tower_lsp::lsp_types::{self, Diagnostic, DiagnosticSeverity, Position};
+use lazy_regex::{regex, regex_captures, regex_is_match};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Arc;
+use tower_lsp::lsp_types::{
+    Diagnostic, DiagnosticSeverity, DocumentLink, DocumentLinkOptions, ExecuteCommandOptions,
+    Hover, HoverContents, HoverProviderCapability, LinkedEditingRangeServerCapabilities,
+    LinkedEditingRanges, Location, MarkupContent, MarkupKind, OneOf, Position, ServerCapabilities,
+    Url, WorkspaceFolder,
+};
 
-use super::{Handler, HandlerError};
+use super::cache::{ModelCache, DEFAULT_MODEL_CACHE_CAPACITY};
+use super::just_model::{JustModel, Recipe};
+use super::process::{
+    output_with_timeout, probe, run_and_parse, InputMode, RegexLineParser, Stream,
+    TempFileStrategy, TempFiles,
+};
+use super::text::position_to_offset;
+use super::{DocumentContext, DocumentDiagnostics, Handler, HandlerError};
+
+/// Settings of the justfile handler, the `just` key of the settings.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct JustConfig {
+    /// Hint at assignments that are never used.
+    pub unused_variables: bool,
+}
 
 #[derive(Debug)]
-pub struct Just {}
+pub struct Just {
+    config: JustConfig,
+    temp_files: TempFiles,
+    /// Models of the recently parsed justfiles.
+    models: ModelCache<JustModel>,
+    /// Models of the files imported by them, apart so that they aren't
+    /// patched into models of open justfiles.
+    imported_models: ModelCache<JustModel>,
+    /// The program checking justfiles, `None` if it can't be run, in which
+    /// case only the checks of the model run.
+    program: Option<&'static str>,
+}
+
+/// Checks every justfile of the workspace with `just --dry-run`.
+pub const CHECK_WORKSPACE: &str = "any_ls.just.checkWorkspace";
+
+/// Limit of dependency chains shown on hover, the number of chains grows
+/// quickly with shared dependencies.
+const MAX_CHAINS: usize = 20;
+
+/// Lines of an imported file shown on hover of its path.
+const PREVIEW_LINES: usize = 10;
+
+/// Whether `error` of `just` is reported by `checks` of the model, which
+/// find every cycle and undefined dependency where `just` stops at the
+/// first one.
+fn is_checked(checks: &[Diagnostic], error: &Diagnostic) -> bool {
+    let reported = |message: &str| checks.iter().any(|check| check.message == message);
+    if let Some((_, name)) = regex_captures!(r#"has unknown dependency `([^`]+)`"#, &error.message)
+    {
+        return reported(&format!("Recipe `{name}` is not defined"));
+    }
+    regex_is_match!(
+        r#"has circular dependency|depends on itself"#,
+        &error.message
+    ) && checks
+        .iter()
+        .any(|check| check.message.starts_with("Dependency cycle: "))
+}
+
+/// Every dependency chain starting at `path`, with a chain ending in
+/// `(cycle)` when it leads back to a recipe already on it.
+fn dependency_chains<'a>(
+    recipes: &HashMap<&str, &'a Recipe>,
+    path: &mut Vec<&'a str>,
+    chains: &mut Vec<String>,
+) {
+    if chains.len() >= MAX_CHAINS {
+        return;
+    }
+    let current = path[path.len() - 1];
+    let dependencies = recipes
+        .get(current)
+        .map(|recipe| recipe.dependencies.as_slice())
+        .unwrap_or_default();
+    if dependencies.is_empty() {
+        chains.push(path.join(" → "));
+        return;
+    }
+    for (dependency, _) in dependencies {
+        if path.contains(&dependency.as_str()) {
+            chains.push(format!("{} → {dependency} (cycle)", path.join(" → ")));
+        } else {
+            path.push(dependency);
+            dependency_chains(recipes, path, chains);
+            path.pop();
+        }
+    }
+}
 
 fn parse_severity(severity: &str) -> Option<DiagnosticSeverity> {
     match severity {
@@ -18,75 +110,779 @@ fn parse_severity(severity: &str) -> Option<DiagnosticSeverity> {
 }
 
 impl Just {
-    pub fn new() -> Result<Self, String> {
-        Ok(Self {})
+    pub fn new(config: JustConfig) -> Result<Self, String> {
+        Ok(Self::with_program(config, "just"))
+    }
+
+    /// Like `new`, checking justfiles with `program` instead of `just`.
+    /// The handler is still created when it can't be run, the model checks
+    /// cycles and dependencies without it.
+    pub(crate) fn with_program(config: JustConfig, program: &'static str) -> Self {
+        let program = match probe(program, &["--version"]) {
+            Ok(()) => Some(program),
+            Err(err) => {
+                log::info!("Just handler runs without `{program}`: {err}");
+                None
+            }
+        };
+        Self {
+            config,
+            temp_files: TempFiles::with_suffix(".just"),
+            models: ModelCache::new(DEFAULT_MODEL_CACHE_CAPACITY),
+            imported_models: ModelCache::new(DEFAULT_MODEL_CACHE_CAPACITY),
+            program,
+        }
+    }
+
+    /// The model of `contents`, parsed again only when they changed, or
+    /// patched for edits of a line.
+    pub fn model(&self, contents: &str) -> Arc<JustModel> {
+        self.models
+            .get_or_patch(contents, JustModel::parse, JustModel::patched)
     }
 }
 
 impl Handler for Just {
+    fn filetype_supported(&self, filetype: &str) -> bool {
+        filetype == "just"
+    }
+
+    fn set_temp_file_strategy(&mut self, strategy: TempFileStrategy) {
+        self.temp_files.set_strategy(strategy);
+    }
+
+    fn set_model_cache_capacity(&mut self, capacity: usize) {
+        self.models.resize(capacity);
+        self.imported_models.resize(capacity);
+    }
+
+    fn get_capabilities(&self) -> ServerCapabilities {
+        ServerCapabilities {
+            hover_provider: Some(HoverProviderCapability::Simple(true)),
+            definition_provider: Some(OneOf::Left(true)),
+            document_link_provider: Some(DocumentLinkOptions {
+                resolve_provider: Some(false),
+                work_done_progress_options: Default::default(),
+            }),
+            linked_editing_range_provider: Some(LinkedEditingRangeServerCapabilities::Simple(true)),
+            execute_command_provider: Some(ExecuteCommandOptions {
+                commands: vec![CHECK_WORKSPACE.to_string()],
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
     async fn update_diagnostics(
         &mut self,
         contents: &str,
     ) -> Result<Vec<Diagnostic>, HandlerError> {
-        let mut temp_file =
-            tempfile::NamedTempFile::new().map_err(|e| HandlerError::Log(format!("{e}")))?;
-        temp_file
-            .write_all(contents.as_bytes())
-            .map_err(|e| HandlerError::Log(format!("{e}")))?;
+        let uri = Url::parse("untitled:justfile").expect("Valid URL");
+        let context = DocumentContext::new(uri, Vec::new());
+        self.update_diagnostics_with_context(&context, contents)
+            .await
+    }
 
-        let out = std::process::Command::new("just")
-            .arg("--dry-run")
-            .arg("--justfile")
-            .arg(temp_file.path())
-            .output()
-            .map_err(|e| HandlerError::Log(format!("{e}")))?;
-
-        if out.status.success() {
-            let stdout =
-                String::from_utf8(out.stdout).map_err(|e| HandlerError::Log(format!("{e}")))?;
-            Ok(Just::parse_stdout(&stdout))
-        } else {
-            let stderr =
-                String::from_utf8(out.stderr).map_err(|e| HandlerError::Log(format!("{e}")))?;
-            Ok(Self::parse_stderr(&stderr))
+    async fn update_diagnostics_with_context(
+        &mut self,
+        context: &DocumentContext,
+        contents: &str,
+    ) -> Result<Vec<Diagnostic>, HandlerError> {
+        self.update_document_diagnostics(context, contents)
+            .await
+            .map(|document| document.diagnostics)
+    }
+
+    async fn update_document_diagnostics(
+        &mut self,
+        context: &DocumentContext,
+        contents: &str,
+    ) -> Result<DocumentDiagnostics, HandlerError> {
+        let model = self.model(contents);
+        let mut diagnostics = model.cycles();
+        diagnostics.extend(model.undefined_dependencies(context.directory().as_deref()));
+        let checks = diagnostics.clone();
+        if self.config.unused_variables {
+            diagnostics.extend(model.unused_variables());
+        }
+
+        // The diagnostics of the model are still worth showing
+        let errors = match self.program {
+            Some(program) => self.run(program, context, contents).unwrap_or_else(|err| {
+                log::info!("Could not check justfile with `{program}`: {err}");
+                Vec::new()
+            }),
+            None => Vec::new(),
+        };
+
+        let mut related: HashMap<Url, Vec<Diagnostic>> = HashMap::new();
+        for (imported, diagnostic) in errors {
+            match imported {
+                Some(uri) => related.entry(uri).or_default().push(diagnostic),
+                None if is_checked(&checks, &diagnostic) => {}
+                None => diagnostics.push(diagnostic),
+            }
+        }
+        Ok(DocumentDiagnostics {
+            diagnostics,
+            related,
+            ..Default::default()
+        })
+    }
+
+    fn definition(
+        &self,
+        context: &DocumentContext,
+        contents: &str,
+        position: Position,
+    ) -> Result<Option<Location>, HandlerError> {
+        let model = self.model(contents);
+        let offset = position_to_offset(contents, position);
+        let Some((name, _)) = model.recipe_at(offset) else {
+            return Ok(None);
+        };
+        if let Some(recipe) = model.recipes.iter().find(|recipe| recipe.name == name) {
+            return Ok(Some(Location::new(
+                context.uri.clone(),
+                model.range(&recipe.range),
+            )));
+        }
+        Ok(context
+            .directory()
+            .and_then(|directory| self.imported_definition(&model, &directory, name)))
+    }
+
+    fn document_links(&self, contents: &str, uri: &Url) -> Result<Vec<DocumentLink>, HandlerError> {
+        Ok(self
+            .model(contents)
+            .imports
+            .iter()
+            .filter_map(|import| {
+                // Relative to the justfile, like `just` resolves them
+                let target = uri.join(&import.path).ok()?;
+                Some(DocumentLink {
+                    range: import.range,
+                    target: Some(target),
+                    tooltip: None,
+                    data: None,
+                })
+            })
+            .collect())
+    }
+
+    fn hover(
+        &self,
+        _filetype: &str,
+        context: &DocumentContext,
+        contents: &str,
+        position: Position,
+    ) -> Result<Option<Hover>, HandlerError> {
+        let model = self.model(contents);
+        if let Some(import) = model
+            .imports
+            .iter()
+            .find(|import| import.range.start <= position && position <= import.range.end)
+        {
+            let preview = context
+                .directory()
+                .and_then(|directory| Self::preview(&directory.join(&import.path)))
+                .unwrap_or_else(|| "(file not found)".to_string());
+            return Ok(Some(Hover {
+                contents: HoverContents::Markup(MarkupContent {
+                    kind: MarkupKind::Markdown,
+                    value: format!("`{}`\n\n{preview}", import.path),
+                }),
+                range: Some(import.range),
+            }));
         }
+
+        let offset = position_to_offset(contents, position);
+        let Some((name, range)) = model.recipe_at(offset) else {
+            return Ok(None);
+        };
+
+        let by_name = model.recipes_by_name();
+        let mut chains = Vec::new();
+        dependency_chains(&by_name, &mut vec![name], &mut chains);
+        if chains.len() >= MAX_CHAINS {
+            chains.push("…".to_string());
+        }
+
+        // The comment above the recipe, as `just --list` shows it
+        let doc = by_name
+            .get(name)
+            .and_then(|recipe| recipe.doc.as_ref())
+            .map(|doc| format!("{doc}\n\n"))
+            .unwrap_or_default();
+        Ok(Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: format!(
+                    "Recipe `{name}`\n\n{doc}```text\n{}\n```",
+                    chains.join("\n")
+                ),
+            }),
+            range: Some(model.range(range)),
+        }))
+    }
+
+    fn linked_editing_ranges(
+        &self,
+        contents: &str,
+        position: Position,
+    ) -> Result<Option<LinkedEditingRanges>, HandlerError> {
+        let offset = position_to_offset(contents, position);
+        let model = self.model(contents);
+        let Some(parameter) = model.parameter_at(offset) else {
+            return Ok(None);
+        };
+
+        let ranges = std::iter::once(&parameter.declaration)
+            .chain(&parameter.uses)
+            .map(|range| model.range(range))
+            .collect();
+        Ok(Some(LinkedEditingRanges {
+            ranges,
+            word_pattern: Some(r"[A-Za-z_][\w-]*".to_string()),
+        }))
+    }
+
+    fn execute_workspace_command(
+        &self,
+        command: &str,
+        workspace_folders: &[WorkspaceFolder],
+    ) -> Result<Option<String>, HandlerError> {
+        if command != CHECK_WORKSPACE {
+            return Ok(None);
+        }
+        let Some(program) = self.program else {
+            return Err(HandlerError::ToolNotFound("just".to_string()));
+        };
+        Ok(Some(Self::check_workspace(
+            workspace_folders,
+            |justfile, context| Self::check_justfile(program, justfile, context),
+        )))
     }
 }
 
 impl Just {
-    pub fn parse_stderr(contents: &str) -> Vec<Diagnostic> {
-        if let Some((_, severity, message, line, col)) =
-            regex_captures!(r#"(\w+):\s(.*)\n.*——▶.*:(\d+):(\d+)"#, contents)
-        {
-            let line = line.parse().unwrap_or(0);
-            let col = col.parse().unwrap_or(0);
-
-            vec![Diagnostic::new(
-                lsp_types::Range {
-                    start: Position::new(line, col),
-                    end: Position::new(line, col),
-                },
-                parse_severity(severity),
-                None,
-                Some("just".to_string()),
-                message.to_string(),
-                None,
-                None,
-            )]
+    /// The errors `program` reports for `contents`, with the file they are
+    /// in when it isn't the document.
+    fn run(
+        &mut self,
+        program: &str,
+        context: &DocumentContext,
+        contents: &str,
+    ) -> Result<Vec<(Option<Url>, Diagnostic)>, HandlerError> {
+        // Next to the document, `just` resolves imports and modules relative
+        // to the justfile
+        let temp_file = match context.directory() {
+            Some(directory) => self.temp_files.write_in(&directory, contents)?,
+            None => self.temp_files.write(contents)?,
+        };
+        let errors = run_and_parse(
+            &mut Self::command(program, temp_file.path(), context),
+            InputMode::File,
+            &Self::error_parser(),
+        )?;
+        Ok(errors
+            .into_iter()
+            .map(|(path, diagnostic)| {
+                let imported =
+                    path.and_then(|path| Self::imported_file(&path, temp_file.path(), context));
+                (imported, diagnostic)
+            })
+            .collect())
+    }
+
+    /// The `program` invocation checking `justfile`, run from the document's
+    /// directory so paths and backticks resolve as they would for the user.
+    fn command(program: &str, justfile: &Path, context: &DocumentContext) -> Command {
+        let mut command = Command::new(program);
+        command
+            .arg("--color")
+            .arg("never")
+            .arg("--dry-run")
+            .arg("--justfile")
+            .arg(justfile);
+        if let Some(directory) = context.directory() {
+            command
+                .arg("--working-directory")
+                .arg(&directory)
+                .current_dir(directory);
+        }
+        command
+    }
+
+    /// The justfiles in `directory` and its subdirectories, sorted. Hidden
+    /// directories, e.g. `.git`, are skipped.
+    fn justfiles(directory: &Path) -> Vec<PathBuf> {
+        let mut justfiles = Vec::new();
+        let Ok(entries) = std::fs::read_dir(directory) else {
+            return justfiles;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let name = entry.file_name().to_string_lossy().to_lowercase();
+            // Not following links, which could lead back to a parent
+            match entry.file_type() {
+                Ok(file_type) if file_type.is_dir() && !name.starts_with('.') => {
+                    justfiles.extend(Self::justfiles(&path));
+                }
+                Ok(file_type)
+                    if file_type.is_file() && matches!(name.as_str(), "justfile" | ".justfile") =>
+                {
+                    justfiles.push(path);
+                }
+                _ => {}
+            }
+        }
+        justfiles.sort();
+        justfiles
+    }
+
+    /// Checks every justfile of `workspace_folders` with `check`, and lists
+    /// which passed and the error of those which failed.
+    fn check_workspace(
+        workspace_folders: &[WorkspaceFolder],
+        check: impl Fn(&Path, &DocumentContext) -> Option<String>,
+    ) -> String {
+        let justfiles: Vec<PathBuf> = workspace_folders
+            .iter()
+            .filter_map(|folder| folder.uri.to_file_path().ok())
+            .flat_map(|root| Self::justfiles(&root))
+            .collect();
+        if justfiles.is_empty() {
+            return "No justfiles found in the workspace".to_string();
+        }
+        let mut failed = 0;
+        let mut lines = Vec::new();
+        for justfile in &justfiles {
+            let Ok(uri) = Url::from_file_path(justfile) else {
+                continue;
+            };
+            let context = DocumentContext::new(uri, workspace_folders.to_vec());
+            let path = context.display_path(justfile);
+            match check(justfile, &context) {
+                Some(error) => {
+                    failed += 1;
+                    lines.push(format!("failed: {path}: {error}"));
+                }
+                None => lines.push(format!("passed: {path}")),
+            }
+        }
+        format!(
+            "Checked {} justfiles, {failed} failed\n{}",
+            justfiles.len(),
+            lines.join("\n")
+        )
+    }
+
+    /// The first error `program --dry-run` reports for `justfile`, if any.
+    fn check_justfile(program: &str, justfile: &Path, context: &DocumentContext) -> Option<String> {
+        match output_with_timeout(&mut Self::command(program, justfile, context)) {
+            Ok(out) if out.status.success() => None,
+            Ok(out) => {
+                let stderr = String::from_utf8_lossy(&out.stderr);
+                let message = Self::error_parser()
+                    .parse_text(&stderr)
+                    .into_iter()
+                    .next()
+                    .map(|(_, diagnostic)| diagnostic.message)
+                    .or_else(|| stderr.lines().next().map(str::to_string));
+                Some(message.unwrap_or_default())
+            }
+            Err(err) => Some(err.to_string()),
+        }
+    }
+
+    /// The file at `path`, as reported by `just`, unless it is the checked
+    /// `justfile` or can't be resolved. Relative paths are relative to the
+    /// working directory, the document's directory.
+    fn imported_file(path: &str, justfile: &Path, context: &DocumentContext) -> Option<Url> {
+        let path = Path::new(path);
+        if path.file_name() == justfile.file_name() {
+            return None;
+        }
+        let path = if path.is_absolute() {
+            path.to_path_buf()
         } else {
-            log::warn!("Could not parse stderr: '{contents}'");
-            vec![]
+            context.directory()?.join(path)
+        };
+        Url::from_file_path(path).ok()
+    }
+
+    /// The recipe `name` in the files `model` imports from `directory`,
+    /// recursively, in the order `just` reads them.
+    fn imported_definition(
+        &self,
+        model: &JustModel,
+        directory: &Path,
+        name: &str,
+    ) -> Option<Location> {
+        let mut pending = model.import_paths(directory);
+        let mut visited = Vec::new();
+        let mut index = 0;
+        while let Some((path, _)) = pending.get(index).cloned() {
+            index += 1;
+            if visited.contains(&path) {
+                continue;
+            }
+            let Ok(contents) = std::fs::read_to_string(&path) else {
+                continue;
+            };
+            let imported =
+                self.imported_models
+                    .get_or_patch(&contents, JustModel::parse, |_, _| None);
+            if let Some(recipe) = imported.recipes.iter().find(|recipe| recipe.name == name) {
+                let uri = Url::from_file_path(&path).ok()?;
+                return Some(Location::new(uri, imported.range(&recipe.range)));
+            }
+            // Imports of the imported file are relative to it
+            pending.extend(imported.import_paths(path.parent().unwrap_or(directory)));
+            visited.push(path);
         }
+        None
     }
 
-    pub fn parse_stdout(_contents: &str) -> Vec<Diagnostic> {
-        vec![]
+    /// The first lines of the file at `path`, fenced, or `None` if it can't
+    /// be read.
+    fn preview(path: &Path) -> Option<String> {
+        let contents = std::fs::read_to_string(path).ok()?;
+        let mut lines: Vec<&str> = contents.lines().take(PREVIEW_LINES + 1).collect();
+        if lines.len() > PREVIEW_LINES {
+            lines[PREVIEW_LINES] = "…";
+        }
+        Some(format!("```just\n{}\n```", lines.join("\n")))
+    }
+
+    /// Parses the error reported by `just`, with the path of the file it
+    /// is in.
+    fn error_parser() -> RegexLineParser {
+        RegexLineParser {
+            regex: regex!(
+                r#"(?P<severity>\w+):\s(?P<message>.*)\n.*——▶\s*(?P<path>.*):(?P<line>\d+):(?P<column>\d+)"#
+            ),
+            source: "just",
+            stream: Stream::Stderr,
+            first_line: 1,
+            first_column: 1,
+            severity: parse_severity,
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::handlers::just::Just;
+    use crate::handlers::just::{is_checked, Just, JustConfig, CHECK_WORKSPACE};
+    use crate::handlers::just_model::JustModel;
+    use crate::handlers::{DocumentContext, Handler, HandlerError};
+    use std::path::Path;
+    use std::sync::Arc;
+    use tower_lsp::lsp_types::{HoverContents, Position, Range, Url, WorkspaceFolder};
+
+    #[test]
+    fn test_document_links() {
+        let contents = "set shell := ['bash', '-c']\n\nimport 'sub/other.just'\nmod? tools \"tools/mod.just\"\n";
+        let uri = Url::from_file_path("/project/justfile").unwrap();
+        let links = Just::new(JustConfig::default())
+            .unwrap()
+            .document_links(contents, &uri)
+            .ok()
+            .unwrap();
+
+        assert_eq!(links.len(), 2);
+        assert_eq!(links[0].range.start, Position::new(2, 8));
+        assert_eq!(links[0].range.end, Position::new(2, 22));
+        assert_eq!(
+            links[0].target,
+            Some(Url::from_file_path("/project/sub/other.just").unwrap())
+        );
+        assert_eq!(
+            links[1].target,
+            Some(Url::from_file_path("/project/tools/mod.just").unwrap())
+        );
+    }
+
+    #[test]
+    fn test_linked_editing_ranges() {
+        let contents = "set shell := ['bash', '-c']\n\nbuild target mode='debug':\n  echo {{target}}\n  cp out/{{ mode }}/{{target}} dist\n\nother:\n  echo {{target}}\n";
+        let just = Just::new(JustConfig::default()).unwrap();
+
+        let ranges = just
+            .linked_editing_ranges(contents, Position::new(2, 8))
+            .ok()
+            .unwrap()
+            .unwrap()
+            .ranges;
+        assert_eq!(
+            ranges,
+            vec![
+                Range::new(Position::new(2, 6), Position::new(2, 12)),
+                Range::new(Position::new(3, 9), Position::new(3, 15)),
+                Range::new(Position::new(4, 22), Position::new(4, 28)),
+            ]
+        );
+        // From a use, and not for the unrelated recipe
+        let from_use = just
+            .linked_editing_ranges(contents, Position::new(4, 25))
+            .ok()
+            .unwrap()
+            .unwrap()
+            .ranges;
+        assert_eq!(from_use, ranges);
+        assert!(just
+            .linked_editing_ranges(contents, Position::new(7, 10))
+            .ok()
+            .unwrap()
+            .is_none());
+    }
+
+    fn hover_text(contents: &str, position: Position) -> String {
+        let uri = Url::from_file_path("/project/justfile").unwrap();
+        let context = DocumentContext::new(uri, vec![]);
+        let hover = Just::new(JustConfig::default())
+            .unwrap()
+            .hover("just", &context, contents, position)
+            .ok()
+            .unwrap()
+            .unwrap();
+        match hover.contents {
+            HoverContents::Markup(markup) => markup.value,
+            _ => panic!("Expected markdown"),
+        }
+    }
+
+    #[test]
+    fn test_hover_dependency_chain() {
+        let contents = "deploy: build (test 'unit') && notify\n  ./deploy.sh\n\nbuild: compile\n\ncompile:\n  cargo build\n\ntest kind:\nnotify:\n";
+        let text = hover_text(contents, Position::new(0, 2));
+        assert_eq!(
+            text,
+            "Recipe `deploy`\n\n```text\ndeploy → build → compile\ndeploy → test\ndeploy → notify\n```"
+        );
+        // On a dependency
+        assert!(hover_text(contents, Position::new(0, 10)).contains("build → compile"));
+    }
+
+    #[test]
+    fn test_hover_dependency_cycle() {
+        let contents = "a: b\nb: c\nc: a\n";
+        assert!(hover_text(contents, Position::new(0, 0)).contains("a → b → c → a (cycle)"));
+    }
+
+    #[test]
+    fn test_hover_doc_comment() {
+        let contents = "# Build the project\n[private]\nbuild:\n  cargo build\n";
+        let text = hover_text(contents, Position::new(2, 1));
+        assert_eq!(
+            text,
+            "Recipe `build`\n\nBuild the project\n\n```text\nbuild\n```"
+        );
+    }
+
+    #[test]
+    fn test_hover_import_preview() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("sub")).unwrap();
+        std::fs::write(
+            dir.path().join("sub/other.just"),
+            "# Shared recipes\nlint:\n  cargo clippy\n",
+        )
+        .unwrap();
+        let contents = "import 'sub/other.just'\nimport? 'missing.just'\n";
+        let uri = Url::from_file_path(dir.path().join("justfile")).unwrap();
+        let context = DocumentContext::new(uri, vec![]);
+        let just = Just::new(JustConfig::default()).unwrap();
+        let hover = |position| {
+            let hover = just
+                .hover("just", &context, contents, position)
+                .ok()
+                .unwrap()
+                .unwrap();
+            match hover.contents {
+                HoverContents::Markup(markup) => markup.value,
+                _ => panic!("Expected markdown"),
+            }
+        };
+
+        assert_eq!(
+            hover(Position::new(0, 12)),
+            "`sub/other.just`\n\n```just\n# Shared recipes\nlint:\n  cargo clippy\n```"
+        );
+        assert_eq!(
+            hover(Position::new(1, 12)),
+            "`missing.just`\n\n(file not found)"
+        );
+    }
+
+    #[test]
+    fn test_definition_in_imported_file() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("sub")).unwrap();
+        std::fs::write(
+            dir.path().join("sub/other.just"),
+            "import 'tools.just'\n\nlint:\n  cargo clippy\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("sub/tools.just"),
+            "# Builds\nb:\n  cargo build\n",
+        )
+        .unwrap();
+        let contents = "import? 'missing.just'\nimport 'sub/other.just'\n\na: b lint\n  echo a\n";
+        let uri = Url::from_file_path(dir.path().join("justfile")).unwrap();
+        let context = DocumentContext::new(uri.clone(), vec![]);
+        let just = Just::new(JustConfig::default()).unwrap();
+        let definition = |position| just.definition(&context, contents, position).ok().unwrap();
+
+        // Imported by the imported file, relative to it
+        let location = definition(Position::new(3, 3)).unwrap();
+        assert_eq!(
+            location.uri,
+            Url::from_file_path(dir.path().join("sub/tools.just")).unwrap()
+        );
+        assert_eq!(
+            location.range,
+            Range::new(Position::new(1, 0), Position::new(1, 1))
+        );
+        assert_eq!(
+            definition(Position::new(3, 6)).unwrap().range.start,
+            Position::new(2, 0)
+        );
+        // Defined in the justfile
+        let location = definition(Position::new(3, 0)).unwrap();
+        assert_eq!(
+            (location.uri, location.range.start),
+            (uri, Position::new(3, 0))
+        );
+    }
+
+    #[test]
+    fn test_model_cache() {
+        let just = Just::new(JustConfig::default()).unwrap();
+        let model = just.model("build:\n  cargo build\n");
+        assert!(Arc::ptr_eq(&model, &just.model("build:\n  cargo build\n")));
+        assert!(!Arc::ptr_eq(&model, &just.model("test:\n  cargo test\n")));
+    }
+
+    #[test]
+    fn test_command_working_directory() {
+        let uri = Url::from_file_path("/project/sub/justfile").unwrap();
+        let context = DocumentContext::new(uri, vec![]);
+        let command = Just::command("just", Path::new("/tmp/justfile"), &context);
+        assert_eq!(command.get_current_dir(), Some(Path::new("/project/sub")));
+    }
+
+    #[test]
+    fn test_justfiles() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("broken")).unwrap();
+        std::fs::create_dir_all(dir.path().join(".git")).unwrap();
+        std::fs::write(dir.path().join("justfile"), "build:\n  cargo build\n").unwrap();
+        std::fs::write(
+            dir.path().join("broken/justfile"),
+            "build: missing\n  cargo build\n",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join(".git/justfile"), "").unwrap();
+        assert_eq!(
+            Just::justfiles(dir.path()),
+            vec![
+                dir.path().join("broken/justfile"),
+                dir.path().join("justfile")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_check_workspace() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("broken")).unwrap();
+        std::fs::write(dir.path().join("justfile"), "build:\n  cargo build\n").unwrap();
+        std::fs::write(
+            dir.path().join("broken/justfile"),
+            "build: missing\n  cargo build\n",
+        )
+        .unwrap();
+        let folder = WorkspaceFolder {
+            uri: Url::from_file_path(dir.path()).unwrap(),
+            name: "project".to_string(),
+        };
+        let summary = Just::check_workspace(&[folder], |justfile, _| {
+            justfile
+                .ends_with("broken/justfile")
+                .then(|| "Recipe `build` has unknown dependency `missing`".to_string())
+        });
+        assert_eq!(
+            summary,
+            "Checked 2 justfiles, 1 failed\nfailed: broken/justfile: Recipe `build` has unknown dependency `missing`\npassed: justfile"
+        );
+
+        let empty = tempfile::tempdir().unwrap();
+        let folder = WorkspaceFolder {
+            uri: Url::from_file_path(empty.path()).unwrap(),
+            name: "empty".to_string(),
+        };
+        assert_eq!(
+            Just::check_workspace(&[folder], |_, _| None),
+            "No justfiles found in the workspace"
+        );
+
+        let missing = Just::with_program(JustConfig::default(), "any-ls-missing-tool");
+        assert!(matches!(
+            missing.execute_workspace_command(CHECK_WORKSPACE, &[]),
+            Err(HandlerError::ToolNotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_imported_file_diagnostics() {
+        let mut just = Just::new(JustConfig::default()).unwrap();
+        if just.program.is_none() {
+            return;
+        }
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("other.just"), "lint:\n  echo lint\n\n!\n").unwrap();
+        let uri = Url::from_file_path(dir.path().join("justfile")).unwrap();
+        let context = DocumentContext::new(uri, vec![]);
+
+        let document = just
+            .update_document_diagnostics(&context, "import 'other.just'\n\nbuild: lint\n")
+            .await
+            .ok()
+            .unwrap();
+        assert!(document.diagnostics.is_empty());
+        let other = Url::from_file_path(dir.path().join("other.just")).unwrap();
+        assert_eq!(document.related[&other].len(), 1);
+        // The checked file is removed
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_parse_imported_file() {
+        let stderr = "error: Unknown start of token:\n ——▶ tools/other.just:3:5\n  │\n";
+        let diagnostics = Just::error_parser().parse_text(stderr);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].0.as_deref(), Some("tools/other.just"));
+
+        let uri = Url::from_file_path("/project/justfile").unwrap();
+        let context = DocumentContext::new(uri, vec![]);
+        let justfile = Path::new("/tmp/.tmpAbC123.just");
+        assert_eq!(
+            Just::imported_file(diagnostics[0].0.as_deref().unwrap(), justfile, &context),
+            Some(Url::from_file_path("/project/tools/other.just").unwrap())
+        );
+        // Errors in the document itself
+        assert_eq!(
+            Just::imported_file("/tmp/.tmpAbC123.just", justfile, &context),
+            None
+        );
+        assert_eq!(
+            Just::imported_file(".tmpAbC123.just", justfile, &context),
+            None
+        );
+    }
 
     #[test]
     fn test_parse() {
@@ -110,11 +906,62 @@ mod tests {
  ——▶ .tmpu9xSRk:3:4
   │
 3 │ a:::b
-  │    ^"#
+  │    ^"#,
         ];
 
-        for error in errors {
-            assert!(!Just::parse_stderr(error).is_empty());
+        for error in &errors {
+            assert!(!Just::error_parser().parse_text(error).is_empty());
         }
+        // 1-based, `.tmpu9xSRk:3:4` is the fourth character of `a:::b`
+        let diagnostics = Just::error_parser().parse_text(errors[3]);
+        assert_eq!(diagnostics[0].1.range.start, Position::new(2, 3));
+    }
+
+    #[test]
+    fn test_parse_invalid_utf8() {
+        let mut bytes = b"error: Unknown start of token \xff\xfe:\n".to_vec();
+        bytes.extend_from_slice(" ——▶ justfile:7:13\n  │\n".as_bytes());
+
+        let stderr = String::from_utf8_lossy(&bytes);
+        let diagnostics = Just::error_parser().parse_text(&stderr);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].1.range.start, Position::new(6, 12));
+    }
+
+    #[test]
+    fn test_checked_errors() {
+        let model = JustModel::parse("build: missing\n  cargo build\n\na: b\nb: a\n");
+        let mut checks = model.cycles();
+        checks.extend(model.undefined_dependencies(None));
+
+        let error = |stderr: &str| Just::error_parser().parse_text(stderr)[0].1.clone();
+        let undefined = error(
+            "error: Recipe `build` has unknown dependency `missing`\n ——▶ justfile:1:8\n  │\n",
+        );
+        assert!(is_checked(&checks, &undefined));
+        let cycle = error(
+            "error: Recipe `b` has circular dependency `a -> b -> a`\n ——▶ justfile:5:4\n  │\n",
+        );
+        assert!(is_checked(&checks, &cycle));
+        let other =
+            error("error: Recipe `lint` has unknown dependency `fmt`\n ——▶ justfile:9:7\n  │\n");
+        assert!(!is_checked(&checks, &other));
+        let syntax = error("error: Unknown start of token:\n ——▶ justfile:2:3\n  │\n");
+        assert!(!is_checked(&checks, &syntax));
+    }
+
+    #[test]
+    fn test_parse_colored() {
+        let plain = "error: Unknown start of token:\n ——▶ justfile:7:13\n  │\n";
+        let colored = "\x1b[1;31merror\x1b[0m: \x1b[1mUnknown start of token:\x1b[0m\n \x1b[1;34m——▶\x1b[0m justfile:7:13\n  │\n";
+
+        assert_eq!(
+            Just::error_parser().parse_text(colored),
+            Just::error_parser().parse_text(plain)
+        );
+        assert_eq!(
+            Just::error_parser().parse_text(colored)[0].1.message,
+            "Unknown start of token:"
+        );
     }
 }
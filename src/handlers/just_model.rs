@@ -0,0 +1,745 @@
+use lazy_regex::{regex, Captures, Regex};
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use tower_lsp::lsp_types::{self, Diagnostic, DiagnosticSeverity, DiagnosticTag};
+
+use super::text::offset_to_position;
+
+/// A file referenced by an `import` or `mod` statement.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Import {
+    pub path: String,
+    /// Range of the path, without quotes.
+    pub range: lsp_types::Range,
+    /// `import?` or `mod?`, the file may not exist.
+    pub optional: bool,
+    /// A `mod` statement, whose recipes are in their own namespace.
+    pub module: bool,
+}
+
+/// A recipe parameter, as byte ranges of its name.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Parameter {
+    pub name: String,
+    pub declaration: Range<usize>,
+    /// Uses in `{{...}}` interpolations of the recipe body.
+    pub uses: Vec<Range<usize>>,
+}
+
+/// A recipe and the recipes it depends on, as byte ranges of their names.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Recipe {
+    pub name: String,
+    pub range: Range<usize>,
+    /// The comment on the line above the recipe, shown by `just --list`.
+    pub doc: Option<String>,
+    pub parameters: Vec<Parameter>,
+    pub dependencies: Vec<(String, Range<usize>)>,
+}
+
+/// A variable assignment, `name := value`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Assignment {
+    pub name: String,
+    pub range: Range<usize>,
+    /// `export name := value`, passed to recipes as an environment variable.
+    pub exported: bool,
+}
+
+/// A justfile parsed once for all features of the Just handler.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JustModel {
+    contents: String,
+    pub imports: Vec<Import>,
+    pub recipes: Vec<Recipe>,
+    pub assignments: Vec<Assignment>,
+    /// Names defined by `alias name := recipe`.
+    pub aliases: Vec<String>,
+    /// Names used by expressions and interpolations, or read back from the
+    /// environment with `env_var`.
+    pub used: Vec<String>,
+    /// `set export`, every assignment is exported.
+    pub export_all: bool,
+    /// What each line added to the model, to patch it after an edit.
+    lines: Vec<ParsedLine>,
+}
+
+/// The parts of the model that come from a line.
+#[derive(Debug, Clone, PartialEq)]
+struct ParsedLine {
+    /// End of the names the line uses in `used`, which start at the end of
+    /// those of the previous line.
+    used_end: usize,
+    /// The recipe whose body is being read after the line.
+    recipe: Option<usize>,
+}
+
+fn identifier() -> &'static Regex {
+    regex!(r#"[A-Za-z_][\w-]*"#)
+}
+
+fn import_statement() -> &'static Regex {
+    regex!(r#"^(import|mod)(\??)(?:\s+[\w-]+)?\s+(?:'([^']*)'|"([^"]*)")"#)
+}
+
+fn alias_statement() -> &'static Regex {
+    regex!(r#"^alias\s+([A-Za-z_][\w-]*)\s*:="#)
+}
+
+fn set_statement() -> &'static Regex {
+    regex!(r#"^set\s"#)
+}
+
+fn assignment_statement() -> &'static Regex {
+    regex!(r#"^(export\s+)?([A-Za-z_][\w-]*)\s*:="#)
+}
+
+/// Whether `line` is in the body of a recipe, or empty.
+fn is_body(line: &str) -> bool {
+    line.starts_with([' ', '\t']) || line.trim().is_empty()
+}
+
+/// The names used by the `{{...}}` interpolations of `line`, with their
+/// byte offsets in it.
+fn interpolated_names(line: &str) -> Vec<(usize, &str)> {
+    regex!(r#"\{\{(.*?)\}\}"#)
+        .captures_iter(line)
+        .flat_map(|interpolation| {
+            let inner = interpolation.get(1).expect("Group 1 always matches");
+            identifier()
+                .find_iter(inner.as_str())
+                .map(move |name| (inner.start() + name.start(), name.as_str()))
+        })
+        .collect()
+}
+
+/// The names of the variables `line` reads from the environment with
+/// `env_var`.
+fn env_var_names(line: &str) -> Vec<&str> {
+    let env_var = regex!(r#"env_var(?:_or_default)?\(\s*(?:'([^']*)'|"([^"]*)")"#);
+    env_var
+        .captures_iter(line)
+        .map(|captures| {
+            let name = captures.get(1).or_else(|| captures.get(2));
+            name.expect("One of the groups matches").as_str()
+        })
+        .collect()
+}
+
+/// The names the assignment `line` uses in its value, `None` for other
+/// lines.
+fn assignment_value_names(line: &str) -> Option<Vec<&str>> {
+    if is_body(line)
+        || line.starts_with('#')
+        || import_statement().is_match(line)
+        || alias_statement().is_match(line)
+        || set_statement().is_match(line)
+    {
+        return None;
+    }
+    let head = assignment_statement().find(line)?;
+    Some(
+        identifier()
+            .find_iter(&line[head.end()..])
+            .map(|name| name.as_str())
+            .collect(),
+    )
+}
+
+/// The recipe header on `line`: groups `name`, `parameters` and
+/// `dependencies`.
+fn recipe_header(line: &str) -> Option<Captures<'_>> {
+    regex!(
+        r#"^@?(?P<name>[A-Za-z_][\w-]*)(?P<parameters>(?:[^:'"]|'[^']*'|"[^"]*")*?)\s*:(?P<dependencies>[^=].*|$)"#
+    )
+    .captures(line)
+}
+
+/// Whether the last recipe of `path` leads back to the first one, in which
+/// case `path` is extended to the whole cycle.
+fn find_cycle<'a>(
+    recipes: &HashMap<&str, &'a Recipe>,
+    path: &mut Vec<&'a str>,
+    visited: &mut Vec<&'a str>,
+) -> bool {
+    let current = path[path.len() - 1];
+    let Some(recipe) = recipes.get(current) else {
+        return false;
+    };
+    for (dependency, _) in &recipe.dependencies {
+        if *dependency == path[0] {
+            path.push(dependency);
+            return true;
+        }
+        if !visited.contains(&dependency.as_str()) {
+            visited.push(dependency);
+            path.push(dependency);
+            if find_cycle(recipes, path, visited) {
+                return true;
+            }
+            path.pop();
+        }
+    }
+    false
+}
+
+impl JustModel {
+    pub fn parse(contents: &str) -> Self {
+        let identifier = identifier();
+        let mut model = Self {
+            contents: contents.to_string(),
+            imports: Vec::new(),
+            recipes: Vec::new(),
+            assignments: Vec::new(),
+            aliases: Vec::new(),
+            used: Vec::new(),
+            export_all: false,
+            lines: Vec::new(),
+        };
+        // The recipe whose body is being read
+        let mut recipe: Option<usize> = None;
+        let mut comment: Option<String> = None;
+        let mut line_start = 0;
+        for line in contents.split('\n') {
+            let mut doc = None;
+            if is_body(line) {
+                for (offset, name) in interpolated_names(line) {
+                    model.used.push(name.to_string());
+                    let start = line_start + offset;
+                    if let Some(parameter) = recipe.and_then(|recipe| {
+                        model.recipes[recipe]
+                            .parameters
+                            .iter_mut()
+                            .find(|parameter| parameter.name == name)
+                    }) {
+                        parameter.uses.push(start..start + name.len());
+                    }
+                }
+            } else if let Some(text) = line.strip_prefix('#') {
+                recipe = None;
+                doc = Some(text.trim().to_string());
+            } else if let Some(import) = import_statement().captures(line) {
+                recipe = None;
+                let path = import.get(3).or_else(|| import.get(4));
+                let path = path.expect("One of the groups matches");
+                model.imports.push(Import {
+                    path: path.as_str().to_string(),
+                    range: lsp_types::Range {
+                        start: offset_to_position(contents, line_start + path.start()),
+                        end: offset_to_position(contents, line_start + path.end()),
+                    },
+                    optional: !import[2].is_empty(),
+                    module: &import[1] == "mod",
+                });
+            } else if let Some(alias) = alias_statement().captures(line) {
+                recipe = None;
+                model.aliases.push(alias[1].to_string());
+            } else if set_statement().is_match(line) {
+                recipe = None;
+                model.export_all |= regex!(r#"^set\s+export\b"#).is_match(line);
+            } else if let Some(assignment) = assignment_statement().captures(line) {
+                recipe = None;
+                let name = assignment.get(2).expect("Group 2 always matches");
+                model.assignments.push(Assignment {
+                    name: name.as_str().to_string(),
+                    range: line_start + name.start()..line_start + name.end(),
+                    exported: assignment.get(1).is_some(),
+                });
+                let value = &line[assignment.get(0).expect("Group 0 always matches").end()..];
+                model.used.extend(
+                    identifier
+                        .find_iter(value)
+                        .map(|name| name.as_str().to_string()),
+                );
+            } else if let Some(header) = recipe_header(line) {
+                recipe = Some(model.recipes.len());
+                model
+                    .recipes
+                    .push(Self::recipe(&header, line_start, comment.take()));
+                let name = header.name("name").expect("Group always matches");
+                model.used.extend(
+                    identifier
+                        .find_iter(&line[name.end()..])
+                        .map(|name| name.as_str().to_string()),
+                );
+            } else {
+                recipe = None;
+                // Attributes, e.g. `[private]`, go between the comment and
+                // the recipe
+                if line.starts_with('[') {
+                    doc = comment.take();
+                }
+                model.used.extend(
+                    identifier
+                        .find_iter(line)
+                        .map(|name| name.as_str().to_string()),
+                );
+            }
+
+            model
+                .used
+                .extend(env_var_names(line).into_iter().map(str::to_string));
+            model.lines.push(ParsedLine {
+                used_end: model.used.len(),
+                recipe,
+            });
+            comment = doc;
+            line_start += line.len() + 1;
+        }
+        model
+    }
+
+    /// The model of `contents`, an edit of the line of a recipe body or of
+    /// the value of an assignment of this model's contents, patched from
+    /// this one without parsing the other lines. `None` for other edits,
+    /// which can change the structure of the justfile.
+    pub fn patched(&self, contents: &str) -> Option<Self> {
+        let old_lines: Vec<&str> = self.contents.split('\n').collect();
+        let new_lines: Vec<&str> = contents.split('\n').collect();
+        if old_lines.len() != new_lines.len() {
+            return None;
+        }
+        let mut changed = (0..old_lines.len()).filter(|&i| old_lines[i] != new_lines[i]);
+        let index = changed.next()?;
+        if changed.next().is_some() {
+            return None;
+        }
+        let (old_line, new_line) = (old_lines[index], new_lines[index]);
+        let line_start: usize = old_lines[..index].iter().map(|line| line.len() + 1).sum();
+        let old_end = line_start + old_line.len();
+
+        let recipe = index
+            .checked_sub(1)
+            .and_then(|previous| self.lines[previous].recipe);
+        let mut uses = Vec::new();
+        let mut names = if is_body(old_line) && is_body(new_line) {
+            interpolated_names(new_line)
+                .into_iter()
+                .map(|(offset, name)| {
+                    uses.push((name, line_start + offset..line_start + offset + name.len()));
+                    name
+                })
+                .collect()
+        } else {
+            // Only the value can change, the name and its range stay
+            let old_head = assignment_statement().find(old_line)?;
+            let new_head = assignment_statement().find(new_line)?;
+            assignment_value_names(old_line)?;
+            if old_head.as_str() != new_head.as_str() {
+                return None;
+            }
+            assignment_value_names(new_line)?
+        };
+        names.extend(env_var_names(new_line));
+
+        let mut model = self.clone();
+        model.contents = contents.to_string();
+        let used_start = index.checked_sub(1).map_or(0, |i| self.lines[i].used_end);
+        let used_end = self.lines[index].used_end;
+        model.used.splice(
+            used_start..used_end,
+            names.iter().map(|name| name.to_string()),
+        );
+        for line in &mut model.lines[index..] {
+            line.used_end = line.used_end - (used_end - used_start) + names.len();
+        }
+
+        // Ranges after the line move with its end
+        let shift = |range: &mut Range<usize>| {
+            if range.start > old_end {
+                range.start = range.start - old_line.len() + new_line.len();
+                range.end = range.end - old_line.len() + new_line.len();
+            }
+        };
+        for (recipe_index, recipe_model) in model.recipes.iter_mut().enumerate() {
+            shift(&mut recipe_model.range);
+            for (_, range) in &mut recipe_model.dependencies {
+                shift(range);
+            }
+            for parameter in &mut recipe_model.parameters {
+                shift(&mut parameter.declaration);
+                let line_uses = parameter
+                    .uses
+                    .iter()
+                    .filter(|range| range.start >= line_start && range.start <= old_end)
+                    .count();
+                let position = parameter
+                    .uses
+                    .iter()
+                    .position(|range| range.start >= line_start)
+                    .unwrap_or(parameter.uses.len());
+                let added = (recipe == Some(recipe_index))
+                    .then_some(&uses)
+                    .into_iter()
+                    .flatten()
+                    .filter(|(name, _)| *name == parameter.name)
+                    .map(|(_, range)| range.clone());
+                parameter.uses.drain(position..position + line_uses);
+                for range in &mut parameter.uses {
+                    shift(range);
+                }
+                parameter.uses.splice(position..position, added);
+            }
+        }
+        for assignment in &mut model.assignments {
+            shift(&mut assignment.range);
+        }
+        Some(model)
+    }
+
+    /// The recipe with the header `header`, on the line starting at byte
+    /// `line_start`.
+    fn recipe(header: &Captures, line_start: usize, doc: Option<String>) -> Recipe {
+        let name = header.name("name").expect("Group always matches");
+
+        let params = header.name("parameters").expect("Group always matches");
+        let parameters = regex!(
+            r#"\$?[+*]?\$?([A-Za-z_][\w-]*)(?:\s*=\s*(?:'[^']*'|"[^"]*"|`[^`]*`|\([^)]*\)|[\w-]+))?"#
+        )
+        .captures_iter(params.as_str())
+        .map(|param| {
+            let name = param.get(1).expect("Group 1 always matches");
+            let start = line_start + params.start() + name.start();
+            Parameter {
+                name: name.as_str().to_string(),
+                declaration: start..start + name.len(),
+                uses: Vec::new(),
+            }
+        })
+        .collect();
+
+        let dependencies = header.name("dependencies").expect("Group always matches");
+        // Dependencies with arguments are written `(name args)`
+        let text = dependencies.as_str().split('#').next().unwrap_or_default();
+        let dependencies = regex!(r#"\(\s*([A-Za-z_][\w-]*)[^)]*\)|([A-Za-z_][\w-]*)"#)
+            .captures_iter(text)
+            .filter_map(|captures| captures.get(1).or_else(|| captures.get(2)))
+            .map(|dependency| {
+                let start = line_start + dependencies.start() + dependency.start();
+                (
+                    dependency.as_str().to_string(),
+                    start..start + dependency.len(),
+                )
+            })
+            .collect();
+
+        Recipe {
+            name: name.as_str().to_string(),
+            range: line_start + name.start()..line_start + name.end(),
+            doc,
+            parameters,
+            dependencies,
+        }
+    }
+
+    /// The positions of the byte `range`.
+    pub fn range(&self, range: &Range<usize>) -> lsp_types::Range {
+        lsp_types::Range::new(
+            offset_to_position(&self.contents, range.start),
+            offset_to_position(&self.contents, range.end),
+        )
+    }
+
+    pub fn recipes_by_name(&self) -> HashMap<&str, &Recipe> {
+        self.recipes
+            .iter()
+            .map(|recipe| (recipe.name.as_str(), recipe))
+            .collect()
+    }
+
+    /// The name of the recipe at byte `offset`, in a header or as a
+    /// dependency, with its range.
+    pub fn recipe_at(&self, offset: usize) -> Option<(&str, &Range<usize>)> {
+        let contains = |range: &Range<usize>| range.start <= offset && offset <= range.end;
+        self.recipes.iter().find_map(|recipe| {
+            std::iter::once((&recipe.name, &recipe.range))
+                .chain(
+                    recipe
+                        .dependencies
+                        .iter()
+                        .map(|(name, range)| (name, range)),
+                )
+                .find(|(_, range)| contains(range))
+                .map(|(name, range)| (name.as_str(), range))
+        })
+    }
+
+    /// The parameter declared or used at byte `offset`.
+    pub fn parameter_at(&self, offset: usize) -> Option<&Parameter> {
+        let contains = |range: &Range<usize>| range.start <= offset && offset <= range.end;
+        self.recipes
+            .iter()
+            .flat_map(|recipe| &recipe.parameters)
+            .find(|parameter| {
+                contains(&parameter.declaration) || parameter.uses.iter().any(contains)
+            })
+    }
+
+    /// Paths of the files imported with `import`, relative to `directory`,
+    /// and whether they are optional.
+    pub fn import_paths(&self, directory: &Path) -> Vec<(PathBuf, bool)> {
+        self.imports
+            .iter()
+            .filter(|import| !import.module)
+            .map(|import| (directory.join(&import.path), import.optional))
+            .collect()
+    }
+
+    /// Names of the recipes and aliases defined by the files this justfile
+    /// imports from `directory`, recursively. `None` if a required import
+    /// can't be read, as any recipe could be defined there.
+    fn imported_recipes(&self, directory: &Path) -> Option<Vec<String>> {
+        let mut names = Vec::new();
+        let mut pending = self.import_paths(directory);
+        let mut visited = Vec::new();
+        while let Some((path, optional)) = pending.pop() {
+            if visited.contains(&path) {
+                continue;
+            }
+            let imported = match std::fs::read_to_string(&path) {
+                Ok(imported) => Self::parse(&imported),
+                Err(_) if optional => continue,
+                Err(_) => return None,
+            };
+            names.extend(imported.recipes.iter().map(|recipe| recipe.name.clone()));
+            names.extend(imported.aliases.iter().cloned());
+            // Imports of the imported file are relative to it
+            pending.extend(imported.import_paths(path.parent().unwrap_or(directory)));
+            visited.push(path);
+        }
+        Some(names)
+    }
+
+    /// Errors on dependencies naming a recipe that isn't defined in the
+    /// justfile or the files it imports from `directory`.
+    pub fn undefined_dependencies(&self, directory: Option<&Path>) -> Vec<Diagnostic> {
+        let mut defined: Vec<String> = self
+            .recipes
+            .iter()
+            .map(|recipe| recipe.name.clone())
+            .collect();
+        defined.extend(self.aliases.iter().cloned());
+        if self.imports.iter().any(|import| !import.module) {
+            match directory.and_then(|directory| self.imported_recipes(directory)) {
+                Some(imported) => defined.extend(imported),
+                None => return Vec::new(),
+            }
+        }
+
+        self.recipes
+            .iter()
+            .flat_map(|recipe| &recipe.dependencies)
+            .filter(|(name, _)| !defined.contains(name))
+            .map(|(name, range)| {
+                Diagnostic::new(
+                    self.range(range),
+                    Some(DiagnosticSeverity::ERROR),
+                    None,
+                    Some("just".to_string()),
+                    format!("Recipe `{name}` is not defined"),
+                    None,
+                    None,
+                )
+            })
+            .collect()
+    }
+
+    /// Hints at assignments whose name isn't used by other assignments,
+    /// recipe headers or interpolations. Exported variables, and those read
+    /// back with `env_var`, are used by the environment of recipes.
+    pub fn unused_variables(&self) -> Vec<Diagnostic> {
+        if self.export_all {
+            return Vec::new();
+        }
+        self.assignments
+            .iter()
+            .filter(|assignment| !assignment.exported && !self.used.contains(&assignment.name))
+            .map(|assignment| {
+                let mut diagnostic = Diagnostic::new(
+                    self.range(&assignment.range),
+                    Some(DiagnosticSeverity::HINT),
+                    None,
+                    Some("just".to_string()),
+                    format!("Variable `{}` is never used", assignment.name),
+                    None,
+                    None,
+                );
+                diagnostic.tags = Some(vec![DiagnosticTag::UNNECESSARY]);
+                diagnostic
+            })
+            .collect()
+    }
+
+    /// Errors on the header of every recipe depending on itself, directly
+    /// or through other recipes.
+    pub fn cycles(&self) -> Vec<Diagnostic> {
+        let by_name = self.recipes_by_name();
+        self.recipes
+            .iter()
+            .filter_map(|recipe| {
+                let mut path = vec![recipe.name.as_str()];
+                if !find_cycle(&by_name, &mut path, &mut Vec::new()) {
+                    return None;
+                }
+                Some(Diagnostic::new(
+                    self.range(&recipe.range),
+                    Some(DiagnosticSeverity::ERROR),
+                    None,
+                    Some("just".to_string()),
+                    format!("Dependency cycle: {}", path.join(" → ")),
+                    None,
+                    None,
+                ))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Assignment, JustModel, Parameter};
+    use tower_lsp::lsp_types::{DiagnosticSeverity, DiagnosticTag, Position, Range};
+
+    #[test]
+    fn test_parse() {
+        let contents = "import? 'local.just'\nmod tools\nversion := \"1.0\"\nexport TOKEN := env_var('TOKEN')\n\n# Build a target\n[no-cd]\nbuild target='all': (lint target) && notify\n  make {{target}} {{version}}\n\nlint name:\nnotify:\n";
+        let model = JustModel::parse(contents);
+
+        assert_eq!(model.imports.len(), 1);
+        assert_eq!(model.imports[0].path, "local.just");
+        assert!(model.imports[0].optional);
+
+        assert_eq!(
+            model.assignments,
+            vec![
+                Assignment {
+                    name: "version".to_string(),
+                    range: 31..38,
+                    exported: false,
+                },
+                Assignment {
+                    name: "TOKEN".to_string(),
+                    range: 55..60,
+                    exported: true,
+                },
+            ]
+        );
+
+        let names: Vec<&str> = model.recipes.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, vec!["build", "lint", "notify"]);
+        let build = &model.recipes[0];
+        assert_eq!(build.doc.as_deref(), Some("Build a target"));
+        assert_eq!(&contents[build.range.clone()], "build");
+        assert_eq!(build.parameters.len(), 1);
+        let Parameter {
+            name,
+            declaration,
+            uses,
+        } = &build.parameters[0];
+        assert_eq!(name, "target");
+        assert_eq!(*declaration, 113..119);
+        assert_eq!(uses.len(), 1);
+        assert_eq!(uses[0], 160..166);
+        let dependencies: Vec<&str> = build
+            .dependencies
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect();
+        assert_eq!(dependencies, vec!["lint", "notify"]);
+        assert_eq!(model.recipes[1].doc, None);
+        assert!(model.used.contains(&"version".to_string()));
+    }
+
+    #[test]
+    fn test_patch_line_edit() {
+        let contents = "version := \"1.0\"\n\nbuild target='all': lint\n  make {{target}}\n  echo {{version}} {{target}}\n\nlint:\n  cargo clippy\n";
+        let model = JustModel::parse(contents);
+
+        // The value of an assignment, moving the ranges after it
+        let edited = contents.replace("\"1.0\"", "env_var('VERSION') + suffix");
+        assert_eq!(model.patched(&edited), Some(JustModel::parse(&edited)));
+
+        // A line of a recipe body, with parameter uses before and after it
+        let edited = contents.replace("make {{target}}", "make -j{{jobs}} {{ target }}");
+        assert_eq!(model.patched(&edited), Some(JustModel::parse(&edited)));
+        let edited = contents.replace("cargo clippy", "cargo clippy {{target}}");
+        assert_eq!(model.patched(&edited), Some(JustModel::parse(&edited)));
+
+        // Structural edits are parsed again
+        let edited = contents.replace("version :=", "release :=");
+        assert_eq!(model.patched(&edited), None);
+        let edited = contents.replace("lint:", "lint target:");
+        assert_eq!(model.patched(&edited), None);
+        let edited = contents.replace("\n\nlint", "\nlint");
+        assert_eq!(model.patched(&edited), None);
+    }
+
+    #[test]
+    fn test_cycles() {
+        let diagnostics = JustModel::parse("a: b\n  echo a\n\nb: a\n\nc: a\n").cycles();
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].message, "Dependency cycle: a → b → a");
+        assert_eq!(
+            diagnostics[0].range,
+            Range::new(Position::new(0, 0), Position::new(0, 1))
+        );
+        assert_eq!(diagnostics[1].message, "Dependency cycle: b → a → b");
+        assert_eq!(diagnostics[1].range.start.line, 3);
+
+        assert!(JustModel::parse("a: b c\nb: c\nc:\n").cycles().is_empty());
+    }
+
+    #[test]
+    fn test_undefined_dependencies() {
+        let contents = "alias b := build\n\nbuild: nonexistent\n\ndeploy: b (build) && notify\n";
+        let diagnostics = JustModel::parse(contents).undefined_dependencies(None);
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(
+            diagnostics[0].message,
+            "Recipe `nonexistent` is not defined"
+        );
+        assert_eq!(
+            diagnostics[0].range,
+            Range::new(Position::new(2, 7), Position::new(2, 18))
+        );
+        assert_eq!(diagnostics[1].message, "Recipe `notify` is not defined");
+    }
+
+    #[test]
+    fn test_undefined_dependencies_imported() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("other.just"), "notify:\n  echo done\n").unwrap();
+        let contents = "import 'other.just'\nimport? 'missing.just'\n\nbuild: notify lint\n";
+        let diagnostics = JustModel::parse(contents).undefined_dependencies(Some(dir.path()));
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].message, "Recipe `lint` is not defined");
+
+        // Recipes may be defined in an import that can't be read
+        let contents = "import 'unreadable.just'\n\nbuild: lint\n";
+        assert!(JustModel::parse(contents)
+            .undefined_dependencies(Some(dir.path()))
+            .is_empty());
+        assert!(JustModel::parse(contents)
+            .undefined_dependencies(None)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_unused_variables() {
+        let contents = "version := \"1.0\"\nunused := \"x\"\nexport TOKEN := \"secret\"\nhome := env_var('HOME')\nHOME := \"/\"\n\nbuild:\n  echo {{ version }} {{home}}\n";
+        let diagnostics = JustModel::parse(contents).unused_variables();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].message, "Variable `unused` is never used");
+        assert_eq!(
+            diagnostics[0].range,
+            Range::new(Position::new(1, 0), Position::new(1, 6))
+        );
+        assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::HINT));
+        assert_eq!(diagnostics[0].tags, Some(vec![DiagnosticTag::UNNECESSARY]));
+
+        assert!(JustModel::parse("set export\n\nunused := \"x\"\n")
+            .unused_variables()
+            .is_empty());
+    }
+}
@@ -0,0 +1,738 @@
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::Path;
+use tower_lsp::lsp_types::{
+    CompletionItem, CompletionItemKind, CompletionOptions, Hover, HoverContents,
+    HoverProviderCapability, MarkupContent, MarkupKind, Position, ServerCapabilities,
+};
+
+use super::text::position_to_offset;
+use super::{DocumentContext, Handler, HandlerError};
+
+/// Hover showing the path of the key under the cursor in JSON and TOML
+/// files, e.g. `servers.prod.port`, to help matching against schema docs.
+/// Known JSON files, like `package.json`, complete keys and values from a
+/// bundled schema.
+#[derive(Debug)]
+pub struct KeyPath {}
+
+/// The JSON schema bundled for the file at `path`.
+fn bundled_schema(path: &Path) -> Option<&'static str> {
+    match path.file_name()?.to_str()? {
+        "package.json" => Some(include_str!("schemas/package.schema.json")),
+        _ => None,
+    }
+}
+
+/// The schema of the value at `path` in a document of `schema`.
+fn schema_at<'a>(schema: &'a Value, path: &[Segment]) -> Option<&'a Value> {
+    path.iter()
+        .try_fold(schema, |schema, segment| match segment {
+            Segment::Key(key) => schema.get("properties")?.get(key),
+            Segment::Index(_) => schema.get("items"),
+        })
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+impl KeyPath {
+    pub fn new() -> Result<Self, String> {
+        Ok(Self {})
+    }
+
+    /// Dotted path with array indices in brackets, e.g. `servers[0].port`.
+    pub fn dotted(path: &[Segment]) -> String {
+        let mut out = String::new();
+        for segment in path {
+            match segment {
+                Segment::Key(key) => {
+                    if !out.is_empty() {
+                        out.push('.');
+                    }
+                    if !key.is_empty()
+                        && key
+                            .chars()
+                            .all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+                    {
+                        out.push_str(key);
+                    } else {
+                        out.push_str(&format!("{key:?}"));
+                    }
+                }
+                Segment::Index(index) => out.push_str(&format!("[{index}]")),
+            }
+        }
+        out
+    }
+
+    /// JSON pointer (RFC 6901), e.g. `/servers/0/port`.
+    pub fn pointer(path: &[Segment]) -> String {
+        path.iter()
+            .map(|segment| match segment {
+                Segment::Key(key) => format!("/{}", key.replace('~', "~0").replace('/', "~1")),
+                Segment::Index(index) => format!("/{index}"),
+            })
+            .collect()
+    }
+}
+
+impl KeyPath {
+    /// The keys and values `schema` allows at `slot`.
+    pub fn schema_completions(schema: &Value, slot: &JsonSlot) -> Vec<CompletionItem> {
+        let Some(schema) = schema_at(schema, &slot.path) else {
+            return Vec::new();
+        };
+        let quote = |text: &str| {
+            if slot.quoted {
+                text.to_string()
+            } else {
+                format!("\"{text}\"")
+            }
+        };
+
+        if !slot.value {
+            let Some(Value::Object(properties)) = schema.get("properties") else {
+                return Vec::new();
+            };
+            return properties
+                .iter()
+                .map(|(key, property)| CompletionItem {
+                    label: key.clone(),
+                    kind: Some(CompletionItemKind::PROPERTY),
+                    detail: property
+                        .get("description")
+                        .and_then(Value::as_str)
+                        .map(str::to_string),
+                    insert_text: Some(quote(key)),
+                    ..Default::default()
+                })
+                .collect();
+        }
+
+        let Some(Value::Array(values)) = schema.get("enum") else {
+            return Vec::new();
+        };
+        values
+            .iter()
+            .map(|value| {
+                let (label, insert_text) = match value {
+                    Value::String(value) => (value.clone(), quote(value)),
+                    value => (value.to_string(), value.to_string()),
+                };
+                CompletionItem {
+                    label,
+                    kind: Some(CompletionItemKind::VALUE),
+                    insert_text: Some(insert_text),
+                    ..Default::default()
+                }
+            })
+            .collect()
+    }
+}
+
+impl Handler for KeyPath {
+    fn filetype_supported(&self, filetype: &str) -> bool {
+        matches!(filetype, "json" | "jsonc" | "json5" | "toml")
+    }
+
+    fn get_capabilities(&self) -> ServerCapabilities {
+        ServerCapabilities {
+            hover_provider: Some(HoverProviderCapability::Simple(true)),
+            completion_provider: Some(CompletionOptions {
+                trigger_characters: Some(vec!["\"".to_string()]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn completions(
+        &self,
+        filetype: &str,
+        context: &DocumentContext,
+        contents: &str,
+        position: Position,
+    ) -> Result<Vec<CompletionItem>, HandlerError> {
+        if filetype == "toml" {
+            return Ok(Vec::new());
+        }
+        let Some(schema) = context
+            .uri
+            .to_file_path()
+            .ok()
+            .and_then(|path| bundled_schema(&path))
+        else {
+            return Ok(Vec::new());
+        };
+        let schema: Value = serde_json::from_str(schema)
+            .map_err(|e| HandlerError::Parse(format!("Invalid bundled schema: {e}")))?;
+
+        let offset = position_to_offset(contents, position);
+        Ok(json_slot_at(contents, offset)
+            .map(|slot| Self::schema_completions(&schema, &slot))
+            .unwrap_or_default())
+    }
+
+    fn hover(
+        &self,
+        filetype: &str,
+        _context: &DocumentContext,
+        contents: &str,
+        position: Position,
+    ) -> Result<Option<Hover>, HandlerError> {
+        let offset = position_to_offset(contents, position);
+        let path = match filetype {
+            "toml" => toml_path_at(contents, offset),
+            _ => json_path_at(contents, offset),
+        };
+        let Some(path) = path.filter(|path| !path.is_empty()) else {
+            return Ok(None);
+        };
+
+        Ok(Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: format!(
+                    "`{}`\n\nJSON pointer: `{}`",
+                    Self::dotted(&path),
+                    Self::pointer(&path)
+                ),
+            }),
+            range: None,
+        }))
+    }
+}
+
+#[derive(Debug)]
+enum Frame {
+    /// Object with the key currently being read or valued.
+    Object(Option<Vec<String>>),
+    Array(usize),
+}
+
+fn frames_path(stack: &[Frame]) -> Vec<Segment> {
+    let mut path = Vec::new();
+    for frame in stack {
+        match frame {
+            Frame::Object(Some(keys)) => {
+                path.extend(keys.iter().cloned().map(Segment::Key));
+            }
+            Frame::Object(None) => break,
+            Frame::Array(index) => path.push(Segment::Index(*index)),
+        }
+    }
+    path
+}
+
+/// Byte index after the string starting with the quote at `start`.
+fn string_end(bytes: &[u8], start: usize) -> usize {
+    let quote = bytes[start];
+    let mut i = start + 1;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' if quote == b'"' => i += 2,
+            c if c == quote => return i + 1,
+            b'\n' => return i,
+            _ => i += 1,
+        }
+    }
+    bytes.len()
+}
+
+/// Contents of a quoted string token with simple escapes resolved.
+fn unquote(token: &str) -> String {
+    let quote = token.chars().next().unwrap_or('"');
+    let inner = token[1..].strip_suffix(quote).unwrap_or(&token[1..]);
+    if quote == '\'' {
+        return inner.to_string();
+    }
+    let mut out = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('n') => out.push('\n'),
+                Some('t') => out.push('\t'),
+                Some(other) => out.push(other),
+                None => {}
+            },
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn is_delimiter(c: u8) -> bool {
+    c.is_ascii_whitespace() || b",:=[]{}#\"'".contains(&c)
+}
+
+/// Path of the key or value at byte `offset` in a JSON (or JSONC/JSON5)
+/// document.
+pub fn json_path_at(contents: &str, offset: usize) -> Option<Vec<Segment>> {
+    let bytes = contents.as_bytes();
+    let mut stack: Vec<Frame> = Vec::new();
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let start = i;
+        match bytes[i] {
+            c if c.is_ascii_whitespace() => {
+                i += 1;
+                continue;
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'/') => {
+                i = contents[i..].find('\n').map_or(bytes.len(), |end| i + end);
+                continue;
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                i = contents[i..]
+                    .find("*/")
+                    .map_or(bytes.len(), |end| i + end + 2);
+                continue;
+            }
+            _ => {}
+        }
+        if offset < start {
+            return Some(frames_path(&stack));
+        }
+
+        let end = match bytes[i] {
+            b'"' | b'\'' => string_end(bytes, i),
+            b'{' | b'}' | b'[' | b']' | b',' | b':' => i + 1,
+            _ => {
+                let mut end = i + 1;
+                while end < bytes.len() && !is_delimiter(bytes[end]) {
+                    end += 1;
+                }
+                end
+            }
+        };
+        let token = &contents[start..end];
+        match bytes[i] {
+            b'{' | b'[' | b',' | b':' if offset < end => return Some(frames_path(&stack)),
+            b'}' | b']' if offset < end => {
+                return Some(frames_path(&stack[..stack.len().saturating_sub(1)]));
+            }
+            b'{' => stack.push(Frame::Object(None)),
+            b'[' => stack.push(Frame::Array(0)),
+            b'}' | b']' => {
+                stack.pop();
+            }
+            b',' => match stack.last_mut() {
+                Some(Frame::Array(index)) => *index += 1,
+                Some(Frame::Object(key)) => *key = None,
+                None => {}
+            },
+            b':' => {}
+            c => {
+                // Strings, and bare identifiers in JSON5, are keys when an
+                // object is waiting for one
+                if let Some(Frame::Object(key @ None)) = stack.last_mut() {
+                    let name = if c == b'"' || c == b'\'' {
+                        unquote(token)
+                    } else {
+                        token.to_string()
+                    };
+                    *key = Some(vec![name]);
+                }
+                if offset < end {
+                    return Some(frames_path(&stack));
+                }
+            }
+        }
+        i = end;
+    }
+    None
+}
+
+/// Where a completion in a JSON document goes.
+#[derive(Debug, PartialEq)]
+pub struct JsonSlot {
+    /// The path of the object whose key is completed, or of the key whose
+    /// value is completed.
+    pub path: Vec<Segment>,
+    pub value: bool,
+    /// Whether the cursor is in a string, after its opening quote.
+    pub quoted: bool,
+}
+
+/// The key or value being typed at byte `offset`, found by the delimiter
+/// before it: keys follow `{` or `,` in objects and values follow `:`.
+pub fn json_slot_at(contents: &str, offset: usize) -> Option<JsonSlot> {
+    let before = contents.get(..offset)?;
+    let word = before.trim_end_matches(|c: char| c.is_alphanumeric() || "-_@/.$".contains(c));
+    let unquoted = word.strip_suffix('"');
+    let delimiter = unquoted.unwrap_or(word).trim_end();
+    let at = delimiter.len().checked_sub(1)?;
+    let mut path = json_path_at(contents, at)?;
+    let value = match delimiter.as_bytes()[at] {
+        b'{' => false,
+        // The key before the comma is on the path
+        b',' => match path.pop() {
+            Some(Segment::Key(_)) => false,
+            _ => return None,
+        },
+        b':' => true,
+        _ => return None,
+    };
+    Some(JsonSlot {
+        path,
+        value,
+        quoted: unquoted.is_some(),
+    })
+}
+
+/// Splits a dotted TOML key like `a."b.c".d` into its parts.
+fn toml_keys(text: &str) -> Vec<String> {
+    let mut keys = Vec::new();
+    let bytes = text.as_bytes();
+    let mut i = 0;
+    let mut current = String::new();
+    while i < bytes.len() {
+        match bytes[i] {
+            b'"' | b'\'' => {
+                let end = string_end(bytes, i);
+                current.push_str(&unquote(&text[i..end]));
+                i = end;
+                continue;
+            }
+            b'.' => keys.push(std::mem::take(&mut current)),
+            c if c.is_ascii_whitespace() => {}
+            _ => {
+                let len = text[i..].chars().next().map_or(1, char::len_utf8);
+                current.push_str(&text[i..i + len]);
+                i += len;
+                continue;
+            }
+        }
+        i += 1;
+    }
+    keys.push(current);
+    keys
+}
+
+/// Resolves a table header's keys to a path, indexing into the latest
+/// element of any array of tables along the way.
+fn resolve_table(keys: &[String], array_tables: &HashMap<Vec<String>, usize>) -> Vec<Segment> {
+    let mut path = Vec::new();
+    for (i, key) in keys.iter().enumerate() {
+        path.push(Segment::Key(key.clone()));
+        if let Some(count) = array_tables.get(&keys[..=i]) {
+            path.push(Segment::Index(count - 1));
+        }
+    }
+    path
+}
+
+/// Path of the key or value at byte `offset` in a TOML document.
+pub fn toml_path_at(contents: &str, offset: usize) -> Option<Vec<Segment>> {
+    let bytes = contents.as_bytes();
+    let mut table: Vec<Segment> = Vec::new();
+    let mut array_tables: HashMap<Vec<String>, usize> = HashMap::new();
+    // Inline arrays and tables of the current value, keyed by the
+    // top-level key of the line
+    let mut stack: Vec<Frame> = Vec::new();
+    let mut i = 0;
+
+    let path = |table: &[Segment], stack: &[Frame]| {
+        let mut path = table.to_vec();
+        path.extend(frames_path(stack));
+        path
+    };
+
+    while i < bytes.len() {
+        let start = i;
+        match bytes[i] {
+            b'\n' if stack.len() <= 1 => {
+                if offset == start {
+                    return Some(path(&table, &stack));
+                }
+                stack.clear();
+                i += 1;
+                continue;
+            }
+            c if c.is_ascii_whitespace() => {
+                i += 1;
+                continue;
+            }
+            b'#' => {
+                i = contents[i..].find('\n').map_or(bytes.len(), |end| i + end);
+                continue;
+            }
+            _ => {}
+        }
+        if offset < start {
+            return Some(path(&table, &stack));
+        }
+
+        // Table headers and keys at the start of a line
+        if stack.is_empty() {
+            let line_end = contents[i..].find('\n').map_or(bytes.len(), |end| i + end);
+            if bytes[i] == b'[' {
+                let is_array = bytes.get(i + 1) == Some(&b'[');
+                let inner_start = if is_array { i + 2 } else { i + 1 };
+                let inner_end = contents[inner_start..line_end]
+                    .find(']')
+                    .map_or(line_end, |end| inner_start + end);
+                let keys = toml_keys(&contents[inner_start..inner_end]);
+                if is_array {
+                    *array_tables.entry(keys.clone()).or_insert(0) += 1;
+                }
+                table = resolve_table(&keys, &array_tables);
+                if offset < line_end {
+                    return Some(table);
+                }
+                i = line_end;
+                continue;
+            }
+
+            let equals = contents[i..line_end]
+                .find('=')
+                .map_or(line_end, |end| i + end);
+            let keys = toml_keys(&contents[i..equals]);
+            stack.push(Frame::Object(Some(keys)));
+            if offset <= equals {
+                return Some(path(&table, &stack));
+            }
+            i = equals + 1;
+            continue;
+        }
+
+        let end = match bytes[i] {
+            b'"' | b'\'' => {
+                // Bytes, the third one may be inside a character
+                if bytes[i..].starts_with(b"\"\"\"") || bytes[i..].starts_with(b"'''") {
+                    let quote = &contents[i..i + 3];
+                    contents[i + 3..]
+                        .find(quote)
+                        .map_or(bytes.len(), |end| i + 3 + end + 3)
+                } else {
+                    string_end(bytes, i)
+                }
+            }
+            b'{' | b'}' | b'[' | b']' | b',' | b'=' => i + 1,
+            _ => {
+                let mut end = i + 1;
+                while end < bytes.len() && !is_delimiter(bytes[end]) {
+                    end += 1;
+                }
+                end
+            }
+        };
+        let token = &contents[start..end];
+        match bytes[i] {
+            b'{' | b'[' | b',' | b'=' if offset < end => return Some(path(&table, &stack)),
+            b'}' | b']' if offset < end => {
+                return Some(path(&table, &stack[..stack.len() - 1]));
+            }
+            b'{' => stack.push(Frame::Object(None)),
+            b'[' => stack.push(Frame::Array(0)),
+            b'}' | b']' => {
+                if stack.len() > 1 {
+                    stack.pop();
+                }
+            }
+            b',' => {
+                let nested = stack.len() > 1;
+                match stack.last_mut() {
+                    Some(Frame::Array(index)) => *index += 1,
+                    Some(Frame::Object(key)) if nested => *key = None,
+                    _ => {}
+                }
+            }
+            b'=' => {}
+            _ => {
+                if let Some(Frame::Object(key @ None)) = stack.last_mut() {
+                    *key = Some(toml_keys(token));
+                }
+                if offset < end {
+                    return Some(path(&table, &stack));
+                }
+            }
+        }
+        i = end;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{json_path_at, json_slot_at, toml_path_at, JsonSlot, KeyPath, Segment};
+    use crate::handlers::{DocumentContext, Handler};
+    use tower_lsp::lsp_types::{Position, Url};
+
+    fn offset_of(contents: &str, needle: &str) -> usize {
+        contents.find(needle).expect("Needle in contents")
+    }
+
+    #[test]
+    fn test_json_nested_key() {
+        let contents = r#"{
+  "servers": {
+    "prod": { "host": "example.com", "port": 8080 },
+    "dev": { "port": 3000 }
+  }
+}"#;
+        let path = json_path_at(contents, offset_of(contents, "port")).unwrap();
+        assert_eq!(KeyPath::dotted(&path), "servers.prod.port");
+        assert_eq!(KeyPath::pointer(&path), "/servers/prod/port");
+
+        let path = json_path_at(contents, offset_of(contents, "3000")).unwrap();
+        assert_eq!(KeyPath::dotted(&path), "servers.dev.port");
+    }
+
+    #[test]
+    fn test_json_array_element() {
+        let contents = r#"{
+  // Comments are skipped
+  "servers": [
+    { "port": 80 },
+    { "port": 443, "tags": ["a", "b"] }
+  ]
+}"#;
+        let path = json_path_at(contents, offset_of(contents, "443")).unwrap();
+        assert_eq!(KeyPath::dotted(&path), "servers[1].port");
+        assert_eq!(KeyPath::pointer(&path), "/servers/1/port");
+
+        let path = json_path_at(contents, offset_of(contents, "\"b\"")).unwrap();
+        assert_eq!(
+            path,
+            vec![
+                Segment::Key("servers".to_string()),
+                Segment::Index(1),
+                Segment::Key("tags".to_string()),
+                Segment::Index(1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_toml_nested_key() {
+        let contents = r#"name = "app"
+
+[servers.prod]
+host = "example.com"
+port = 8080
+
+[servers.dev]
+port = 3000
+"#;
+        let path = toml_path_at(contents, offset_of(contents, "port")).unwrap();
+        assert_eq!(KeyPath::dotted(&path), "servers.prod.port");
+
+        let path = toml_path_at(contents, offset_of(contents, "3000")).unwrap();
+        assert_eq!(KeyPath::dotted(&path), "servers.dev.port");
+
+        let path = toml_path_at(contents, offset_of(contents, "app")).unwrap();
+        assert_eq!(KeyPath::dotted(&path), "name");
+    }
+
+    #[test]
+    fn test_toml_array_element() {
+        let contents = r#"[[servers]]
+port = 80
+
+[[servers]]
+port = 443
+tags = ["a", "b"]
+inline = { nested.key = 1 }
+"#;
+        let path = toml_path_at(contents, offset_of(contents, "443")).unwrap();
+        assert_eq!(KeyPath::dotted(&path), "servers[1].port");
+
+        let path = toml_path_at(contents, offset_of(contents, "\"b\"")).unwrap();
+        assert_eq!(KeyPath::dotted(&path), "servers[1].tags[1]");
+
+        let path = toml_path_at(contents, offset_of(contents, "1 }")).unwrap();
+        assert_eq!(KeyPath::dotted(&path), "servers[1].inline.nested.key");
+    }
+
+    #[test]
+    fn test_toml_non_ascii_string() {
+        let contents = "name = \"aé\"\nbio = '''\nnaïve\n'''\nport = 80\n";
+        for offset in 0..=contents.len() {
+            if contents.is_char_boundary(offset) {
+                toml_path_at(contents, offset);
+            }
+        }
+        let path = toml_path_at(contents, offset_of(contents, "80")).unwrap();
+        assert_eq!(KeyPath::dotted(&path), "port");
+    }
+
+    #[test]
+    fn test_json_slot() {
+        let contents = r#"{"name": "app", "publishConfig": {"access": "pu"}, "de"#;
+        assert_eq!(
+            json_slot_at(contents, contents.len()),
+            Some(JsonSlot {
+                path: vec![],
+                value: false,
+                quoted: true,
+            })
+        );
+
+        let offset = offset_of(contents, "pu\"") + 2;
+        assert_eq!(
+            json_slot_at(contents, offset),
+            Some(JsonSlot {
+                path: vec![
+                    Segment::Key("publishConfig".to_string()),
+                    Segment::Key("access".to_string())
+                ],
+                value: true,
+                quoted: true,
+            })
+        );
+        assert_eq!(json_slot_at("[1, ", 4), None);
+    }
+
+    #[test]
+    fn test_package_json_completion() {
+        let dir = tempfile::tempdir().unwrap();
+        let uri = Url::from_file_path(dir.path().join("package.json")).unwrap();
+        let context = DocumentContext::new(uri, vec![]);
+        let key_path = KeyPath::new().unwrap();
+
+        let contents = "{\n  \n}\n";
+        let items = key_path
+            .completions("json", &context, contents, Position::new(1, 2))
+            .ok()
+            .unwrap();
+        let labels: Vec<&str> = items.iter().map(|item| item.label.as_str()).collect();
+        assert!(labels.contains(&"dependencies"));
+        assert!(labels.contains(&"devDependencies"));
+        let dependencies = items
+            .iter()
+            .find(|item| item.label == "dependencies")
+            .unwrap();
+        assert_eq!(
+            dependencies.insert_text.as_deref(),
+            Some("\"dependencies\"")
+        );
+
+        let contents = "{\n  \"type\": \n}\n";
+        let items = key_path
+            .completions("json", &context, contents, Position::new(1, 10))
+            .ok()
+            .unwrap();
+        let labels: Vec<&str> = items.iter().map(|item| item.label.as_str()).collect();
+        assert_eq!(labels, vec!["commonjs", "module"]);
+
+        // Other JSON files have no schema
+        let uri = Url::from_file_path(dir.path().join("data.json")).unwrap();
+        let items = key_path
+            .completions(
+                "json",
+                &DocumentContext::new(uri, vec![]),
+                "{\n  \n}\n",
+                Position::new(1, 2),
+            )
+            .ok()
+            .unwrap();
+        assert!(items.is_empty());
+    }
+}
@@ -0,0 +1,302 @@
+use lazy_regex::regex;
+use serde::Deserialize;
+use std::process::Command;
+use tower_lsp::lsp_types::{self, Diagnostic, DiagnosticSeverity, Position};
+
+use super::process::{probe, run_with_stdin};
+use super::{DocumentContext, Handler, HandlerError};
+
+/// Kubernetes manifest validation with kubeconform, for YAML documents
+/// that look like manifests.
+#[derive(Debug)]
+pub struct Kubeconform {}
+
+#[derive(Debug, Deserialize)]
+struct ValidationError {
+    /// JSON pointer to the invalid value, e.g. `/spec/replicas`.
+    path: String,
+    msg: String,
+}
+
+/// A resource of `kubeconform -output json`.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Resource {
+    #[serde(default)]
+    kind: String,
+    #[serde(default)]
+    name: String,
+    /// `statusValid`, `statusInvalid`, `statusError`, `statusSkipped` or
+    /// `statusEmpty`.
+    status: String,
+    #[serde(default)]
+    msg: String,
+    #[serde(default)]
+    validation_errors: Vec<ValidationError>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Output {
+    #[serde(default)]
+    resources: Vec<Resource>,
+}
+
+/// A document of a multi-document YAML file.
+struct Document<'a> {
+    /// Line of the document's first line in the file.
+    start: u32,
+    lines: Vec<&'a str>,
+}
+
+impl Document<'_> {
+    /// The value of the top-level `key`, or of `key` one level below
+    /// `parent`.
+    fn value(&self, parent: Option<&str>, key: &str) -> Option<&str> {
+        let line = match parent {
+            Some(parent) => self.find(&[parent, key])?,
+            None => self.find(&[key])?,
+        };
+        let value = self.lines[line as usize].split_once(':')?.1.trim();
+        Some(value.trim_matches(['"', '\'']))
+    }
+
+    /// Index of the line of the key at `path`, following indentation.
+    /// Array indices are skipped, their items are searched like a mapping.
+    fn find(&self, path: &[&str]) -> Option<u32> {
+        let depth = |line: &str| line.len() - line.trim_start_matches([' ', '-']).len();
+        let mut found: Option<usize> = None;
+        for key in path.iter().filter(|key| key.parse::<usize>().is_err()) {
+            let (from, indent) = match found {
+                Some(index) => (index + 1, Some(depth(self.lines[index]))),
+                None => (0, None),
+            };
+            found = None;
+            for (index, line) in self.lines.iter().enumerate().skip(from) {
+                if line.trim().is_empty() || line.trim_start().starts_with('#') {
+                    continue;
+                }
+                let inside = indent.map_or(depth(line) == 0, |indent| depth(line) > indent);
+                if !inside {
+                    // Left the parent's block
+                    if indent.is_some() {
+                        break;
+                    }
+                    continue;
+                }
+                let content = line.trim_start_matches([' ', '-']);
+                if content
+                    .strip_prefix(key)
+                    .is_some_and(|rest| rest.trim_start().starts_with(':'))
+                {
+                    found = Some(index);
+                    break;
+                }
+            }
+            found?;
+        }
+        found.map(|index| index as u32)
+    }
+}
+
+/// The documents of `contents`, separated by `---` lines.
+fn documents(contents: &str) -> Vec<Document<'_>> {
+    let mut documents = vec![Document {
+        start: 0,
+        lines: Vec::new(),
+    }];
+    for (index, line) in contents.lines().enumerate() {
+        if regex!(r#"^---\s*(?:#.*)?$"#).is_match(line) {
+            documents.push(Document {
+                start: index as u32 + 1,
+                lines: Vec::new(),
+            });
+        } else if let Some(document) = documents.last_mut() {
+            document.lines.push(line);
+        }
+    }
+    documents
+}
+
+impl Kubeconform {
+    pub fn new() -> Result<Self, String> {
+        probe("kubeconform", &["-v"])?;
+        Ok(Self {})
+    }
+
+    /// Whether the document looks like a Kubernetes manifest, with a
+    /// top-level `apiVersion` and `kind`.
+    pub fn is_manifest(contents: &str) -> bool {
+        regex!(r#"(?m)^apiVersion:"#).is_match(contents)
+            && regex!(r#"(?m)^kind:"#).is_match(contents)
+    }
+
+    /// Diagnostics of the invalid resources in `stdout`, placed on the
+    /// invalid key of the document of `contents` defining the resource.
+    pub fn parse_stdout(stdout: &str, contents: &str) -> Result<Vec<Diagnostic>, HandlerError> {
+        let output: Output = serde_json::from_str(stdout)
+            .map_err(|e| HandlerError::Parse(format!("Invalid kubeconform output: {e}")))?;
+        let documents = documents(contents);
+        let mut diagnostics = Vec::new();
+        for resource in output.resources {
+            if !matches!(resource.status.as_str(), "statusInvalid" | "statusError") {
+                continue;
+            }
+            // Resources are reported in any order, match them by kind and
+            // name
+            let document = documents
+                .iter()
+                .find(|document| {
+                    document.value(None, "kind") == Some(resource.kind.as_str())
+                        && document.value(Some("metadata"), "name") == Some(resource.name.as_str())
+                })
+                .or_else(|| {
+                    documents
+                        .iter()
+                        .find(|document| document.find(&["kind"]).is_some())
+                });
+            let Some(document) = document else {
+                continue;
+            };
+            let diagnostic = |line: Option<u32>, message: &str| {
+                let line = line.or_else(|| document.find(&["kind"])).unwrap_or(0);
+                let text = document
+                    .lines
+                    .get(line as usize)
+                    .copied()
+                    .unwrap_or_default();
+                let start = text.len() - text.trim_start_matches([' ', '-']).len();
+                Diagnostic::new(
+                    lsp_types::Range {
+                        start: Position::new(document.start + line, start as u32),
+                        end: Position::new(
+                            document.start + line,
+                            text.trim_end().encode_utf16().count() as u32,
+                        ),
+                    },
+                    Some(DiagnosticSeverity::ERROR),
+                    None,
+                    Some("kubeconform".to_string()),
+                    message.to_string(),
+                    None,
+                    None,
+                )
+            };
+
+            if resource.validation_errors.is_empty() {
+                diagnostics.push(diagnostic(None, &resource.msg));
+            }
+            for error in &resource.validation_errors {
+                let path: Vec<&str> = error.path.split('/').filter(|s| !s.is_empty()).collect();
+                let message = format!("{}: {}", error.path, error.msg);
+                diagnostics.push(diagnostic(document.find(&path), &message));
+            }
+        }
+        Ok(diagnostics)
+    }
+}
+
+impl Handler for Kubeconform {
+    fn filetype_supported(&self, filetype: &str) -> bool {
+        filetype == "yaml"
+    }
+
+    fn contents_supported(&self, _context: &DocumentContext, contents: &str) -> bool {
+        Self::is_manifest(contents)
+    }
+
+    async fn update_diagnostics(
+        &mut self,
+        contents: &str,
+    ) -> Result<Vec<Diagnostic>, HandlerError> {
+        let out = run_with_stdin(
+            Command::new("kubeconform")
+                .arg("-output")
+                .arg("json")
+                .arg("-"),
+            contents,
+        )?;
+        // Exits with 1 when a resource is invalid
+        if out.stdout.is_empty() {
+            return Err(HandlerError::Log(
+                String::from_utf8_lossy(&out.stderr).into_owned(),
+            ));
+        }
+        Self::parse_stdout(&String::from_utf8_lossy(&out.stdout), contents)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Kubeconform;
+    use tower_lsp::lsp_types::{DiagnosticSeverity, Position, Range};
+
+    #[test]
+    fn test_parse_schema_error() {
+        let contents = r#"apiVersion: v1
+kind: Service
+metadata:
+  name: web
+spec:
+  ports:
+    - port: 80
+---
+apiVersion: apps/v1
+kind: Deployment
+metadata:
+  name: web
+spec:
+  replicas: "3"
+  template:
+    spec:
+      containers:
+        - name: web
+          image: nginx
+"#;
+        let stdout = r#"{
+  "resources": [
+    {
+      "filename": "stdin",
+      "kind": "Deployment",
+      "name": "web",
+      "version": "apps/v1",
+      "status": "statusInvalid",
+      "msg": "problem validating schema. Check JSON formatting: jsonschema: '/spec/replicas' does not validate with https://kubernetesjsonschema.dev/master-standalone/deployment-apps-v1.json#/properties/spec/properties/replicas/type: expected integer or null, but got string",
+      "validationErrors": [
+        {
+          "path": "/spec/replicas",
+          "msg": "expected integer or null, but got string"
+        }
+      ]
+    },
+    {
+      "filename": "stdin",
+      "kind": "Service",
+      "name": "web",
+      "version": "v1",
+      "status": "statusValid",
+      "msg": ""
+    }
+  ],
+  "summary": { "valid": 1, "invalid": 1, "errors": 0, "skipped": 0 }
+}"#;
+        let diagnostics = Kubeconform::parse_stdout(stdout, contents).ok().unwrap();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].message,
+            "/spec/replicas: expected integer or null, but got string"
+        );
+        assert_eq!(
+            diagnostics[0].range,
+            Range::new(Position::new(13, 2), Position::new(13, 15))
+        );
+        assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::ERROR));
+    }
+
+    #[test]
+    fn test_is_manifest() {
+        assert!(Kubeconform::is_manifest(
+            "apiVersion: v1\nkind: ConfigMap\n"
+        ));
+        assert!(!Kubeconform::is_manifest("name: service\nkind: web\n"));
+    }
+}
@@ -0,0 +1,120 @@
+use lazy_regex::regex;
+use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity};
+
+use super::process::{
+    probe, run_and_parse, InputMode, RegexLineParser, Stream, TempFileStrategy, TempFiles,
+};
+use super::{Handler, HandlerError};
+
+/// R linting with lintr.
+///
+/// Starting R is slow, so documents are only checked when they are opened
+/// or saved.
+#[derive(Debug)]
+pub struct Lintr {
+    temp_files: TempFiles,
+}
+
+fn parse_severity(severity: &str) -> DiagnosticSeverity {
+    match severity {
+        "error" => DiagnosticSeverity::ERROR,
+        "warning" => DiagnosticSeverity::WARNING,
+        "style" => DiagnosticSeverity::INFORMATION,
+        _ => {
+            log::info!("Unknown severity when parsing lintr output: '{severity}'");
+            DiagnosticSeverity::WARNING
+        }
+    }
+}
+
+impl Lintr {
+    pub fn new() -> Result<Self, String> {
+        probe("Rscript", &["--version"])?;
+        Ok(Self {
+            temp_files: TempFiles::with_suffix(".R"),
+        })
+    }
+
+    /// Parses the lints printed by `lintr::lint`, each followed by the
+    /// source line.
+    fn parser() -> RegexLineParser {
+        RegexLineParser {
+            // Newer versions prefix the message with the linter name
+            regex: regex!(
+                r#"(?m)^.*:(?P<line>\d+):(?P<column>\d+): (?P<severity>style|warning|error): (?:\[(?P<code>\w+)\] )?(?P<message>.*)$"#
+            ),
+            source: "lintr",
+            stream: Stream::Stdout,
+            first_line: 1,
+            first_column: 1,
+            severity: |severity| Some(parse_severity(severity)),
+        }
+    }
+}
+
+impl Handler for Lintr {
+    fn filetype_supported(&self, filetype: &str) -> bool {
+        filetype == "r"
+    }
+
+    fn on_save_only(&self) -> bool {
+        true
+    }
+
+    fn set_temp_file_strategy(&mut self, strategy: TempFileStrategy) {
+        self.temp_files.set_strategy(strategy);
+    }
+
+    async fn update_diagnostics(
+        &mut self,
+        contents: &str,
+    ) -> Result<Vec<Diagnostic>, HandlerError> {
+        let temp_file = self.temp_files.write(contents)?;
+
+        let lints = run_and_parse(
+            std::process::Command::new("Rscript")
+                .arg("-e")
+                .arg("lintr::lint(commandArgs(TRUE))")
+                .arg(temp_file.path()),
+            InputMode::File,
+            &Self::parser(),
+        )?;
+        Ok(lints
+            .into_iter()
+            .map(|(_, diagnostic)| diagnostic)
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Lintr;
+    use tower_lsp::lsp_types::{DiagnosticSeverity, NumberOrString, Position};
+
+    #[test]
+    fn test_parse_style() {
+        let stdout = "/tmp/.tmpQ1w2E3:3:7: style: [infix_spaces_linter] Put spaces around all infix operators.
+x<-1
+      ^~
+";
+        let diagnostics: Vec<_> = Lintr::parser()
+            .parse_text(stdout)
+            .into_iter()
+            .map(|(_, diagnostic)| diagnostic)
+            .collect();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].range.start, Position::new(2, 6));
+        assert_eq!(
+            diagnostics[0].severity,
+            Some(DiagnosticSeverity::INFORMATION)
+        );
+        assert_eq!(
+            diagnostics[0].code,
+            Some(NumberOrString::String("infix_spaces_linter".to_string()))
+        );
+        assert_eq!(
+            diagnostics[0].message,
+            "Put spaces around all infix operators."
+        );
+    }
+}
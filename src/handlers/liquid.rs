@@ -0,0 +1,302 @@
+use lazy_regex::regex;
+use std::ops::Range;
+use tower_lsp::lsp_types::{self, Diagnostic, DiagnosticSeverity};
+
+use super::text::{closest, offset_to_position};
+use super::{Handler, HandlerError};
+
+/// Checks that the blocks of Liquid templates are balanced and that their
+/// tags are known, without an external tool.
+#[derive(Debug)]
+pub struct Liquid {}
+
+/// Tags closed by an `end` tag, e.g. `{% for %}` by `{% endfor %}`.
+const BLOCK_TAGS: &[&str] = &[
+    "if",
+    "unless",
+    "case",
+    "for",
+    "tablerow",
+    "capture",
+    "comment",
+    "raw",
+    "form",
+    "paginate",
+    "schema",
+    "style",
+    "javascript",
+    "stylesheet",
+    "highlight",
+];
+
+/// Tags without a body, of Liquid, Shopify themes and Jekyll.
+const SIMPLE_TAGS: &[&str] = &[
+    "assign",
+    "increment",
+    "decrement",
+    "cycle",
+    "echo",
+    "liquid",
+    "include",
+    "include_relative",
+    "render",
+    "section",
+    "sections",
+    "layout",
+    "break",
+    "continue",
+    "link",
+    "post_url",
+];
+
+/// Tags dividing the body of a block, with the blocks they may divide.
+const BRANCH_TAGS: &[(&str, &[&str])] = &[
+    ("else", &["if", "unless", "case", "for"]),
+    ("elsif", &["if", "unless"]),
+    ("when", &["case"]),
+];
+
+/// A tag of a template, e.g. `for` of `{%- for item in items %}`.
+#[derive(Debug, PartialEq)]
+struct Tag<'a> {
+    name: &'a str,
+    /// Byte range of the whole tag, from `{%` to `%}`.
+    range: Range<usize>,
+}
+
+/// The tags of `contents`, and the byte offsets of `{%` and `{{` that are
+/// never closed.
+fn tags(contents: &str) -> (Vec<Tag<'_>>, Vec<usize>) {
+    let mut tags = Vec::new();
+    let mut unclosed = Vec::new();
+    for delimiter in regex!(r#"\{%|\{\{"#).find_iter(contents) {
+        let close = if delimiter.as_str() == "{%" {
+            "%}"
+        } else {
+            "}}"
+        };
+        let rest = &contents[delimiter.end()..];
+        let Some(length) = rest.find(close) else {
+            unclosed.push(delimiter.start());
+            continue;
+        };
+        if delimiter.as_str() == "{%" {
+            let name = rest[..length]
+                .trim_start_matches('-')
+                .split_whitespace()
+                .next()
+                .unwrap_or_default();
+            tags.push(Tag {
+                name,
+                range: delimiter.start()..delimiter.end() + length + close.len(),
+            });
+        }
+    }
+    // Delimiters inside other tags, e.g. `{{` in a string of a tag, aren't
+    // reported twice
+    tags.dedup_by(|next, previous| next.range.start < previous.range.end);
+    unclosed.retain(|offset| !tags.iter().any(|tag| tag.range.contains(offset)));
+    (tags, unclosed)
+}
+
+impl Liquid {
+    pub fn new() -> Result<Self, String> {
+        Ok(Self {})
+    }
+
+    fn diagnostic(
+        contents: &str,
+        range: Range<usize>,
+        severity: DiagnosticSeverity,
+        message: String,
+    ) -> Diagnostic {
+        Diagnostic::new(
+            lsp_types::Range::new(
+                offset_to_position(contents, range.start),
+                offset_to_position(contents, range.end),
+            ),
+            Some(severity),
+            None,
+            Some("liquid".to_string()),
+            message,
+            None,
+            None,
+        )
+    }
+
+    pub fn check(contents: &str) -> Vec<Diagnostic> {
+        let (tags, unclosed) = tags(contents);
+        let mut diagnostics: Vec<Diagnostic> = unclosed
+            .into_iter()
+            .map(|offset| {
+                let delimiter = &contents[offset..offset + 2];
+                let close = if delimiter == "{%" { "%}" } else { "}}" };
+                Self::diagnostic(
+                    contents,
+                    offset..offset + 2,
+                    DiagnosticSeverity::ERROR,
+                    format!("`{delimiter}` is never closed with `{close}`"),
+                )
+            })
+            .collect();
+
+        let mut open: Vec<&Tag> = Vec::new();
+        for tag in &tags {
+            // The bodies of `raw` and `comment` aren't templates
+            if let Some(verbatim) = open
+                .last()
+                .filter(|block| matches!(block.name, "raw" | "comment"))
+            {
+                if tag.name.strip_prefix("end") == Some(verbatim.name) {
+                    open.pop();
+                }
+                continue;
+            }
+
+            if BLOCK_TAGS.contains(&tag.name) {
+                open.push(tag);
+            } else if let Some(block) = tag
+                .name
+                .strip_prefix("end")
+                .filter(|block| BLOCK_TAGS.contains(block))
+            {
+                let Some(index) = open.iter().rposition(|open| open.name == block) else {
+                    diagnostics.push(Self::diagnostic(
+                        contents,
+                        tag.range.clone(),
+                        DiagnosticSeverity::ERROR,
+                        format!("`{{% {} %}}` closes no `{{% {block} %}}`", tag.name),
+                    ));
+                    continue;
+                };
+                // Blocks opened inside this one are never closed
+                for unclosed in open.drain(index..).skip(1) {
+                    diagnostics.push(Self::unclosed(contents, unclosed));
+                }
+            } else if let Some((_, blocks)) = BRANCH_TAGS.iter().find(|(name, _)| *name == tag.name)
+            {
+                if !open
+                    .last()
+                    .is_some_and(|block| blocks.contains(&block.name))
+                {
+                    diagnostics.push(Self::diagnostic(
+                        contents,
+                        tag.range.clone(),
+                        DiagnosticSeverity::ERROR,
+                        format!("`{{% {} %}}` outside of `{{% {} %}}`", tag.name, blocks[0]),
+                    ));
+                }
+            } else if !SIMPLE_TAGS.contains(&tag.name) && !tag.name.starts_with('#') {
+                let known: Vec<&str> = BLOCK_TAGS
+                    .iter()
+                    .chain(SIMPLE_TAGS)
+                    .chain(BRANCH_TAGS.iter().map(|(name, _)| name))
+                    .copied()
+                    .collect();
+                let message = match closest(tag.name, &known) {
+                    Some(suggestion) => {
+                        format!("Unknown tag `{}`, did you mean `{suggestion}`?", tag.name)
+                    }
+                    None => format!("Unknown tag `{}`", tag.name),
+                };
+                diagnostics.push(Self::diagnostic(
+                    contents,
+                    tag.range.clone(),
+                    DiagnosticSeverity::WARNING,
+                    message,
+                ));
+            }
+        }
+        for unclosed in open {
+            diagnostics.push(Self::unclosed(contents, unclosed));
+        }
+        diagnostics.sort_by_key(|diagnostic| diagnostic.range.start);
+        diagnostics
+    }
+
+    fn unclosed(contents: &str, tag: &Tag) -> Diagnostic {
+        Self::diagnostic(
+            contents,
+            tag.range.clone(),
+            DiagnosticSeverity::ERROR,
+            format!(
+                "`{{% {} %}}` is never closed with `{{% end{} %}}`",
+                tag.name, tag.name
+            ),
+        )
+    }
+}
+
+impl Handler for Liquid {
+    fn filetype_supported(&self, filetype: &str) -> bool {
+        filetype == "liquid"
+    }
+
+    async fn update_diagnostics(
+        &mut self,
+        contents: &str,
+    ) -> Result<Vec<Diagnostic>, HandlerError> {
+        Ok(Self::check(contents))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Liquid;
+    use tower_lsp::lsp_types::{Position, Range};
+
+    fn messages(contents: &str) -> Vec<String> {
+        Liquid::check(contents)
+            .into_iter()
+            .map(|diagnostic| diagnostic.message)
+            .collect()
+    }
+
+    #[test]
+    fn test_unclosed_for() {
+        let contents = "<ul>\n  {% for item in items %}\n  <li>{{ item | upcase }}</li>\n</ul>\n";
+        let diagnostics = Liquid::check(contents);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].message,
+            "`{% for %}` is never closed with `{% endfor %}`"
+        );
+        assert_eq!(
+            diagnostics[0].range,
+            Range::new(Position::new(1, 2), Position::new(1, 25))
+        );
+
+        // Closing an outer block leaves the inner one unclosed
+        assert_eq!(
+            messages("{% if a %}{% for x in y %}{% endif %}"),
+            vec!["`{% for %}` is never closed with `{% endfor %}`"]
+        );
+    }
+
+    #[test]
+    fn test_stray_endfor() {
+        let contents = "{% if user %}\n  Hi {{ user.name }}\n{% endif %}\n{%- endfor -%}\n";
+        let diagnostics = Liquid::check(contents);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].message,
+            "`{% endfor %}` closes no `{% for %}`"
+        );
+        assert_eq!(diagnostics[0].range.start, Position::new(3, 0));
+    }
+
+    #[test]
+    fn test_tags() {
+        let contents =
+            "{% assign x = 1 %}{% asign y = 2 %}{% else %}{% raw %}{% endfor %}{% endraw %}{{ x";
+        assert_eq!(
+            messages(contents),
+            vec![
+                "Unknown tag `asign`, did you mean `assign`?",
+                "`{% else %}` outside of `{% if %}`",
+                "`{{` is never closed with `}}`",
+            ]
+        );
+        assert!(messages("{% case x %}{% when 1 %}{% else %}{% endcase %}").is_empty());
+    }
+}
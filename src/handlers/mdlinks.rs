@@ -0,0 +1,254 @@
+use lazy_regex::{regex, regex_captures};
+use std::collections::HashMap;
+use std::ops::Range;
+use tower_lsp::lsp_types::{self, Diagnostic, DiagnosticSeverity, Url};
+
+use super::text::offset_to_position;
+use super::{DocumentContext, Handler, HandlerError};
+
+/// Warns about relative links in Markdown documents whose target file, or
+/// heading anchor, doesn't exist. External links aren't checked.
+#[derive(Debug)]
+pub struct MdLinks {}
+
+/// A link target, with the byte range of the whole link.
+#[derive(Debug, PartialEq)]
+pub struct Link {
+    pub target: String,
+    pub range: Range<usize>,
+}
+
+/// Inline links, images and reference definitions, outside of code.
+pub fn links(contents: &str) -> Vec<Link> {
+    let mut links = Vec::new();
+    let mut fence: Option<&str> = None;
+    let mut line_start = 0;
+    for line in contents.split('\n') {
+        let trimmed = line.trim_start();
+        if let Some(open) = fence {
+            if trimmed.starts_with(open) {
+                fence = None;
+            }
+        } else if let Some((_, open)) = regex_captures!(r#"^(```|~~~)"#, trimmed) {
+            fence = Some(open);
+        } else if let Some((definition, _, target)) =
+            regex_captures!(r#"^ {0,3}\[([^\]]+)\]:\s*<?([^\s>]+)"#, line)
+        {
+            links.push(Link {
+                target: target.to_string(),
+                range: line_start..line_start + definition.len(),
+            });
+        } else {
+            // Links in code spans are text
+            let code = regex!(r#"`[^`]*`"#);
+            let spans: Vec<Range<usize>> = code.find_iter(line).map(|span| span.range()).collect();
+            for link in
+                regex!(r#"!?\[[^\]]*\]\(\s*(?:<([^>]*)>|([^\s)]*))[^)]*\)"#).captures_iter(line)
+            {
+                let all = link.get(0).expect("Group 0 always matches");
+                if spans.iter().any(|span| span.contains(&all.start())) {
+                    continue;
+                }
+                let target = link.get(1).or_else(|| link.get(2));
+                links.push(Link {
+                    target: target.map_or("", |target| target.as_str()).to_string(),
+                    range: line_start + all.start()..line_start + all.end(),
+                });
+            }
+        }
+        line_start += line.len() + 1;
+    }
+    links
+}
+
+/// A heading of a Markdown document.
+#[derive(Debug, PartialEq)]
+pub struct Heading {
+    /// 1 for `#`, up to 6.
+    pub level: usize,
+    pub text: String,
+    /// The anchor linking to the heading, see `anchors`.
+    pub anchor: String,
+}
+
+/// The headings of `contents` outside of code blocks.
+pub fn headings(contents: &str) -> Vec<Heading> {
+    let mut headings = Vec::new();
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    let mut in_fence = false;
+    for line in contents.lines() {
+        if regex!(r#"^\s*(```|~~~)"#).is_match(line) {
+            in_fence = !in_fence;
+            continue;
+        }
+        let Some((_, level, text)) = regex_captures!(r#"^ {0,3}(#{1,6})\s+(.*?)[\s#]*$"#, line)
+        else {
+            continue;
+        };
+        if in_fence {
+            continue;
+        }
+        let slug: String = text
+            .to_lowercase()
+            .chars()
+            .filter(|c| c.is_alphanumeric() || matches!(c, ' ' | '-' | '_'))
+            .map(|c| if c == ' ' { '-' } else { c })
+            .collect();
+        let count = counts.entry(slug.clone()).or_default();
+        let anchor = match *count {
+            0 => slug,
+            n => format!("{slug}-{n}"),
+        };
+        *count += 1;
+        headings.push(Heading {
+            level: level.len(),
+            text: text.to_string(),
+            anchor,
+        });
+    }
+    headings
+}
+
+/// Anchors of the headings of `contents`, as generated by GitHub: lowercase,
+/// punctuation removed, spaces replaced by `-`, and `-1`, `-2`, ... appended
+/// to repeated headings.
+pub fn anchors(contents: &str) -> Vec<String> {
+    headings(contents)
+        .into_iter()
+        .map(|heading| heading.anchor)
+        .collect()
+}
+
+impl MdLinks {
+    pub fn new() -> Result<Self, String> {
+        Ok(Self {})
+    }
+
+    /// The problem with `target`, relative to the document at `document`.
+    fn check(target: &str, document: &Url, contents: &str) -> Option<String> {
+        // External links, `mailto:` and site-absolute paths aren't files
+        if target.is_empty()
+            || target.starts_with('/')
+            || regex!(r#"^[A-Za-z][A-Za-z0-9+.-]*:"#).is_match(target)
+        {
+            return None;
+        }
+        let (path, anchor) = match target.split_once('#') {
+            Some((path, anchor)) => (path, Some(anchor)),
+            None => (target, None),
+        };
+
+        let linked;
+        let text = if path.is_empty() {
+            contents
+        } else {
+            let file = document.join(path).ok()?.to_file_path().ok()?;
+            if !file.exists() {
+                return Some(format!("File '{path}' not found"));
+            }
+            let is_markdown = file
+                .extension()
+                .is_some_and(|ext| ext == "md" || ext == "markdown");
+            if anchor.is_none() || !is_markdown {
+                return None;
+            }
+            linked = std::fs::read_to_string(&file).ok()?;
+            &linked
+        };
+
+        let anchor = anchor?;
+        if anchor.is_empty() || anchors(text).iter().any(|known| known == anchor) {
+            None
+        } else if path.is_empty() {
+            Some(format!("Heading '#{anchor}' not found"))
+        } else {
+            Some(format!("Heading '#{anchor}' not found in '{path}'"))
+        }
+    }
+
+    pub fn parse(contents: &str, document: &Url) -> Vec<Diagnostic> {
+        links(contents)
+            .into_iter()
+            .filter_map(|link| {
+                let message = Self::check(&link.target, document, contents)?;
+                Some(Diagnostic::new(
+                    lsp_types::Range::new(
+                        offset_to_position(contents, link.range.start),
+                        offset_to_position(contents, link.range.end),
+                    ),
+                    Some(DiagnosticSeverity::WARNING),
+                    None,
+                    Some("mdlinks".to_string()),
+                    message,
+                    None,
+                    None,
+                ))
+            })
+            .collect()
+    }
+}
+
+impl Handler for MdLinks {
+    fn filetype_supported(&self, filetype: &str) -> bool {
+        filetype == "markdown"
+    }
+
+    async fn update_diagnostics_with_context(
+        &mut self,
+        context: &DocumentContext,
+        contents: &str,
+    ) -> Result<Vec<Diagnostic>, HandlerError> {
+        // Relative links of unsaved documents can't be resolved
+        if context.uri.scheme() != "file" {
+            return Ok(Vec::new());
+        }
+        Ok(Self::parse(contents, &context.uri))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{anchors, links, MdLinks};
+    use tower_lsp::lsp_types::{Position, Range, Url};
+
+    #[test]
+    fn test_links() {
+        let contents = "See [docs](docs/a.md \"Title\") and ![logo](<img/logo 1.png>).\n\n```\n[not](a link)\n```\n`[code](x)` [site]\n\n[site]: https://example.com\n";
+        let targets: Vec<String> = links(contents).into_iter().map(|l| l.target).collect();
+        assert_eq!(
+            targets,
+            vec!["docs/a.md", "img/logo 1.png", "https://example.com"]
+        );
+    }
+
+    #[test]
+    fn test_anchors() {
+        assert_eq!(
+            anchors("# Getting Started!\n## API: `new`\n```\n# comment\n```\n# Getting started\n"),
+            vec!["getting-started", "api-new", "getting-started-1"]
+        );
+    }
+
+    #[test]
+    fn test_broken_and_valid_links() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("guide.md"), "# Install\n").unwrap();
+        let document = Url::from_file_path(dir.path().join("README.md")).unwrap();
+        let contents = "# Usage\n\n[guide](guide.md) [install](guide.md#install)\n[usage](#usage) [web](https://example.com/missing)\n[missing](missing.md)\n[bad anchor](guide.md#setup) [self](#nope)\n";
+
+        let diagnostics = MdLinks::parse(contents, &document);
+        let messages: Vec<&str> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "File 'missing.md' not found",
+                "Heading '#setup' not found in 'guide.md'",
+                "Heading '#nope' not found",
+            ]
+        );
+        assert_eq!(
+            diagnostics[0].range,
+            Range::new(Position::new(4, 0), Position::new(4, 21))
+        );
+    }
+}
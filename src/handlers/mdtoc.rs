@@ -0,0 +1,165 @@
+use serde_json::Value;
+use std::collections::HashMap;
+use std::ops::Range;
+use tower_lsp::lsp_types::{
+    self, ExecuteCommandOptions, Position, ServerCapabilities, TextEdit, WorkspaceEdit,
+};
+
+use super::mdlinks::headings;
+use super::text::{offset_to_position, position_to_offset};
+use super::{DocumentContext, Handler, HandlerError};
+
+/// Generates the table of contents of Markdown documents with the
+/// `any_ls.markdown.generateToc` command.
+#[derive(Debug)]
+pub struct MdToc {}
+
+pub const GENERATE_TOC: &str = "any_ls.markdown.generateToc";
+
+const TOC_START: &str = "<!-- toc -->";
+const TOC_END: &str = "<!-- /toc -->";
+
+/// The table of contents of the headings of `contents`, between the
+/// markers, as a nested list of links indented from the highest level.
+fn toc(contents: &str) -> String {
+    let headings = headings(contents);
+    let top = headings
+        .iter()
+        .map(|heading| heading.level)
+        .min()
+        .unwrap_or(1);
+    let mut toc = format!("{TOC_START}\n\n");
+    for heading in &headings {
+        let indent = "  ".repeat(heading.level - top);
+        toc.push_str(&format!(
+            "{indent}- [{}](#{})\n",
+            heading.text, heading.anchor
+        ));
+    }
+    toc.push('\n');
+    toc.push_str(TOC_END);
+    toc
+}
+
+/// Byte range of the table of contents of `contents`, from the start of
+/// its opening marker to the end of its closing one.
+fn toc_block(contents: &str) -> Option<Range<usize>> {
+    let mut start = None;
+    let mut offset = 0;
+    for line in contents.split('\n') {
+        match line.trim() {
+            TOC_START if start.is_none() => start = Some(offset),
+            TOC_END => {
+                if let Some(start) = start {
+                    return Some(start..offset + line.trim_end().len());
+                }
+            }
+            _ => {}
+        }
+        offset += line.len() + 1;
+    }
+    None
+}
+
+/// The edit replacing the table of contents between the markers, or
+/// inserting one at `position` when the document has none.
+pub fn generate_toc(contents: &str, position: Position) -> TextEdit {
+    let toc = toc(contents);
+    match toc_block(contents) {
+        Some(block) => TextEdit::new(
+            lsp_types::Range::new(
+                offset_to_position(contents, block.start),
+                offset_to_position(contents, block.end),
+            ),
+            toc,
+        ),
+        None => {
+            // At the start of a line
+            let offset = position_to_offset(contents, Position::new(position.line, 0));
+            let position = offset_to_position(contents, offset);
+            TextEdit::new(
+                lsp_types::Range::new(position, position),
+                format!("{toc}\n\n"),
+            )
+        }
+    }
+}
+
+impl MdToc {
+    pub fn new() -> Result<Self, String> {
+        Ok(Self {})
+    }
+}
+
+impl Handler for MdToc {
+    fn filetype_supported(&self, filetype: &str) -> bool {
+        filetype == "markdown"
+    }
+
+    fn get_capabilities(&self) -> ServerCapabilities {
+        ServerCapabilities {
+            execute_command_provider: Some(ExecuteCommandOptions {
+                commands: vec![GENERATE_TOC.to_string()],
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn execute_command(
+        &self,
+        command: &str,
+        arguments: &[Value],
+        _filetype: &str,
+        context: &DocumentContext,
+        document_contents: &str,
+    ) -> Result<Option<WorkspaceEdit>, HandlerError> {
+        if command != GENERATE_TOC {
+            return Ok(None);
+        }
+        // Where to insert a new table of contents, the start by default
+        let position = arguments
+            .first()
+            .and_then(|position| serde_json::from_value(position.clone()).ok())
+            .unwrap_or_default();
+        let edit = generate_toc(document_contents, position);
+        Ok(Some(WorkspaceEdit {
+            changes: Some(HashMap::from([(context.uri.clone(), vec![edit])])),
+            ..Default::default()
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::generate_toc;
+    use tower_lsp::lsp_types::{Position, Range};
+
+    #[test]
+    fn test_generate_toc() {
+        let contents = "Intro text.\n\n## Install\n\n### From source\n\n```sh\n# not a heading\n```\n\n## Usage\n\n### Install\n";
+        let edit = generate_toc(contents, Position::new(1, 4));
+        assert_eq!(
+            edit.range,
+            Range::new(Position::new(1, 0), Position::new(1, 0))
+        );
+        assert_eq!(
+            edit.new_text,
+            "<!-- toc -->\n\n- [Install](#install)\n  - [From source](#from-source)\n- [Usage](#usage)\n  - [Install](#install-1)\n\n<!-- /toc -->\n\n"
+        );
+    }
+
+    #[test]
+    fn test_update_stale_toc() {
+        let contents = "# Project\n\n<!-- toc -->\n\n- [Old](#old)\n\n<!-- /toc -->\n\n## Setup\n";
+        let edit = generate_toc(contents, Position::new(0, 0));
+        assert_eq!(
+            edit.range,
+            Range::new(Position::new(2, 0), Position::new(6, 13))
+        );
+        assert_eq!(
+            edit.new_text,
+            "<!-- toc -->\n\n- [Project](#project)\n  - [Setup](#setup)\n\n<!-- /toc -->"
+        );
+    }
+}
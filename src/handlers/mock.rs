@@ -0,0 +1,74 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::sync::Notify;
+use tower_lsp::lsp_types::{Diagnostic, ServerCapabilities};
+
+use super::{Handler, HandlerError};
+
+/// Handler with canned responses for testing `AnyHandler`.
+#[derive(Debug, Default)]
+pub struct Mock {
+    pub filetypes: Vec<&'static str>,
+    /// Supports documents of any filetype, like the spell checkers.
+    pub any_filetype: bool,
+    pub priority: i32,
+    /// Only checks documents when they are opened or saved.
+    pub on_save_only: bool,
+    pub capabilities: ServerCapabilities,
+    pub diagnostics: Vec<Diagnostic>,
+    pub formatted: Option<String>,
+    /// A tool whose timeout diagnostics fail with.
+    pub timed_out: Option<&'static str>,
+    /// An error message diagnostics fail with.
+    pub failed: Option<&'static str>,
+    /// Times diagnostics were computed, shared with the test.
+    pub diagnostics_runs: Arc<AtomicUsize>,
+    /// Times the document was formatted, shared with the test.
+    pub format_runs: Arc<AtomicUsize>,
+    /// Diagnostics wait to be notified to finish, e.g. after an edit.
+    pub blocked: Option<Arc<Notify>>,
+}
+
+impl Handler for Mock {
+    fn filetype_supported(&self, filetype: &str) -> bool {
+        self.any_filetype || self.filetypes.contains(&filetype)
+    }
+
+    fn priority(&self) -> i32 {
+        self.priority
+    }
+
+    fn on_save_only(&self) -> bool {
+        self.on_save_only
+    }
+
+    fn get_capabilities(&self) -> ServerCapabilities {
+        self.capabilities.clone()
+    }
+
+    async fn update_diagnostics(
+        &mut self,
+        _document_contents: &str,
+    ) -> Result<Vec<Diagnostic>, HandlerError> {
+        self.diagnostics_runs.fetch_add(1, Ordering::SeqCst);
+        if let Some(blocked) = &self.blocked {
+            blocked.notified().await;
+        }
+        if let Some(tool) = self.timed_out {
+            return Err(HandlerError::Timeout(tool.to_string()));
+        }
+        if let Some(message) = self.failed {
+            return Err(HandlerError::Log(message.to_string()));
+        }
+        Ok(self.diagnostics.clone())
+    }
+
+    async fn format(
+        &mut self,
+        _filetype: &str,
+        _document_contents: &str,
+    ) -> Result<Option<String>, HandlerError> {
+        self.format_runs.fetch_add(1, Ordering::SeqCst);
+        Ok(self.formatted.clone())
+    }
+}
@@ -1,41 +1,2486 @@
-use tower_lsp::lsp_types::Diagnostic;
+use encoding_rs::{Encoding, UTF_8};
+use globset::{Glob, GlobMatcher};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use tower_lsp::lsp_types::{
+    CodeAction, Color, ColorInformation, ColorPresentation, CompletionItem, Diagnostic,
+    DiagnosticSeverity, DocumentLink, DocumentSymbol, Hover, LinkedEditingRanges, Location,
+    NumberOrString, Position, Range, ServerCapabilities, TextEdit, Url, WorkspaceEdit,
+    WorkspaceFolder,
+};
 
+use crate::config::{Config, SeverityOverride};
+use process::{with_output_encoding, with_output_encoding_sync};
+
+mod awk;
+mod bashn;
+mod buildifier;
+mod cache;
+mod cargo_toml;
+mod codespell;
+mod color;
+mod convert;
+mod csv;
+mod desktop;
+mod dockerfile;
+mod editorconfig_lint;
+mod elixir;
+mod embedded;
+mod filetype;
+mod fortran;
+mod generic;
+mod gomod;
+mod graphql;
+mod groovy;
+mod helm;
+mod http;
+mod idl;
+mod ignorefile;
+mod javaprops;
+mod jsonc;
 mod just;
+mod just_model;
+mod keypath;
+mod kubeconform;
+mod lintr;
+mod liquid;
+mod mdlinks;
+mod mdtoc;
+#[cfg(test)]
+pub mod mock;
+mod ndjson;
+mod nim;
+mod ocaml;
+mod process;
+mod props;
+mod pug;
+mod racket;
+mod requirements;
+mod rstcheck;
+mod ruff;
+mod scala;
+mod selene;
+mod sfc;
+mod shader;
+mod solhint;
+mod spectral;
+mod spelling;
+mod stylelint;
+mod suppress;
+mod systemd;
+mod tcl;
+mod text;
+#[cfg(feature = "treesitter")]
+mod treesitter;
+mod verible;
+mod yaml_anchors;
 
-pub use just::Just;
+pub use awk::Awk;
+pub use bashn::BashN;
+pub use buildifier::Buildifier;
+pub use cargo_toml::CargoToml;
+pub use codespell::Codespell;
+pub use color::ColorHandler;
+pub use convert::Convert;
+pub use csv::{Csv, CsvConfig};
+pub use desktop::Desktop;
+pub use dockerfile::Dockerfile;
+pub use editorconfig_lint::EditorConfigLint;
+pub use elixir::Elixir;
+pub use filetype::detect_filetype;
+pub use fortran::Fortran;
+pub use generic::{GenericHandler, GenericHandlerConfig};
+pub use gomod::GoMod;
+pub use graphql::GraphQl;
+pub use groovy::Groovy;
+pub use helm::Helm;
+pub use http::Http;
+pub use idl::Idl;
+pub use ignorefile::IgnoreFile;
+pub use javaprops::JavaProps;
+pub use jsonc::Jsonc;
+pub use just::{Just, JustConfig};
+pub use keypath::KeyPath;
+pub use kubeconform::Kubeconform;
+pub use lintr::Lintr;
+pub use liquid::Liquid;
+pub use mdlinks::MdLinks;
+pub use mdtoc::MdToc;
+pub use ndjson::Ndjson;
+pub use nim::Nim;
+pub use ocaml::OCaml;
+pub use process::{TempFileStrategy, TEMP_FILE_PREFIX};
+pub use props::PropsHandler;
+pub use pug::Pug;
+pub use racket::Racket;
+pub use requirements::Requirements;
+pub use rstcheck::Rstcheck;
+pub use ruff::Ruff;
+pub use scala::Scala;
+pub use selene::Selene;
+pub use sfc::Sfc;
+pub use shader::Shader;
+pub use solhint::Solhint;
+pub use spectral::Spectral;
+pub use stylelint::Stylelint;
+pub use systemd::Systemd;
+pub use tcl::Tcl;
+pub use text::apply_text_edits;
+#[cfg(feature = "treesitter")]
+pub use treesitter::TreeSitter;
+pub use verible::Verible;
+pub use yaml_anchors::YamlAnchors;
 
+#[derive(Debug)]
 pub enum HandlerError {
+    /// A failure only worth logging, e.g. a tool exiting with an error.
     Log(String),
+    /// A request for a document that isn't open.
+    NoSuchDocument(Url),
+    /// The program of a handler could not be found.
+    ToolNotFound(String),
+    /// The output of a tool could not be parsed.
+    Parse(String),
+    /// The program of a handler did not finish in time and was killed.
+    Timeout(String),
+    /// The program of a handler exited with an error instead of formatting
+    /// the document, e.g. on invalid syntax, with its error output.
+    ToolFailed { tool: String, message: String },
+}
+
+impl std::fmt::Display for HandlerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HandlerError::Log(text) => write!(f, "{text}"),
+            HandlerError::NoSuchDocument(uri) => write!(f, "Document is not open: {uri}"),
+            HandlerError::ToolNotFound(tool) => write!(f, "Could not find '{tool}'"),
+            HandlerError::Parse(text) => write!(f, "{text}"),
+            HandlerError::Timeout(tool) => write!(f, "'{tool}' timed out"),
+            HandlerError::ToolFailed { tool, message } => write!(f, "'{tool}' failed: {message}"),
+        }
+    }
+}
+
+/// The document a handler runs for.
+#[derive(Debug, Clone)]
+pub struct DocumentContext {
+    pub uri: Url,
+    pub workspace_folders: Vec<WorkspaceFolder>,
+    /// Names marking the root of a project, see `traverse_parents`.
+    pub root_markers: Vec<String>,
+    /// Whether the document was saved since it was last checked, see
+    /// `Handler::on_save_only`.
+    pub saved: bool,
+    /// The filetype the document is checked as, set by `AnyHandler`. Code
+    /// embedded in the document has its own.
+    pub filetype: String,
+}
+
+impl DocumentContext {
+    pub fn new(uri: Url, workspace_folders: Vec<WorkspaceFolder>) -> Self {
+        Self {
+            uri,
+            workspace_folders,
+            root_markers: process::DEFAULT_ROOT_MARKERS
+                .iter()
+                .map(|marker| marker.to_string())
+                .collect(),
+            saved: false,
+            filetype: String::new(),
+        }
+    }
+
+    pub fn with_root_markers(mut self, root_markers: Vec<String>) -> Self {
+        self.root_markers = root_markers;
+        self
+    }
+
+    /// Directory containing the document. Documents not on disk (e.g.
+    /// untitled buffers) resolve to the first workspace folder instead.
+    pub fn directory(&self) -> Option<PathBuf> {
+        if let Ok(path) = self.uri.to_file_path() {
+            return path.parent().map(|parent| parent.to_path_buf());
+        }
+        self.workspace_folders
+            .iter()
+            .find_map(|folder| folder.uri.to_file_path().ok())
+    }
+
+    /// `path` relative to the innermost workspace folder containing it, for
+    /// display. Paths outside all workspace folders stay absolute.
+    pub fn display_path(&self, path: &Path) -> String {
+        self.workspace_folders
+            .iter()
+            .filter_map(|folder| folder.uri.to_file_path().ok())
+            .filter_map(|root| path.strip_prefix(root).ok())
+            .min_by_key(|relative| relative.components().count())
+            .unwrap_or(path)
+            .display()
+            .to_string()
+    }
+}
+
+/// Diagnostics of a document, and of other files found while checking it,
+/// e.g. files it imports.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct DocumentDiagnostics {
+    pub diagnostics: Vec<Diagnostic>,
+    pub related: HashMap<Url, Vec<Diagnostic>>,
+    /// Errors of the handlers that failed to check the document, whose
+    /// diagnostics are missing.
+    pub errors: Vec<String>,
 }
 
 pub trait Handler {
+    /// Whether the handler should run for documents of `filetype`.
+    fn filetype_supported(&self, filetype: &str) -> bool;
+
+    /// Whether the handler should run for the file at `path` regardless of
+    /// its filetype, e.g. for files without an extension like `Jenkinsfile`.
+    fn path_supported(&self, _path: &Path) -> bool {
+        false
+    }
+
+    /// Whether the handler should check a document it is active for, e.g.
+    /// only YAML documents that are API descriptions.
+    fn contents_supported(&self, _context: &DocumentContext, _document_contents: &str) -> bool {
+        true
+    }
+
+    /// Handlers with higher priority run first. Their capabilities take
+    /// precedence when several handlers set the same field, and their
+    /// diagnostics are kept over those of lower priority handlers with the
+    /// same range and message. Fallbacks, like `bash -n` for shellcheck,
+    /// should use a negative priority.
+    fn priority(&self) -> i32 {
+        0
+    }
+
+    /// Whether the handler only checks documents when they are opened or
+    /// saved, for slow tools. Diagnostics pulled for unsaved changes are
+    /// those of the last check.
+    fn on_save_only(&self) -> bool {
+        false
+    }
+
+    /// Capabilities the server must advertise for this handler to work.
+    fn get_capabilities(&self) -> ServerCapabilities {
+        ServerCapabilities::default()
+    }
+
+    /// How documents are written to disk, for handlers running tools on a
+    /// temporary file.
+    fn set_temp_file_strategy(&mut self, _strategy: TempFileStrategy) {}
+
+    /// Number of parsed models kept, for handlers caching them across
+    /// documents.
+    fn set_model_cache_capacity(&mut self, _capacity: usize) {}
+
     async fn update_diagnostics(
         &mut self,
+        _document_contents: &str,
+    ) -> Result<Vec<Diagnostic>, HandlerError> {
+        Ok(vec![])
+    }
+
+    /// Like `update_diagnostics` but for handlers that need to know where
+    /// the document is, e.g. to run tools in the project directory.
+    async fn update_diagnostics_with_context(
+        &mut self,
+        _context: &DocumentContext,
+        document_contents: &str,
+    ) -> Result<Vec<Diagnostic>, HandlerError> {
+        self.update_diagnostics(document_contents).await
+    }
+
+    /// Like `update_diagnostics_with_context` but for handlers that also
+    /// report problems in other files.
+    async fn update_document_diagnostics(
+        &mut self,
+        context: &DocumentContext,
         document_contents: &str,
-    ) -> Result<Vec<Diagnostic>, HandlerError>;
+    ) -> Result<DocumentDiagnostics, HandlerError> {
+        Ok(DocumentDiagnostics {
+            diagnostics: self
+                .update_diagnostics_with_context(context, document_contents)
+                .await?,
+            ..Default::default()
+        })
+    }
+
+    /// Returns the formatted document, or `None` if the handler doesn't format.
+    async fn format(
+        &mut self,
+        _filetype: &str,
+        _document_contents: &str,
+    ) -> Result<Option<String>, HandlerError> {
+        Ok(None)
+    }
+
+    /// Like `format` but for handlers that need to know where the document
+    /// is, e.g. to find the project's formatter settings.
+    async fn format_with_context(
+        &mut self,
+        _context: &DocumentContext,
+        filetype: &str,
+        document_contents: &str,
+    ) -> Result<Option<String>, HandlerError> {
+        self.format(filetype, document_contents).await
+    }
+
+    fn hover(
+        &self,
+        _filetype: &str,
+        _context: &DocumentContext,
+        _document_contents: &str,
+        _position: Position,
+    ) -> Result<Option<Hover>, HandlerError> {
+        Ok(None)
+    }
+
+    /// Where the symbol at `position` is defined, which may be in another
+    /// file.
+    fn definition(
+        &self,
+        _context: &DocumentContext,
+        _document_contents: &str,
+        _position: Position,
+    ) -> Result<Option<Location>, HandlerError> {
+        Ok(None)
+    }
+
+    /// Clickable references to other files, resolved relative to `uri`.
+    fn document_links(
+        &self,
+        _document_contents: &str,
+        _uri: &Url,
+    ) -> Result<Vec<DocumentLink>, HandlerError> {
+        Ok(vec![])
+    }
+
+    fn document_colors(
+        &self,
+        _filetype: &str,
+        _document_contents: &str,
+    ) -> Result<Vec<ColorInformation>, HandlerError> {
+        Ok(vec![])
+    }
+
+    /// Alternative ways of writing `color` at `range`.
+    fn color_presentations(
+        &self,
+        _color: Color,
+        _range: Range,
+    ) -> Result<Vec<ColorPresentation>, HandlerError> {
+        Ok(vec![])
+    }
+
+    /// Ranges to edit together with the symbol at `position`, e.g. a
+    /// declaration and its uses.
+    fn linked_editing_ranges(
+        &self,
+        _document_contents: &str,
+        _position: Position,
+    ) -> Result<Option<LinkedEditingRanges>, HandlerError> {
+        Ok(None)
+    }
+
+    /// The outline of the document, e.g. its functions and classes.
+    fn document_symbols(
+        &self,
+        _filetype: &str,
+        _document_contents: &str,
+    ) -> Result<Vec<DocumentSymbol>, HandlerError> {
+        Ok(vec![])
+    }
+
+    /// Completions at `position`, e.g. the keys a schema allows there.
+    fn completions(
+        &self,
+        _filetype: &str,
+        _context: &DocumentContext,
+        _document_contents: &str,
+        _position: Position,
+    ) -> Result<Vec<CompletionItem>, HandlerError> {
+        Ok(vec![])
+    }
+
+    /// Fixes of `diagnostics`, those the client shows in the range it asks
+    /// code actions for.
+    fn code_actions(
+        &self,
+        _context: &DocumentContext,
+        _diagnostics: &[Diagnostic],
+    ) -> Result<Vec<CodeAction>, HandlerError> {
+        Ok(vec![])
+    }
+
+    /// Runs `command`, one of the `execute_command_provider` commands of
+    /// the handler, for the document. `arguments` are those after the URI
+    /// of the document. `None` for commands of other handlers.
+    fn execute_command(
+        &self,
+        _command: &str,
+        _arguments: &[Value],
+        _filetype: &str,
+        _context: &DocumentContext,
+        _document_contents: &str,
+    ) -> Result<Option<WorkspaceEdit>, HandlerError> {
+        Ok(None)
+    }
+
+    /// Runs `command`, one of the `execute_command_provider` commands of
+    /// the handler, for the files of the workspace rather than a document.
+    /// Returns a summary for the user, `None` for commands of other
+    /// handlers.
+    fn execute_workspace_command(
+        &self,
+        _command: &str,
+        _workspace_folders: &[WorkspaceFolder],
+    ) -> Result<Option<String>, HandlerError> {
+        Ok(None)
+    }
 }
 
+/// Names of the built-in handlers, by which the settings refer to them,
+/// e.g. in `Config::disabled`.
+pub const BUILTIN_HANDLERS: &[&str] = &[
+    "Just",
+    "Sfc",
+    "Jsonc",
+    "BashN",
+    "KeyPath",
+    "Groovy",
+    "ColorHandler",
+    "Lintr",
+    "Ndjson",
+    "Idl",
+    "IgnoreFile",
+    "CargoToml",
+    "MdLinks",
+    "Ruff",
+    "Solhint",
+    "Spectral",
+    "GoMod",
+    "Helm",
+    "Kubeconform",
+    "Buildifier",
+    "PropsHandler",
+    "Racket",
+    "Nim",
+    "Scala",
+    "OCaml",
+    "Verible",
+    "Rstcheck",
+    "GraphQl",
+    "Shader",
+    "JavaProps",
+    "Fortran",
+    "Pug",
+    "Liquid",
+    "EditorConfigLint",
+    "Codespell",
+    "Stylelint",
+    "MdToc",
+    "Dockerfile",
+    "YamlAnchors",
+    "Systemd",
+    "Csv",
+    "Requirements",
+    "Awk",
+    "Http",
+    "Convert",
+    "Selene",
+    "Tcl",
+    "Elixir",
+    "Desktop",
+    "TreeSitter",
+];
+
 #[derive(Debug)]
-pub enum AnyHandler {
+pub enum HandlerKind {
     Just(Just),
+    Sfc(Sfc),
+    Jsonc(Jsonc),
+    BashN(BashN),
+    KeyPath(KeyPath),
+    Groovy(Groovy),
+    ColorHandler(ColorHandler),
+    Generic(Box<GenericHandler>),
+    Lintr(Lintr),
+    Ndjson(Ndjson),
+    Idl(Idl),
+    IgnoreFile(IgnoreFile),
+    CargoToml(CargoToml),
+    MdLinks(MdLinks),
+    Ruff(Ruff),
+    Solhint(Solhint),
+    Spectral(Spectral),
+    GoMod(GoMod),
+    Helm(Helm),
+    Kubeconform(Kubeconform),
+    Buildifier(Buildifier),
+    PropsHandler(PropsHandler),
+    Racket(Racket),
+    Nim(Nim),
+    Scala(Scala),
+    OCaml(OCaml),
+    Verible(Verible),
+    Rstcheck(Rstcheck),
+    GraphQl(GraphQl),
+    Shader(Shader),
+    JavaProps(JavaProps),
+    Fortran(Fortran),
+    Pug(Pug),
+    Liquid(Liquid),
+    EditorConfigLint(EditorConfigLint),
+    Codespell(Codespell),
+    Stylelint(Stylelint),
+    MdToc(MdToc),
+    Dockerfile(Dockerfile),
+    YamlAnchors(YamlAnchors),
+    Systemd(Systemd),
+    Csv(Csv),
+    Requirements(Requirements),
+    Awk(Awk),
+    Http(Http),
+    Convert(Convert),
+    Selene(Selene),
+    Tcl(Tcl),
+    Elixir(Elixir),
+    Desktop(Desktop),
+    #[cfg(feature = "treesitter")]
+    TreeSitter(TreeSitter),
+    #[cfg(test)]
+    Mock(Box<mock::Mock>),
 }
 
-impl AnyHandler {
-    pub fn from_filetype(filetype: &str) -> Option<Self> {
-        match filetype {
-            "just" => Some(Self::Just(Just::new().ok()?)),
-            _ => None,
+/// Evaluates `$body` with `$handler` bound to the concrete handler.
+macro_rules! dispatch {
+    ($kind:expr, $handler:ident => $body:expr) => {
+        match $kind {
+            HandlerKind::Just($handler) => $body,
+            HandlerKind::Sfc($handler) => $body,
+            HandlerKind::Jsonc($handler) => $body,
+            HandlerKind::BashN($handler) => $body,
+            HandlerKind::KeyPath($handler) => $body,
+            HandlerKind::Groovy($handler) => $body,
+            HandlerKind::ColorHandler($handler) => $body,
+            HandlerKind::Generic($handler) => $body,
+            HandlerKind::Lintr($handler) => $body,
+            HandlerKind::Ndjson($handler) => $body,
+            HandlerKind::Idl($handler) => $body,
+            HandlerKind::IgnoreFile($handler) => $body,
+            HandlerKind::CargoToml($handler) => $body,
+            HandlerKind::MdLinks($handler) => $body,
+            HandlerKind::Ruff($handler) => $body,
+            HandlerKind::Solhint($handler) => $body,
+            HandlerKind::Spectral($handler) => $body,
+            HandlerKind::GoMod($handler) => $body,
+            HandlerKind::Helm($handler) => $body,
+            HandlerKind::Kubeconform($handler) => $body,
+            HandlerKind::Buildifier($handler) => $body,
+            HandlerKind::PropsHandler($handler) => $body,
+            HandlerKind::Racket($handler) => $body,
+            HandlerKind::Nim($handler) => $body,
+            HandlerKind::Scala($handler) => $body,
+            HandlerKind::OCaml($handler) => $body,
+            HandlerKind::Verible($handler) => $body,
+            HandlerKind::Rstcheck($handler) => $body,
+            HandlerKind::GraphQl($handler) => $body,
+            HandlerKind::Shader($handler) => $body,
+            HandlerKind::JavaProps($handler) => $body,
+            HandlerKind::Fortran($handler) => $body,
+            HandlerKind::Pug($handler) => $body,
+            HandlerKind::Liquid($handler) => $body,
+            HandlerKind::EditorConfigLint($handler) => $body,
+            HandlerKind::Codespell($handler) => $body,
+            HandlerKind::Stylelint($handler) => $body,
+            HandlerKind::MdToc($handler) => $body,
+            HandlerKind::Dockerfile($handler) => $body,
+            HandlerKind::YamlAnchors($handler) => $body,
+            HandlerKind::Systemd($handler) => $body,
+            HandlerKind::Csv($handler) => $body,
+            HandlerKind::Requirements($handler) => $body,
+            HandlerKind::Awk($handler) => $body,
+            HandlerKind::Http($handler) => $body,
+            HandlerKind::Convert($handler) => $body,
+            HandlerKind::Selene($handler) => $body,
+            HandlerKind::Tcl($handler) => $body,
+            HandlerKind::Elixir($handler) => $body,
+            HandlerKind::Desktop($handler) => $body,
+            #[cfg(feature = "treesitter")]
+            HandlerKind::TreeSitter($handler) => $body,
+            #[cfg(test)]
+            HandlerKind::Mock($handler) => $body,
         }
-    }
+    };
 }
 
-impl Handler for AnyHandler {
+impl Handler for HandlerKind {
+    fn filetype_supported(&self, filetype: &str) -> bool {
+        dispatch!(self, handler => handler.filetype_supported(filetype))
+    }
+
+    fn path_supported(&self, path: &Path) -> bool {
+        dispatch!(self, handler => handler.path_supported(path))
+    }
+
+    fn contents_supported(&self, context: &DocumentContext, document_contents: &str) -> bool {
+        dispatch!(self, handler => handler.contents_supported(context, document_contents))
+    }
+
+    fn priority(&self) -> i32 {
+        dispatch!(self, handler => handler.priority())
+    }
+
+    fn on_save_only(&self) -> bool {
+        dispatch!(self, handler => handler.on_save_only())
+    }
+
+    fn get_capabilities(&self) -> ServerCapabilities {
+        dispatch!(self, handler => handler.get_capabilities())
+    }
+
+    fn set_temp_file_strategy(&mut self, strategy: TempFileStrategy) {
+        dispatch!(self, handler => handler.set_temp_file_strategy(strategy))
+    }
+
+    fn set_model_cache_capacity(&mut self, capacity: usize) {
+        dispatch!(self, handler => handler.set_model_cache_capacity(capacity))
+    }
+
     async fn update_diagnostics(
         &mut self,
         document_contents: &str,
     ) -> Result<Vec<Diagnostic>, HandlerError> {
-        match self {
-            Self::Just(just) => just.update_diagnostics(document_contents).await,
+        dispatch!(self, handler => handler.update_diagnostics(document_contents).await)
+    }
+
+    async fn update_diagnostics_with_context(
+        &mut self,
+        context: &DocumentContext,
+        document_contents: &str,
+    ) -> Result<Vec<Diagnostic>, HandlerError> {
+        dispatch!(self, handler => {
+            handler
+                .update_diagnostics_with_context(context, document_contents)
+                .await
+        })
+    }
+
+    async fn update_document_diagnostics(
+        &mut self,
+        context: &DocumentContext,
+        document_contents: &str,
+    ) -> Result<DocumentDiagnostics, HandlerError> {
+        dispatch!(self, handler => {
+            handler
+                .update_document_diagnostics(context, document_contents)
+                .await
+        })
+    }
+
+    async fn format(
+        &mut self,
+        filetype: &str,
+        document_contents: &str,
+    ) -> Result<Option<String>, HandlerError> {
+        dispatch!(self, handler => handler.format(filetype, document_contents).await)
+    }
+
+    async fn format_with_context(
+        &mut self,
+        context: &DocumentContext,
+        filetype: &str,
+        document_contents: &str,
+    ) -> Result<Option<String>, HandlerError> {
+        dispatch!(self, handler => {
+            handler
+                .format_with_context(context, filetype, document_contents)
+                .await
+        })
+    }
+
+    fn hover(
+        &self,
+        filetype: &str,
+        context: &DocumentContext,
+        document_contents: &str,
+        position: Position,
+    ) -> Result<Option<Hover>, HandlerError> {
+        dispatch!(self, handler => handler.hover(filetype, context, document_contents, position))
+    }
+
+    fn definition(
+        &self,
+        context: &DocumentContext,
+        document_contents: &str,
+        position: Position,
+    ) -> Result<Option<Location>, HandlerError> {
+        dispatch!(self, handler => handler.definition(context, document_contents, position))
+    }
+
+    fn document_links(
+        &self,
+        document_contents: &str,
+        uri: &Url,
+    ) -> Result<Vec<DocumentLink>, HandlerError> {
+        dispatch!(self, handler => handler.document_links(document_contents, uri))
+    }
+
+    fn document_colors(
+        &self,
+        filetype: &str,
+        document_contents: &str,
+    ) -> Result<Vec<ColorInformation>, HandlerError> {
+        dispatch!(self, handler => handler.document_colors(filetype, document_contents))
+    }
+
+    fn color_presentations(
+        &self,
+        color: Color,
+        range: Range,
+    ) -> Result<Vec<ColorPresentation>, HandlerError> {
+        dispatch!(self, handler => handler.color_presentations(color, range))
+    }
+
+    fn linked_editing_ranges(
+        &self,
+        document_contents: &str,
+        position: Position,
+    ) -> Result<Option<LinkedEditingRanges>, HandlerError> {
+        dispatch!(self, handler => handler.linked_editing_ranges(document_contents, position))
+    }
+
+    fn document_symbols(
+        &self,
+        filetype: &str,
+        document_contents: &str,
+    ) -> Result<Vec<DocumentSymbol>, HandlerError> {
+        dispatch!(self, handler => handler.document_symbols(filetype, document_contents))
+    }
+
+    fn completions(
+        &self,
+        filetype: &str,
+        context: &DocumentContext,
+        document_contents: &str,
+        position: Position,
+    ) -> Result<Vec<CompletionItem>, HandlerError> {
+        dispatch!(self, handler => handler.completions(filetype, context, document_contents, position))
+    }
+
+    fn code_actions(
+        &self,
+        context: &DocumentContext,
+        diagnostics: &[Diagnostic],
+    ) -> Result<Vec<CodeAction>, HandlerError> {
+        dispatch!(self, handler => handler.code_actions(context, diagnostics))
+    }
+
+    fn execute_command(
+        &self,
+        command: &str,
+        arguments: &[Value],
+        filetype: &str,
+        context: &DocumentContext,
+        document_contents: &str,
+    ) -> Result<Option<WorkspaceEdit>, HandlerError> {
+        dispatch!(self, handler => handler.execute_command(command, arguments, filetype, context, document_contents))
+    }
+
+    fn execute_workspace_command(
+        &self,
+        command: &str,
+        workspace_folders: &[WorkspaceFolder],
+    ) -> Result<Option<String>, HandlerError> {
+        dispatch!(self, handler => handler.execute_workspace_command(command, workspace_folders))
+    }
+}
+
+/// Sets every listed capability of `$capabilities` that is still `None` to
+/// the value from `$other`, i.e. the first handler setting a field wins.
+/// Handlers are sorted by priority, so that is the highest priority one.
+/// Fields listed with their own fields in braces are merged field by field.
+/// `$set` is called with the path of every field set, e.g.
+/// `workspace.file_operations.did_create`, below `$prefix`.
+macro_rules! get_capabilities {
+    ($capabilities:expr, $other:expr, $set:expr, $prefix:expr, [$($field:ident $({ $($nested:tt)* })?),* $(,)?]) => {
+        $(
+            get_capabilities!(@field $capabilities, $other, $set, $prefix, $field $({ $($nested)* })?);
+        )*
+    };
+    (@field $capabilities:expr, $other:expr, $set:expr, $prefix:expr, $field:ident) => {
+        if $capabilities.$field.is_none() && $other.$field.is_some() {
+            $set(format!("{}{}", $prefix, stringify!($field)));
+            $capabilities.$field = $other.$field;
+        }
+    };
+    (@field $capabilities:expr, $other:expr, $set:expr, $prefix:expr, $field:ident { $($nested:tt)* }) => {
+        if let Some(other) = $other.$field {
+            match &mut $capabilities.$field {
+                Some(capabilities) => {
+                    let prefix = format!("{}{}.", $prefix, stringify!($field));
+                    get_capabilities!(capabilities, other, $set, prefix, [$($nested)*]);
+                }
+                None => {
+                    $set(format!("{}{}", $prefix, stringify!($field)));
+                    $capabilities.$field = Some(other);
+                }
+            }
         }
+    };
+}
+
+/// `field` with the words of each segment after the first joined in camel
+/// case, e.g. `hoverProvider` for `hover_provider`.
+fn camel_case(field: &str) -> String {
+    field
+        .split('.')
+        .map(|segment| {
+            let mut words = segment.split('_');
+            let first = words.next().unwrap_or_default().to_string();
+            words.fold(first, |mut name, word| {
+                let mut chars = word.chars();
+                name.extend(chars.next().map(|c| c.to_ascii_uppercase()));
+                name.push_str(chars.as_str());
+                name
+            })
+        })
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// Capabilities of `handlers`, sorted by descending priority, and the index
+/// of the handler that set each field by its path.
+fn merge_capabilities(handlers: &[HandlerKind]) -> (ServerCapabilities, Vec<(String, usize)>) {
+    let mut capabilities = ServerCapabilities::default();
+    let mut sources = Vec::new();
+    for (index, handler) in handlers.iter().enumerate() {
+        let other = handler.get_capabilities();
+        let mut set = |field: String| sources.push((field, index));
+        get_capabilities!(
+            capabilities,
+            other,
+            set,
+            "",
+            [
+                hover_provider,
+                completion_provider,
+                definition_provider,
+                references_provider,
+                document_symbol_provider,
+                code_action_provider,
+                document_formatting_provider,
+                document_link_provider,
+                color_provider,
+                linked_editing_range_provider,
+                diagnostic_provider,
+                workspace {
+                    workspace_folders,
+                    file_operations {
+                        did_create,
+                        will_create,
+                        did_rename,
+                        will_rename,
+                        did_delete,
+                        will_delete,
+                    },
+                },
+            ]
+        );
+        // Each handler runs its own commands, so all are advertised
+        if let Some(other) = other.execute_command_provider {
+            match &mut capabilities.execute_command_provider {
+                Some(commands) => commands.commands.extend(other.commands),
+                None => {
+                    set("execute_command_provider".to_string());
+                    capabilities.execute_command_provider = Some(other);
+                }
+            }
+        }
+    }
+    (capabilities, sources)
+}
+
+/// All available handlers, dispatched to by filetype and ordered by
+/// descending priority.
+#[derive(Debug, Default)]
+pub struct AnyHandler {
+    handlers: Vec<HandlerKind>,
+    /// Diagnostics kept per document, see
+    /// `Config::max_diagnostics_per_document`.
+    max_diagnostics: Option<usize>,
+    /// `Config::severity_overrides`, most specific first.
+    severity_overrides: Vec<(GlobMatcher, SeverityOverride)>,
+    /// Merged capabilities of `handlers`, which don't change once created.
+    capabilities: ServerCapabilities,
+    /// Paths of the fields of `capabilities`, with the index in `handlers`
+    /// of the handler that set them.
+    capability_sources: Vec<(String, usize)>,
+    /// Names of `handlers`, in the same order. Empty for handlers created
+    /// without names.
+    names: Vec<String>,
+    /// Encodings of the output of the tools of `handlers`, in the same
+    /// order, see `Config::output_encoding`. Empty for handlers created
+    /// without names.
+    output_encodings: Vec<&'static Encoding>,
+    /// Filetypes by the aliases clients send for them, see
+    /// `Config::filetype_aliases`.
+    filetype_aliases: HashMap<String, String>,
+    /// Names of the handlers formatting each filetype, see
+    /// `Config::default_formatter`.
+    default_formatter: HashMap<String, String>,
+    /// The last diagnostics of handlers only checking saved documents, by
+    /// the index of the handler and the document.
+    saved_diagnostics: HashMap<(usize, Url), DocumentDiagnostics>,
+}
+
+/// Filetypes with the `language_id`s some clients send for them instead,
+/// extended by `Config::filetype_aliases`.
+const DEFAULT_FILETYPE_ALIASES: &[(&str, &[&str])] = &[
+    ("sh", &["shellscript", "bash"]),
+    ("yaml", &["yml"]),
+    ("markdown", &["md"]),
+];
+
+/// The filetype of each alias, of the defaults and the `configured` ones
+/// which take precedence.
+fn filetype_aliases(configured: &HashMap<String, Vec<String>>) -> HashMap<String, String> {
+    let defaults = DEFAULT_FILETYPE_ALIASES
+        .iter()
+        .flat_map(|(filetype, aliases)| {
+            aliases
+                .iter()
+                .map(|alias| (alias.to_string(), filetype.to_string()))
+        });
+    let configured = configured.iter().flat_map(|(filetype, aliases)| {
+        aliases
+            .iter()
+            .map(|alias| (alias.clone(), filetype.clone()))
+    });
+    defaults.chain(configured).collect()
+}
+
+/// Diagnostics kept per document when the settings don't set a limit.
+const DEFAULT_MAX_DIAGNOSTICS: usize = 1000;
+
+/// Sorts diagnostics by the start of their range, then severity, most
+/// severe first, source, message and end of their range, so that they are
+/// listed in the same order whatever the order handlers ran in.
+fn sort_diagnostics(diagnostics: &mut [Diagnostic]) {
+    diagnostics.sort_by(|a, b| {
+        a.range
+            .start
+            .cmp(&b.range.start)
+            // Clients show diagnostics without a severity as errors
+            .then_with(|| {
+                let severity = |d: &Diagnostic| d.severity.unwrap_or(DiagnosticSeverity::ERROR);
+                severity(a).cmp(&severity(b))
+            })
+            .then_with(|| a.source.cmp(&b.source))
+            .then_with(|| a.message.cmp(&b.message))
+            .then_with(|| a.range.end.cmp(&b.range.end))
+    });
+}
+
+/// Identifies diagnostics several handlers report, by range and message.
+fn dedup_key(diagnostic: &Diagnostic) -> ((u32, u32, u32, u32), String) {
+    let Range { start, end } = diagnostic.range;
+    let range = (start.line, start.character, end.line, end.character);
+    (range, diagnostic.message.clone())
+}
+
+/// Moves the ends of ranges past the end of their line, or of the
+/// document, to that end. Tools report e.g. errors at the end of the file
+/// on the line after the last one, which some clients reject.
+fn clamp_ranges(document_contents: &str, diagnostics: &mut [Diagnostic]) {
+    // Lengths in UTF-16 code units, an empty document has one empty line
+    let lengths: Vec<u32> = document_contents
+        .split('\n')
+        .map(|line| {
+            line.strip_suffix('\r')
+                .unwrap_or(line)
+                .encode_utf16()
+                .count() as u32
+        })
+        .collect();
+    let last = lengths.len() as u32 - 1;
+    let clamp = |position: &mut Position| match lengths.get(position.line as usize) {
+        Some(length) => position.character = position.character.min(*length),
+        None => *position = Position::new(last, lengths[last as usize]),
+    };
+    for diagnostic in diagnostics {
+        clamp(&mut diagnostic.range.start);
+        clamp(&mut diagnostic.range.end);
+    }
+}
+
+/// Drops the diagnostics after the first `max`, replaced by one noting how
+/// many were omitted.
+fn truncate_diagnostics(diagnostics: &mut Vec<Diagnostic>, max: usize) {
+    if diagnostics.len() <= max {
+        return;
+    }
+    let omitted = diagnostics.len() - max;
+    diagnostics.truncate(max);
+    diagnostics.push(Diagnostic::new(
+        Range::default(),
+        Some(DiagnosticSeverity::INFORMATION),
+        None,
+        Some("any_ls".to_string()),
+        format!("{omitted} more diagnostics omitted"),
+        None,
+        None,
+    ));
+}
+
+/// Compiles the severity overrides of the settings, longer patterns first
+/// as they are more specific. Invalid patterns are reported by
+/// `Config::validate`.
+fn severity_rules(
+    overrides: &HashMap<String, SeverityOverride>,
+) -> Vec<(GlobMatcher, SeverityOverride)> {
+    let mut patterns: Vec<_> = overrides.iter().collect();
+    patterns.sort_by(|(a, _), (b, _)| b.len().cmp(&a.len()).then_with(|| a.cmp(b)));
+    patterns
+        .into_iter()
+        .filter_map(|(pattern, action)| {
+            let matcher = Glob::new(pattern).ok()?.compile_matcher();
+            Some((matcher, *action))
+        })
+        .collect()
+}
+
+/// Changes the severity of the diagnostics matching a rule by their
+/// `source/code`, or drops them.
+fn override_severities(
+    diagnostics: &mut Vec<Diagnostic>,
+    rules: &[(GlobMatcher, SeverityOverride)],
+) {
+    if rules.is_empty() {
+        return;
+    }
+    diagnostics.retain_mut(|diagnostic| {
+        let code = match &diagnostic.code {
+            Some(NumberOrString::String(code)) => code.clone(),
+            Some(NumberOrString::Number(code)) => code.to_string(),
+            None => String::new(),
+        };
+        let key = format!(
+            "{}/{code}",
+            diagnostic.source.as_deref().unwrap_or_default()
+        );
+        let Some((_, action)) = rules.iter().find(|(matcher, _)| matcher.is_match(&key)) else {
+            return true;
+        };
+        match action.severity() {
+            Some(severity) => {
+                diagnostic.severity = Some(severity);
+                true
+            }
+            None => false,
+        }
+    });
+}
+
+impl AnyHandler {
+    pub fn new(config: &Config) -> Self {
+        let mut handlers = Vec::new();
+        add_handler(
+            &mut handlers,
+            "Just",
+            Just::new(config.just.clone()),
+            HandlerKind::Just,
+        );
+        add_handler(&mut handlers, "Sfc", Sfc::new(), HandlerKind::Sfc);
+        add_handler(&mut handlers, "Jsonc", Jsonc::new(), HandlerKind::Jsonc);
+        add_handler(&mut handlers, "BashN", BashN::new(), HandlerKind::BashN);
+        add_handler(
+            &mut handlers,
+            "KeyPath",
+            KeyPath::new(),
+            HandlerKind::KeyPath,
+        );
+        add_handler(&mut handlers, "Groovy", Groovy::new(), HandlerKind::Groovy);
+        add_handler(
+            &mut handlers,
+            "ColorHandler",
+            ColorHandler::new(),
+            HandlerKind::ColorHandler,
+        );
+        for generic in &config.generic {
+            add_handler(
+                &mut handlers,
+                &generic.name,
+                GenericHandler::new(generic.clone()),
+                |handler| HandlerKind::Generic(Box::new(handler)),
+            );
+        }
+        add_handler(&mut handlers, "Lintr", Lintr::new(), HandlerKind::Lintr);
+        add_handler(&mut handlers, "Ndjson", Ndjson::new(), HandlerKind::Ndjson);
+        add_handler(&mut handlers, "Idl", Idl::new(), HandlerKind::Idl);
+        add_handler(
+            &mut handlers,
+            "IgnoreFile",
+            IgnoreFile::new(),
+            HandlerKind::IgnoreFile,
+        );
+        add_handler(
+            &mut handlers,
+            "CargoToml",
+            CargoToml::new(),
+            HandlerKind::CargoToml,
+        );
+        add_handler(
+            &mut handlers,
+            "MdLinks",
+            MdLinks::new(),
+            HandlerKind::MdLinks,
+        );
+        add_handler(&mut handlers, "Ruff", Ruff::new(), HandlerKind::Ruff);
+        add_handler(
+            &mut handlers,
+            "Solhint",
+            Solhint::new(),
+            HandlerKind::Solhint,
+        );
+        add_handler(
+            &mut handlers,
+            "Spectral",
+            Spectral::new(),
+            HandlerKind::Spectral,
+        );
+        add_handler(&mut handlers, "GoMod", GoMod::new(), HandlerKind::GoMod);
+        add_handler(&mut handlers, "Helm", Helm::new(), HandlerKind::Helm);
+        add_handler(
+            &mut handlers,
+            "Kubeconform",
+            Kubeconform::new(),
+            HandlerKind::Kubeconform,
+        );
+        add_handler(
+            &mut handlers,
+            "Buildifier",
+            Buildifier::new(),
+            HandlerKind::Buildifier,
+        );
+        add_handler(
+            &mut handlers,
+            "PropsHandler",
+            PropsHandler::new(config.mask_secret_values.as_deref()),
+            HandlerKind::PropsHandler,
+        );
+        add_handler(&mut handlers, "Racket", Racket::new(), HandlerKind::Racket);
+        add_handler(&mut handlers, "Nim", Nim::new(), HandlerKind::Nim);
+        add_handler(&mut handlers, "Scala", Scala::new(), HandlerKind::Scala);
+        add_handler(&mut handlers, "OCaml", OCaml::new(), HandlerKind::OCaml);
+        add_handler(
+            &mut handlers,
+            "Verible",
+            Verible::new(),
+            HandlerKind::Verible,
+        );
+        add_handler(
+            &mut handlers,
+            "Rstcheck",
+            Rstcheck::new(),
+            HandlerKind::Rstcheck,
+        );
+        add_handler(
+            &mut handlers,
+            "GraphQl",
+            GraphQl::new(),
+            HandlerKind::GraphQl,
+        );
+        add_handler(&mut handlers, "Shader", Shader::new(), HandlerKind::Shader);
+        add_handler(
+            &mut handlers,
+            "JavaProps",
+            JavaProps::new(),
+            HandlerKind::JavaProps,
+        );
+        add_handler(
+            &mut handlers,
+            "Fortran",
+            Fortran::new(),
+            HandlerKind::Fortran,
+        );
+        add_handler(&mut handlers, "Pug", Pug::new(), HandlerKind::Pug);
+        add_handler(&mut handlers, "Liquid", Liquid::new(), HandlerKind::Liquid);
+        add_handler(
+            &mut handlers,
+            "EditorConfigLint",
+            EditorConfigLint::new(),
+            HandlerKind::EditorConfigLint,
+        );
+        if config.codespell {
+            add_handler(
+                &mut handlers,
+                "Codespell",
+                Codespell::new(),
+                HandlerKind::Codespell,
+            );
+        }
+        add_handler(
+            &mut handlers,
+            "Stylelint",
+            Stylelint::new(),
+            HandlerKind::Stylelint,
+        );
+        add_handler(&mut handlers, "MdToc", MdToc::new(), HandlerKind::MdToc);
+        add_handler(
+            &mut handlers,
+            "Dockerfile",
+            Dockerfile::new(),
+            HandlerKind::Dockerfile,
+        );
+        add_handler(
+            &mut handlers,
+            "YamlAnchors",
+            YamlAnchors::new(),
+            HandlerKind::YamlAnchors,
+        );
+        add_handler(
+            &mut handlers,
+            "Systemd",
+            Systemd::new(),
+            HandlerKind::Systemd,
+        );
+        add_handler(
+            &mut handlers,
+            "Csv",
+            Csv::new(config.csv.clone()),
+            HandlerKind::Csv,
+        );
+        add_handler(
+            &mut handlers,
+            "Requirements",
+            Requirements::new(),
+            HandlerKind::Requirements,
+        );
+        add_handler(&mut handlers, "Awk", Awk::new(), HandlerKind::Awk);
+        add_handler(&mut handlers, "Http", Http::new(), HandlerKind::Http);
+        add_handler(
+            &mut handlers,
+            "Convert",
+            Convert::new(),
+            HandlerKind::Convert,
+        );
+        add_handler(&mut handlers, "Selene", Selene::new(), HandlerKind::Selene);
+        add_handler(&mut handlers, "Tcl", Tcl::new(), HandlerKind::Tcl);
+        add_handler(&mut handlers, "Elixir", Elixir::new(), HandlerKind::Elixir);
+        add_handler(
+            &mut handlers,
+            "Desktop",
+            Desktop::new(),
+            HandlerKind::Desktop,
+        );
+        #[cfg(feature = "treesitter")]
+        add_handler(
+            &mut handlers,
+            "TreeSitter",
+            TreeSitter::new(),
+            HandlerKind::TreeSitter,
+        );
+        let model_cache_capacity = config
+            .model_cache_capacity
+            .unwrap_or(cache::DEFAULT_MODEL_CACHE_CAPACITY);
+        for (_, handler) in &mut handlers {
+            handler.set_temp_file_strategy(config.temp_files);
+            handler.set_model_cache_capacity(model_cache_capacity);
+        }
+        handlers.retain(|(name, _)| {
+            let disabled = config.disabled.contains(name);
+            if disabled {
+                log::info!("{name} handler disabled in settings");
+            }
+            !disabled
+        });
+
+        // Sorted here as well to keep the names in the order of `handlers`
+        handlers.sort_by_key(|(_, handler)| std::cmp::Reverse(handler.priority()));
+        let (names, handlers): (Vec<String>, _) = handlers.into_iter().unzip();
+        // Invalid labels are reported by `Config::validate`
+        let output_encodings = names
+            .iter()
+            .map(|name| {
+                config
+                    .output_encoding
+                    .get(name)
+                    .and_then(|label| Encoding::for_label(label.as_bytes()))
+                    .unwrap_or(UTF_8)
+            })
+            .collect();
+        Self {
+            names,
+            output_encodings,
+            max_diagnostics: Some(
+                config
+                    .max_diagnostics_per_document
+                    .unwrap_or(DEFAULT_MAX_DIAGNOSTICS),
+            ),
+            severity_overrides: severity_rules(&config.severity_overrides),
+            filetype_aliases: filetype_aliases(&config.filetype_aliases),
+            default_formatter: config.default_formatter.clone(),
+            ..Self::from_handlers(handlers)
+        }
+    }
+
+    pub(crate) fn from_handlers(mut handlers: Vec<HandlerKind>) -> Self {
+        // Stable, so equal priorities keep their registration order
+        handlers.sort_by_key(|handler| std::cmp::Reverse(handler.priority()));
+        let (capabilities, capability_sources) = merge_capabilities(&handlers);
+        Self {
+            capabilities,
+            capability_sources,
+            handlers,
+            names: Vec::new(),
+            output_encodings: Vec::new(),
+            max_diagnostics: None,
+            severity_overrides: Vec::new(),
+            filetype_aliases: filetype_aliases(&HashMap::new()),
+            default_formatter: HashMap::new(),
+            saved_diagnostics: HashMap::new(),
+        }
+    }
+
+    /// The encoding of the output of the tools of the handler at `index`.
+    fn output_encoding(&self, index: usize) -> &'static Encoding {
+        self.output_encodings.get(index).copied().unwrap_or(UTF_8)
+    }
+
+    /// The filetype handlers know `filetype` by, when it is an alias.
+    fn canonical_filetype(&self, filetype: &str) -> String {
+        self.filetype_aliases
+            .get(filetype)
+            .map_or(filetype, String::as_str)
+            .to_string()
+    }
+
+    /// Names of the enabled handlers, by descending priority.
+    pub fn enabled(&self) -> &[String] {
+        &self.names
+    }
+
+    /// Whether any handler runs for the document, by its filetype from the
+    /// client or the one detected from the document.
+    pub fn document_supported(
+        &self,
+        filetype: &str,
+        context: &DocumentContext,
+        document_contents: &str,
+    ) -> bool {
+        let filetype = &self.canonical_filetype(filetype);
+        let detected = detected_filetype(filetype, context, document_contents);
+        let embedded = embedded::regions(filetype, document_contents);
+        self.handlers.iter().any(|handler| {
+            is_active_detected(handler, filetype, detected.as_deref(), context)
+                || embedded
+                    .iter()
+                    .any(|region| handler.filetype_supported(region.filetype))
+        })
+    }
+
+    /// Forgets the diagnostics kept for the document at `uri` once it is
+    /// closed.
+    pub fn close_document(&mut self, uri: &Url) {
+        self.saved_diagnostics.retain(|(_, saved), _| saved != uri);
+    }
+
+    /// Merged capabilities of all handlers.
+    pub fn get_capabilities(&self) -> ServerCapabilities {
+        self.capabilities.clone()
+    }
+
+    /// Names of the handlers that set the fields of the merged
+    /// capabilities, by the path of the field as it is serialized, e.g.
+    /// `workspace.fileOperations.didCreate`.
+    pub fn capability_sources(&self) -> BTreeMap<String, String> {
+        self.capability_sources
+            .iter()
+            .map(|(field, index)| {
+                let name = self.names.get(*index).cloned().unwrap_or_default();
+                (camel_case(field), name)
+            })
+            .collect()
+    }
+
+    pub async fn update_diagnostics(
+        &mut self,
+        filetype: &str,
+        context: &DocumentContext,
+        document_contents: &str,
+    ) -> Result<Vec<Diagnostic>, HandlerError> {
+        self.update_document_diagnostics(filetype, context, document_contents)
+            .await
+            .map(|document| document.diagnostics)
+    }
+
+    /// Diagnostics of all active handlers, with those of other files
+    /// grouped by file.
+    pub async fn update_document_diagnostics(
+        &mut self,
+        filetype: &str,
+        context: &DocumentContext,
+        document_contents: &str,
+    ) -> Result<DocumentDiagnostics, HandlerError> {
+        let filetype = &self.canonical_filetype(filetype);
+        let detected = detected_filetype(filetype, context, document_contents);
+        let mut ran = Vec::new();
+        let mut diagnostics = self
+            .handler_diagnostics(
+                filetype,
+                detected.as_deref(),
+                &DocumentContext {
+                    filetype: filetype.clone(),
+                    ..context.clone()
+                },
+                document_contents,
+                &mut ran,
+            )
+            .await;
+        // Embedded languages are checked by their own handlers, those that
+        // checked the whole document, e.g. of any filetype, already did
+        for region in embedded::regions(filetype, document_contents) {
+            let mut region_diagnostics = self
+                .handler_diagnostics(
+                    region.filetype,
+                    None,
+                    &DocumentContext {
+                        filetype: region.filetype.to_string(),
+                        ..context.clone()
+                    },
+                    &region.contents,
+                    &mut ran.clone(),
+                )
+                .await;
+            region.to_document(&mut region_diagnostics.diagnostics);
+            diagnostics
+                .diagnostics
+                .extend(region_diagnostics.diagnostics);
+            for (uri, related) in region_diagnostics.related {
+                diagnostics.related.entry(uri).or_default().extend(related);
+            }
+            diagnostics.errors.extend(region_diagnostics.errors);
+        }
+        clamp_ranges(document_contents, &mut diagnostics.diagnostics);
+        suppress::drop_ignored(
+            detected.as_deref().unwrap_or(filetype),
+            document_contents,
+            &mut diagnostics.diagnostics,
+        );
+        override_severities(&mut diagnostics.diagnostics, &self.severity_overrides);
+        sort_diagnostics(&mut diagnostics.diagnostics);
+        for related in diagnostics.related.values_mut() {
+            override_severities(related, &self.severity_overrides);
+            sort_diagnostics(related);
+        }
+        if let Some(max) = self.max_diagnostics {
+            truncate_diagnostics(&mut diagnostics.diagnostics, max);
+            for related in diagnostics.related.values_mut() {
+                truncate_diagnostics(related, max);
+            }
+        }
+        Ok(diagnostics)
+    }
+
+    /// Diagnostics of the handlers active for `document_contents`, of
+    /// `filetype` or the `detected` one, except the handlers of `ran` by
+    /// index, to which those that run are added. Diagnostics a higher
+    /// priority handler reported too are dropped, and a handler failing
+    /// is noted by an information diagnostic instead of its own.
+    async fn handler_diagnostics(
+        &mut self,
+        filetype: &str,
+        detected: Option<&str>,
+        context: &DocumentContext,
+        document_contents: &str,
+        ran: &mut Vec<usize>,
+    ) -> DocumentDiagnostics {
+        let mut diagnostics = DocumentDiagnostics::default();
+        // The priority of the first handler reporting each diagnostic, the
+        // highest as handlers are sorted by it
+        let mut reported = HashMap::new();
+        for (index, handler) in self.handlers.iter_mut().enumerate() {
+            if !ran.contains(&index)
+                && is_active_detected(handler, filetype, detected, context)
+                && handler.contents_supported(context, document_contents)
+            {
+                ran.push(index);
+                // Unsaved changes keep the diagnostics of the last check
+                let key = (index, context.uri.clone());
+                let saved = self
+                    .saved_diagnostics
+                    .get(&key)
+                    .filter(|_| handler.on_save_only() && !context.saved)
+                    .cloned();
+                let result = match saved {
+                    Some(document) => Ok(document),
+                    None => {
+                        with_output_encoding(
+                            self.output_encodings.get(index).copied().unwrap_or(UTF_8),
+                            handler.update_document_diagnostics(context, document_contents),
+                        )
+                        .await
+                    }
+                };
+                let document = match result {
+                    Ok(document) => {
+                        if handler.on_save_only() {
+                            self.saved_diagnostics.insert(key, document.clone());
+                        }
+                        document
+                    }
+                    // The other handlers' diagnostics are still worth showing,
+                    // the error is noted in the first line of the document
+                    Err(err) => {
+                        let message = err.to_string();
+                        log::warn!("Could not check {}: {message}", context.uri);
+                        let first_line = message.lines().next().unwrap_or_default();
+                        diagnostics.diagnostics.push(Diagnostic::new(
+                            Range::default(),
+                            Some(DiagnosticSeverity::INFORMATION),
+                            None,
+                            Some("any_ls".to_string()),
+                            first_line.to_string(),
+                            None,
+                            None,
+                        ));
+                        diagnostics.errors.push(message);
+                        continue;
+                    }
+                };
+                let priority = handler.priority();
+                for diagnostic in document.diagnostics {
+                    let first = *reported.entry(dedup_key(&diagnostic)).or_insert(priority);
+                    if first == priority {
+                        diagnostics.diagnostics.push(diagnostic);
+                    }
+                }
+                for (uri, related) in document.related {
+                    diagnostics.related.entry(uri).or_default().extend(related);
+                }
+            }
+        }
+        diagnostics
+    }
+
+    /// Formats the document with the handler set for its filetype in
+    /// `Config::default_formatter`, or else the first handler able to, as
+    /// edits of the lines that changed.
+    pub async fn format(
+        &mut self,
+        filetype: &str,
+        context: &DocumentContext,
+        document_contents: &str,
+    ) -> Result<Option<Vec<TextEdit>>, HandlerError> {
+        let filetype = &self.canonical_filetype(filetype);
+        let preferred = self
+            .default_formatter
+            .get(filetype)
+            .and_then(|name| self.names.iter().position(|handler| handler == name));
+        let others = (0..self.handlers.len()).filter(|&index| Some(index) != preferred);
+        for index in preferred.into_iter().chain(others) {
+            let encoding = self.output_encoding(index);
+            let handler = &mut self.handlers[index];
+            if !is_active(handler, filetype, context) {
+                continue;
+            }
+            let formatted = with_output_encoding(
+                encoding,
+                handler.format_with_context(context, filetype, document_contents),
+            )
+            .await?;
+            if let Some(formatted) = formatted {
+                return Ok(Some(text::compute_text_edits(
+                    document_contents,
+                    &formatted,
+                )));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Hover from the highest priority handler that has one.
+    pub fn hover(
+        &self,
+        filetype: &str,
+        context: &DocumentContext,
+        document_contents: &str,
+        position: Position,
+    ) -> Result<Option<Hover>, HandlerError> {
+        let filetype = &self.canonical_filetype(filetype);
+        for handler in &self.handlers {
+            if !is_active(handler, filetype, context) {
+                continue;
+            }
+            if let Some(hover) = handler.hover(filetype, context, document_contents, position)? {
+                return Ok(Some(hover));
+            }
+        }
+        Ok(None)
+    }
+
+    /// The definition from the highest priority handler that finds one.
+    pub fn definition(
+        &self,
+        filetype: &str,
+        context: &DocumentContext,
+        document_contents: &str,
+        position: Position,
+    ) -> Result<Option<Location>, HandlerError> {
+        let filetype = &self.canonical_filetype(filetype);
+        for handler in &self.handlers {
+            if !is_active(handler, filetype, context) {
+                continue;
+            }
+            if let Some(location) = handler.definition(context, document_contents, position)? {
+                return Ok(Some(location));
+            }
+        }
+        Ok(None)
+    }
+
+    pub fn document_links(
+        &self,
+        filetype: &str,
+        context: &DocumentContext,
+        document_contents: &str,
+    ) -> Result<Vec<DocumentLink>, HandlerError> {
+        let filetype = &self.canonical_filetype(filetype);
+        let mut links = Vec::new();
+        for handler in &self.handlers {
+            if is_active(handler, filetype, context) {
+                links.extend(handler.document_links(document_contents, &context.uri)?);
+            }
+        }
+        Ok(links)
+    }
+
+    pub fn document_colors(
+        &self,
+        filetype: &str,
+        context: &DocumentContext,
+        document_contents: &str,
+    ) -> Result<Vec<ColorInformation>, HandlerError> {
+        let filetype = &self.canonical_filetype(filetype);
+        let mut colors = Vec::new();
+        for handler in &self.handlers {
+            if is_active(handler, filetype, context) {
+                colors.extend(handler.document_colors(filetype, document_contents)?);
+            }
+        }
+        Ok(colors)
+    }
+
+    pub fn color_presentations(
+        &self,
+        filetype: &str,
+        context: &DocumentContext,
+        color: Color,
+        range: Range,
+    ) -> Result<Vec<ColorPresentation>, HandlerError> {
+        let filetype = &self.canonical_filetype(filetype);
+        let mut presentations = Vec::new();
+        for handler in &self.handlers {
+            if is_active(handler, filetype, context) {
+                presentations.extend(handler.color_presentations(color, range)?);
+            }
+        }
+        Ok(presentations)
+    }
+
+    /// Linked editing ranges from the highest priority handler that has them.
+    pub fn linked_editing_ranges(
+        &self,
+        filetype: &str,
+        context: &DocumentContext,
+        document_contents: &str,
+        position: Position,
+    ) -> Result<Option<LinkedEditingRanges>, HandlerError> {
+        let filetype = &self.canonical_filetype(filetype);
+        for handler in &self.handlers {
+            if !is_active(handler, filetype, context) {
+                continue;
+            }
+            if let Some(ranges) = handler.linked_editing_ranges(document_contents, position)? {
+                return Ok(Some(ranges));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Symbols from the highest priority handler that has any.
+    pub fn document_symbols(
+        &self,
+        filetype: &str,
+        context: &DocumentContext,
+        document_contents: &str,
+    ) -> Result<Vec<DocumentSymbol>, HandlerError> {
+        let filetype = &self.canonical_filetype(filetype);
+        for handler in &self.handlers {
+            if !is_active(handler, filetype, context) {
+                continue;
+            }
+            let symbols = handler.document_symbols(filetype, document_contents)?;
+            if !symbols.is_empty() {
+                return Ok(symbols);
+            }
+        }
+        Ok(Vec::new())
+    }
+
+    /// Completions of every active handler.
+    pub fn completions(
+        &self,
+        filetype: &str,
+        context: &DocumentContext,
+        document_contents: &str,
+        position: Position,
+    ) -> Result<Vec<CompletionItem>, HandlerError> {
+        let filetype = &self.canonical_filetype(filetype);
+        let mut items = Vec::new();
+        for handler in &self.handlers {
+            if is_active(handler, filetype, context) {
+                items.extend(handler.completions(
+                    filetype,
+                    context,
+                    document_contents,
+                    position,
+                )?);
+            }
+        }
+        Ok(items)
+    }
+
+    /// Code actions of every active handler.
+    pub fn code_actions(
+        &self,
+        filetype: &str,
+        context: &DocumentContext,
+        diagnostics: &[Diagnostic],
+    ) -> Result<Vec<CodeAction>, HandlerError> {
+        let filetype = &self.canonical_filetype(filetype);
+        let mut actions = Vec::new();
+        for handler in &self.handlers {
+            if is_active(handler, filetype, context) {
+                actions.extend(handler.code_actions(context, diagnostics)?);
+            }
+        }
+        Ok(actions)
+    }
+
+    /// The edit of the first active handler running `command`.
+    pub fn execute_command(
+        &self,
+        command: &str,
+        arguments: &[Value],
+        filetype: &str,
+        context: &DocumentContext,
+        document_contents: &str,
+    ) -> Result<Option<WorkspaceEdit>, HandlerError> {
+        let filetype = &self.canonical_filetype(filetype);
+        for handler in &self.handlers {
+            if !is_active(handler, filetype, context) {
+                continue;
+            }
+            if let Some(edit) =
+                handler.execute_command(command, arguments, filetype, context, document_contents)?
+            {
+                return Ok(Some(edit));
+            }
+        }
+        Ok(None)
+    }
+
+    /// The summary of the first handler running `command` for the
+    /// workspace, whatever the filetypes of the handlers.
+    pub fn execute_workspace_command(
+        &self,
+        command: &str,
+        workspace_folders: &[WorkspaceFolder],
+    ) -> Result<Option<String>, HandlerError> {
+        for (index, handler) in self.handlers.iter().enumerate() {
+            let summary = with_output_encoding_sync(self.output_encoding(index), || {
+                handler.execute_workspace_command(command, workspace_folders)
+            })?;
+            if summary.is_some() {
+                return Ok(summary);
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Whether `handler` runs for the document, by filetype or path.
+fn is_active(handler: &HandlerKind, filetype: &str, context: &DocumentContext) -> bool {
+    handler.filetype_supported(filetype)
+        || context
+            .uri
+            .to_file_path()
+            .is_ok_and(|path| handler.path_supported(&path))
+}
+
+/// The filetype detected from the document, if it differs from `filetype`.
+fn detected_filetype(
+    filetype: &str,
+    context: &DocumentContext,
+    document_contents: &str,
+) -> Option<String> {
+    let first_line = document_contents.lines().next().unwrap_or_default();
+    detect_filetype(&context.uri, first_line, document_contents)
+        .filter(|detected| detected != filetype)
+}
+
+/// Like `is_active`, also running handlers for the `detected` filetype.
+fn is_active_detected(
+    handler: &HandlerKind,
+    filetype: &str,
+    detected: Option<&str>,
+    context: &DocumentContext,
+) -> bool {
+    is_active(handler, filetype, context)
+        || detected.is_some_and(|detected| handler.filetype_supported(detected))
+}
+
+/// Adds the handler if it could be created, e.g. its tool is installed.
+fn add_handler<H>(
+    handlers: &mut Vec<(String, HandlerKind)>,
+    name: &str,
+    handler: Result<H, String>,
+    kind: fn(H) -> HandlerKind,
+) {
+    match handler {
+        Ok(handler) => handlers.push((name.to_string(), kind(handler))),
+        Err(err) => log::info!("{name} handler disabled: {err}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::mock::Mock;
+    use super::{
+        filetype_aliases, severity_rules, AnyHandler, DocumentContext, HandlerKind, Just,
+        JustConfig, BUILTIN_HANDLERS,
+    };
+    use crate::config::{Config, SeverityOverride};
+    use serde_json::json;
+    use std::collections::HashMap;
+    use std::path::Path;
+    use std::sync::atomic::Ordering;
+    use tower_lsp::lsp_types::{
+        Diagnostic, DiagnosticSeverity, FileOperationRegistrationOptions, HoverProviderCapability,
+        NumberOrString, OneOf, Position, Range, ServerCapabilities, TextEdit, Url,
+        WorkspaceFileOperationsServerCapabilities, WorkspaceFolder,
+        WorkspaceFoldersServerCapabilities, WorkspaceServerCapabilities,
+    };
+
+    #[tokio::test]
+    async fn test_disabled() {
+        let config = Config::from_value(Some(json!({ "disabled": ["Just"] }))).unwrap();
+        let mut handler = AnyHandler::new(&config);
+        assert!(!handler.enabled().iter().any(|name| name == "Just"));
+        assert!(handler.enabled().iter().any(|name| name == "Ndjson"));
+
+        let uri = Url::from_file_path("/project/justfile").unwrap();
+        let context = DocumentContext::new(uri, vec![]);
+        let diagnostics = handler
+            .update_diagnostics("just", &context, "a:::b\n")
+            .await
+            .ok()
+            .unwrap();
+        assert!(diagnostics.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_just_without_program() {
+        let just = Just::with_program(JustConfig::default(), "any-ls-missing-tool");
+        let mut handler = AnyHandler::from_handlers(vec![HandlerKind::Just(just)]);
+
+        let uri = Url::from_file_path("/project/justfile").unwrap();
+        let context = DocumentContext::new(uri, vec![]);
+        let diagnostics = handler
+            .update_diagnostics("just", &context, "a: b\nb: a\n\nbuild: missing\n")
+            .await
+            .ok()
+            .unwrap();
+        let messages: Vec<&str> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+        assert!(messages.iter().any(|m| m.starts_with("Dependency cycle: ")));
+        assert!(messages.contains(&"Recipe `missing` is not defined"));
+    }
+
+    #[test]
+    fn test_builtin_handlers() {
+        let handler = AnyHandler::new(&Config {
+            codespell: true,
+            ..Default::default()
+        });
+        for name in handler.enabled() {
+            assert!(BUILTIN_HANDLERS.contains(&name.as_str()), "{name}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_max_diagnostics() {
+        let mock = Mock {
+            filetypes: vec!["text"],
+            diagnostics: (0..5)
+                .map(|line| {
+                    let position = Position::new(line, 0);
+                    Diagnostic::new_simple(Range::new(position, position), "error".to_string())
+                })
+                .collect(),
+            ..Default::default()
+        };
+        let mut handler = AnyHandler {
+            max_diagnostics: Some(3),
+            ..AnyHandler::from_handlers(vec![HandlerKind::Mock(Box::new(mock))])
+        };
+
+        let uri = Url::from_file_path("/project/notes.txt").unwrap();
+        let context = DocumentContext::new(uri, vec![]);
+        let diagnostics = handler
+            .update_diagnostics("text", &context, "a\nb\nc\nd\ne\n")
+            .await
+            .ok()
+            .unwrap();
+        assert_eq!(diagnostics.len(), 4);
+        assert_eq!(diagnostics[2].range.start, Position::new(2, 0));
+        assert_eq!(
+            diagnostics[3].severity,
+            Some(DiagnosticSeverity::INFORMATION)
+        );
+        assert_eq!(diagnostics[3].message, "2 more diagnostics omitted");
+    }
+
+    #[tokio::test]
+    async fn test_timeout_keeps_other_diagnostics() {
+        let slow = Mock {
+            filetypes: vec!["text"],
+            priority: 10,
+            timed_out: Some("slowlint"),
+            ..Default::default()
+        };
+        let broken = Mock {
+            filetypes: vec!["text"],
+            priority: 5,
+            failed: Some("brokenlint: no config found\nsee --help"),
+            ..Default::default()
+        };
+        let fast = Mock {
+            filetypes: vec!["text"],
+            diagnostics: vec![Diagnostic::new_simple(
+                Range::new(Position::new(1, 0), Position::new(1, 4)),
+                "error".to_string(),
+            )],
+            ..Default::default()
+        };
+        let mut handler = AnyHandler::from_handlers(vec![
+            HandlerKind::Mock(Box::new(slow)),
+            HandlerKind::Mock(Box::new(broken)),
+            HandlerKind::Mock(Box::new(fast)),
+        ]);
+
+        let uri = Url::from_file_path("/project/notes.txt").unwrap();
+        let context = DocumentContext::new(uri, vec![]);
+        let diagnostics = handler
+            .update_diagnostics("text", &context, "first\nsecond\n")
+            .await
+            .ok()
+            .unwrap();
+        assert_eq!(diagnostics.len(), 3);
+        assert_eq!(diagnostics[0].range.start, Position::new(0, 0));
+        assert_eq!(
+            diagnostics[0].severity,
+            Some(DiagnosticSeverity::INFORMATION)
+        );
+        assert_eq!(diagnostics[0].message, "'slowlint' timed out");
+        assert_eq!(diagnostics[1].range.start, Position::new(0, 0));
+        assert_eq!(
+            diagnostics[1].severity,
+            Some(DiagnosticSeverity::INFORMATION)
+        );
+        assert_eq!(diagnostics[1].message, "brokenlint: no config found");
+        assert_eq!(diagnostics[2].message, "error");
+    }
+
+    #[tokio::test]
+    async fn test_dedup_by_priority() {
+        let error = |source: &str, message: &str| Diagnostic {
+            source: Some(source.to_string()),
+            ..Diagnostic::new_simple(
+                Range::new(Position::new(0, 0), Position::new(0, 4)),
+                message.to_string(),
+            )
+        };
+        let fallback = Mock {
+            filetypes: vec!["sh"],
+            priority: -10,
+            diagnostics: vec![
+                error("bash", "syntax error"),
+                error("bash", "unexpected end of file"),
+            ],
+            ..Default::default()
+        };
+        let linter = Mock {
+            filetypes: vec!["sh"],
+            diagnostics: vec![error("shellcheck", "syntax error")],
+            ..Default::default()
+        };
+        let mut handler = AnyHandler::from_handlers(vec![
+            HandlerKind::Mock(Box::new(fallback)),
+            HandlerKind::Mock(Box::new(linter)),
+        ]);
+
+        let uri = Url::from_file_path("/project/build.sh").unwrap();
+        let context = DocumentContext::new(uri, vec![]);
+        let diagnostics = handler
+            .update_diagnostics("sh", &context, "echo\n")
+            .await
+            .ok()
+            .unwrap();
+        let sources: Vec<(&str, &str)> = diagnostics
+            .iter()
+            .map(|d| (d.source.as_deref().unwrap(), d.message.as_str()))
+            .collect();
+        assert_eq!(
+            sources,
+            vec![
+                ("bash", "unexpected end of file"),
+                ("shellcheck", "syntax error")
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_clamp_out_of_range_line() {
+        let at = |start: Position, end: Position| {
+            Diagnostic::new_simple(Range::new(start, end), "error".to_string())
+        };
+        let mock = Mock {
+            filetypes: vec!["text"],
+            diagnostics: vec![
+                // At the end of the file, on the line after the last one
+                at(Position::new(3, 0), Position::new(3, 0)),
+                at(Position::new(1, 2), Position::new(7, 1)),
+            ],
+            ..Default::default()
+        };
+        let mut handler = AnyHandler::from_handlers(vec![HandlerKind::Mock(Box::new(mock))]);
+
+        let uri = Url::from_file_path("/project/notes.txt").unwrap();
+        let context = DocumentContext::new(uri, vec![]);
+        let diagnostics = handler
+            .update_diagnostics("text", &context, "one\r\ntwo")
+            .await
+            .ok()
+            .unwrap();
+        let end = Position::new(1, 3);
+        assert_eq!(diagnostics[0].range, Range::new(Position::new(1, 2), end));
+        assert_eq!(diagnostics[1].range, Range::new(end, end));
+
+        // Empty documents have a single empty line
+        let diagnostics = handler
+            .update_diagnostics("text", &context, "")
+            .await
+            .ok()
+            .unwrap();
+        assert_eq!(diagnostics[0].range, Range::default());
+        assert_eq!(diagnostics[1].range, Range::default());
+    }
+
+    #[tokio::test]
+    async fn test_clamp_out_of_range_column() {
+        let mock = Mock {
+            filetypes: vec!["text"],
+            diagnostics: vec![Diagnostic::new_simple(
+                Range::new(Position::new(0, 4), Position::new(0, 40)),
+                "error".to_string(),
+            )],
+            ..Default::default()
+        };
+        let mut handler = AnyHandler::from_handlers(vec![HandlerKind::Mock(Box::new(mock))]);
+
+        let uri = Url::from_file_path("/project/notes.txt").unwrap();
+        let context = DocumentContext::new(uri, vec![]);
+        // Characters are UTF-16 code units
+        let diagnostics = handler
+            .update_diagnostics("text", &context, "naïve 🦀\nnext\n")
+            .await
+            .ok()
+            .unwrap();
+        assert_eq!(
+            diagnostics[0].range,
+            Range::new(Position::new(0, 4), Position::new(0, 8))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_diagnostics_order() {
+        let diagnostic = |line, severity, source: &str, message: &str| {
+            Diagnostic::new(
+                Range::new(Position::new(line, 0), Position::new(line, 1)),
+                Some(severity),
+                None,
+                Some(source.to_string()),
+                message.to_string(),
+                None,
+                None,
+            )
+        };
+        let linter = || Mock {
+            filetypes: vec!["text"],
+            diagnostics: vec![
+                diagnostic(4, DiagnosticSeverity::WARNING, "linter", "unused"),
+                diagnostic(1, DiagnosticSeverity::WARNING, "linter", "shadowed"),
+            ],
+            ..Default::default()
+        };
+        let checker = || Mock {
+            filetypes: vec!["text"],
+            diagnostics: vec![
+                diagnostic(1, DiagnosticSeverity::ERROR, "checker", "mismatched"),
+                diagnostic(4, DiagnosticSeverity::WARNING, "checker", "unused"),
+                diagnostic(1, DiagnosticSeverity::WARNING, "checker", "shadowed"),
+            ],
+            ..Default::default()
+        };
+
+        let uri = Url::from_file_path("/project/notes.txt").unwrap();
+        let context = DocumentContext::new(uri, vec![]);
+        let mut orders = Vec::new();
+        for handlers in [
+            vec![
+                HandlerKind::Mock(Box::new(linter())),
+                HandlerKind::Mock(Box::new(checker())),
+            ],
+            vec![
+                HandlerKind::Mock(Box::new(checker())),
+                HandlerKind::Mock(Box::new(linter())),
+            ],
+        ] {
+            let diagnostics = AnyHandler::from_handlers(handlers)
+                .update_diagnostics("text", &context, "a\nb\nc\nd\ne\n")
+                .await
+                .ok()
+                .unwrap();
+            let order: Vec<(u32, String)> = diagnostics
+                .into_iter()
+                .map(|d| {
+                    (
+                        d.range.start.line,
+                        format!("{}: {}", d.source.unwrap(), d.message),
+                    )
+                })
+                .collect();
+            orders.push(order);
+        }
+        assert_eq!(
+            orders[0],
+            vec![
+                (1, "checker: mismatched".to_string()),
+                (1, "checker: shadowed".to_string()),
+                (1, "linter: shadowed".to_string()),
+                (4, "checker: unused".to_string()),
+                (4, "linter: unused".to_string()),
+            ]
+        );
+        assert_eq!(orders[0], orders[1]);
+    }
+
+    #[tokio::test]
+    async fn test_mdx_code_block_diagnostics() {
+        // An error on the second line of the code block
+        let javascript = Mock {
+            filetypes: vec!["javascript"],
+            diagnostics: vec![Diagnostic::new_simple(
+                Range::new(Position::new(1, 10), Position::new(1, 11)),
+                "Unexpected token".to_string(),
+            )],
+            ..Default::default()
+        };
+        let mut handler = AnyHandler::from_handlers(vec![HandlerKind::Mock(Box::new(javascript))]);
+
+        let uri = Url::from_file_path("/project/docs/intro.mdx").unwrap();
+        let context = DocumentContext::new(uri, vec![]);
+        let contents = "import { Chart } from './chart'\n\n# Intro\n\n```js\nconst a = 1\nconst b = ;\n```\n\n<Chart />\n";
+        assert!(handler.document_supported("mdx", &context, contents));
+        let diagnostics = handler
+            .update_diagnostics("mdx", &context, contents)
+            .await
+            .ok()
+            .unwrap();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].range,
+            Range::new(Position::new(6, 10), Position::new(6, 11))
+        );
+        assert!(!handler.document_supported("markdown", &context, contents));
+    }
+
+    #[tokio::test]
+    async fn test_mdx_any_filetype_diagnostics() {
+        // A typo in the code block, seen by a spell checker of any filetype
+        let spelling = Mock {
+            any_filetype: true,
+            diagnostics: vec![Diagnostic::new_simple(
+                Range::new(Position::new(1, 6), Position::new(1, 9)),
+                "teh ==> the".to_string(),
+            )],
+            ..Default::default()
+        };
+        let runs = spelling.diagnostics_runs.clone();
+        let mut handler = AnyHandler::from_handlers(vec![HandlerKind::Mock(Box::new(spelling))]);
+
+        let uri = Url::from_file_path("/project/docs/intro.mdx").unwrap();
+        let context = DocumentContext::new(uri, vec![]);
+        let contents = "# Intro\n\n```js\nconst teh = 1\n```\n";
+        let diagnostics = handler
+            .update_diagnostics("mdx", &context, contents)
+            .await
+            .ok()
+            .unwrap();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_filetype_aliases() {
+        let sh = Mock {
+            filetypes: vec!["sh"],
+            diagnostics: vec![Diagnostic::new_simple(
+                Range::default(),
+                "Unterminated string".to_string(),
+            )],
+            ..Default::default()
+        };
+        let mut handler = AnyHandler::from_handlers(vec![HandlerKind::Mock(Box::new(sh))]);
+
+        let uri = Url::from_file_path("/project/build").unwrap();
+        let context = DocumentContext::new(uri, vec![]);
+        assert!(handler.document_supported("shellscript", &context, "echo \"hi\n"));
+        let diagnostics = handler
+            .update_diagnostics("shellscript", &context, "echo \"hi\n")
+            .await
+            .ok()
+            .unwrap();
+        assert_eq!(diagnostics.len(), 1);
+
+        assert!(!handler.document_supported("ksh", &context, ""));
+        handler.filetype_aliases = filetype_aliases(&HashMap::from([(
+            "sh".to_string(),
+            vec!["ksh".to_string()],
+        )]));
+        assert!(handler.document_supported("ksh", &context, ""));
+        assert!(handler.document_supported("shellscript", &context, ""));
+    }
+
+    #[tokio::test]
+    async fn test_default_formatter() {
+        let formatter = |formatted: &str| {
+            HandlerKind::Mock(Box::new(Mock {
+                filetypes: vec!["javascript", "typescript"],
+                formatted: Some(formatted.to_string()),
+                ..Default::default()
+            }))
+        };
+        let mut handler = AnyHandler {
+            names: vec!["Prettier".to_string(), "Biome".to_string()],
+            default_formatter: HashMap::from([("javascript".to_string(), "Biome".to_string())]),
+            ..AnyHandler::from_handlers(vec![formatter("prettier\n"), formatter("biome\n")])
+        };
+
+        let uri = Url::from_file_path("/project/src/index.js").unwrap();
+        let context = DocumentContext::new(uri, vec![]);
+        let formatted = |edits: Option<Vec<TextEdit>>| edits.unwrap()[0].new_text.clone();
+        let edits = handler
+            .format("javascript", &context, "input\n")
+            .await
+            .ok()
+            .unwrap();
+        assert_eq!(formatted(edits), "biome\n");
+        // Other filetypes are formatted by the first handler
+        let edits = handler
+            .format("typescript", &context, "input\n")
+            .await
+            .ok()
+            .unwrap();
+        assert_eq!(formatted(edits), "prettier\n");
+    }
+
+    fn lint(source: &str, code: &str) -> Diagnostic {
+        Diagnostic {
+            severity: Some(DiagnosticSeverity::ERROR),
+            code: Some(NumberOrString::String(code.to_string())),
+            source: Some(source.to_string()),
+            ..Diagnostic::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_severity_overrides() {
+        let mock = Mock {
+            filetypes: vec!["javascript"],
+            diagnostics: vec![
+                lint("eslint", "no-console"),
+                lint("eslint", "no-undef"),
+                lint("cspell", "unknown-word"),
+                lint("tsc", "2304"),
+            ],
+            ..Default::default()
+        };
+        let overrides = HashMap::from([
+            ("eslint/no-console".to_string(), SeverityOverride::Hint),
+            ("eslint/*".to_string(), SeverityOverride::Warning),
+            ("cspell/*".to_string(), SeverityOverride::Off),
+        ]);
+        let mut handler = AnyHandler {
+            severity_overrides: severity_rules(&overrides),
+            ..AnyHandler::from_handlers(vec![HandlerKind::Mock(Box::new(mock))])
+        };
+
+        let uri = Url::from_file_path("/project/index.js").unwrap();
+        let context = DocumentContext::new(uri, vec![]);
+        let diagnostics = handler
+            .update_diagnostics("javascript", &context, "")
+            .await
+            .ok()
+            .unwrap();
+        let severities: Vec<(&str, Option<DiagnosticSeverity>)> = diagnostics
+            .iter()
+            .map(|diagnostic| (diagnostic.source.as_deref().unwrap(), diagnostic.severity))
+            .collect();
+        // The exact rule wins over the wildcard, cspell is suppressed
+        assert_eq!(
+            severities,
+            vec![
+                ("tsc", Some(DiagnosticSeverity::ERROR)),
+                ("eslint", Some(DiagnosticSeverity::WARNING)),
+                ("eslint", Some(DiagnosticSeverity::HINT)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_capabilities_workspace_merge() {
+        let folders = Mock {
+            priority: 10,
+            capabilities: ServerCapabilities {
+                workspace: Some(WorkspaceServerCapabilities {
+                    workspace_folders: Some(WorkspaceFoldersServerCapabilities {
+                        supported: Some(true),
+                        change_notifications: None,
+                    }),
+                    file_operations: Some(WorkspaceFileOperationsServerCapabilities {
+                        did_create: Some(FileOperationRegistrationOptions::default()),
+                        ..Default::default()
+                    }),
+                }),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let renames = Mock {
+            capabilities: ServerCapabilities {
+                workspace: Some(WorkspaceServerCapabilities {
+                    workspace_folders: None,
+                    file_operations: Some(WorkspaceFileOperationsServerCapabilities {
+                        did_rename: Some(FileOperationRegistrationOptions::default()),
+                        ..Default::default()
+                    }),
+                }),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let handler = AnyHandler::from_handlers(vec![
+            HandlerKind::Mock(Box::new(renames)),
+            HandlerKind::Mock(Box::new(folders)),
+        ]);
+        let workspace = handler.get_capabilities().workspace.unwrap();
+        assert!(workspace.workspace_folders.is_some());
+        let file_operations = workspace.file_operations.unwrap();
+        assert!(file_operations.did_create.is_some());
+        assert!(file_operations.did_rename.is_some());
+        assert!(file_operations.did_delete.is_none());
+    }
+
+    #[test]
+    fn test_capabilities_priority() {
+        let low = Mock {
+            priority: -10,
+            capabilities: ServerCapabilities {
+                hover_provider: Some(HoverProviderCapability::Simple(false)),
+                document_formatting_provider: Some(OneOf::Left(true)),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let high = Mock {
+            priority: 10,
+            capabilities: ServerCapabilities {
+                hover_provider: Some(HoverProviderCapability::Simple(true)),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let handler = AnyHandler::from_handlers(vec![
+            HandlerKind::Mock(Box::new(low)),
+            HandlerKind::Mock(Box::new(high)),
+        ]);
+        let capabilities = handler.get_capabilities();
+        assert_eq!(
+            capabilities.hover_provider,
+            Some(HoverProviderCapability::Simple(true))
+        );
+        // Fields only set by the lower priority handler are still merged
+        assert_eq!(
+            capabilities.document_formatting_provider,
+            Some(OneOf::Left(true))
+        );
+    }
+
+    #[test]
+    fn test_document_directory() {
+        let uri = Url::from_file_path("/project/sub/justfile").unwrap();
+        let context = DocumentContext::new(uri, vec![]);
+        assert_eq!(
+            context.directory().as_deref(),
+            Some(Path::new("/project/sub"))
+        );
+
+        let uri = Url::parse("untitled:Untitled-1").unwrap();
+        assert_eq!(DocumentContext::new(uri.clone(), vec![]).directory(), None);
+
+        let folder = WorkspaceFolder {
+            uri: Url::from_file_path("/project").unwrap(),
+            name: "project".to_string(),
+        };
+        let context = DocumentContext::new(uri, vec![folder]);
+        assert_eq!(context.directory().as_deref(), Some(Path::new("/project")));
+    }
+
+    #[test]
+    fn test_display_path() {
+        let folder = |path: &str| WorkspaceFolder {
+            uri: Url::from_file_path(path).unwrap(),
+            name: path.to_string(),
+        };
+        let uri = Url::from_file_path("/project/web/compose.yaml").unwrap();
+        let context = DocumentContext::new(uri, vec![folder("/project"), folder("/project/web")]);
+        assert_eq!(context.display_path(Path::new("/project/web/.env")), ".env");
+        assert_eq!(
+            context.display_path(Path::new("/project/api/.env")),
+            "api/.env"
+        );
+        assert_eq!(
+            context.display_path(Path::new("/other/.env")),
+            "/other/.env"
+        );
     }
 }
@@ -0,0 +1,90 @@
+use serde::de::IgnoredAny;
+use tower_lsp::lsp_types::{self, Diagnostic, DiagnosticSeverity};
+
+use super::text::offset_to_position;
+use super::{Handler, HandlerError};
+
+/// Validates JSON Lines / NDJSON, where every non-blank line is a JSON value.
+#[derive(Debug)]
+pub struct Ndjson {}
+
+impl Ndjson {
+    pub fn new() -> Result<Self, String> {
+        Ok(Self {})
+    }
+
+    pub fn parse(contents: &str) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        let mut line_start = 0;
+        for line in contents.split('\n') {
+            let json = line.trim_end_matches('\r');
+            if !json.trim().is_empty() {
+                if let Err(err) = serde_json::from_str::<IgnoredAny>(json) {
+                    // The column is 1-based and counted in bytes, 0 at the
+                    // end of the input
+                    let column = err.column().saturating_sub(1).min(json.len());
+                    let position = offset_to_position(contents, line_start + column);
+                    diagnostics.push(Diagnostic::new(
+                        lsp_types::Range {
+                            start: position,
+                            end: position,
+                        },
+                        Some(DiagnosticSeverity::ERROR),
+                        None,
+                        Some("ndjson".to_string()),
+                        err.to_string(),
+                        None,
+                        None,
+                    ));
+                }
+            }
+            line_start += line.len() + 1;
+        }
+        diagnostics
+    }
+}
+
+impl Handler for Ndjson {
+    fn filetype_supported(&self, filetype: &str) -> bool {
+        matches!(filetype, "ndjson" | "jsonl")
+    }
+
+    async fn update_diagnostics(
+        &mut self,
+        contents: &str,
+    ) -> Result<Vec<Diagnostic>, HandlerError> {
+        Ok(Self::parse(contents))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Ndjson;
+    use tower_lsp::lsp_types::Position;
+
+    #[test]
+    fn test_malformed_line() {
+        let contents = r#"{"id": 1, "name": "a"}
+
+{"id": 2, "name": "b",}
+{"id": 3, "name": "c"}
+"#;
+        let diagnostics = Ndjson::parse(contents);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].range.start, Position::new(2, 22));
+    }
+
+    #[test]
+    fn test_unterminated_non_ascii_string() {
+        for contents in ["{\"name\": \"José", "\"é", "{\"a\": \"é\n{\"b\": 1}\n"] {
+            let diagnostics = Ndjson::parse(contents);
+            assert_eq!(diagnostics.len(), 1);
+            assert_eq!(diagnostics[0].range.start.line, 0);
+        }
+    }
+
+    #[test]
+    fn test_valid() {
+        assert!(Ndjson::parse("{\"a\": 1}\r\n[1, 2]\r\n\r\n\"text\"\r\n").is_empty());
+    }
+}
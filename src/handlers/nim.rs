@@ -0,0 +1,159 @@
+use lazy_regex::regex;
+use std::path::Path;
+use std::process::Command;
+use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity, OneOf, ServerCapabilities};
+
+use super::process::{
+    formatted, output_with_timeout, probe, run_and_parse, InputMode, RegexLineParser, Stream,
+    TempFileStrategy, TempFiles,
+};
+use super::{Handler, HandlerError};
+
+/// Errors, warnings and hints of `nim check`, and formatting with
+/// `nimpretty`.
+#[derive(Debug)]
+pub struct Nim {
+    temp_files: TempFiles,
+}
+
+fn parse_severity(severity: &str) -> Option<DiagnosticSeverity> {
+    match severity {
+        "Error" => Some(DiagnosticSeverity::ERROR),
+        "Warning" => Some(DiagnosticSeverity::WARNING),
+        _ => Some(DiagnosticSeverity::HINT),
+    }
+}
+
+impl Nim {
+    pub fn new() -> Result<Self, String> {
+        probe("nim", &["--version"])?;
+        Ok(Self {
+            // Nim derives the module name from the file name, which must be
+            // an identifier
+            temp_files: TempFiles::with_suffix(".nim").with_prefix("any_ls_"),
+        })
+    }
+
+    /// Parses `path(line, column) Severity: message [Name]` messages.
+    fn parser() -> RegexLineParser {
+        RegexLineParser {
+            regex: regex!(
+                r#"(?m)^(?P<path>[^(\n]+)\((?P<line>\d+), (?P<column>\d+)\) (?P<severity>Error|Warning|Hint): (?P<message>.*?)(?: \[(?P<code>\w+)\])?$"#
+            ),
+            source: "nim",
+            stream: Stream::Stdout,
+            first_line: 1,
+            first_column: 1,
+            severity: parse_severity,
+        }
+    }
+}
+
+impl Handler for Nim {
+    fn filetype_supported(&self, filetype: &str) -> bool {
+        filetype == "nim"
+    }
+
+    fn set_temp_file_strategy(&mut self, strategy: TempFileStrategy) {
+        self.temp_files.set_strategy(strategy);
+    }
+
+    fn get_capabilities(&self) -> ServerCapabilities {
+        ServerCapabilities {
+            document_formatting_provider: Some(OneOf::Left(true)),
+            ..Default::default()
+        }
+    }
+
+    async fn update_diagnostics(
+        &mut self,
+        contents: &str,
+    ) -> Result<Vec<Diagnostic>, HandlerError> {
+        let temp_file = self.temp_files.write(contents)?;
+
+        let messages = run_and_parse(
+            Command::new("nim")
+                .arg("check")
+                .arg("--listFullPaths")
+                .arg("--stdout")
+                .arg("--colors:off")
+                .arg(temp_file.path()),
+            InputMode::File,
+            &Self::parser(),
+        )?;
+        // Imported modules, including the standard library, report their
+        // own messages
+        Ok(messages
+            .into_iter()
+            .filter(|(path, _)| {
+                path.as_deref().map(|path| Path::new(path).file_name())
+                    == Some(temp_file.path().file_name())
+            })
+            .map(|(_, diagnostic)| diagnostic)
+            .collect())
+    }
+
+    async fn format(
+        &mut self,
+        _filetype: &str,
+        contents: &str,
+    ) -> Result<Option<String>, HandlerError> {
+        let temp_file = self.temp_files.write(contents)?;
+
+        // Formats the file in place
+        let mut command = Command::new("nimpretty");
+        let out = output_with_timeout(command.arg(temp_file.path()))?;
+        formatted(&command, &out)?;
+        std::fs::read_to_string(temp_file.path())
+            .map(Some)
+            .map_err(|e| HandlerError::Log(format!("{e}")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Nim;
+    use crate::handlers::Handler;
+    use tower_lsp::lsp_types::{DiagnosticSeverity, NumberOrString, Position};
+
+    #[test]
+    fn test_parse_type_error() {
+        let stdout = "Hint: used config file '/etc/nim/nim.cfg' [Conf]
+/tmp/any_ls_x1.nim(2, 5) Hint: 'unused' is declared but not used [XDeclaredButNotUsed]
+/tmp/any_ls_x1.nim(3, 15) Error: type mismatch: got 'string' for '\"a\"' but expected 'int'
+";
+        let messages = Nim::parser().parse_text(stdout);
+        assert_eq!(messages.len(), 2);
+
+        let (path, hint) = &messages[0];
+        assert_eq!(path.as_deref(), Some("/tmp/any_ls_x1.nim"));
+        assert_eq!(hint.severity, Some(DiagnosticSeverity::HINT));
+        assert_eq!(hint.message, "'unused' is declared but not used");
+        assert_eq!(
+            hint.code,
+            Some(NumberOrString::String("XDeclaredButNotUsed".to_string()))
+        );
+
+        let (_, error) = &messages[1];
+        assert_eq!(error.range.start, Position::new(2, 14));
+        assert_eq!(error.severity, Some(DiagnosticSeverity::ERROR));
+        assert_eq!(
+            error.message,
+            "type mismatch: got 'string' for '\"a\"' but expected 'int'"
+        );
+        assert_eq!(error.code, None);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs nimpretty"]
+    async fn test_format() {
+        let mut nim = Nim::new().unwrap();
+
+        let contents = "proc add(a:int,b:int):int=\n  a+b\n";
+        let formatted = nim.format("nim", contents).await.ok().unwrap();
+        assert_eq!(
+            formatted.as_deref(),
+            Some("proc add(a: int, b: int): int =\n  a+b\n")
+        );
+    }
+}
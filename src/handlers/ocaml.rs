@@ -0,0 +1,189 @@
+use lazy_regex::regex;
+use std::path::PathBuf;
+use std::process::Command;
+use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity, OneOf, ServerCapabilities, Url};
+
+use super::process::{
+    format_stdin, probe, run_and_parse, traverse_parents, InputMode, RegexLineParser, Stream,
+};
+use super::{DocumentContext, Handler, HandlerError};
+
+/// OCaml formatting with ocamlformat, whose parse errors are reported as
+/// syntax errors.
+#[derive(Debug)]
+pub struct OCaml {}
+
+impl OCaml {
+    pub fn new() -> Result<Self, String> {
+        probe("ocamlformat", &["--version"])?;
+        Ok(Self {})
+    }
+
+    /// The `.ocamlformat` closest to the document.
+    pub fn find_config(context: &DocumentContext) -> Option<PathBuf> {
+        traverse_parents(
+            &context.directory()?,
+            &[".ocamlformat"],
+            &context.root_markers,
+            |_| true,
+        )
+    }
+
+    /// Formats an implementation from stdin with the project's settings.
+    /// Outside of a project ocamlformat refuses to format unless told to.
+    fn command(context: &DocumentContext) -> Command {
+        let mut command = Command::new("ocamlformat");
+        command.arg("--impl");
+        match Self::find_config(context) {
+            Some(config) => {
+                if let Some(directory) = config.parent() {
+                    command.current_dir(directory);
+                }
+            }
+            None => {
+                command.arg("--enable-outside-detected-project");
+            }
+        }
+        // Settings are looked up from the document's path
+        if let Ok(path) = context.uri.to_file_path() {
+            command.arg("--name").arg(path);
+        }
+        command.arg("-");
+        command
+    }
+
+    /// Parses `File "...", line 1, characters 8-9:` errors. Characters
+    /// count from 0.
+    fn parser() -> RegexLineParser {
+        RegexLineParser {
+            regex: regex!(
+                r#"File "(?P<path>[^"]*)", line (?P<line>\d+), characters (?P<column>\d+)-\d+:\n(?:.*\n)*?Error: (?P<message>.*)"#
+            ),
+            source: "ocamlformat",
+            stream: Stream::Stderr,
+            first_line: 1,
+            first_column: 0,
+            severity: |_| Some(DiagnosticSeverity::ERROR),
+        }
+    }
+}
+
+impl Handler for OCaml {
+    fn filetype_supported(&self, filetype: &str) -> bool {
+        filetype == "ocaml"
+    }
+
+    fn get_capabilities(&self) -> ServerCapabilities {
+        ServerCapabilities {
+            document_formatting_provider: Some(OneOf::Left(true)),
+            ..Default::default()
+        }
+    }
+
+    async fn update_diagnostics(
+        &mut self,
+        contents: &str,
+    ) -> Result<Vec<Diagnostic>, HandlerError> {
+        let uri = Url::parse("untitled:untitled.ml").expect("Valid URL");
+        let context = DocumentContext::new(uri, Vec::new());
+        self.update_diagnostics_with_context(&context, contents)
+            .await
+    }
+
+    async fn update_diagnostics_with_context(
+        &mut self,
+        context: &DocumentContext,
+        contents: &str,
+    ) -> Result<Vec<Diagnostic>, HandlerError> {
+        let errors = run_and_parse(
+            &mut Self::command(context),
+            InputMode::Stdin(contents),
+            &Self::parser(),
+        )?;
+        Ok(errors
+            .into_iter()
+            .map(|(_, diagnostic)| diagnostic)
+            .collect())
+    }
+
+    async fn format_with_context(
+        &mut self,
+        context: &DocumentContext,
+        _filetype: &str,
+        contents: &str,
+    ) -> Result<Option<String>, HandlerError> {
+        // Invalid syntax fails instead of formatting what it could
+        format_stdin(&mut Self::command(context), contents)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OCaml;
+    use crate::handlers::{DocumentContext, Handler, HandlerError};
+    use std::ffi::OsStr;
+    use tower_lsp::lsp_types::{Position, Url};
+
+    fn context() -> DocumentContext {
+        DocumentContext::new(Url::parse("untitled:Untitled-1").unwrap(), vec![])
+    }
+
+    #[test]
+    fn test_find_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let lib = dir.path().join("project/lib");
+        std::fs::create_dir_all(&lib).unwrap();
+        std::fs::write(
+            dir.path().join("project/.ocamlformat"),
+            "profile = default\n",
+        )
+        .unwrap();
+
+        let uri = Url::from_file_path(lib.join("main.ml")).unwrap();
+        let command = OCaml::command(&DocumentContext::new(uri, vec![]));
+        let args: Vec<&OsStr> = command.get_args().collect();
+        assert!(!args.contains(&OsStr::new("--enable-outside-detected-project")));
+        assert_eq!(
+            command.get_current_dir(),
+            Some(dir.path().join("project").as_path())
+        );
+
+        let args: Vec<String> = OCaml::command(&context())
+            .get_args()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(
+            args,
+            vec!["--impl", "--enable-outside-detected-project", "-"]
+        );
+    }
+
+    #[test]
+    fn test_parse_syntax_error() {
+        let stderr = "ocamlformat: ignoring \"-\" (syntax error)\nFile \"-\", line 2, characters 8-9:\n2 | let x = )\n            ^\nError: Syntax error\n";
+        let errors = OCaml::parser().parse_text(stderr);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].1.range.start, Position::new(1, 8));
+        assert_eq!(errors[0].1.message, "Syntax error");
+    }
+
+    #[tokio::test]
+    #[ignore = "needs ocamlformat"]
+    async fn test_format() {
+        let mut ocaml = OCaml::new().unwrap();
+
+        let contents = "let add x y=x+y\n";
+        let formatted = ocaml
+            .format_with_context(&context(), "ocaml", contents)
+            .await
+            .ok()
+            .unwrap();
+        assert_eq!(formatted.as_deref(), Some("let add x y = x + y\n"));
+
+        // The buffer is left alone
+        let formatted = ocaml
+            .format_with_context(&context(), "ocaml", "let x = )\n")
+            .await;
+        assert!(matches!(formatted, Err(HandlerError::ToolFailed { .. })));
+    }
+}
@@ -0,0 +1,873 @@
+use encoding_rs::{Encoding, UTF_8};
+use lazy_regex::{regex_replace_all, Regex};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::future::Future;
+use std::io::{self, Read, Write};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Output, Stdio};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use tempfile::NamedTempFile;
+use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity, NumberOrString, Position, Range};
+
+use super::HandlerError;
+
+/// Remove ANSI escape sequences (colors, cursor movement) from tool output.
+pub fn strip_ansi(text: &str) -> String {
+    regex_replace_all!(r#"\x1b\[[0-9;?]*[ -/]*[@-~]"#, text, "").into_owned()
+}
+
+/// The error of `command` failing to start, telling a missing program
+/// apart.
+fn spawn_error(command: &Command, error: io::Error) -> HandlerError {
+    if error.kind() == io::ErrorKind::NotFound {
+        HandlerError::ToolNotFound(command.get_program().to_string_lossy().into_owned())
+    } else {
+        HandlerError::Log(format!("{error}"))
+    }
+}
+
+/// Run `command` with `input` written to its stdin and collect its output.
+pub fn run_with_stdin(command: &mut Command, input: &str) -> Result<Output, HandlerError> {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| spawn_error(command, e))?;
+
+    // Written while the output is read and the timeout runs, a tool may
+    // only read more once its output is read. Stdin is taken so it is
+    // closed once written, otherwise the tool never sees EOF.
+    let writer = child.stdin.take().map(|mut stdin| {
+        let input = input.to_string();
+        thread::spawn(move || stdin.write_all(input.as_bytes()))
+    });
+    let output = wait_with_timeout(command, child, PROCESS_TIMEOUT)?;
+
+    // The pipe is closed once the tool exits, tools may exit without
+    // reading all of it
+    match writer.map(thread::JoinHandle::join) {
+        Some(Ok(Err(e))) if e.kind() != io::ErrorKind::BrokenPipe => {
+            Err(HandlerError::Log(format!("{e}")))
+        }
+        _ => Ok(output),
+    }
+}
+
+/// Time a tool gets to check or format a document before it is killed.
+pub const PROCESS_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Run `command` with its output collected, like `Command::output`, but
+/// killed after `PROCESS_TIMEOUT`.
+pub fn output_with_timeout(command: &mut Command) -> Result<Output, HandlerError> {
+    let child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| spawn_error(command, e))?;
+    wait_with_timeout(command, child, PROCESS_TIMEOUT)
+}
+
+/// Waits for `child` to exit and collects its output, killing it once
+/// `timeout` has passed.
+fn wait_with_timeout(
+    command: &Command,
+    mut child: Child,
+    timeout: Duration,
+) -> Result<Output, HandlerError> {
+    // Read the pipes while waiting, a tool blocks once they are full
+    fn read_all(pipe: Option<impl Read + Send + 'static>) -> thread::JoinHandle<Vec<u8>> {
+        thread::spawn(move || {
+            let mut bytes = Vec::new();
+            if let Some(mut pipe) = pipe {
+                let _ = pipe.read_to_end(&mut bytes);
+            }
+            bytes
+        })
+    }
+    let stdout = read_all(child.stdout.take());
+    let stderr = read_all(child.stderr.take());
+
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if Instant::now() < deadline => thread::sleep(Duration::from_millis(10)),
+            Ok(None) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(HandlerError::Timeout(
+                    command.get_program().to_string_lossy().into_owned(),
+                ));
+            }
+            Err(e) => return Err(HandlerError::Log(format!("{e}"))),
+        }
+    };
+    // Handlers read UTF-8
+    let encoding = OUTPUT_ENCODING
+        .try_with(|encoding| *encoding)
+        .unwrap_or(UTF_8);
+    Ok(Output {
+        status,
+        stdout: decode(stdout.join().unwrap_or_default(), encoding),
+        stderr: decode(stderr.join().unwrap_or_default(), encoding),
+    })
+}
+
+tokio::task_local! {
+    /// Encoding of the output of the tools of the handler running, see
+    /// `Config::output_encoding`.
+    static OUTPUT_ENCODING: &'static Encoding;
+}
+
+/// Runs `future` of a handler with the output of its tools decoded from
+/// `encoding`.
+pub async fn with_output_encoding<F: Future>(encoding: &'static Encoding, future: F) -> F::Output {
+    OUTPUT_ENCODING.scope(encoding, future).await
+}
+
+/// Like `with_output_encoding`, for handlers running tools synchronously.
+pub fn with_output_encoding_sync<R>(encoding: &'static Encoding, f: impl FnOnce() -> R) -> R {
+    OUTPUT_ENCODING.sync_scope(encoding, f)
+}
+
+/// `bytes` of a tool's output in `encoding` as UTF-8, with invalid
+/// sequences replaced.
+fn decode(bytes: Vec<u8>, encoding: &'static Encoding) -> Vec<u8> {
+    if encoding == UTF_8 {
+        return bytes;
+    }
+    encoding
+        .decode_without_bom_handling(&bytes)
+        .0
+        .into_owned()
+        .into_bytes()
+}
+
+/// The document a formatter wrote to stdout, or `ToolFailed` if it exited
+/// with an error, for the document to be left as it is.
+pub fn formatted(command: &Command, output: &Output) -> Result<Option<String>, HandlerError> {
+    if output.status.success() {
+        return Ok(Some(String::from_utf8_lossy(&output.stdout).into_owned()));
+    }
+    let stderr = strip_ansi(&String::from_utf8_lossy(&output.stderr));
+    let message = match stderr.trim() {
+        "" => format!("exited with {}", output.status),
+        stderr => stderr.to_string(),
+    };
+    Err(HandlerError::ToolFailed {
+        tool: command.get_program().to_string_lossy().into_owned(),
+        message,
+    })
+}
+
+/// Formats `contents` with `command`, reading stdin and writing the
+/// document to stdout, see `formatted`.
+pub fn format_stdin(command: &mut Command, contents: &str) -> Result<Option<String>, HandlerError> {
+    let output = run_with_stdin(command, contents)?;
+    formatted(command, &output)
+}
+
+/// Whether `program` can be executed, probed by running it with `args`.
+pub fn probe(program: &str, args: &[&str]) -> Result<(), String> {
+    let out = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| format!("could not run '{program}': {e}"))?;
+
+    if out.status.success() {
+        Ok(())
+    } else {
+        Err(format!("'{program}' exited with {}", out.status))
+    }
+}
+
+/// How a command gets the document it checks.
+pub enum InputMode<'a> {
+    /// The document is written to the command's stdin.
+    Stdin(&'a str),
+    /// The command reads the document from a file in its arguments.
+    File,
+}
+
+/// Which output of a command a parser reads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+/// Turns the output of a tool into diagnostics.
+pub trait DiagnosticParser {
+    /// A diagnostic, possibly with the file it was found in.
+    type Item;
+
+    fn parse(&self, output: &Output) -> Result<Vec<Self::Item>, HandlerError>;
+}
+
+/// Runs `command` on the document and parses its output with `parser`.
+pub fn run_and_parse<P: DiagnosticParser>(
+    command: &mut Command,
+    input: InputMode,
+    parser: &P,
+) -> Result<Vec<P::Item>, HandlerError> {
+    let output = match input {
+        InputMode::Stdin(contents) => run_with_stdin(command, contents)?,
+        InputMode::File => output_with_timeout(command)?,
+    };
+    parser.parse(&output)
+}
+
+/// Parses every match of `regex` in the output into a diagnostic, with the
+/// named groups `message` and optionally `path`, `line`, `column`,
+/// `severity` and `code`.
+pub struct RegexLineParser {
+    pub regex: &'static Regex,
+    /// The `source` of the diagnostics, e.g. the tool name.
+    pub source: &'static str,
+    pub stream: Stream,
+    /// Number of the first line, 1 for most tools.
+    pub first_line: u32,
+    /// Number of the first column of a line.
+    pub first_column: u32,
+    /// The severity of the `severity` group, errors without one.
+    pub severity: fn(&str) -> Option<DiagnosticSeverity>,
+}
+
+impl RegexLineParser {
+    /// The diagnostics in `text`, with the path of the file each one is in
+    /// if the regex has a `path` group.
+    pub fn parse_text(&self, text: &str) -> Vec<(Option<String>, Diagnostic)> {
+        let text = strip_ansi(text);
+        self.regex
+            .captures_iter(&text)
+            .map(|captures| {
+                let number = |name, first: u32| {
+                    let number = captures.name(name).and_then(|n| n.as_str().parse().ok());
+                    number.unwrap_or(first).saturating_sub(first)
+                };
+                let position = Position::new(
+                    number("line", self.first_line),
+                    number("column", self.first_column),
+                );
+                let severity = match captures.name("severity") {
+                    Some(severity) => (self.severity)(severity.as_str()),
+                    None => Some(DiagnosticSeverity::ERROR),
+                };
+                let diagnostic = Diagnostic::new(
+                    Range::new(position, position),
+                    severity,
+                    captures
+                        .name("code")
+                        .map(|code| NumberOrString::String(code.as_str().to_string())),
+                    Some(self.source.to_string()),
+                    captures
+                        .name("message")
+                        .map_or("", |message| message.as_str())
+                        .to_string(),
+                    None,
+                    None,
+                );
+                let path = captures.name("path").map(|path| path.as_str().to_string());
+                (path, diagnostic)
+            })
+            .collect()
+    }
+}
+
+impl DiagnosticParser for RegexLineParser {
+    type Item = (Option<String>, Diagnostic);
+
+    fn parse(&self, output: &Output) -> Result<Vec<Self::Item>, HandlerError> {
+        // Tools may emit stray non-UTF8 bytes (colors, locale issues),
+        // parse whatever is valid instead of dropping all diagnostics.
+        let text = match self.stream {
+            Stream::Stdout => String::from_utf8_lossy(&output.stdout),
+            Stream::Stderr => String::from_utf8_lossy(&output.stderr),
+        };
+        let diagnostics = self.parse_text(&text);
+        if diagnostics.is_empty() && !output.status.success() {
+            log::warn!("Could not parse {} output: '{text}'", self.source);
+        }
+        Ok(diagnostics)
+    }
+}
+
+/// A diagnostic as a tool reports it, with positions counted from the
+/// tool's first line and column.
+#[derive(Debug, Default)]
+pub struct ToolDiagnostic {
+    pub line: u32,
+    pub column: u32,
+    /// Line and column of the end, the diagnostic is empty without one.
+    pub end: Option<(u32, u32)>,
+    /// The tool's name of the severity, e.g. `"warning"`.
+    pub severity: Option<String>,
+    pub code: Option<String>,
+    pub message: String,
+}
+
+/// An element of the JSON array a tool outputs.
+pub trait JsonDiagnostic: DeserializeOwned {
+    /// The diagnostic, or `None` for elements that aren't one, e.g. a
+    /// summary.
+    fn into_diagnostic(self) -> Option<ToolDiagnostic>;
+}
+
+/// Parses a JSON array on stdout into diagnostics, one per element.
+pub struct JsonArrayParser<T> {
+    /// The tool name, the source of the diagnostics.
+    pub tool: &'static str,
+    /// Number of the first line, 1 for most tools.
+    pub first_line: u32,
+    /// Number of the first column of a line.
+    pub first_column: u32,
+    /// Severities by the tool's names, compared ignoring case. Others are
+    /// warnings.
+    pub severities: &'static [(&'static str, DiagnosticSeverity)],
+    element: PhantomData<T>,
+}
+
+impl<T: JsonDiagnostic> JsonArrayParser<T> {
+    /// A parser of 1-based positions, with every diagnostic a warning.
+    pub fn new(tool: &'static str) -> Self {
+        Self {
+            tool,
+            first_line: 1,
+            first_column: 1,
+            severities: &[],
+            element: PhantomData,
+        }
+    }
+
+    pub fn first_line(mut self, first_line: u32) -> Self {
+        self.first_line = first_line;
+        self
+    }
+
+    pub fn first_column(mut self, first_column: u32) -> Self {
+        self.first_column = first_column;
+        self
+    }
+
+    pub fn severities(mut self, severities: &'static [(&'static str, DiagnosticSeverity)]) -> Self {
+        self.severities = severities;
+        self
+    }
+
+    pub fn parse_text(&self, text: &str) -> Result<Vec<Diagnostic>, HandlerError> {
+        let elements: Vec<T> = serde_json::from_str(text)
+            .map_err(|e| HandlerError::Parse(format!("Invalid {} output: {e}", self.tool)))?;
+        Ok(self.diagnostics(elements))
+    }
+
+    /// The diagnostics of already parsed elements, for tools wrapping the
+    /// array in an object.
+    pub fn diagnostics(&self, elements: Vec<T>) -> Vec<Diagnostic> {
+        elements
+            .into_iter()
+            .filter_map(T::into_diagnostic)
+            .map(|diagnostic| self.convert(diagnostic))
+            .collect()
+    }
+
+    fn convert(&self, diagnostic: ToolDiagnostic) -> Diagnostic {
+        let position = |line: u32, column: u32| {
+            Position::new(
+                line.saturating_sub(self.first_line),
+                column.saturating_sub(self.first_column),
+            )
+        };
+        let start = position(diagnostic.line, diagnostic.column);
+        let end = diagnostic
+            .end
+            .map_or(start, |(line, column)| position(line, column));
+        let severity = diagnostic.severity.and_then(|name| {
+            self.severities
+                .iter()
+                .find(|(tool_name, _)| tool_name.eq_ignore_ascii_case(&name))
+                .map(|(_, severity)| *severity)
+        });
+        Diagnostic::new(
+            Range::new(start, end),
+            Some(severity.unwrap_or(DiagnosticSeverity::WARNING)),
+            diagnostic.code.map(NumberOrString::String),
+            Some(self.tool.to_string()),
+            diagnostic.message,
+            None,
+            None,
+        )
+    }
+}
+
+impl<T: JsonDiagnostic> DiagnosticParser for JsonArrayParser<T> {
+    type Item = Diagnostic;
+
+    fn parse(&self, output: &Output) -> Result<Vec<Self::Item>, HandlerError> {
+        // Tools usually exit with an error when there are diagnostics, only
+        // missing output is a failure
+        if output.stdout.is_empty() {
+            return Err(HandlerError::Log(
+                String::from_utf8_lossy(&output.stderr).into_owned(),
+            ));
+        }
+        self.parse_text(&String::from_utf8_lossy(&output.stdout))
+    }
+}
+
+/// Names of files or directories marking the root of a project, where
+/// searches for settings files stop when no setting changes them.
+pub const DEFAULT_ROOT_MARKERS: &[&str] = &[".git", ".hg", ".svn"];
+
+/// The closest file named one of `names` in `directory` or its parents,
+/// for which `accept` holds. Earlier names win within a directory. The
+/// search ends at the first directory containing one of `root_markers`.
+pub fn traverse_parents(
+    directory: &Path,
+    names: &[&str],
+    root_markers: &[String],
+    accept: impl Fn(&Path) -> bool,
+) -> Option<PathBuf> {
+    for directory in directory.ancestors() {
+        let found = names
+            .iter()
+            .map(|name| directory.join(name))
+            .find(|path| path.is_file() && accept(path));
+        if found.is_some() {
+            return found;
+        }
+        if root_markers
+            .iter()
+            .any(|marker| directory.join(marker).exists())
+        {
+            return None;
+        }
+    }
+    None
+}
+
+/// How handlers running tools on a file write the document to disk.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TempFileStrategy {
+    /// Rewrite a single file per handler. Requests must not overlap.
+    Reuse,
+    /// Create a fresh file for every request, safe when requests overlap.
+    #[default]
+    PerRequest,
+}
+
+/// Start of the names of the temporary files written next to documents,
+/// whose changes don't change diagnostics.
+pub const TEMP_FILE_PREFIX: &str = ".any_ls";
+
+/// Temporary files holding documents for tools that only read files.
+#[derive(Debug, Default)]
+pub struct TempFiles {
+    strategy: TempFileStrategy,
+    /// Extension, e.g. `.sh`, for tools detecting the language from the
+    /// file name.
+    suffix: String,
+    /// Start of the file names instead of `TEMP_FILE_PREFIX`, for tools
+    /// deriving names from the file name, e.g. module names.
+    prefix: String,
+    reused: Option<Arc<NamedTempFile>>,
+}
+
+impl TempFiles {
+    pub fn with_suffix(suffix: impl Into<String>) -> Self {
+        Self {
+            suffix: suffix.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    pub fn set_strategy(&mut self, strategy: TempFileStrategy) {
+        self.strategy = strategy;
+        self.reused = None;
+    }
+
+    /// A file with `contents`, removed once the last handle is dropped.
+    pub fn write(&mut self, contents: &str) -> Result<Arc<NamedTempFile>, HandlerError> {
+        let file = match (&self.reused, self.strategy) {
+            (Some(file), TempFileStrategy::Reuse) => file.clone(),
+            _ => Arc::new(
+                self.builder(&self.prefix)
+                    .tempfile()
+                    .map_err(|e| HandlerError::Log(format!("{e}")))?,
+            ),
+        };
+        // Truncates a reused file
+        std::fs::write(file.path(), contents).map_err(|e| HandlerError::Log(format!("{e}")))?;
+        if self.strategy == TempFileStrategy::Reuse {
+            self.reused = Some(file.clone());
+        }
+        Ok(file)
+    }
+
+    /// Like `write`, with the file in `directory`, for tools resolving
+    /// paths relative to the file. In the temporary directory if it can't
+    /// be created there, e.g. the directory is read-only.
+    ///
+    /// The file is never reused so it doesn't stay in the project, and its
+    /// name starts with `TEMP_FILE_PREFIX` for file watchers to ignore it.
+    pub fn write_in(
+        &mut self,
+        directory: &Path,
+        contents: &str,
+    ) -> Result<Arc<NamedTempFile>, HandlerError> {
+        let prefix = format!("{TEMP_FILE_PREFIX}{}", self.prefix);
+        let file = match self.builder(&prefix).tempfile_in(directory) {
+            Ok(file) => file,
+            Err(_) => return self.write(contents),
+        };
+        std::fs::write(file.path(), contents).map_err(|e| HandlerError::Log(format!("{e}")))?;
+        Ok(Arc::new(file))
+    }
+
+    fn builder<'a>(&'a self, prefix: &'a str) -> tempfile::Builder<'a, 'a> {
+        let mut builder = tempfile::Builder::new();
+        builder.prefix(if prefix.is_empty() {
+            TEMP_FILE_PREFIX
+        } else {
+            prefix
+        });
+        builder.suffix(&self.suffix);
+        builder
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        format_stdin, output_with_timeout, run_and_parse, run_with_stdin, strip_ansi,
+        traverse_parents, wait_with_timeout, with_output_encoding, with_output_encoding_sync,
+        InputMode, JsonArrayParser, JsonDiagnostic, RegexLineParser, Stream, TempFileStrategy,
+        TempFiles, ToolDiagnostic, TEMP_FILE_PREFIX,
+    };
+    use crate::handlers::HandlerError;
+    use encoding_rs::Encoding;
+    use lazy_regex::regex;
+    use serde::Deserialize;
+    use std::process::{Command, Stdio};
+    use std::sync::{Barrier, Mutex};
+    use std::time::{Duration, Instant};
+    use tower_lsp::lsp_types::{DiagnosticSeverity, NumberOrString, Position, Range};
+
+    #[test]
+    fn test_output_encoding() {
+        let parser = RegexLineParser {
+            regex: regex!(r#"(?m)^line (?P<line>\d+): (?P<message>.*)$"#),
+            source: "tool",
+            stream: Stream::Stdout,
+            first_line: 1,
+            first_column: 1,
+            severity: |_| Some(DiagnosticSeverity::ERROR),
+        };
+        // `è` in latin-1
+        let mut command = Command::new("printf");
+        command.arg("line 3: caract\\350re inattendu\\n");
+
+        let latin1 = Encoding::for_label(b"latin1").unwrap();
+        let messages = with_output_encoding_sync(latin1, || {
+            run_and_parse(&mut command, InputMode::File, &parser)
+        })
+        .ok()
+        .unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].1.range.start, Position::new(2, 0));
+        assert_eq!(messages[0].1.message, "caractère inattendu");
+
+        // UTF-8 outside of a handler
+        let messages = run_and_parse(&mut command, InputMode::File, &parser)
+            .ok()
+            .unwrap();
+        assert_eq!(messages[0].1.message, "caract\u{fffd}re inattendu");
+    }
+
+    #[tokio::test]
+    async fn test_output_encoding_async() {
+        // `ア` in Shift_JIS
+        let shift_jis = Encoding::for_label(b"shift_jis").unwrap();
+        let output = with_output_encoding(shift_jis, async {
+            run_with_stdin(Command::new("printf").arg("\\203A"), "")
+        })
+        .await
+        .ok()
+        .unwrap();
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), "ア");
+    }
+
+    #[test]
+    fn test_regex_line_parser() {
+        let parser = RegexLineParser {
+            regex: regex!(
+                r#"(?m)^(?P<path>[^:\n]+):(?P<line>\d+):(?P<column>\d+): (?P<severity>\w+) (?P<code>[A-Z]\d+): (?P<message>.*)$"#
+            ),
+            source: "tool",
+            stream: Stream::Stdout,
+            first_line: 1,
+            first_column: 1,
+            severity: |severity| match severity {
+                "error" => Some(DiagnosticSeverity::ERROR),
+                _ => Some(DiagnosticSeverity::WARNING),
+            },
+        };
+        let stdout = "src/a.py:3:7: warning W101: unused import\nnot a diagnostic\n\x1b[31msrc/b.py:1:1: error E1: syntax\x1b[0m\n";
+        let diagnostics = parser.parse_text(stdout);
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].0.as_deref(), Some("src/a.py"));
+        assert_eq!(diagnostics[0].1.range.start, Position::new(2, 6));
+        assert_eq!(diagnostics[0].1.severity, Some(DiagnosticSeverity::WARNING));
+        assert_eq!(
+            diagnostics[0].1.code,
+            Some(NumberOrString::String("W101".to_string()))
+        );
+        assert_eq!(diagnostics[0].1.message, "unused import");
+        assert_eq!(diagnostics[1].1.severity, Some(DiagnosticSeverity::ERROR));
+        assert_eq!(diagnostics[1].1.message, "syntax");
+    }
+
+    #[derive(Deserialize)]
+    struct Finding {
+        line: Option<u32>,
+        column: Option<u32>,
+        end_column: Option<u32>,
+        level: Option<String>,
+        message: String,
+    }
+
+    impl JsonDiagnostic for Finding {
+        fn into_diagnostic(self) -> Option<ToolDiagnostic> {
+            let line = self.line?;
+            Some(ToolDiagnostic {
+                line,
+                column: self.column.unwrap_or(1),
+                end: self.end_column.map(|column| (line, column)),
+                severity: self.level,
+                code: None,
+                message: self.message,
+            })
+        }
+    }
+
+    #[test]
+    fn test_json_array_parser() {
+        let parser = JsonArrayParser::<Finding>::new("tool")
+            .severities(&[("error", DiagnosticSeverity::ERROR)]);
+        let stdout = r#"[
+            {"line": 4, "column": 3, "end_column": 9, "level": "Error", "message": "too long"},
+            {"line": 1, "level": "style", "message": "style"},
+            {"message": "summary"}
+        ]"#;
+        let diagnostics = parser.parse_text(stdout).ok().unwrap();
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(
+            diagnostics[0].range,
+            Range::new(Position::new(3, 2), Position::new(3, 8))
+        );
+        assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::ERROR));
+        assert_eq!(diagnostics[0].source.as_deref(), Some("tool"));
+        assert_eq!(diagnostics[0].message, "too long");
+        // Unknown severities are warnings
+        assert_eq!(diagnostics[1].severity, Some(DiagnosticSeverity::WARNING));
+        assert_eq!(diagnostics[1].range.end, Position::new(0, 0));
+
+        // Positions counted from 0
+        let parser = JsonArrayParser::<Finding>::new("tool")
+            .first_line(0)
+            .first_column(0);
+        let diagnostics = parser.parse_text(stdout).ok().unwrap();
+        assert_eq!(diagnostics[0].range.start, Position::new(4, 3));
+
+        assert!(matches!(
+            parser.parse_text("Usage: tool [options]"),
+            Err(HandlerError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn test_tool_not_found() {
+        let out = run_with_stdin(&mut Command::new("any-ls-missing-tool"), "");
+        assert!(matches!(
+            out,
+            Err(HandlerError::ToolNotFound(tool)) if tool == "any-ls-missing-tool"
+        ));
+    }
+
+    #[test]
+    fn test_large_stdin() {
+        // More than the pipes hold, `cat` writes while it is written to
+        let input = "line of the document\n".repeat(50_000);
+        let out = run_with_stdin(&mut Command::new("cat"), &input)
+            .ok()
+            .unwrap();
+        assert_eq!(out.stdout, input.as_bytes());
+
+        // Exiting without reading it
+        let out = run_with_stdin(&mut Command::new("true"), &input)
+            .ok()
+            .unwrap();
+        assert!(out.status.success());
+    }
+
+    #[test]
+    fn test_format_stdin() {
+        let formatted = format_stdin(&mut Command::new("cat"), "let x = 1\n")
+            .ok()
+            .unwrap();
+        assert_eq!(formatted.as_deref(), Some("let x = 1\n"));
+
+        // A formatter failing on invalid syntax, printing what it could
+        let mut command = Command::new("sh");
+        command
+            .arg("-c")
+            .arg("echo 'let x ='; echo 'Error: syntax error' >&2; exit 1");
+        let formatted = format_stdin(&mut command, "let x = )\n");
+        assert!(matches!(
+            formatted,
+            Err(HandlerError::ToolFailed { tool, message })
+                if tool == "sh" && message == "Error: syntax error"
+        ));
+
+        let formatted = format_stdin(&mut Command::new("false"), "");
+        assert!(matches!(
+            formatted,
+            Err(HandlerError::ToolFailed { message, .. }) if message.starts_with("exited with")
+        ));
+    }
+
+    #[test]
+    fn test_timeout() {
+        let mut command = Command::new("sleep");
+        command
+            .arg("5")
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        let child = command.spawn().unwrap();
+        let started = Instant::now();
+        let out = wait_with_timeout(&command, child, Duration::from_millis(100));
+        assert!(matches!(out, Err(HandlerError::Timeout(tool)) if tool == "sleep"));
+        assert!(started.elapsed() < Duration::from_secs(5));
+
+        let out = output_with_timeout(Command::new("echo").arg("done"))
+            .ok()
+            .unwrap();
+        assert_eq!(out.stdout, b"done\n");
+    }
+
+    #[test]
+    fn test_strip_ansi() {
+        let colored = "\x1b[1;31merror\x1b[0m: \x1b[1mUnknown start of token:\x1b[0m";
+        assert_eq!(strip_ansi(colored), "error: Unknown start of token:");
+        assert_eq!(strip_ansi("no colors"), "no colors");
+    }
+
+    #[test]
+    fn test_traverse_parents() {
+        let dir = tempfile::tempdir().unwrap();
+        let nested = dir.path().join("a/b");
+        std::fs::create_dir_all(&nested).unwrap();
+        std::fs::write(dir.path().join("a/config.toml"), "").unwrap();
+        std::fs::write(dir.path().join("a/.config.toml"), "").unwrap();
+
+        let names = &[".config.toml", "config.toml"];
+        let found = traverse_parents(&nested, names, &[], |_| true);
+        assert_eq!(found, Some(dir.path().join("a/.config.toml")));
+        let found = traverse_parents(&nested, names, &[], |path| !path.ends_with(".config.toml"));
+        assert_eq!(found, Some(dir.path().join("a/config.toml")));
+    }
+
+    #[test]
+    fn test_traverse_parents_root_marker() {
+        let dir = tempfile::tempdir().unwrap();
+        let nested = dir.path().join("repo/crates/app/src");
+        std::fs::create_dir_all(&nested).unwrap();
+        std::fs::write(dir.path().join("repo/config.toml"), "").unwrap();
+        std::fs::write(dir.path().join("repo/crates/app/BUILD.root"), "").unwrap();
+
+        let markers = ["BUILD.root".to_string()];
+        let found = traverse_parents(&nested, &["config.toml"], &markers, |_| true);
+        assert_eq!(found, None);
+        // Files next to the marker are found
+        std::fs::write(dir.path().join("repo/crates/app/config.toml"), "").unwrap();
+        let found = traverse_parents(&nested, &["config.toml"], &markers, |_| true);
+        assert_eq!(found, Some(dir.path().join("repo/crates/app/config.toml")));
+    }
+
+    #[test]
+    fn test_temp_files_per_request() {
+        let temp_files = Mutex::new(TempFiles::default());
+        let barrier = Barrier::new(2);
+
+        // Both requests hold their file while the other one writes
+        std::thread::scope(|scope| {
+            for contents in ["first request", "second request"] {
+                let (temp_files, barrier) = (&temp_files, &barrier);
+                scope.spawn(move || {
+                    let file = temp_files.lock().unwrap().write(contents).ok().unwrap();
+                    barrier.wait();
+                    assert_eq!(std::fs::read_to_string(file.path()).unwrap(), contents);
+                });
+            }
+        });
+    }
+
+    #[test]
+    fn test_temp_files_reuse() {
+        let mut temp_files = TempFiles::default();
+        temp_files.set_strategy(TempFileStrategy::Reuse);
+        let first = temp_files.write("a longer first document").ok().unwrap();
+        let second = temp_files.write("second").ok().unwrap();
+        assert_eq!(first.path(), second.path());
+        assert_eq!(std::fs::read_to_string(second.path()).unwrap(), "second");
+    }
+
+    #[test]
+    fn test_temp_files_suffix() {
+        for strategy in [TempFileStrategy::PerRequest, TempFileStrategy::Reuse] {
+            let mut temp_files = TempFiles::with_suffix(".just");
+            temp_files.set_strategy(strategy);
+            let file = temp_files.write("default:").ok().unwrap();
+            assert_eq!(
+                file.path().extension().and_then(|ext| ext.to_str()),
+                Some("just")
+            );
+        }
+    }
+
+    #[test]
+    fn test_temp_files_in_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        for strategy in [TempFileStrategy::PerRequest, TempFileStrategy::Reuse] {
+            let mut temp_files = TempFiles::with_suffix(".just");
+            temp_files.set_strategy(strategy);
+            let file = temp_files.write_in(dir.path(), "default:").ok().unwrap();
+            assert_eq!(file.path().parent(), Some(dir.path()));
+            let name = file.path().file_name().unwrap().to_str().unwrap();
+            assert!(name.starts_with(TEMP_FILE_PREFIX));
+            // Removed from the project once checked, even when reusing files
+            let path = file.path().to_path_buf();
+            drop(file);
+            assert!(!path.exists());
+            let elsewhere = temp_files.write("default:").ok().unwrap();
+            assert_ne!(elsewhere.path().parent(), Some(dir.path()));
+        }
+        // Falls back to the temporary directory
+        let missing = dir.path().join("missing");
+        let file = TempFiles::default().write_in(&missing, "").ok().unwrap();
+        assert!(file.path().exists());
+        assert_ne!(file.path().parent(), Some(missing.as_path()));
+    }
+}
@@ -0,0 +1,392 @@
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use lazy_regex::{regex, regex_captures};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use tower_lsp::lsp_types::{
+    self, Diagnostic, DiagnosticSeverity, Hover, HoverContents, HoverProviderCapability,
+    MarkupContent, MarkupKind, Position, ServerCapabilities,
+};
+
+use super::text::{offset_to_position, position_to_offset};
+use super::{DocumentContext, Handler, HandlerError};
+
+/// Checks the variables docker-compose files interpolate, like `${TAG}`,
+/// against the definitions of the `.env` file next to them and the
+/// environment of the server.
+#[derive(Debug)]
+pub struct PropsHandler {
+    /// Variables whose values are masked on hover.
+    secret_keys: GlobSet,
+}
+
+/// Patterns of the variables holding secrets, see
+/// `Config::mask_secret_values`.
+pub const DEFAULT_SECRET_KEYS: &[&str] = &["*KEY*", "*SECRET*", "*TOKEN*", "*PASSWORD*"];
+
+/// What masked values are shown as, the same for all values to not reveal
+/// their length.
+const MASK: &str = "••••";
+
+/// Compiles patterns of variable names, matched ignoring case. Invalid
+/// patterns are reported by `Config::validate`.
+fn compile_secret_keys(patterns: &[&str]) -> GlobSet {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        if let Ok(glob) = GlobBuilder::new(pattern).case_insensitive(true).build() {
+            builder.add(glob);
+        }
+    }
+    builder.build().unwrap_or_else(|_| GlobSet::empty())
+}
+
+/// A `$VAR` or `${VAR...}` interpolation, with the byte range of the name.
+#[derive(Debug, PartialEq)]
+pub struct Reference {
+    pub name: String,
+    pub range: Range<usize>,
+    /// Whether the interpolation has a fallback, e.g. `${VAR:-default}`,
+    /// and can be left undefined.
+    pub has_default: bool,
+}
+
+/// A `KEY=value` line of a `.env` file.
+#[derive(Debug, PartialEq)]
+pub struct Definition {
+    pub name: String,
+    pub value: String,
+    /// The `.env` file defining the variable.
+    pub from_path: PathBuf,
+}
+
+/// The definitions of the `.env` file at `from_path` with `contents`.
+pub fn definitions(contents: &str, from_path: &Path) -> Vec<Definition> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            regex_captures!(
+                r#"^\s*(?:export\s+)?([A-Za-z_][A-Za-z0-9_]*)\s*=\s*(.*)$"#,
+                line
+            )
+        })
+        .map(|(_, name, value)| Definition {
+            name: name.to_string(),
+            value: value.trim_end().trim_matches(['"', '\'']).to_string(),
+            from_path: from_path.to_path_buf(),
+        })
+        .collect()
+}
+
+/// The interpolations of a compose file, outside of comments. `$$` is an
+/// escaped `$`.
+pub fn references(contents: &str) -> Vec<Reference> {
+    let mut references = Vec::new();
+    let mut line_start = 0;
+    for line in contents.split('\n') {
+        let code = if line.trim_start().starts_with('#') {
+            ""
+        } else {
+            line
+        };
+        let interpolation = regex!(
+            r#"\$\$|\$\{([A-Za-z_][A-Za-z0-9_]*)(:?[-+?=])?[^}]*\}|\$([A-Za-z_][A-Za-z0-9_]*)"#
+        );
+        for captures in interpolation.captures_iter(code) {
+            let Some(name) = captures.get(1).or_else(|| captures.get(3)) else {
+                continue;
+            };
+            // `?` fails with an error when undefined, `+` is empty then
+            let modifier = captures.get(2).map_or("", |modifier| modifier.as_str());
+            references.push(Reference {
+                name: name.as_str().to_string(),
+                range: line_start + name.start()..line_start + name.end(),
+                has_default: !modifier.is_empty() && !modifier.ends_with('?'),
+            });
+        }
+        line_start += line.len() + 1;
+    }
+    references
+}
+
+impl PropsHandler {
+    /// `secret_keys` are the patterns of `Config::mask_secret_values`, the
+    /// defaults when `None`.
+    pub fn new(secret_keys: Option<&[String]>) -> Result<Self, String> {
+        let patterns: Vec<&str> = match secret_keys {
+            Some(patterns) => patterns.iter().map(String::as_str).collect(),
+            None => DEFAULT_SECRET_KEYS.to_vec(),
+        };
+        Ok(Self {
+            secret_keys: compile_secret_keys(&patterns),
+        })
+    }
+
+    /// `value` of the variable `name` as shown to users, masked for
+    /// secrets so they don't leak when sharing screens.
+    pub fn shown_value<'a>(&self, name: &str, value: &'a str) -> &'a str {
+        if self.secret_keys.is_match(name) {
+            MASK
+        } else {
+            value
+        }
+    }
+
+    /// Whether `path` is a docker-compose file, e.g. `compose.yaml` or
+    /// `docker-compose.override.yml`.
+    pub fn is_compose_file(path: &Path) -> bool {
+        path.file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| regex!(r#"^(?:docker-compose.*|compose)\.ya?ml$"#).is_match(name))
+    }
+
+    /// The `.env` file docker compose reads for the document, in its
+    /// project directory, and its definitions.
+    fn env_file(context: &DocumentContext) -> (PathBuf, Vec<Definition>) {
+        let path = context.directory().unwrap_or_default().join(".env");
+        let defined = std::fs::read_to_string(&path)
+            .map(|env| definitions(&env, &path))
+            .unwrap_or_default();
+        (path, defined)
+    }
+
+    /// Warnings on interpolations without a default of variables that
+    /// aren't in `defined` or the environment. `env_file` names the `.env`
+    /// file in messages.
+    pub fn undefined(contents: &str, defined: &[Definition], env_file: &str) -> Vec<Diagnostic> {
+        references(contents)
+            .into_iter()
+            .filter(|reference| {
+                !reference.has_default
+                    && !defined.iter().any(|def| def.name == reference.name)
+                    && std::env::var_os(&reference.name).is_none()
+            })
+            .map(|reference| {
+                Diagnostic::new(
+                    lsp_types::Range::new(
+                        offset_to_position(contents, reference.range.start),
+                        offset_to_position(contents, reference.range.end),
+                    ),
+                    Some(DiagnosticSeverity::WARNING),
+                    None,
+                    Some("compose".to_string()),
+                    format!(
+                        "Variable `{}` is not defined in {env_file} and has no default",
+                        reference.name
+                    ),
+                    None,
+                    None,
+                )
+            })
+            .collect()
+    }
+}
+
+impl Handler for PropsHandler {
+    fn filetype_supported(&self, _filetype: &str) -> bool {
+        false
+    }
+
+    fn path_supported(&self, path: &Path) -> bool {
+        Self::is_compose_file(path)
+    }
+
+    fn get_capabilities(&self) -> ServerCapabilities {
+        ServerCapabilities {
+            hover_provider: Some(HoverProviderCapability::Simple(true)),
+            ..Default::default()
+        }
+    }
+
+    async fn update_diagnostics(
+        &mut self,
+        contents: &str,
+    ) -> Result<Vec<Diagnostic>, HandlerError> {
+        Ok(Self::undefined(contents, &[], ".env"))
+    }
+
+    async fn update_diagnostics_with_context(
+        &mut self,
+        context: &DocumentContext,
+        contents: &str,
+    ) -> Result<Vec<Diagnostic>, HandlerError> {
+        let (path, defined) = Self::env_file(context);
+        Ok(Self::undefined(
+            contents,
+            &defined,
+            &context.display_path(&path),
+        ))
+    }
+
+    fn hover(
+        &self,
+        _filetype: &str,
+        context: &DocumentContext,
+        contents: &str,
+        position: Position,
+    ) -> Result<Option<Hover>, HandlerError> {
+        let offset = position_to_offset(contents, position);
+        let Some(reference) = references(contents)
+            .into_iter()
+            .find(|reference| reference.range.contains(&offset))
+        else {
+            return Ok(None);
+        };
+
+        let (_, defined) = Self::env_file(context);
+        let value = match defined.iter().find(|def| def.name == reference.name) {
+            Some(def) => format!(
+                "`{}={}`\n\nDefined in `{}`",
+                def.name,
+                self.shown_value(&def.name, &def.value),
+                context.display_path(&def.from_path)
+            ),
+            None => match std::env::var(&reference.name) {
+                Ok(value) => format!(
+                    "`{}={}`\n\nFrom the environment of the server",
+                    reference.name,
+                    self.shown_value(&reference.name, &value)
+                ),
+                Err(_) => return Ok(None),
+            },
+        };
+        Ok(Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value,
+            }),
+            range: Some(lsp_types::Range::new(
+                offset_to_position(contents, reference.range.start),
+                offset_to_position(contents, reference.range.end),
+            )),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{definitions, PropsHandler};
+    use crate::handlers::{DocumentContext, Handler};
+    use std::path::Path;
+    use tower_lsp::lsp_types::{HoverContents, Position, Range, Url, WorkspaceFolder};
+
+    #[test]
+    fn test_definitions() {
+        let env =
+            "# Database\nANY_LS_DB_HOST=db\nexport ANY_LS_TAG = \"latest\"\n\nnot a definition\n";
+        let defined = definitions(env, Path::new("/app/.env"));
+        let names: Vec<&str> = defined.iter().map(|def| def.name.as_str()).collect();
+        assert_eq!(names, vec!["ANY_LS_DB_HOST", "ANY_LS_TAG"]);
+        assert_eq!(defined[1].value, "latest");
+        assert_eq!(defined[1].from_path, Path::new("/app/.env"));
+    }
+
+    #[test]
+    fn test_hover_relative_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let web = dir.path().join("web");
+        std::fs::create_dir(&web).unwrap();
+        std::fs::write(web.join(".env"), "ANY_LS_TAG=1.2\n").unwrap();
+        let folder = WorkspaceFolder {
+            uri: Url::from_file_path(dir.path()).unwrap(),
+            name: "project".to_string(),
+        };
+        let uri = Url::from_file_path(web.join("compose.yaml")).unwrap();
+        let context = DocumentContext::new(uri, vec![folder]);
+
+        let compose = "services:\n  web:\n    image: \"app:${ANY_LS_TAG}\"\n";
+        let hover = PropsHandler::new(None)
+            .unwrap()
+            .hover("yaml", &context, compose, Position::new(2, 20))
+            .ok()
+            .unwrap()
+            .unwrap();
+        let HoverContents::Markup(markup) = hover.contents else {
+            panic!("Expected markup");
+        };
+        assert_eq!(markup.value, "`ANY_LS_TAG=1.2`\n\nDefined in `web/.env`");
+        assert_eq!(
+            hover.range,
+            Some(Range::new(Position::new(2, 18), Position::new(2, 28)))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_compose_undefined_variable() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join(".env"), "ANY_LS_DB_HOST=db\n").unwrap();
+        let compose = r#"services:
+  web:
+    image: "app:${ANY_LS_TAG:-latest}"
+    environment:
+      DB_HOST: ${ANY_LS_DB_HOST}
+      API_KEY: ${ANY_LS_API_KEY}
+      PRICE: $$5
+    # command: run ${ANY_LS_COMMENTED}
+"#;
+        let uri = Url::from_file_path(dir.path().join("docker-compose.yml")).unwrap();
+        let folder = WorkspaceFolder {
+            uri: Url::from_file_path(dir.path()).unwrap(),
+            name: "project".to_string(),
+        };
+        let context = DocumentContext::new(uri, vec![folder]);
+        let diagnostics = PropsHandler::new(None)
+            .unwrap()
+            .update_diagnostics_with_context(&context, compose)
+            .await
+            .ok()
+            .unwrap();
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].message,
+            "Variable `ANY_LS_API_KEY` is not defined in .env and has no default"
+        );
+        assert_eq!(
+            diagnostics[0].range,
+            Range::new(Position::new(5, 17), Position::new(5, 31))
+        );
+    }
+
+    #[test]
+    fn test_hover_masks_secrets() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join(".env"),
+            "ANY_LS_API_SECRET=hunter2\nANY_LS_PORT=8080\n",
+        )
+        .unwrap();
+        let uri = Url::from_file_path(dir.path().join("compose.yaml")).unwrap();
+        let context = DocumentContext::new(uri, vec![]);
+        let compose = "services:\n  web:\n    environment:\n      SECRET: ${ANY_LS_API_SECRET}\n      PORT: ${ANY_LS_PORT}\n";
+        let hover = |handler: &PropsHandler, line| {
+            let hover = handler
+                .hover("yaml", &context, compose, Position::new(line, 20))
+                .ok()
+                .unwrap()
+                .unwrap();
+            let HoverContents::Markup(markup) = hover.contents else {
+                panic!("Expected markup");
+            };
+            markup.value
+        };
+
+        let handler = PropsHandler::new(None).unwrap();
+        assert!(hover(&handler, 3).starts_with("`ANY_LS_API_SECRET=••••`"));
+        assert!(hover(&handler, 4).starts_with("`ANY_LS_PORT=8080`"));
+
+        // Configured patterns replace the defaults
+        let handler = PropsHandler::new(Some(&["*port".to_string()])).unwrap();
+        assert!(hover(&handler, 3).starts_with("`ANY_LS_API_SECRET=hunter2`"));
+        assert!(hover(&handler, 4).starts_with("`ANY_LS_PORT=••••`"));
+    }
+
+    #[test]
+    fn test_is_compose_file() {
+        assert!(PropsHandler::is_compose_file(Path::new(
+            "/app/compose.yaml"
+        )));
+        assert!(PropsHandler::is_compose_file(Path::new(
+            "/app/docker-compose.override.yml"
+        )));
+        assert!(!PropsHandler::is_compose_file(Path::new("/app/config.yml")));
+    }
+}
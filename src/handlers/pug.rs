@@ -0,0 +1,189 @@
+use serde::Deserialize;
+use std::process::Command;
+use std::sync::Arc;
+use tempfile::NamedTempFile;
+use tower_lsp::lsp_types::Diagnostic;
+
+use super::process::{
+    output_with_timeout, probe, strip_ansi, JsonArrayParser, JsonDiagnostic, TempFileStrategy,
+    TempFiles, ToolDiagnostic,
+};
+use super::{Handler, HandlerError};
+
+/// A reporter printing the errors of pug-lint as a JSON array, its own
+/// reporters leave out the rule.
+const REPORTER: &str = "module.exports = function (errors) {
+  console.log(JSON.stringify(errors.map(function (error) {
+    return { code: error.code, msg: error.msg, line: error.line, column: error.column };
+  })));
+};
+";
+
+/// The rules of pug-lint, to name the rule of an error by its code, which
+/// has the name in upper case.
+const RULES: &[&str] = &[
+    "disallowAttributeConcatenation",
+    "disallowAttributeInterpolation",
+    "disallowAttributeTemplateString",
+    "disallowBlockExpansion",
+    "disallowClassAttributeWithStaticValue",
+    "disallowClassLiteralsBeforeAttributes",
+    "disallowClassLiteralsBeforeIdLiterals",
+    "disallowClassLiterals",
+    "disallowDuplicateAttributes",
+    "disallowHtmlText",
+    "disallowIdAttributeWithStaticValue",
+    "disallowIdLiteralsBeforeAttributes",
+    "disallowIdLiterals",
+    "disallowLegacyMixinCall",
+    "disallowMultipleLineBreaks",
+    "disallowSpaceAfterCodeOperator",
+    "disallowSpacesInsideAttributeBrackets",
+    "disallowSpecificAttributes",
+    "disallowSpecificTags",
+    "disallowStringConcatenation",
+    "disallowStringInterpolation",
+    "disallowTagInterpolation",
+    "maximumLineLength",
+    "maximumNumberOfLines",
+    "requireClassLiteralsBeforeAttributes",
+    "requireClassLiteralsBeforeIdLiterals",
+    "requireIdLiteralsBeforeAttributes",
+    "requireLineFeedAtFileEnd",
+    "requireLowerCaseAttributes",
+    "requireLowerCaseTags",
+    "requireSpaceAfterCodeOperator",
+    "requireSpacesInsideAttributeBrackets",
+    "requireSpecificAttributes",
+    "requireStrictEqualityOperators",
+    "validateAttributeQuoteMarks",
+    "validateAttributeSeparator",
+    "validateDivTags",
+    "validateExtensions",
+    "validateIndentation",
+    "validateLineBreaks",
+    "validateSelfClosingTags",
+    "validateTemplateString",
+];
+
+/// Linting of Pug templates with pug-lint.
+#[derive(Debug)]
+pub struct Pug {
+    temp_files: TempFiles,
+    /// The reporter pug-lint is run with, see `REPORTER`.
+    reporter: Arc<NamedTempFile>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LintError {
+    /// E.g. `PUG:LINT_REQUIRESPACESINSIDEATTRIBUTEBRACKETS`, or a code of
+    /// the Pug parser for syntax errors.
+    code: Option<String>,
+    msg: String,
+    line: Option<u32>,
+    column: Option<u32>,
+}
+
+/// The name of the rule of an error `code`, the code without its prefix
+/// for rules not known.
+fn rule_name(code: &str) -> String {
+    let code = code.strip_prefix("PUG:").unwrap_or(code);
+    let Some(rule) = code.strip_prefix("LINT_") else {
+        return code.to_string();
+    };
+    RULES
+        .iter()
+        .find(|name| name.eq_ignore_ascii_case(rule))
+        .map_or_else(|| rule.to_string(), |name| name.to_string())
+}
+
+impl JsonDiagnostic for LintError {
+    fn into_diagnostic(self) -> Option<ToolDiagnostic> {
+        Some(ToolDiagnostic {
+            line: self.line.unwrap_or(1),
+            column: self.column.unwrap_or(1),
+            code: self.code.as_deref().map(rule_name),
+            message: self.msg,
+            ..Default::default()
+        })
+    }
+}
+
+impl Pug {
+    pub fn new() -> Result<Self, String> {
+        probe("pug-lint", &["--version"])?;
+        let reporter = TempFiles::with_suffix(".js")
+            .write(REPORTER)
+            .map_err(|e| e.to_string())?;
+        Ok(Self {
+            temp_files: TempFiles::with_suffix(".pug"),
+            reporter,
+        })
+    }
+
+    fn parser() -> JsonArrayParser<LintError> {
+        JsonArrayParser::new("pug-lint")
+    }
+}
+
+impl Handler for Pug {
+    fn filetype_supported(&self, filetype: &str) -> bool {
+        matches!(filetype, "pug" | "jade")
+    }
+
+    fn set_temp_file_strategy(&mut self, strategy: TempFileStrategy) {
+        self.temp_files.set_strategy(strategy);
+    }
+
+    async fn update_diagnostics(
+        &mut self,
+        contents: &str,
+    ) -> Result<Vec<Diagnostic>, HandlerError> {
+        let temp_file = self.temp_files.write(contents)?;
+
+        let out = output_with_timeout(
+            Command::new("pug-lint")
+                .arg("--reporter")
+                .arg(self.reporter.path())
+                .arg(temp_file.path()),
+        )?;
+        // Exits with an error when there are lint errors, without a report
+        // when it couldn't run
+        let stdout = String::from_utf8_lossy(&out.stdout);
+        if stdout.trim().is_empty() {
+            if out.status.success() {
+                return Ok(Vec::new());
+            }
+            return Err(HandlerError::Log(strip_ansi(&String::from_utf8_lossy(
+                &out.stderr,
+            ))));
+        }
+        Self::parser().parse_text(&stdout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{rule_name, Pug};
+    use tower_lsp::lsp_types::{NumberOrString, Position};
+
+    #[test]
+    fn test_parse_report() {
+        let stdout = r#"[{"code":"PUG:LINT_REQUIRESPACESINSIDEATTRIBUTEBRACKETS","msg":"One space required after opening bracket","line":3,"column":6}]"#;
+        let diagnostics = Pug::parser().parse_text(stdout).ok().unwrap();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].range.start, Position::new(2, 5));
+        assert_eq!(
+            diagnostics[0].code,
+            Some(NumberOrString::String(
+                "requireSpacesInsideAttributeBrackets".to_string()
+            ))
+        );
+        assert_eq!(
+            diagnostics[0].message,
+            "One space required after opening bracket"
+        );
+
+        assert_eq!(rule_name("PUG:UNEXPECTED_TEXT"), "UNEXPECTED_TEXT");
+    }
+}
@@ -0,0 +1,152 @@
+use lazy_regex::regex;
+use std::path::Path;
+use std::process::Command;
+use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity, OneOf, ServerCapabilities};
+
+use super::process::{
+    formatted, output_with_timeout, probe, run_and_parse, InputMode, RegexLineParser, Stream,
+    TempFileStrategy, TempFiles,
+};
+use super::{Handler, HandlerError};
+
+/// Read and expansion errors of Racket and Scheme files with `raco`, and
+/// formatting with `raco fmt` when the fmt package is installed.
+#[derive(Debug)]
+pub struct Racket {
+    temp_files: TempFiles,
+    /// Whether `raco fmt` is available.
+    format: bool,
+}
+
+impl Racket {
+    pub fn new() -> Result<Self, String> {
+        probe("raco", &["--version"])?;
+        Ok(Self {
+            temp_files: TempFiles::with_suffix(".rkt"),
+            format: probe("raco", &["fmt", "--help"]).is_ok(),
+        })
+    }
+
+    /// Parses `path:line:column: message` errors. Racket counts columns
+    /// from 0.
+    fn parser() -> RegexLineParser {
+        RegexLineParser {
+            regex: regex!(
+                r#"(?m)^(?P<path>[^:\n]+):(?P<line>\d+):(?P<column>\d+): (?P<message>.*)$"#
+            ),
+            source: "raco",
+            stream: Stream::Stderr,
+            first_line: 1,
+            first_column: 0,
+            severity: |_| Some(DiagnosticSeverity::ERROR),
+        }
+    }
+
+    /// Expanding a module also reads it, Scheme files without a `#lang`
+    /// line can only be read.
+    fn subcommand(contents: &str) -> &'static str {
+        if contents.trim_start().starts_with("#lang") {
+            "expand"
+        } else {
+            "read"
+        }
+    }
+}
+
+impl Handler for Racket {
+    fn filetype_supported(&self, filetype: &str) -> bool {
+        matches!(filetype, "racket" | "scheme")
+    }
+
+    fn set_temp_file_strategy(&mut self, strategy: TempFileStrategy) {
+        self.temp_files.set_strategy(strategy);
+    }
+
+    fn get_capabilities(&self) -> ServerCapabilities {
+        ServerCapabilities {
+            document_formatting_provider: self.format.then_some(OneOf::Left(true)),
+            ..Default::default()
+        }
+    }
+
+    async fn update_diagnostics(
+        &mut self,
+        contents: &str,
+    ) -> Result<Vec<Diagnostic>, HandlerError> {
+        let temp_file = self.temp_files.write(contents)?;
+
+        let errors = run_and_parse(
+            Command::new("raco")
+                .arg(Self::subcommand(contents))
+                .arg(temp_file.path()),
+            InputMode::File,
+            &Self::parser(),
+        )?;
+        // Errors in required modules name their own files
+        Ok(errors
+            .into_iter()
+            .filter(|(path, _)| {
+                path.as_deref().map(|path| Path::new(path).file_name())
+                    == Some(temp_file.path().file_name())
+            })
+            .map(|(_, diagnostic)| diagnostic)
+            .collect())
+    }
+
+    async fn format(
+        &mut self,
+        _filetype: &str,
+        contents: &str,
+    ) -> Result<Option<String>, HandlerError> {
+        if !self.format {
+            return Ok(None);
+        }
+        let temp_file = self.temp_files.write(contents)?;
+
+        let mut command = Command::new("raco");
+        let out = output_with_timeout(command.arg("fmt").arg(temp_file.path()))?;
+        formatted(&command, &out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Racket;
+    use crate::handlers::Handler;
+    use tower_lsp::lsp_types::{DiagnosticSeverity, Position};
+
+    #[test]
+    fn test_parse_unbalanced_paren() {
+        let stderr = "/tmp/.tmpAbC123.rkt:3:0: read-syntax: expected a `)` to close `(`\n  possible cause: indentation suggests a missing `)` before line 5\n";
+        let errors = Racket::parser().parse_text(stderr);
+        assert_eq!(errors.len(), 1);
+        let (path, diagnostic) = &errors[0];
+        assert_eq!(path.as_deref(), Some("/tmp/.tmpAbC123.rkt"));
+        assert_eq!(diagnostic.range.start, Position::new(2, 0));
+        assert_eq!(diagnostic.severity, Some(DiagnosticSeverity::ERROR));
+        assert_eq!(
+            diagnostic.message,
+            "read-syntax: expected a `)` to close `(`"
+        );
+    }
+
+    #[test]
+    fn test_subcommand() {
+        assert_eq!(Racket::subcommand("#lang racket\n(+ 1 2)\n"), "expand");
+        assert_eq!(Racket::subcommand("(define x 1)\n"), "read");
+    }
+
+    #[tokio::test]
+    #[ignore = "needs raco fmt"]
+    async fn test_format() {
+        let mut racket = Racket::new().unwrap();
+        assert!(racket.format, "the fmt package is not installed");
+
+        let contents = "#lang racket\n(define (f x)\n(+ x 1))\n";
+        let formatted = racket.format("racket", contents).await.ok().unwrap();
+        assert_eq!(
+            formatted.as_deref(),
+            Some("#lang racket\n(define (f x)\n  (+ x 1))\n")
+        );
+    }
+}
@@ -0,0 +1,483 @@
+use lazy_regex::{regex, regex_captures};
+use std::collections::HashMap;
+use std::path::Path;
+use tower_lsp::lsp_types::{
+    self, Diagnostic, DiagnosticSeverity, DocumentLink, DocumentLinkOptions, Hover, HoverContents,
+    HoverProviderCapability, MarkupContent, MarkupKind, Position, ServerCapabilities, Url,
+};
+
+use super::{DocumentContext, Handler, HandlerError};
+
+/// Checks of the requirements of pip requirements and constraints files
+/// against PEP 508, and their parsed constraints on hover.
+#[derive(Debug)]
+pub struct Requirements {}
+
+/// A requirement or option, joined from the lines continued with `\`,
+/// without comments.
+#[derive(Debug, PartialEq)]
+struct Entry {
+    /// The first line of the entry, where it is reported.
+    line: u32,
+    /// Characters of the entry on its first line.
+    start: u32,
+    end: u32,
+    text: String,
+}
+
+/// A PEP 508 requirement, e.g. `requests[socks]>=2.31; python_version>"3.8"`.
+#[derive(Debug, PartialEq)]
+pub struct Requirement {
+    pub name: String,
+    pub extras: Vec<String>,
+    /// The version clauses, e.g. `>=2.31`, any version without one.
+    pub specifiers: Vec<String>,
+    pub url: Option<String>,
+    pub marker: Option<String>,
+}
+
+/// What a line of a requirements file is.
+#[derive(Debug, PartialEq)]
+enum Line {
+    Requirement(Requirement),
+    /// Another requirements or constraints file, `-r path` or `-c path`.
+    Include(String),
+    /// Other options, editable installs, and requirements given by a path
+    /// or URL.
+    Other,
+}
+
+/// The entries of `contents`. Comments start with a `#` at the start of a
+/// line or after whitespace, others are part of URLs.
+fn entries(contents: &str) -> Vec<Entry> {
+    let mut entries = Vec::new();
+    let mut pending: Option<Entry> = None;
+    for (number, line) in contents.lines().enumerate() {
+        let code = match regex!(r#"(?:^|\s)#"#).find(line) {
+            Some(comment) => &line[..comment.start()],
+            None => line,
+        };
+        let (code, continued) = match code.trim_end().strip_suffix('\\') {
+            Some(code) => (code, true),
+            None => (code, false),
+        };
+        let entry = match pending.take() {
+            Some(mut entry) => {
+                entry.text.push(' ');
+                entry.text.push_str(code.trim());
+                entry
+            }
+            None => {
+                let character = |offset: usize| line[..offset].encode_utf16().count() as u32;
+                let start = code.len() - code.trim_start().len();
+                Entry {
+                    line: number as u32,
+                    start: character(start),
+                    end: character(code.trim_end().len().max(start)),
+                    text: code.trim().to_string(),
+                }
+            }
+        };
+        if continued {
+            pending = Some(entry);
+        } else if !entry.text.trim().is_empty() {
+            entries.push(entry);
+        }
+    }
+    entries.extend(pending.filter(|entry| !entry.text.trim().is_empty()));
+    entries
+}
+
+/// Whether `marker` is an environment marker, comparisons of marker
+/// variables with quoted strings combined with `and` and `or`.
+fn is_marker(marker: &str) -> bool {
+    let comparison = regex!(
+        r#"^\s*(?:[a-z_]+\s*(?:===|==|!=|<=|>=|~=|<|>|not\s+in|in)\s*(?:"[^"]*"|'[^']*')|(?:"[^"]*"|'[^']*')\s*(?:===|==|!=|<=|>=|~=|<|>|not\s+in|in)\s*[a-z_]+)\s*$"#
+    );
+    let without_parentheses = marker.replace(['(', ')'], " ");
+    regex!(r#"\s(?:and|or)\s"#)
+        .split(&without_parentheses)
+        .all(|comparison_text| comparison.is_match(comparison_text))
+}
+
+/// Parses a PEP 508 requirement, with an error describing the first part
+/// that isn't valid.
+pub fn parse_requirement(text: &str) -> Result<Requirement, String> {
+    let (spec, marker) = match text.split_once(';') {
+        Some((spec, marker)) => (spec, Some(marker.trim())),
+        None => (text, None),
+    };
+    if let Some(marker) = marker {
+        if marker.is_empty() {
+            return Err("Expected an environment marker after `;`".to_string());
+        }
+        if !is_marker(marker) {
+            return Err(format!("Invalid environment marker `{marker}`"));
+        }
+    }
+
+    let package_name = regex!(r#"^[A-Za-z0-9](?:[A-Za-z0-9._-]*[A-Za-z0-9])?"#);
+    let spec = spec.trim();
+    let name = package_name
+        .find(spec)
+        .ok_or_else(|| format!("Expected a package name, found `{spec}`"))?
+        .as_str();
+    let mut rest = spec[name.len()..].trim_start();
+
+    let mut extras = Vec::new();
+    if let Some(after) = rest.strip_prefix('[') {
+        let (list, after) = after
+            .split_once(']')
+            .ok_or_else(|| "Expected `]` after the extras".to_string())?;
+        for extra in list
+            .split(',')
+            .map(str::trim)
+            .filter(|extra| !extra.is_empty())
+        {
+            if package_name.find(extra).map(|name| name.as_str()) != Some(extra) {
+                return Err(format!("Invalid extra `{extra}`"));
+            }
+            extras.push(extra.to_string());
+        }
+        rest = after.trim_start();
+    }
+
+    let mut requirement = Requirement {
+        name: name.to_string(),
+        extras,
+        specifiers: Vec::new(),
+        url: None,
+        marker: marker.map(str::to_string),
+    };
+    if let Some(url) = rest.strip_prefix('@') {
+        let url = url.trim();
+        if url.is_empty() {
+            return Err("Expected a URL after `@`".to_string());
+        }
+        requirement.url = Some(url.to_string());
+        return Ok(requirement);
+    }
+
+    let rest = match rest.strip_prefix('(') {
+        Some(inner) => inner
+            .strip_suffix(')')
+            .ok_or_else(|| "Expected `)` after the version specifiers".to_string())?,
+        None => rest,
+    };
+    if rest.trim().is_empty() {
+        return Ok(requirement);
+    }
+    for clause in rest.split(',').map(str::trim) {
+        let Some((_, operator, version)) = regex_captures!(
+            r#"^(~=|===|==|!=|<=|>=|<|>)\s*([A-Za-z0-9_.*+!-]+)$"#,
+            clause
+        ) else {
+            return Err(format!("Invalid version specifier `{clause}`"));
+        };
+        // Only prefix matching takes wildcards, e.g. `==2.*`
+        if version.contains('*') && !matches!(operator, "==" | "!=") {
+            return Err(format!("Invalid version specifier `{clause}`"));
+        }
+        requirement.specifiers.push(format!("{operator}{version}"));
+    }
+    Ok(requirement)
+}
+
+/// The name of the package `name` under which pip compares them, e.g.
+/// `zope-interface` for `Zope.Interface`.
+fn normalize(name: &str) -> String {
+    regex!(r#"[-_.]+"#).replace_all(name, "-").to_lowercase()
+}
+
+/// What the entry `text` is, or why it isn't a valid requirement.
+fn parse_line(text: &str) -> Result<Line, String> {
+    if text.starts_with('-') {
+        let include = regex_captures!(
+            r#"^(?:-r|--requirement|-c|--constraint)(?:\s*=\s*|\s+|\b)(\S+)"#,
+            text
+        );
+        return Ok(match include {
+            Some((_, path)) => Line::Include(path.to_string()),
+            None => Line::Other,
+        });
+    }
+    // Options of the requirement, e.g. the `--hash` of pip-compile output
+    let text = match regex!(r#"\s--"#).find(text) {
+        Some(option) => &text[..option.start()],
+        None => text,
+    };
+    let path_or_url = text.starts_with(['.', '/', '~'])
+        || (text.contains("://") && !text.contains('@'))
+        || regex!(r#"^[A-Za-z]:[\\/]"#).is_match(text);
+    if path_or_url {
+        return Ok(Line::Other);
+    }
+    parse_requirement(text).map(Line::Requirement)
+}
+
+impl Requirements {
+    pub fn new() -> Result<Self, String> {
+        Ok(Self {})
+    }
+
+    /// Whether `path` is a requirements or constraints file, e.g.
+    /// `requirements-dev.txt`, or a file of a `requirements` directory.
+    pub fn is_requirements_file(path: &Path) -> bool {
+        let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+            return false;
+        };
+        regex!(r#"^(?:requirements|constraints).*\.txt$"#).is_match(name)
+            || (name.ends_with(".txt")
+                && path
+                    .parent()
+                    .and_then(Path::file_name)
+                    .is_some_and(|parent| parent == "requirements"))
+    }
+
+    /// Errors on entries that aren't valid, and warnings on packages
+    /// required again with the same environment marker.
+    pub fn check(contents: &str) -> Vec<Diagnostic> {
+        let mut required: HashMap<(String, Option<String>), u32> = HashMap::new();
+        let mut diagnostics = Vec::new();
+        for entry in entries(contents) {
+            let range = lsp_types::Range::new(
+                Position::new(entry.line, entry.start),
+                Position::new(entry.line, entry.end),
+            );
+            let (severity, message) = match parse_line(&entry.text) {
+                Ok(Line::Requirement(requirement)) => {
+                    let key = (normalize(&requirement.name), requirement.marker);
+                    match required.get(&key) {
+                        Some(line) => (
+                            DiagnosticSeverity::WARNING,
+                            format!(
+                                "`{}` is already required on line {}",
+                                requirement.name,
+                                line + 1
+                            ),
+                        ),
+                        None => {
+                            required.insert(key, entry.line);
+                            continue;
+                        }
+                    }
+                }
+                Ok(_) => continue,
+                Err(message) => (DiagnosticSeverity::ERROR, message),
+            };
+            diagnostics.push(Diagnostic::new(
+                range,
+                Some(severity),
+                None,
+                Some("requirements".to_string()),
+                message,
+                None,
+                None,
+            ));
+        }
+        diagnostics
+    }
+}
+
+impl Handler for Requirements {
+    fn filetype_supported(&self, filetype: &str) -> bool {
+        filetype == "requirements"
+    }
+
+    fn path_supported(&self, path: &Path) -> bool {
+        Self::is_requirements_file(path)
+    }
+
+    fn get_capabilities(&self) -> ServerCapabilities {
+        ServerCapabilities {
+            hover_provider: Some(HoverProviderCapability::Simple(true)),
+            document_link_provider: Some(DocumentLinkOptions {
+                resolve_provider: Some(false),
+                work_done_progress_options: Default::default(),
+            }),
+            ..Default::default()
+        }
+    }
+
+    async fn update_diagnostics(
+        &mut self,
+        contents: &str,
+    ) -> Result<Vec<Diagnostic>, HandlerError> {
+        Ok(Self::check(contents))
+    }
+
+    fn hover(
+        &self,
+        _filetype: &str,
+        _context: &DocumentContext,
+        contents: &str,
+        position: Position,
+    ) -> Result<Option<Hover>, HandlerError> {
+        let Some(entry) = entries(contents).into_iter().find(|entry| {
+            entry.line == position.line && (entry.start..=entry.end).contains(&position.character)
+        }) else {
+            return Ok(None);
+        };
+        let Ok(Line::Requirement(requirement)) = parse_line(&entry.text) else {
+            return Ok(None);
+        };
+
+        let mut value = format!("**{}**\n\n", requirement.name);
+        match (&requirement.url, requirement.specifiers.is_empty()) {
+            (Some(url), _) => value.push_str(&format!("From `{url}`")),
+            (None, true) => value.push_str("Any version"),
+            (None, false) => {
+                value.push_str(&format!("Version `{}`", requirement.specifiers.join(", ")))
+            }
+        }
+        if !requirement.extras.is_empty() {
+            value.push_str(&format!(
+                "\n\nExtras: `{}`",
+                requirement.extras.join("`, `")
+            ));
+        }
+        if let Some(marker) = &requirement.marker {
+            value.push_str(&format!("\n\nOnly when `{marker}`"));
+        }
+        Ok(Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value,
+            }),
+            range: Some(lsp_types::Range::new(
+                Position::new(entry.line, entry.start),
+                Position::new(entry.line, entry.end),
+            )),
+        }))
+    }
+
+    fn document_links(&self, contents: &str, uri: &Url) -> Result<Vec<DocumentLink>, HandlerError> {
+        Ok(entries(contents)
+            .into_iter()
+            .filter_map(|entry| {
+                let Ok(Line::Include(path)) = parse_line(&entry.text) else {
+                    return None;
+                };
+                // Relative to the including file, like pip resolves them
+                let target = uri.join(&path).ok()?;
+                let line = contents.lines().nth(entry.line as usize)?;
+                let start = line.rfind(path.as_str())?;
+                let character = |offset: usize| line[..offset].encode_utf16().count() as u32;
+                Some(DocumentLink {
+                    range: lsp_types::Range::new(
+                        Position::new(entry.line, character(start)),
+                        Position::new(entry.line, character(start + path.len())),
+                    ),
+                    target: Some(target),
+                    tooltip: None,
+                    data: None,
+                })
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_requirement, Requirement, Requirements};
+    use crate::handlers::{DocumentContext, Handler};
+    use std::path::Path;
+    use tower_lsp::lsp_types::{DiagnosticSeverity, HoverContents, Position, Range, Url};
+
+    #[test]
+    fn test_parse_requirement() {
+        assert_eq!(
+            parse_requirement("requests[socks, security] >=2.31,<3 ; python_version >= \"3.8\""),
+            Ok(Requirement {
+                name: "requests".to_string(),
+                extras: vec!["socks".to_string(), "security".to_string()],
+                specifiers: vec![">=2.31".to_string(), "<3".to_string()],
+                url: None,
+                marker: Some("python_version >= \"3.8\"".to_string()),
+            })
+        );
+        assert_eq!(
+            parse_requirement("pip @ https://github.com/pypa/pip/archive/22.0.2.zip")
+                .unwrap()
+                .url
+                .as_deref(),
+            Some("https://github.com/pypa/pip/archive/22.0.2.zip")
+        );
+        assert_eq!(
+            parse_requirement("django~=4.*"),
+            Err("Invalid version specifier `~=4.*`".to_string())
+        );
+    }
+
+    #[test]
+    fn test_malformed_specifier() {
+        let contents = "# Web\n-r base.txt\n-e ./libs/shared\n./wheels/tool-1.0-py3-none-any.whl\nflask==3.0.0 \\\n    --hash=sha256:abc123\ndjango=4.2  # pinned\n";
+        let diagnostics = Requirements::check(contents);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].message, "Invalid version specifier `=4.2`");
+        assert_eq!(
+            diagnostics[0].range,
+            Range::new(Position::new(6, 0), Position::new(6, 10))
+        );
+        assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::ERROR));
+    }
+
+    #[test]
+    fn test_duplicate_package() {
+        // Names are compared normalized, and requirements for other
+        // environments aren't duplicates
+        let contents = "Django>=4.2\nrequests\nimportlib-metadata; python_version < \"3.10\"\nimportlib_metadata\ndjango==5.0\n";
+        let diagnostics = Requirements::check(contents);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].message,
+            "`django` is already required on line 1"
+        );
+        assert_eq!(diagnostics[0].range.start, Position::new(4, 0));
+        assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::WARNING));
+    }
+
+    #[test]
+    fn test_hover_and_links() {
+        let contents = "-r base.txt\nrequests[socks]>=2.31,<3; sys_platform == 'linux'\n";
+        let uri = Url::parse("file:///app/requirements/dev.txt").unwrap();
+        let context = DocumentContext::new(uri.clone(), vec![]);
+        let hover = Requirements::new()
+            .unwrap()
+            .hover("requirements", &context, contents, Position::new(1, 3))
+            .ok()
+            .unwrap()
+            .unwrap();
+        let HoverContents::Markup(markup) = hover.contents else {
+            panic!("Expected markup");
+        };
+        assert_eq!(
+            markup.value,
+            "**requests**\n\nVersion `>=2.31, <3`\n\nExtras: `socks`\n\nOnly when `sys_platform == 'linux'`"
+        );
+
+        let links = Requirements::new()
+            .unwrap()
+            .document_links(contents, &uri)
+            .ok()
+            .unwrap();
+        assert_eq!(links.len(), 1);
+        assert_eq!(
+            links[0].target.as_ref().map(Url::as_str),
+            Some("file:///app/requirements/base.txt")
+        );
+        assert_eq!(
+            links[0].range,
+            Range::new(Position::new(0, 3), Position::new(0, 11))
+        );
+        assert!(Requirements::is_requirements_file(Path::new(
+            "/app/requirements/dev.txt"
+        )));
+        assert!(Requirements::is_requirements_file(Path::new(
+            "/app/requirements-test.txt"
+        )));
+        assert!(!Requirements::is_requirements_file(Path::new(
+            "/app/notes.txt"
+        )));
+    }
+}
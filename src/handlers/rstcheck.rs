@@ -0,0 +1,86 @@
+use lazy_regex::regex;
+use std::process::Command;
+use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity};
+
+use super::process::{probe, run_and_parse, InputMode, RegexLineParser, Stream};
+use super::{Handler, HandlerError};
+
+/// reStructuredText checks with rstcheck, including the syntax of code
+/// blocks.
+#[derive(Debug)]
+pub struct Rstcheck {}
+
+/// Maps the docutils level number of a message to its severity.
+fn parse_severity(level: &str) -> Option<DiagnosticSeverity> {
+    match level {
+        "0" => Some(DiagnosticSeverity::HINT),
+        "1" => Some(DiagnosticSeverity::INFORMATION),
+        "2" => Some(DiagnosticSeverity::WARNING),
+        _ => Some(DiagnosticSeverity::ERROR),
+    }
+}
+
+impl Rstcheck {
+    pub fn new() -> Result<Self, String> {
+        probe("rstcheck", &["--version"])?;
+        Ok(Self {})
+    }
+
+    /// Parses `-:line: (LEVEL/N) message` messages, which have no column.
+    fn parser() -> RegexLineParser {
+        RegexLineParser {
+            regex: regex!(
+                r#"(?m)^(?P<path>[^:\n]+):(?P<line>\d+): \([A-Z]+/(?P<severity>\d)\) (?P<message>.*)$"#
+            ),
+            source: "rstcheck",
+            stream: Stream::Stderr,
+            first_line: 1,
+            first_column: 1,
+            severity: parse_severity,
+        }
+    }
+}
+
+impl Handler for Rstcheck {
+    fn filetype_supported(&self, filetype: &str) -> bool {
+        matches!(filetype, "rst" | "restructuredtext")
+    }
+
+    async fn update_diagnostics(
+        &mut self,
+        contents: &str,
+    ) -> Result<Vec<Diagnostic>, HandlerError> {
+        let messages = run_and_parse(
+            Command::new("rstcheck").arg("-"),
+            InputMode::Stdin(contents),
+            &Self::parser(),
+        )?;
+        Ok(messages
+            .into_iter()
+            .map(|(_, diagnostic)| diagnostic)
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Rstcheck;
+    use tower_lsp::lsp_types::{DiagnosticSeverity, Position};
+
+    #[test]
+    fn test_parse_title_underline() {
+        let stderr = "-:4: (WARNING/2) Title underline too short.\n-:9: (ERROR/3) Unknown directive type \"note2\".\nError! Issues detected.\n";
+        let messages = Rstcheck::parser().parse_text(stderr);
+        assert_eq!(messages.len(), 2);
+
+        let (path, warning) = &messages[0];
+        assert_eq!(path.as_deref(), Some("-"));
+        assert_eq!(warning.range.start, Position::new(3, 0));
+        assert_eq!(warning.severity, Some(DiagnosticSeverity::WARNING));
+        assert_eq!(warning.message, "Title underline too short.");
+
+        let (_, error) = &messages[1];
+        assert_eq!(error.range.start, Position::new(8, 0));
+        assert_eq!(error.severity, Some(DiagnosticSeverity::ERROR));
+    }
+}
@@ -0,0 +1,204 @@
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity, Url};
+
+use super::process::{
+    probe, run_and_parse, traverse_parents, InputMode, JsonArrayParser, JsonDiagnostic,
+    ToolDiagnostic,
+};
+use super::{DocumentContext, Handler, HandlerError};
+
+/// Python linting with ruff, using the project's ruff settings.
+#[derive(Debug)]
+pub struct Ruff {}
+
+/// Files ruff reads settings from, in order of precedence within a
+/// directory.
+const CONFIG_FILES: &[&str] = &[".ruff.toml", "ruff.toml", "pyproject.toml"];
+
+#[derive(Debug, Deserialize)]
+struct Location {
+    row: u32,
+    column: u32,
+}
+
+/// A diagnostic of `ruff check --output-format json`.
+#[derive(Debug, Deserialize)]
+struct RuffDiagnostic {
+    /// The rule, `None` for syntax errors.
+    code: Option<String>,
+    message: String,
+    location: Location,
+    end_location: Location,
+}
+
+impl JsonDiagnostic for RuffDiagnostic {
+    fn into_diagnostic(self) -> Option<ToolDiagnostic> {
+        // Syntax errors have no rule
+        let severity = match self.code {
+            Some(_) => "warning",
+            None => "error",
+        };
+        Some(ToolDiagnostic {
+            line: self.location.row,
+            column: self.location.column,
+            end: Some((self.end_location.row, self.end_location.column)),
+            severity: Some(severity.to_string()),
+            code: self.code,
+            message: self.message,
+        })
+    }
+}
+
+impl Ruff {
+    pub fn new() -> Result<Self, String> {
+        probe("ruff", &["--version"])?;
+        Ok(Self {})
+    }
+
+    /// The settings file closest to `directory`. `pyproject.toml` files
+    /// without a `[tool.ruff]` table are skipped, like ruff does.
+    pub fn find_config(directory: &Path, root_markers: &[String]) -> Option<PathBuf> {
+        traverse_parents(directory, CONFIG_FILES, root_markers, |path| {
+            !path.ends_with("pyproject.toml")
+                || std::fs::read_to_string(path)
+                    .is_ok_and(|contents| contents.contains("[tool.ruff"))
+        })
+    }
+
+    /// Checks the document from stdin, named after the document so rules
+    /// and per-file ignores apply to it, from the project directory.
+    fn command(context: &DocumentContext) -> Command {
+        let mut command = Command::new("ruff");
+        command
+            .arg("check")
+            .arg("--output-format")
+            .arg("json")
+            .arg("--no-fix")
+            .arg("--stdin-filename");
+        match context.uri.to_file_path() {
+            Ok(path) => command.arg(path),
+            Err(_) => command.arg("untitled.py"),
+        };
+
+        let config = context
+            .directory()
+            .and_then(|directory| Self::find_config(&directory, &context.root_markers));
+        if let Some(config) = &config {
+            command.arg("--config").arg(config);
+        }
+        if let Some(directory) = config
+            .as_deref()
+            .and_then(Path::parent)
+            .map(Path::to_path_buf)
+            .or_else(|| context.directory())
+        {
+            command.current_dir(directory);
+        }
+        command.arg("-");
+        command
+    }
+
+    fn parser() -> JsonArrayParser<RuffDiagnostic> {
+        // ruff reports 1-based positions
+        JsonArrayParser::new("ruff").severities(&[("error", DiagnosticSeverity::ERROR)])
+    }
+}
+
+impl Handler for Ruff {
+    fn filetype_supported(&self, filetype: &str) -> bool {
+        filetype == "python"
+    }
+
+    async fn update_diagnostics(
+        &mut self,
+        contents: &str,
+    ) -> Result<Vec<Diagnostic>, HandlerError> {
+        let uri = Url::parse("untitled:untitled.py").expect("Valid URL");
+        let context = DocumentContext::new(uri, Vec::new());
+        self.update_diagnostics_with_context(&context, contents)
+            .await
+    }
+
+    async fn update_diagnostics_with_context(
+        &mut self,
+        context: &DocumentContext,
+        contents: &str,
+    ) -> Result<Vec<Diagnostic>, HandlerError> {
+        run_and_parse(
+            &mut Self::command(context),
+            InputMode::Stdin(contents),
+            &Self::parser(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Ruff;
+    use crate::handlers::DocumentContext;
+    use std::ffi::OsStr;
+    use tower_lsp::lsp_types::{NumberOrString, Position, Url};
+
+    #[test]
+    fn test_find_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let package = dir.path().join("project/src/package");
+        std::fs::create_dir_all(&package).unwrap();
+        std::fs::write(dir.path().join("project/ruff.toml"), "line-length = 100\n").unwrap();
+        // Without ruff settings
+        std::fs::write(
+            dir.path().join("project/src/pyproject.toml"),
+            "[project]\nname = \"package\"\n",
+        )
+        .unwrap();
+
+        let config = dir.path().join("project/ruff.toml");
+        assert_eq!(Ruff::find_config(&package, &[]), Some(config.clone()));
+
+        let uri = Url::from_file_path(package.join("main.py")).unwrap();
+        let command = Ruff::command(&DocumentContext::new(uri, vec![]));
+        let args: Vec<&OsStr> = command.get_args().collect();
+        let config_arg = args.iter().position(|arg| *arg == "--config").unwrap();
+        assert_eq!(args[config_arg + 1], config.as_os_str());
+        assert!(args.contains(&package.join("main.py").as_os_str()));
+        assert_eq!(
+            command.get_current_dir(),
+            Some(dir.path().join("project").as_path())
+        );
+    }
+
+    #[test]
+    fn test_parse() {
+        let stdout = r#"[
+  {
+    "cell": null,
+    "code": "F401",
+    "end_location": { "column": 10, "row": 1 },
+    "filename": "/project/main.py",
+    "fix": null,
+    "location": { "column": 8, "row": 1 },
+    "message": "`os` imported but unused",
+    "noqa_row": 1,
+    "url": "https://docs.astral.sh/ruff/rules/unused-import"
+  },
+  {
+    "code": null,
+    "end_location": { "column": 1, "row": 4 },
+    "location": { "column": 5, "row": 3 },
+    "message": "SyntaxError: Expected an expression"
+  }
+]"#;
+        let diagnostics = Ruff::parser().parse_text(stdout).ok().unwrap();
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].range.start, Position::new(0, 7));
+        assert_eq!(diagnostics[0].range.end, Position::new(0, 9));
+        assert_eq!(
+            diagnostics[0].code,
+            Some(NumberOrString::String("F401".to_string()))
+        );
+        assert!(diagnostics[1].code.is_none());
+        assert!(Ruff::parser().parse_text("not json").is_err());
+    }
+}
@@ -0,0 +1,214 @@
+use lazy_regex::regex;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity, OneOf, ServerCapabilities};
+
+use super::process::{
+    format_stdin, probe, run_and_parse, traverse_parents, InputMode, RegexLineParser, Stream,
+    TempFileStrategy, TempFiles,
+};
+use super::{DocumentContext, Handler, HandlerError};
+
+/// Scala formatting with scalafmt, and linting with scalafix for projects
+/// with a `.scalafix.conf`.
+#[derive(Debug)]
+pub struct Scala {
+    temp_files: TempFiles,
+    /// Whether `scalafix` is available.
+    scalafix: bool,
+}
+
+fn parse_severity(severity: &str) -> Option<DiagnosticSeverity> {
+    match severity {
+        "error" => Some(DiagnosticSeverity::ERROR),
+        "warning" => Some(DiagnosticSeverity::WARNING),
+        _ => Some(DiagnosticSeverity::INFORMATION),
+    }
+}
+
+impl Scala {
+    pub fn new() -> Result<Self, String> {
+        probe("scalafmt", &["--version"])?;
+        Ok(Self {
+            temp_files: TempFiles::with_suffix(".scala"),
+            scalafix: probe("scalafix", &["--version"]).is_ok(),
+        })
+    }
+
+    /// The settings file named `name` closest to the document.
+    pub fn find_config(context: &DocumentContext, name: &str) -> Option<PathBuf> {
+        traverse_parents(
+            &context.directory()?,
+            &[name],
+            &context.root_markers,
+            |_| true,
+        )
+    }
+
+    /// Formats stdin with the project's `.scalafmt.conf`, run from the
+    /// project directory.
+    fn format_command(context: &DocumentContext) -> Command {
+        let mut command = Command::new("scalafmt");
+        command.arg("--stdin").arg("--stdout").arg("--quiet");
+        if let Some(config) = Self::find_config(context, ".scalafmt.conf") {
+            command.arg("--config").arg(&config);
+            if let Some(directory) = config.parent() {
+                command.current_dir(directory);
+            }
+        }
+        command
+    }
+
+    /// Parses `path:line:column: severity: [Rule] message` lint messages.
+    fn parser() -> RegexLineParser {
+        RegexLineParser {
+            regex: regex!(
+                r#"(?m)^(?P<path>[^:\n]+):(?P<line>\d+):(?P<column>\d+): (?P<severity>error|warning|info): (?:\[(?P<code>[^\]]+)\] )?(?P<message>.*)$"#
+            ),
+            source: "scalafix",
+            stream: Stream::Stderr,
+            first_line: 1,
+            first_column: 1,
+            severity: parse_severity,
+        }
+    }
+}
+
+impl Handler for Scala {
+    fn filetype_supported(&self, filetype: &str) -> bool {
+        filetype == "scala"
+    }
+
+    fn set_temp_file_strategy(&mut self, strategy: TempFileStrategy) {
+        self.temp_files.set_strategy(strategy);
+    }
+
+    fn get_capabilities(&self) -> ServerCapabilities {
+        ServerCapabilities {
+            document_formatting_provider: Some(OneOf::Left(true)),
+            ..Default::default()
+        }
+    }
+
+    async fn update_diagnostics(
+        &mut self,
+        _contents: &str,
+    ) -> Result<Vec<Diagnostic>, HandlerError> {
+        // The rules are in the project's settings
+        Ok(Vec::new())
+    }
+
+    async fn update_diagnostics_with_context(
+        &mut self,
+        context: &DocumentContext,
+        contents: &str,
+    ) -> Result<Vec<Diagnostic>, HandlerError> {
+        if !self.scalafix {
+            return Ok(Vec::new());
+        }
+        let Some(config) = Self::find_config(context, ".scalafix.conf") else {
+            return Ok(Vec::new());
+        };
+        let temp_file = self.temp_files.write(contents)?;
+
+        // Prints fixes instead of applying them, only syntactic rules run
+        // without a compiled project
+        let mut command = Command::new("scalafix");
+        command
+            .arg("--syntactic")
+            .arg("--stdout")
+            .arg("--config")
+            .arg(&config)
+            .arg(temp_file.path());
+        if let Some(directory) = config.parent() {
+            command.current_dir(directory);
+        }
+        let messages = run_and_parse(&mut command, InputMode::File, &Self::parser())?;
+        Ok(messages
+            .into_iter()
+            .filter(|(path, _)| {
+                path.as_deref().map(|path| Path::new(path).file_name())
+                    == Some(temp_file.path().file_name())
+            })
+            .map(|(_, diagnostic)| diagnostic)
+            .collect())
+    }
+
+    async fn format_with_context(
+        &mut self,
+        context: &DocumentContext,
+        _filetype: &str,
+        contents: &str,
+    ) -> Result<Option<String>, HandlerError> {
+        format_stdin(&mut Self::format_command(context), contents)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Scala;
+    use crate::handlers::{DocumentContext, Handler};
+    use std::ffi::OsStr;
+    use tower_lsp::lsp_types::{DiagnosticSeverity, NumberOrString, Position, Url};
+
+    #[test]
+    fn test_find_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let package = dir.path().join("project/src/main/scala");
+        std::fs::create_dir_all(&package).unwrap();
+        let config = dir.path().join("project/.scalafmt.conf");
+        std::fs::write(&config, "version = 3.7.17\nmaxColumn = 100\n").unwrap();
+
+        let uri = Url::from_file_path(package.join("Main.scala")).unwrap();
+        let context = DocumentContext::new(uri, vec![]);
+        assert_eq!(
+            Scala::find_config(&context, ".scalafmt.conf"),
+            Some(config.clone())
+        );
+        assert_eq!(Scala::find_config(&context, ".scalafix.conf"), None);
+
+        let command = Scala::format_command(&context);
+        let args: Vec<&OsStr> = command.get_args().collect();
+        let config_arg = args.iter().position(|arg| *arg == "--config").unwrap();
+        assert_eq!(args[config_arg + 1], config.as_os_str());
+        assert_eq!(
+            command.get_current_dir(),
+            Some(dir.path().join("project").as_path())
+        );
+    }
+
+    #[test]
+    fn test_parse_lint() {
+        let stderr = "/tmp/.tmpAbC123.scala:3:3: error: [DisableSyntax.var] mutable state should be avoided\n  var count = 0\n  ^^^\n";
+        let messages = Scala::parser().parse_text(stderr);
+        assert_eq!(messages.len(), 1);
+        let (path, diagnostic) = &messages[0];
+        assert_eq!(path.as_deref(), Some("/tmp/.tmpAbC123.scala"));
+        assert_eq!(diagnostic.range.start, Position::new(2, 2));
+        assert_eq!(diagnostic.severity, Some(DiagnosticSeverity::ERROR));
+        assert_eq!(
+            diagnostic.code,
+            Some(NumberOrString::String("DisableSyntax.var".to_string()))
+        );
+        assert_eq!(diagnostic.message, "mutable state should be avoided");
+    }
+
+    #[tokio::test]
+    #[ignore = "needs scalafmt"]
+    async fn test_format() {
+        let mut scala = Scala::new().unwrap();
+
+        let uri = Url::parse("untitled:Untitled-1").unwrap();
+        let context = DocumentContext::new(uri, vec![]);
+        let contents = "class Point(val x:Int,val y:Int){def norm=x*x+y*y}\n";
+        let formatted = scala
+            .format_with_context(&context, "scala", contents)
+            .await
+            .ok()
+            .unwrap();
+        assert_eq!(
+            formatted.as_deref(),
+            Some("class Point(val x: Int, val y: Int) { def norm = x * x + y * y }\n")
+        );
+    }
+}
@@ -0,0 +1,170 @@
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity};
+
+use super::process::{
+    probe, run_with_stdin, traverse_parents, JsonArrayParser, JsonDiagnostic, ToolDiagnostic,
+};
+use super::{DocumentContext, Handler, HandlerError};
+
+/// Lua linting with selene, an alternative to luacheck.
+#[derive(Debug)]
+pub struct Selene {}
+
+/// The file selene reads its settings from, in the directory it runs in.
+const CONFIG_FILE: &str = "selene.toml";
+
+#[derive(Debug, Deserialize)]
+struct Span {
+    start_line: u32,
+    start_column: u32,
+    end_line: u32,
+    end_column: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct Label {
+    span: Span,
+}
+
+/// A line of `selene --display-style=json`. Summaries of newer versions
+/// have no label.
+#[derive(Debug, Deserialize)]
+struct Finding {
+    severity: Option<String>,
+    code: Option<String>,
+    message: Option<String>,
+    primary_label: Option<Label>,
+}
+
+impl JsonDiagnostic for Finding {
+    fn into_diagnostic(self) -> Option<ToolDiagnostic> {
+        let span = self.primary_label?.span;
+        Some(ToolDiagnostic {
+            line: span.start_line,
+            column: span.start_column,
+            end: Some((span.end_line, span.end_column)),
+            severity: self.severity,
+            code: self.code,
+            message: self.message?,
+        })
+    }
+}
+
+impl Selene {
+    pub fn new() -> Result<Self, String> {
+        probe("selene", &["--version"])?;
+        Ok(Self {})
+    }
+
+    /// The settings file closest to `directory`.
+    pub fn find_config(directory: &Path, root_markers: &[String]) -> Option<PathBuf> {
+        traverse_parents(directory, &[CONFIG_FILE], root_markers, |_| true)
+    }
+
+    /// Checks the document from stdin, from the directory of the settings
+    /// or else of the document.
+    fn command(context: &DocumentContext) -> Command {
+        let mut command = Command::new("selene");
+        command.arg("--display-style=json").arg("-");
+        let directory = context.directory();
+        if let Some(directory) = directory
+            .as_deref()
+            .and_then(|directory| Self::find_config(directory, &context.root_markers))
+            .as_deref()
+            .and_then(Path::parent)
+            .or(directory.as_deref())
+        {
+            command.current_dir(directory);
+        }
+        command
+    }
+
+    fn parser() -> JsonArrayParser<Finding> {
+        // selene reports 0-based positions
+        JsonArrayParser::new("selene")
+            .first_line(0)
+            .first_column(0)
+            .severities(&[("error", DiagnosticSeverity::ERROR)])
+    }
+
+    /// The diagnostics of the JSON objects of `output`, one per line.
+    pub fn parse(output: &str) -> Vec<Diagnostic> {
+        let findings = output
+            .lines()
+            .filter_map(|line| serde_json::from_str::<Finding>(line).ok())
+            .collect();
+        Self::parser().diagnostics(findings)
+    }
+}
+
+impl Handler for Selene {
+    fn filetype_supported(&self, filetype: &str) -> bool {
+        filetype == "lua"
+    }
+
+    async fn update_diagnostics_with_context(
+        &mut self,
+        context: &DocumentContext,
+        contents: &str,
+    ) -> Result<Vec<Diagnostic>, HandlerError> {
+        let out = run_with_stdin(&mut Self::command(context), contents)?;
+        // selene exits with an error when there are problems
+        let stdout = String::from_utf8_lossy(&out.stdout);
+        if stdout.trim().is_empty() && !out.status.success() {
+            return Err(HandlerError::Log(
+                String::from_utf8_lossy(&out.stderr).into_owned(),
+            ));
+        }
+        Ok(Self::parse(&stdout))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Selene;
+    use crate::handlers::DocumentContext;
+    use tower_lsp::lsp_types::{DiagnosticSeverity, NumberOrString, Position, Range, Url};
+
+    #[test]
+    fn test_parse_unused_variable() {
+        let output = r#"{"severity":"Warning","code":"unused_variable","message":"count is assigned a value, but never used","primary_label":{"span":{"start":28,"start_line":2,"start_column":6,"end":33,"end_line":2,"end_column":11},"message":""},"notes":[],"secondary_labels":[]}
+{"severity":"Error","code":"parse_error","message":"unexpected token `end`","primary_label":{"span":{"start":60,"start_line":5,"start_column":0,"end":63,"end_line":5,"end_column":3},"message":"unexpected token"},"notes":[],"secondary_labels":[]}
+Results:
+1 errors
+1 warnings
+"#;
+        let diagnostics = Selene::parse(output);
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(
+            diagnostics[0].range,
+            Range::new(Position::new(2, 6), Position::new(2, 11))
+        );
+        assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::WARNING));
+        assert_eq!(
+            diagnostics[0].code,
+            Some(NumberOrString::String("unused_variable".to_string()))
+        );
+        assert_eq!(
+            diagnostics[0].message,
+            "count is assigned a value, but never used"
+        );
+        assert_eq!(diagnostics[1].severity, Some(DiagnosticSeverity::ERROR));
+    }
+
+    #[test]
+    fn test_command_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let scripts = dir.path().join("game/scripts");
+        std::fs::create_dir_all(&scripts).unwrap();
+        std::fs::write(dir.path().join("game/selene.toml"), "std = \"lua51\"\n").unwrap();
+
+        let uri = Url::from_file_path(scripts.join("main.lua")).unwrap();
+        let command = Selene::command(&DocumentContext::new(uri, vec![]));
+        assert_eq!(
+            command.get_current_dir(),
+            Some(dir.path().join("game").as_path())
+        );
+    }
+}
@@ -0,0 +1,99 @@
+use std::process::Command;
+use tower_lsp::lsp_types::{OneOf, ServerCapabilities};
+
+use super::process::{format_stdin, probe};
+use super::{Handler, HandlerError};
+
+/// Formats Svelte and Vue single-file components with Prettier.
+///
+/// The components are passed whole to Prettier, with a file path telling it
+/// which parser to use, so the `<script>`/`<style>`/template sections are
+/// each formatted in their own language. Svelte requires
+/// `prettier-plugin-svelte` to be installed.
+#[derive(Debug)]
+pub struct Sfc {}
+
+impl Sfc {
+    pub fn new() -> Result<Self, String> {
+        probe("prettier", &["--version"])?;
+        Ok(Self {})
+    }
+
+    /// Path passed to `--stdin-filepath`, Prettier picks its parser by the
+    /// extension.
+    fn stdin_filepath(filetype: &str) -> Option<&'static str> {
+        match filetype {
+            "svelte" => Some("component.svelte"),
+            "vue" => Some("component.vue"),
+            _ => None,
+        }
+    }
+}
+
+impl Handler for Sfc {
+    fn filetype_supported(&self, filetype: &str) -> bool {
+        Self::stdin_filepath(filetype).is_some()
+    }
+
+    fn get_capabilities(&self) -> ServerCapabilities {
+        ServerCapabilities {
+            document_formatting_provider: Some(OneOf::Left(true)),
+            ..Default::default()
+        }
+    }
+
+    async fn format(
+        &mut self,
+        filetype: &str,
+        contents: &str,
+    ) -> Result<Option<String>, HandlerError> {
+        let Some(filepath) = Self::stdin_filepath(filetype) else {
+            return Ok(None);
+        };
+
+        format_stdin(
+            Command::new("prettier")
+                .arg("--stdin-filepath")
+                .arg(filepath),
+            contents,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Sfc;
+    use crate::handlers::Handler;
+
+    #[test]
+    fn test_filetype_supported() {
+        assert_eq!(Sfc::stdin_filepath("svelte"), Some("component.svelte"));
+        assert_eq!(Sfc::stdin_filepath("vue"), Some("component.vue"));
+        assert_eq!(Sfc::stdin_filepath("html"), None);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs prettier with prettier-plugin-svelte"]
+    async fn test_format_svelte() {
+        let mut sfc = Sfc::new().unwrap();
+
+        let component = r#"<script>
+let count=0
+</script>
+
+<button on:click={()=>count+=1}>{count}</button>
+
+<style>
+button{color:red}
+</style>
+"#;
+        let formatted = sfc.format("svelte", component).await.ok().unwrap().unwrap();
+
+        assert!(formatted.contains("let count = 0;"));
+        assert!(formatted.contains("color: red;"));
+        let script = formatted.find("</script>").unwrap();
+        let button = formatted.find("<button").unwrap();
+        let style = formatted.find("<style>").unwrap();
+        assert!(script < button && button < style);
+    }
+}
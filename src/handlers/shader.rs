@@ -0,0 +1,170 @@
+use lazy_regex::regex;
+use std::path::Path;
+use std::process::Command;
+use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity, Url};
+
+use super::process::{probe, run_and_parse, InputMode, RegexLineParser, Stream};
+use super::{DocumentContext, Handler, HandlerError};
+
+/// Shader validation, of WGSL with naga and of GLSL with glslangValidator.
+/// Either tool is enough for the handler to run for its language.
+#[derive(Debug)]
+pub struct Shader {
+    /// Whether `naga` is available.
+    naga: bool,
+    /// Whether `glslangValidator` is available.
+    glslang: bool,
+}
+
+fn parse_severity(severity: &str) -> Option<DiagnosticSeverity> {
+    match severity {
+        "WARNING" | "warning" => Some(DiagnosticSeverity::WARNING),
+        _ => Some(DiagnosticSeverity::ERROR),
+    }
+}
+
+/// The shader stage glslangValidator compiles a document as, by the
+/// conventional extensions. Fragment shaders when the extension doesn't
+/// tell.
+fn glsl_stage(path: &Path) -> &'static str {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("vert") => "vert",
+        Some("geom") => "geom",
+        Some("tesc") => "tesc",
+        Some("tese") => "tese",
+        Some("comp") => "comp",
+        _ => "frag",
+    }
+}
+
+impl Shader {
+    pub fn new() -> Result<Self, String> {
+        let naga = probe("naga", &["--version"]);
+        let glslang = probe("glslangValidator", &["--version"]);
+        if let (Err(naga), Err(glslang)) = (&naga, &glslang) {
+            return Err(format!("{naga}, {glslang}"));
+        }
+        Ok(Self {
+            naga: naga.is_ok(),
+            glslang: glslang.is_ok(),
+        })
+    }
+
+    /// Parses the `error: message` line of naga errors and the
+    /// `┌─ path:line:column` line pointing at them.
+    fn naga_parser() -> RegexLineParser {
+        RegexLineParser {
+            regex: regex!(
+                r#"(?m)^(?P<severity>error|warning): (?P<message>.*)\n\s*┌─ (?P<path>[^\n]*?):(?P<line>\d+):(?P<column>\d+)$"#
+            ),
+            source: "naga",
+            stream: Stream::Stderr,
+            first_line: 1,
+            first_column: 1,
+            severity: parse_severity,
+        }
+    }
+
+    /// Parses `ERROR: 0:line: message` messages, the 0 being the index of
+    /// the source read from stdin. Some versions add a column.
+    fn glslang_parser() -> RegexLineParser {
+        RegexLineParser {
+            regex: regex!(
+                r#"(?m)^(?P<severity>ERROR|WARNING): (?P<path>[^:\n]*):(?P<line>\d+):(?:(?P<column>\d+):)? (?P<message>.*)$"#
+            ),
+            source: "glslang",
+            stream: Stream::Stdout,
+            first_line: 1,
+            first_column: 1,
+            severity: parse_severity,
+        }
+    }
+}
+
+impl Handler for Shader {
+    fn filetype_supported(&self, filetype: &str) -> bool {
+        match filetype {
+            "wgsl" => self.naga,
+            "glsl" => self.glslang,
+            _ => false,
+        }
+    }
+
+    async fn update_diagnostics(
+        &mut self,
+        contents: &str,
+    ) -> Result<Vec<Diagnostic>, HandlerError> {
+        let uri = Url::parse("untitled:untitled.glsl").expect("Valid URL");
+        let context = DocumentContext::new(uri, Vec::new());
+        self.update_diagnostics_with_context(&context, contents)
+            .await
+    }
+
+    async fn update_diagnostics_with_context(
+        &mut self,
+        context: &DocumentContext,
+        contents: &str,
+    ) -> Result<Vec<Diagnostic>, HandlerError> {
+        // The filetype isn't known here, WGSL has a single extension
+        let path = Path::new(context.uri.path());
+        let wgsl = path.extension().is_some_and(|ext| ext == "wgsl");
+        let (mut command, parser) = if wgsl && self.naga {
+            let mut command = Command::new("naga");
+            command.arg("--stdin-file-path").arg("shader.wgsl").arg("-");
+            (command, Self::naga_parser())
+        } else if !wgsl && self.glslang {
+            let mut command = Command::new("glslangValidator");
+            command.arg("--stdin").arg("-S").arg(glsl_stage(path));
+            (command, Self::glslang_parser())
+        } else {
+            return Ok(Vec::new());
+        };
+
+        let messages = run_and_parse(&mut command, InputMode::Stdin(contents), &parser)?;
+        Ok(messages
+            .into_iter()
+            .map(|(_, diagnostic)| diagnostic)
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{glsl_stage, Shader};
+    use std::path::Path;
+    use tower_lsp::lsp_types::{DiagnosticSeverity, Position};
+
+    #[test]
+    fn test_parse_wgsl_type_error() {
+        let stderr = "error: the type of `x` is expected to be `f32`, but got `i32`
+  ┌─ shader.wgsl:3:18
+  │
+3 │     let x: f32 = 1i;
+  │                  ^^ definition of `x`
+
+Could not parse WGSL
+";
+        let messages = Shader::naga_parser().parse_text(stderr);
+        assert_eq!(messages.len(), 1);
+        let (path, error) = &messages[0];
+        assert_eq!(path.as_deref(), Some("shader.wgsl"));
+        assert_eq!(error.range.start, Position::new(2, 17));
+        assert_eq!(error.severity, Some(DiagnosticSeverity::ERROR));
+        assert_eq!(
+            error.message,
+            "the type of `x` is expected to be `f32`, but got `i32`"
+        );
+    }
+
+    #[test]
+    fn test_parse_glsl_error() {
+        let stdout = "stdin\nERROR: 0:4: 'colour' : undeclared identifier \nERROR: 1 compilation errors.  No code generated.\n";
+        let messages = Shader::glslang_parser().parse_text(stdout);
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].1.range.start, Position::new(3, 0));
+        assert_eq!(messages[0].1.message, "'colour' : undeclared identifier ");
+
+        assert_eq!(glsl_stage(Path::new("/shaders/light.vert")), "vert");
+        assert_eq!(glsl_stage(Path::new("/shaders/light.glsl")), "frag");
+    }
+}
@@ -0,0 +1,128 @@
+use serde::Deserialize;
+use serde_json::Value;
+use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity};
+
+use super::process::{
+    probe, run_and_parse, InputMode, JsonArrayParser, JsonDiagnostic, TempFileStrategy, TempFiles,
+    ToolDiagnostic,
+};
+use super::{Handler, HandlerError};
+
+/// Solidity linting with solhint.
+#[derive(Debug)]
+pub struct Solhint {
+    temp_files: TempFiles,
+}
+
+/// A finding of `solhint --formatter json`. The report ends with a summary
+/// entry without a position.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Finding {
+    line: Option<u32>,
+    column: Option<u32>,
+    /// 2 for warnings and 3 for errors, or their names in some versions.
+    severity: Option<Value>,
+    message: Option<String>,
+    rule_id: Option<String>,
+}
+
+impl JsonDiagnostic for Finding {
+    fn into_diagnostic(self) -> Option<ToolDiagnostic> {
+        let severity = match self.severity {
+            Some(Value::Number(number)) => number.to_string(),
+            Some(Value::String(name)) => name,
+            _ => String::new(),
+        };
+        Some(ToolDiagnostic {
+            line: self.line?,
+            column: self.column.unwrap_or(1),
+            end: None,
+            severity: Some(severity),
+            code: self.rule_id,
+            message: self.message.unwrap_or_default(),
+        })
+    }
+}
+
+impl Solhint {
+    pub fn new() -> Result<Self, String> {
+        probe("solhint", &["--version"])?;
+        Ok(Self {
+            temp_files: TempFiles::with_suffix(".sol"),
+        })
+    }
+
+    fn parser() -> JsonArrayParser<Finding> {
+        // solhint reports 1-based positions
+        JsonArrayParser::new("solhint").severities(&[
+            ("3", DiagnosticSeverity::ERROR),
+            ("error", DiagnosticSeverity::ERROR),
+        ])
+    }
+}
+
+impl Handler for Solhint {
+    fn filetype_supported(&self, filetype: &str) -> bool {
+        filetype == "solidity"
+    }
+
+    fn set_temp_file_strategy(&mut self, strategy: TempFileStrategy) {
+        self.temp_files.set_strategy(strategy);
+    }
+
+    async fn update_diagnostics(
+        &mut self,
+        contents: &str,
+    ) -> Result<Vec<Diagnostic>, HandlerError> {
+        let temp_file = self.temp_files.write(contents)?;
+
+        run_and_parse(
+            std::process::Command::new("solhint")
+                .arg("--formatter")
+                .arg("json")
+                .arg(temp_file.path()),
+            InputMode::File,
+            &Self::parser(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Solhint;
+    use tower_lsp::lsp_types::{DiagnosticSeverity, NumberOrString, Position};
+
+    #[test]
+    fn test_parse_func_visibility() {
+        let stdout = r#"[
+  {
+    "line": 7,
+    "column": 5,
+    "severity": 2,
+    "message": "Explicitly mark visibility in function (Set ignoreConstructors to true if using solidity >=0.7.0)",
+    "ruleId": "func-visibility",
+    "fix": null,
+    "filePath": "/tmp/.tmpAbC123.sol"
+  },
+  {
+    "line": 12,
+    "column": 1,
+    "severity": 3,
+    "message": "Compiler version ^0.5.0 does not satisfy the ^0.8.0 semver requirement",
+    "ruleId": "compiler-version",
+    "filePath": "/tmp/.tmpAbC123.sol"
+  },
+  { "conclusion": "2 problems (1 error, 1 warning)" }
+]"#;
+        let diagnostics = Solhint::parser().parse_text(stdout).ok().unwrap();
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].range.start, Position::new(6, 4));
+        assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::WARNING));
+        assert_eq!(
+            diagnostics[0].code,
+            Some(NumberOrString::String("func-visibility".to_string()))
+        );
+        assert_eq!(diagnostics[1].severity, Some(DiagnosticSeverity::ERROR));
+    }
+}
@@ -0,0 +1,179 @@
+use lazy_regex::regex;
+use serde::Deserialize;
+use std::process::Command;
+use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity, NumberOrString, Position};
+
+use super::process::{probe, run_with_stdin, JsonArrayParser, JsonDiagnostic, ToolDiagnostic};
+use super::{DocumentContext, Handler, HandlerError};
+
+/// OpenAPI and Swagger linting with spectral, for YAML and JSON documents
+/// that are API descriptions.
+#[derive(Debug)]
+pub struct Spectral {}
+
+#[derive(Debug, Deserialize)]
+struct SpectralRange {
+    start: Position,
+    end: Position,
+}
+
+/// A result of `spectral lint --format json`.
+#[derive(Debug, Deserialize)]
+struct SpectralResult {
+    code: Option<NumberOrString>,
+    message: String,
+    /// 0 to 3, from error to hint.
+    severity: u8,
+    range: SpectralRange,
+}
+
+impl JsonDiagnostic for SpectralResult {
+    fn into_diagnostic(self) -> Option<ToolDiagnostic> {
+        let code = self.code.map(|code| match code {
+            NumberOrString::Number(number) => number.to_string(),
+            NumberOrString::String(code) => code,
+        });
+        Some(ToolDiagnostic {
+            line: self.range.start.line,
+            column: self.range.start.character,
+            end: Some((self.range.end.line, self.range.end.character)),
+            severity: Some(self.severity.to_string()),
+            code,
+            message: self.message,
+        })
+    }
+}
+
+impl Spectral {
+    pub fn new() -> Result<Self, String> {
+        probe("spectral", &["--version"])?;
+        Ok(Self {})
+    }
+
+    /// Whether the document is an API description, from its file name, e.g.
+    /// `openapi.yaml`, or its top-level `openapi`/`swagger` version key.
+    pub fn is_api_description(context: &DocumentContext, contents: &str) -> bool {
+        let named = context.uri.path_segments().and_then(|mut segments| {
+            let name = segments.next_back()?.to_lowercase();
+            Some(name.starts_with("openapi") || name.starts_with("swagger"))
+        });
+        named.unwrap_or(false)
+            || regex!(r#"(?m)^(?:openapi|swagger):|^\s*"(?:openapi|swagger)"\s*:"#)
+                .is_match(contents)
+    }
+
+    fn command(context: &DocumentContext) -> Command {
+        let mut command = Command::new("spectral");
+        command
+            .arg("lint")
+            .arg("--format")
+            .arg("json")
+            .arg("--stdin-filepath");
+        match context.uri.to_file_path() {
+            Ok(path) => command.arg(path),
+            Err(_) => command.arg("openapi.yaml"),
+        };
+        // Finds the project's `.spectral.yaml` ruleset
+        if let Some(directory) = context.directory() {
+            command.current_dir(directory);
+        }
+        command
+    }
+
+    /// Parses results with 0-based positions.
+    fn parser() -> JsonArrayParser<SpectralResult> {
+        JsonArrayParser::new("spectral")
+            .first_line(0)
+            .first_column(0)
+            .severities(&[
+                ("0", DiagnosticSeverity::ERROR),
+                ("1", DiagnosticSeverity::WARNING),
+                ("2", DiagnosticSeverity::INFORMATION),
+                ("3", DiagnosticSeverity::HINT),
+            ])
+    }
+}
+
+impl Handler for Spectral {
+    fn filetype_supported(&self, filetype: &str) -> bool {
+        matches!(filetype, "yaml" | "json")
+    }
+
+    fn contents_supported(&self, context: &DocumentContext, contents: &str) -> bool {
+        Self::is_api_description(context, contents)
+    }
+
+    async fn update_diagnostics_with_context(
+        &mut self,
+        context: &DocumentContext,
+        contents: &str,
+    ) -> Result<Vec<Diagnostic>, HandlerError> {
+        let out = run_with_stdin(&mut Self::command(context), contents)?;
+        let stdout = String::from_utf8_lossy(&out.stdout);
+        // Without results a message is printed instead of `[]`
+        if !stdout.trim_start().starts_with('[') {
+            if out.status.success() {
+                return Ok(Vec::new());
+            }
+            return Err(HandlerError::Log(
+                String::from_utf8_lossy(&out.stderr).into_owned(),
+            ));
+        }
+        Self::parser().parse_text(&stdout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Spectral;
+    use crate::handlers::DocumentContext;
+    use tower_lsp::lsp_types::{DiagnosticSeverity, NumberOrString, Position, Url};
+
+    #[test]
+    fn test_parse_oas3_schema() {
+        let stdout = r#"[
+  {
+    "code": "oas3-schema",
+    "path": ["paths", "/users", "get", "responses"],
+    "message": "\"responses\" property must not have fewer than 1 properties.",
+    "severity": 0,
+    "range": {
+      "start": { "line": 7, "character": 16 },
+      "end": { "line": 7, "character": 18 }
+    },
+    "source": "/project/openapi.yaml"
+  }
+]"#;
+        let diagnostics = Spectral::parser().parse_text(stdout).ok().unwrap();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].range.start, Position::new(7, 16));
+        assert_eq!(diagnostics[0].range.end, Position::new(7, 18));
+        assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::ERROR));
+        assert_eq!(
+            diagnostics[0].code,
+            Some(NumberOrString::String("oas3-schema".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_is_api_description() {
+        let context = |path| DocumentContext::new(Url::from_file_path(path).unwrap(), vec![]);
+        let config = context("/project/config.yaml");
+        assert!(!Spectral::is_api_description(
+            &config,
+            "name: service\nopenapi_version: 3\n"
+        ));
+        assert!(Spectral::is_api_description(
+            &config,
+            "openapi: 3.1.0\ninfo:\n  title: API\n"
+        ));
+        assert!(Spectral::is_api_description(
+            &context("/project/api.json"),
+            "{\n  \"swagger\": \"2.0\"\n}\n"
+        ));
+        assert!(Spectral::is_api_description(
+            &context("/project/openapi.yaml"),
+            ""
+        ));
+    }
+}
@@ -0,0 +1,93 @@
+use serde_json::json;
+use std::collections::HashMap;
+use tower_lsp::lsp_types::{CodeAction, CodeActionKind, Diagnostic, TextEdit, Url, WorkspaceEdit};
+
+/// Sets the corrections of a misspelled word on its `diagnostic`, kept in
+/// its `data` for the client to send back with code action requests.
+pub fn with_corrections(mut diagnostic: Diagnostic, corrections: &[String]) -> Diagnostic {
+    diagnostic.data = Some(json!({ "corrections": corrections }));
+    diagnostic
+}
+
+/// The corrections set with `with_corrections`.
+fn corrections(diagnostic: &Diagnostic) -> Vec<&str> {
+    diagnostic
+        .data
+        .as_ref()
+        .and_then(|data| data.get("corrections"))
+        .and_then(|corrections| corrections.as_array())
+        .map(|corrections| {
+            corrections
+                .iter()
+                .filter_map(|correction| correction.as_str())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// A "Replace with" quick fix for each correction of the `diagnostics` of
+/// `source` in the document at `uri`.
+pub fn replace_actions(uri: &Url, source: &str, diagnostics: &[Diagnostic]) -> Vec<CodeAction> {
+    diagnostics
+        .iter()
+        .filter(|diagnostic| diagnostic.source.as_deref() == Some(source))
+        .flat_map(|diagnostic| {
+            corrections(diagnostic)
+                .into_iter()
+                .map(move |correction| CodeAction {
+                    title: format!("Replace with `{correction}`"),
+                    kind: Some(CodeActionKind::QUICKFIX),
+                    diagnostics: Some(vec![diagnostic.clone()]),
+                    edit: Some(WorkspaceEdit {
+                        changes: Some(HashMap::from([(
+                            uri.clone(),
+                            vec![TextEdit::new(diagnostic.range, correction.to_string())],
+                        )])),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{replace_actions, with_corrections};
+    use tower_lsp::lsp_types::{Diagnostic, Position, Range, TextEdit, Url};
+
+    #[test]
+    fn test_replace_actions() {
+        let uri = Url::parse("file:///tmp/README.md").unwrap();
+        let range = Range::new(Position::new(2, 4), Position::new(2, 8));
+        let typo = Diagnostic {
+            range,
+            source: Some("codespell".to_string()),
+            message: "teh ==> the, tech".to_string(),
+            ..Default::default()
+        };
+        let typo = with_corrections(typo, &["the".to_string(), "tech".to_string()]);
+        let other = Diagnostic {
+            source: Some("markdownlint".to_string()),
+            ..typo.clone()
+        };
+
+        let actions = replace_actions(&uri, "codespell", &[typo.clone(), other]);
+        assert_eq!(actions.len(), 2);
+        assert_eq!(actions[0].title, "Replace with `the`");
+        assert_eq!(actions[1].title, "Replace with `tech`");
+        assert_eq!(actions[1].diagnostics, Some(vec![typo]));
+        let edits = |index: usize| {
+            actions[index]
+                .edit
+                .as_ref()
+                .unwrap()
+                .changes
+                .as_ref()
+                .unwrap()[&uri]
+                .clone()
+        };
+        assert_eq!(edits(0), vec![TextEdit::new(range, "the".to_string())]);
+        assert_eq!(edits(1), vec![TextEdit::new(range, "tech".to_string())]);
+    }
+}
@@ -0,0 +1,204 @@
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity};
+
+use super::process::{
+    probe, run_with_stdin, traverse_parents, JsonArrayParser, JsonDiagnostic, ToolDiagnostic,
+};
+use super::{DocumentContext, Handler, HandlerError};
+
+/// Linting of stylesheets with stylelint, for projects configuring it.
+#[derive(Debug)]
+pub struct Stylelint {}
+
+/// Files stylelint reads its settings from, `package.json` only with a
+/// `stylelint` key.
+const CONFIG_FILES: &[&str] = &[
+    "package.json",
+    ".stylelintrc",
+    ".stylelintrc.json",
+    ".stylelintrc.yaml",
+    ".stylelintrc.yml",
+    ".stylelintrc.js",
+    ".stylelintrc.cjs",
+    ".stylelintrc.mjs",
+    "stylelint.config.js",
+    "stylelint.config.cjs",
+    "stylelint.config.mjs",
+];
+
+/// The results of a file of `stylelint --formatter json`.
+#[derive(Debug, Deserialize)]
+struct FileResult {
+    warnings: Vec<Warning>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Warning {
+    line: u32,
+    column: u32,
+    end_line: Option<u32>,
+    end_column: Option<u32>,
+    rule: Option<String>,
+    severity: Option<String>,
+    text: String,
+}
+
+impl JsonDiagnostic for Warning {
+    fn into_diagnostic(self) -> Option<ToolDiagnostic> {
+        // The text ends with the rule, which goes to the code instead
+        let message = match &self.rule {
+            Some(rule) => self
+                .text
+                .strip_suffix(&format!(" ({rule})"))
+                .unwrap_or(&self.text)
+                .to_string(),
+            None => self.text,
+        };
+        Some(ToolDiagnostic {
+            line: self.line,
+            column: self.column,
+            end: self.end_line.zip(self.end_column),
+            severity: self.severity,
+            code: self.rule,
+            message,
+        })
+    }
+}
+
+impl Stylelint {
+    pub fn new() -> Result<Self, String> {
+        probe("stylelint", &["--version"])?;
+        Ok(Self {})
+    }
+
+    /// The settings file closest to `directory`.
+    pub fn find_config(directory: &Path, root_markers: &[String]) -> Option<PathBuf> {
+        traverse_parents(directory, CONFIG_FILES, root_markers, |path| {
+            !path.ends_with("package.json")
+                || std::fs::read_to_string(path)
+                    .ok()
+                    .and_then(|contents| serde_json::from_str::<serde_json::Value>(&contents).ok())
+                    .is_some_and(|package| package.get("stylelint").is_some())
+        })
+    }
+
+    /// Checks the document from stdin, named after the document so the
+    /// overrides of the settings apply to it, from the project directory.
+    fn command(context: &DocumentContext) -> Command {
+        let mut command = Command::new("stylelint");
+        command
+            .arg("--formatter")
+            .arg("json")
+            .arg("--stdin")
+            .arg("--stdin-filename");
+        match context.uri.to_file_path() {
+            Ok(path) => command.arg(path),
+            Err(_) => command.arg("untitled.css"),
+        };
+        if let Some(directory) = context
+            .directory()
+            .and_then(|directory| Self::find_config(&directory, &context.root_markers))
+            .as_deref()
+            .and_then(Path::parent)
+        {
+            command.current_dir(directory);
+        }
+        command
+    }
+
+    fn parser() -> JsonArrayParser<Warning> {
+        // stylelint reports 1-based positions
+        JsonArrayParser::new("stylelint").severities(&[("error", DiagnosticSeverity::ERROR)])
+    }
+
+    /// The diagnostics of the first file of a report.
+    pub fn parse(report: &str) -> Result<Vec<Diagnostic>, HandlerError> {
+        let files: Vec<FileResult> = serde_json::from_str(report)
+            .map_err(|e| HandlerError::Parse(format!("Invalid stylelint output: {e}")))?;
+        let warnings = files
+            .into_iter()
+            .next()
+            .map(|file| file.warnings)
+            .unwrap_or_default();
+        Ok(Self::parser().diagnostics(warnings))
+    }
+}
+
+impl Handler for Stylelint {
+    fn filetype_supported(&self, filetype: &str) -> bool {
+        matches!(filetype, "css" | "scss" | "less")
+    }
+
+    fn contents_supported(&self, context: &DocumentContext, _contents: &str) -> bool {
+        context
+            .directory()
+            .and_then(|directory| Self::find_config(&directory, &context.root_markers))
+            .is_some()
+    }
+
+    async fn update_diagnostics_with_context(
+        &mut self,
+        context: &DocumentContext,
+        contents: &str,
+    ) -> Result<Vec<Diagnostic>, HandlerError> {
+        let out = run_with_stdin(&mut Self::command(context), contents)?;
+        // Newer versions print the report to stderr when there are problems
+        let stdout = String::from_utf8_lossy(&out.stdout);
+        let stderr = String::from_utf8_lossy(&out.stderr);
+        let report = [&stdout, &stderr]
+            .into_iter()
+            .find(|output| output.trim_start().starts_with('['));
+        match report {
+            Some(report) => Self::parse(report),
+            None => Err(HandlerError::Log(stderr.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Stylelint;
+    use tower_lsp::lsp_types::{DiagnosticSeverity, NumberOrString, Position, Range};
+
+    #[test]
+    fn test_parse_color_hex_length() {
+        let report = r##"[
+  {
+    "source": "/project/src/main.css",
+    "deprecations": [],
+    "invalidOptionWarnings": [],
+    "parseErrors": [],
+    "errored": true,
+    "warnings": [
+      {
+        "line": 2,
+        "column": 10,
+        "endLine": 2,
+        "endColumn": 17,
+        "rule": "color-hex-length",
+        "severity": "error",
+        "text": "Expected \"#ffffff\" to be \"#fff\" (color-hex-length)"
+      }
+    ]
+  }
+]"##;
+        let diagnostics = Stylelint::parse(report).ok().unwrap();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].range,
+            Range::new(Position::new(1, 9), Position::new(1, 16))
+        );
+        assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::ERROR));
+        assert_eq!(
+            diagnostics[0].code,
+            Some(NumberOrString::String("color-hex-length".to_string()))
+        );
+        assert_eq!(
+            diagnostics[0].message,
+            "Expected \"#ffffff\" to be \"#fff\""
+        );
+    }
+}
@@ -0,0 +1,123 @@
+use lazy_regex::regex_captures;
+use tower_lsp::lsp_types::{Diagnostic, NumberOrString};
+
+/// Prefixes of line comments in documents of `filetype`. Both `#` and `//`
+/// for filetypes not listed.
+fn comment_prefixes(filetype: &str) -> &'static [&'static str] {
+    match filetype {
+        "sh" | "bash" | "zsh" | "python" | "r" | "ruby" | "perl" | "yaml" | "toml" | "just"
+        | "make" | "dockerfile" | "nim" | "properties" | "graphql" | "gitignore" | "starlark"
+        | "bazel" | "bzl" | "cmake" | "nix" | "elixir" | "julia" => &["#"],
+        "javascript" | "javascriptreact" | "typescript" | "typescriptreact" | "jsonc" | "json5"
+        | "c" | "cpp" | "java" | "go" | "gomod" | "rust" | "scala" | "groovy" | "jenkinsfile"
+        | "kotlin" | "swift" | "dart" | "solidity" | "verilog" | "systemverilog" | "wgsl"
+        | "glsl" | "proto" | "thrift" | "fbs" | "pug" | "jade" => &["//"],
+        "lua" | "sql" | "haskell" => &["--"],
+        "racket" | "scheme" | "lisp" | "clojure" => &[";"],
+        "tex" | "latex" | "erlang" => &["%"],
+        _ => &["#", "//"],
+    }
+}
+
+/// The codes an `any_ls: ignore` comment on `line` suppresses, empty to
+/// suppress every diagnostic. `None` when the line isn't one.
+fn ignored_codes(line: &str, prefixes: &[&str]) -> Option<Vec<String>> {
+    let line = line.trim_start();
+    let comment = prefixes
+        .iter()
+        .find_map(|prefix| line.strip_prefix(prefix))?;
+    let (_, codes) = regex_captures!(
+        r#"^[#/;%-]*\s*any_ls:\s*ignore(?:\[([^\]]*)\])?(?:\s|$)"#,
+        comment
+    )?;
+    Some(
+        codes
+            .split(',')
+            .map(str::trim)
+            .filter(|code| !code.is_empty())
+            .map(str::to_string)
+            .collect(),
+    )
+}
+
+/// Drops the diagnostics on lines right below an `any_ls: ignore` comment,
+/// or with a code listed in it, e.g. `# any_ls: ignore[SC2086, SC2034]`.
+pub fn drop_ignored(filetype: &str, contents: &str, diagnostics: &mut Vec<Diagnostic>) {
+    let prefixes = comment_prefixes(filetype);
+    let lines: Vec<&str> = contents.lines().collect();
+    diagnostics.retain(|diagnostic| {
+        let Some(previous) = (diagnostic.range.start.line as usize)
+            .checked_sub(1)
+            .and_then(|line| lines.get(line))
+        else {
+            return true;
+        };
+        let Some(codes) = ignored_codes(previous, prefixes) else {
+            return true;
+        };
+        let code = match &diagnostic.code {
+            Some(NumberOrString::String(code)) => code.clone(),
+            Some(NumberOrString::Number(code)) => code.to_string(),
+            None => String::new(),
+        };
+        !codes.is_empty() && !codes.contains(&code)
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{drop_ignored, ignored_codes};
+    use tower_lsp::lsp_types::{Diagnostic, NumberOrString, Position, Range};
+
+    fn diagnostic(line: u32, code: &str) -> Diagnostic {
+        Diagnostic {
+            range: Range::new(Position::new(line, 0), Position::new(line, 1)),
+            code: Some(NumberOrString::String(code.to_string())),
+            ..Diagnostic::default()
+        }
+    }
+
+    fn lines(diagnostics: &[Diagnostic]) -> Vec<u32> {
+        diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.range.start.line)
+            .collect()
+    }
+
+    #[test]
+    fn test_ignore_next_line() {
+        let contents = "#!/bin/sh\n# any_ls: ignore\necho $1\necho $2\n";
+        let mut diagnostics = vec![diagnostic(2, "SC2086"), diagnostic(3, "SC2086")];
+        drop_ignored("sh", contents, &mut diagnostics);
+        assert_eq!(lines(&diagnostics), vec![3]);
+    }
+
+    #[test]
+    fn test_ignore_code() {
+        let contents = "let a = 1;\n  // any_ls: ignore[no-unused-vars, eqeqeq]\nlet b = a == 1;\n";
+        let mut diagnostics = vec![
+            diagnostic(2, "no-unused-vars"),
+            diagnostic(2, "no-undef"),
+            diagnostic(2, "eqeqeq"),
+        ];
+        drop_ignored("javascript", contents, &mut diagnostics);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].code,
+            Some(NumberOrString::String("no-undef".to_string()))
+        );
+
+        // `#` isn't a comment in JavaScript
+        assert_eq!(ignored_codes("# any_ls: ignore", &["//"]), None);
+        assert_eq!(ignored_codes("# any_ls: ignored", &["#"]), None);
+    }
+
+    #[test]
+    fn test_ignore_wrong_line() {
+        // The comment is two lines above, or below the diagnostic
+        let contents = "# any_ls: ignore\n\nkey: value\n# any_ls: ignore\n";
+        let mut diagnostics = vec![diagnostic(2, "syntax")];
+        drop_ignored("yaml", contents, &mut diagnostics);
+        assert_eq!(lines(&diagnostics), vec![2]);
+    }
+}
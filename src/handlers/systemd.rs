@@ -0,0 +1,280 @@
+use lazy_regex::{regex, regex_captures};
+use std::path::Path;
+use std::process::Command;
+use tower_lsp::lsp_types::{
+    self, Diagnostic, DiagnosticSeverity, Hover, HoverContents, HoverProviderCapability,
+    MarkupContent, MarkupKind, Position, ServerCapabilities,
+};
+
+use super::process::{
+    probe, run_and_parse, InputMode, RegexLineParser, Stream, TempFileStrategy, TempFiles,
+};
+use super::{DocumentContext, Handler, HandlerError};
+
+/// Checks of systemd unit files with `systemd-analyze verify`, and the
+/// documentation of their directives on hover.
+#[derive(Debug)]
+pub struct Systemd {
+    /// Files by unit type, as systemd tells units apart by their suffix.
+    temp_files: Vec<(&'static str, TempFiles)>,
+}
+
+/// Suffixes of the unit files checked, the first for documents without a
+/// path.
+const UNIT_TYPES: &[&str] = &[".service", ".timer", ".socket"];
+
+/// Common directives by section, with what they do.
+const DIRECTIVES: &[(&str, &str, &str)] = &[
+    ("Unit", "Description", "A short human readable title of the unit."),
+    ("Unit", "Documentation", "URIs of the documentation of the unit, e.g. `man:` or `https:` ones."),
+    ("Unit", "Requires", "Units started along with this one, which is stopped when they fail to start or stop."),
+    ("Unit", "Wants", "Units started along with this one, without depending on their success."),
+    ("Unit", "BindsTo", "Like `Requires=`, also stopping this unit whenever the other unit stops."),
+    ("Unit", "PartOf", "Units whose stops and restarts also stop or restart this unit."),
+    ("Unit", "Conflicts", "Units stopped when this one starts, and the other way around."),
+    ("Unit", "Before", "Units that start after this one when both are started, unrelated to requirements."),
+    ("Unit", "After", "Units that start before this one when both are started, unrelated to requirements."),
+    ("Unit", "ConditionPathExists", "Skips starting the unit unless the path exists, or doesn't with a `!` prefix."),
+    ("Service", "Type", "How the start of the service is detected, e.g. `simple`, `exec`, `forking`, `oneshot` or `notify`."),
+    ("Service", "ExecStart", "The command run when the service starts, with its arguments."),
+    ("Service", "ExecStartPre", "Commands run before `ExecStart=`, failing the start when they fail unless prefixed with `-`."),
+    ("Service", "ExecStartPost", "Commands run after `ExecStart=` has started the service."),
+    ("Service", "ExecReload", "The command reloading the configuration of the service."),
+    ("Service", "ExecStop", "The command stopping the service, which is killed afterwards according to `KillMode=`."),
+    ("Service", "Restart", "When the service is restarted after exiting, e.g. `no`, `on-failure` or `always`."),
+    ("Service", "RestartSec", "Time to wait before restarting the service."),
+    ("Service", "RemainAfterExit", "Whether the service is considered active after all its processes exited."),
+    ("Service", "User", "The user the processes of the service run as."),
+    ("Service", "Group", "The group the processes of the service run as."),
+    ("Service", "WorkingDirectory", "The working directory of the processes of the service."),
+    ("Service", "Environment", "Environment variables of the processes, as space-separated `VAR=value` assignments."),
+    ("Service", "EnvironmentFile", "Files of `VAR=value` lines read into the environment, optional with a `-` prefix."),
+    ("Service", "TimeoutStartSec", "Time to wait for the service to start before failing it."),
+    ("Service", "TimeoutStopSec", "Time to wait for the service to stop before killing it."),
+    ("Service", "KillMode", "Which processes are killed on stop, e.g. `control-group`, `mixed` or `process`."),
+    ("Timer", "OnCalendar", "Calendar events the timer elapses on, e.g. `daily` or `Mon *-*-* 09:00`."),
+    ("Timer", "OnBootSec", "Time after boot the timer elapses."),
+    ("Timer", "OnUnitActiveSec", "Time after the last activation of the unit the timer elapses."),
+    ("Timer", "Persistent", "Whether runs missed while the system was off happen when the timer starts."),
+    ("Timer", "AccuracySec", "How much later than scheduled the timer may elapse, to coalesce wakeups."),
+    ("Timer", "RandomizedDelaySec", "Random delay added to each elapse, up to this time."),
+    ("Timer", "Unit", "The unit activated when the timer elapses, the service of the same name by default."),
+    ("Socket", "ListenStream", "An address of a stream socket to listen on, e.g. a port or a path."),
+    ("Socket", "ListenDatagram", "An address of a datagram socket to listen on, e.g. a port or a path."),
+    ("Socket", "Accept", "Whether a service instance is started per connection, rather than one for all."),
+    ("Socket", "Service", "The service activated on incoming traffic, the one of the same name by default."),
+    ("Install", "WantedBy", "Units wanting this one once it is enabled, e.g. `multi-user.target`."),
+    ("Install", "RequiredBy", "Units requiring this one once it is enabled."),
+    ("Install", "Alias", "Other names of the unit, linked when it is enabled."),
+    ("Install", "Also", "Units enabled and disabled along with this one."),
+];
+
+/// The man page documenting the directives of `section`.
+fn man_page(section: &str) -> &'static str {
+    match section {
+        "Service" => "systemd.service",
+        "Timer" => "systemd.timer",
+        "Socket" => "systemd.socket",
+        _ => "systemd.unit",
+    }
+}
+
+/// The directive whose key is at `position` of `contents`, with its
+/// section, description and the range of the key on the line.
+fn directive_at(
+    contents: &str,
+    position: Position,
+) -> Option<(&'static str, &'static str, &'static str, u32, u32)> {
+    let lines: Vec<&str> = contents.lines().collect();
+    let line = *lines.get(position.line as usize)?;
+    let (_, indent, key) = regex_captures!(r#"^(\s*)([A-Za-z0-9]+)\s*="#, line)?;
+    let character = |offset: usize| line[..offset].encode_utf16().count() as u32;
+    let (start, end) = (character(indent.len()), character(indent.len() + key.len()));
+    if !(start..=end).contains(&position.character) {
+        return None;
+    }
+    let section = lines[..position.line as usize]
+        .iter()
+        .rev()
+        .find_map(|line| regex_captures!(r#"^\s*\[([^\]]+)\]"#, line))
+        .map(|(_, section)| section);
+    DIRECTIVES
+        .iter()
+        .filter(|(_, name, _)| *name == key)
+        // Directives with the same key in other sections otherwise
+        .min_by_key(|(directive_section, _, _)| Some(*directive_section) != section)
+        .map(|(section, name, description)| (*section, *name, *description, start, end))
+}
+
+impl Systemd {
+    pub fn new() -> Result<Self, String> {
+        probe("systemd-analyze", &["--version"])?;
+        Ok(Self {
+            temp_files: UNIT_TYPES
+                .iter()
+                // Unit names can't start with a dot
+                .map(|suffix| {
+                    (
+                        *suffix,
+                        TempFiles::with_suffix(*suffix).with_prefix("any-ls-"),
+                    )
+                })
+                .collect(),
+        })
+    }
+
+    fn is_unit_file(path: &Path) -> bool {
+        path.file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| UNIT_TYPES.iter().any(|suffix| name.ends_with(suffix)))
+    }
+
+    /// Parses `path:line: message` messages, and `path: message` ones about
+    /// the whole unit, e.g. a missing `ExecStart=`.
+    fn parser() -> RegexLineParser {
+        RegexLineParser {
+            regex: regex!(r#"(?m)^(?P<path>[^:\n]+):(?:(?P<line>\d+):)? (?P<message>.+)$"#),
+            source: "systemd-analyze",
+            stream: Stream::Stderr,
+            first_line: 1,
+            first_column: 1,
+            severity: |_| Some(DiagnosticSeverity::WARNING),
+        }
+    }
+
+    /// The diagnostics of the messages of `output` about `unit`, not those
+    /// about the units it refers to. systemd ignores what it warns about,
+    /// so they are all warnings.
+    fn diagnostics(messages: Vec<(Option<String>, Diagnostic)>, unit: &Path) -> Vec<Diagnostic> {
+        messages
+            .into_iter()
+            .filter(|(path, _)| {
+                path.as_deref()
+                    .is_some_and(|path| Path::new(path).file_name() == unit.file_name())
+            })
+            .map(|(_, diagnostic)| Diagnostic {
+                severity: Some(DiagnosticSeverity::WARNING),
+                ..diagnostic
+            })
+            .collect()
+    }
+}
+
+impl Handler for Systemd {
+    fn filetype_supported(&self, filetype: &str) -> bool {
+        filetype == "systemd"
+    }
+
+    fn path_supported(&self, path: &Path) -> bool {
+        Self::is_unit_file(path)
+    }
+
+    fn get_capabilities(&self) -> ServerCapabilities {
+        ServerCapabilities {
+            hover_provider: Some(HoverProviderCapability::Simple(true)),
+            ..Default::default()
+        }
+    }
+
+    fn set_temp_file_strategy(&mut self, strategy: TempFileStrategy) {
+        for (_, temp_files) in &mut self.temp_files {
+            temp_files.set_strategy(strategy);
+        }
+    }
+
+    async fn update_diagnostics_with_context(
+        &mut self,
+        context: &DocumentContext,
+        contents: &str,
+    ) -> Result<Vec<Diagnostic>, HandlerError> {
+        let path = context.uri.path();
+        let index = self
+            .temp_files
+            .iter()
+            .position(|(suffix, _)| path.ends_with(suffix))
+            .unwrap_or(0);
+        let temp_file = self.temp_files[index].1.write(contents)?;
+
+        let messages = run_and_parse(
+            Command::new("systemd-analyze")
+                .arg("verify")
+                .arg(temp_file.path()),
+            InputMode::File,
+            &Self::parser(),
+        )?;
+        Ok(Self::diagnostics(messages, temp_file.path()))
+    }
+
+    fn hover(
+        &self,
+        _filetype: &str,
+        _context: &DocumentContext,
+        contents: &str,
+        position: Position,
+    ) -> Result<Option<Hover>, HandlerError> {
+        let Some((section, name, description, start, end)) = directive_at(contents, position)
+        else {
+            return Ok(None);
+        };
+        let reference = format!(
+            "https://www.freedesktop.org/software/systemd/man/latest/{}.html#{name}=",
+            man_page(section)
+        );
+        Ok(Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: format!(
+                    "**{name}=** in `[{section}]`\n\n{description}\n\n[Reference]({reference})"
+                ),
+            }),
+            range: Some(lsp_types::Range::new(
+                Position::new(position.line, start),
+                Position::new(position.line, end),
+            )),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{directive_at, Systemd};
+    use std::path::Path;
+    use tower_lsp::lsp_types::{DiagnosticSeverity, Position, Range};
+
+    #[test]
+    fn test_parse_unknown_key() {
+        let stderr = "/tmp/any-ls-Ab12.service:7: Unknown key name 'ExecStat' in section 'Service', ignoring.\n/tmp/any-ls-Ab12.service: Service has no ExecStart=, ExecStop=, or SuccessAction=. Refusing.\nnetwork.target: Unit is not loaded.\n";
+        let messages = Systemd::parser().parse_text(stderr);
+        let diagnostics = Systemd::diagnostics(messages, Path::new("/tmp/any-ls-Ab12.service"));
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(
+            diagnostics[0].message,
+            "Unknown key name 'ExecStat' in section 'Service', ignoring."
+        );
+        assert_eq!(
+            diagnostics[0].range,
+            Range::new(Position::new(6, 0), Position::new(6, 0))
+        );
+        assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::WARNING));
+        // About the whole unit
+        assert_eq!(diagnostics[1].range.start, Position::new(0, 0));
+    }
+
+    #[test]
+    fn test_hover_exec_start() {
+        let contents = "[Unit]\nDescription=Web\n\n[Service]\n  ExecStart=/usr/bin/web --port 80\n";
+        let (section, name, description, start, end) =
+            directive_at(contents, Position::new(4, 5)).unwrap();
+        assert_eq!((section, name), ("Service", "ExecStart"));
+        assert!(description.starts_with("The command run"));
+        assert_eq!((start, end), (2, 11));
+
+        // The value
+        assert_eq!(directive_at(contents, Position::new(4, 20)), None);
+        // Not a known directive
+        assert_eq!(
+            directive_at("[Service]\nFoo=bar\n", Position::new(1, 1)),
+            None
+        );
+    }
+}
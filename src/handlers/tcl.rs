@@ -0,0 +1,93 @@
+use lazy_regex::regex;
+use std::process::Command;
+use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity};
+
+use super::process::{
+    probe, run_and_parse, InputMode, RegexLineParser, Stream, TempFileStrategy, TempFiles,
+};
+use super::{Handler, HandlerError};
+
+/// Tcl syntax checking with nagelfar.
+#[derive(Debug)]
+pub struct Tcl {
+    temp_files: TempFiles,
+}
+
+/// nagelfar's levels: errors, warnings and notes.
+fn parse_severity(severity: &str) -> Option<DiagnosticSeverity> {
+    match severity {
+        "E" => Some(DiagnosticSeverity::ERROR),
+        "W" => Some(DiagnosticSeverity::WARNING),
+        _ => Some(DiagnosticSeverity::INFORMATION),
+    }
+}
+
+impl Tcl {
+    pub fn new() -> Result<Self, String> {
+        probe("nagelfar", &["-help"])?;
+        Ok(Self {
+            temp_files: TempFiles::with_suffix(".tcl"),
+        })
+    }
+
+    /// Parses `Line   N: E message` messages, after the `Checking file`
+    /// line of the file.
+    fn parser() -> RegexLineParser {
+        RegexLineParser {
+            regex: regex!(r#"(?m)^Line\s+(?P<line>\d+): (?P<severity>[EWN]) (?P<message>.*)$"#),
+            source: "nagelfar",
+            stream: Stream::Stdout,
+            first_line: 1,
+            first_column: 1,
+            severity: parse_severity,
+        }
+    }
+}
+
+impl Handler for Tcl {
+    fn filetype_supported(&self, filetype: &str) -> bool {
+        filetype == "tcl"
+    }
+
+    fn set_temp_file_strategy(&mut self, strategy: TempFileStrategy) {
+        self.temp_files.set_strategy(strategy);
+    }
+
+    async fn update_diagnostics(
+        &mut self,
+        contents: &str,
+    ) -> Result<Vec<Diagnostic>, HandlerError> {
+        let temp_file = self.temp_files.write(contents)?;
+
+        let messages = run_and_parse(
+            Command::new("nagelfar").arg(temp_file.path()),
+            InputMode::File,
+            &Self::parser(),
+        )?;
+        Ok(messages
+            .into_iter()
+            .map(|(_, diagnostic)| diagnostic)
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Tcl;
+    use tower_lsp::lsp_types::{DiagnosticSeverity, Position};
+
+    #[test]
+    fn test_parse_wrong_number_of_args() {
+        let stdout = "Checking file /tmp/.tmpAbC123.tcl\nLine   4: E Wrong number of args (3) to set\nLine  12: N Suspicious variable name \"$name\"\n";
+        let messages = Tcl::parser().parse_text(stdout);
+        assert_eq!(messages.len(), 2);
+        let (_, diagnostic) = &messages[0];
+        assert_eq!(diagnostic.range.start, Position::new(3, 0));
+        assert_eq!(diagnostic.severity, Some(DiagnosticSeverity::ERROR));
+        assert_eq!(diagnostic.message, "Wrong number of args (3) to set");
+        assert_eq!(
+            messages[1].1.severity,
+            Some(DiagnosticSeverity::INFORMATION)
+        );
+    }
+}
@@ -0,0 +1,191 @@
+use similar::TextDiff;
+use tower_lsp::lsp_types::{Position, Range, TextEdit};
+
+/// Byte offset of `position` in `contents`. Characters are counted in
+/// UTF-16 code units and positions past the end of a line are clamped to it.
+pub fn position_to_offset(contents: &str, position: Position) -> usize {
+    let mut line_start = 0;
+    for _ in 0..position.line {
+        match contents[line_start..].find('\n') {
+            Some(newline) => line_start += newline + 1,
+            None => return contents.len(),
+        }
+    }
+
+    let mut units = 0;
+    for (offset, c) in contents[line_start..].char_indices() {
+        if c == '\n' || units >= position.character {
+            return line_start + offset;
+        }
+        units += c.len_utf16() as u32;
+    }
+    contents.len()
+}
+
+/// Position of the byte `offset` in `contents`, in UTF-16 code units.
+/// Offsets inside a character are moved to its start.
+pub fn offset_to_position(contents: &str, offset: usize) -> Position {
+    let mut offset = offset.min(contents.len());
+    while !contents.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    let before = &contents[..offset];
+    let line = before.matches('\n').count();
+    let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+    let character = before[line_start..].encode_utf16().count();
+    Position::new(line as u32, character as u32)
+}
+
+/// Edits turning `old` into `new`, replacing only the lines that changed so
+/// the cursor and other selections outside of them stay in place.
+pub fn compute_text_edits(old: &str, new: &str) -> Vec<TextEdit> {
+    let diff = TextDiff::from_lines(old, new);
+    let new_lines = diff.new_slices();
+    // Byte offset of the start of every line of `old`, and of its end
+    let mut offsets = vec![0];
+    for line in diff.old_slices() {
+        offsets.push(offsets[offsets.len() - 1] + line.len());
+    }
+
+    diff.grouped_ops(0)
+        .iter()
+        .filter_map(|group| {
+            let (first, last) = (group.first()?, group.last()?);
+            let old_range = first.old_range().start..last.old_range().end;
+            let new_range = first.new_range().start..last.new_range().end;
+            Some(TextEdit::new(
+                Range::new(
+                    offset_to_position(old, offsets[old_range.start]),
+                    offset_to_position(old, offsets[old_range.end]),
+                ),
+                new_lines[new_range].concat(),
+            ))
+        })
+        .collect()
+}
+
+/// `contents` with `edits` applied, which must not overlap.
+pub fn apply_text_edits(contents: &str, edits: &[TextEdit]) -> String {
+    let mut edits: Vec<&TextEdit> = edits.iter().collect();
+    // From the end, so that the positions of the other edits stay valid
+    edits.sort_by_key(|edit| std::cmp::Reverse(edit.range.start));
+    let mut contents = contents.to_string();
+    for edit in edits {
+        let start = position_to_offset(&contents, edit.range.start);
+        let end = position_to_offset(&contents, edit.range.end);
+        contents.replace_range(start..end, &edit.new_text);
+    }
+    contents
+}
+
+/// Levenshtein distance between `a` and `b`, counted in characters.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a != *b);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// The candidate closest to `word`, if it is close enough to be a typo.
+pub fn closest<'a>(word: &str, candidates: &[&'a str]) -> Option<&'a str> {
+    let max_distance = (word.chars().count() / 3).max(1);
+    candidates
+        .iter()
+        .map(|candidate| (edit_distance(word, candidate), *candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        apply_text_edits, closest, compute_text_edits, edit_distance, offset_to_position,
+        position_to_offset,
+    };
+    use tower_lsp::lsp_types::{Position, Range, TextEdit};
+
+    #[test]
+    fn test_end_position() {
+        let end_position = |contents: &str| offset_to_position(contents, contents.len());
+        assert_eq!(end_position(""), Position::new(0, 0));
+        assert_eq!(end_position("a\nbc"), Position::new(1, 2));
+        assert_eq!(end_position("a\n"), Position::new(1, 0));
+        assert_eq!(end_position("ä😀"), Position::new(0, 3));
+    }
+
+    #[test]
+    fn test_offsets() {
+        let contents = "ab\nä😀c\n";
+        assert_eq!(position_to_offset(contents, Position::new(0, 1)), 1);
+        assert_eq!(position_to_offset(contents, Position::new(1, 3)), 9);
+        assert_eq!(position_to_offset(contents, Position::new(1, 99)), 10);
+        assert_eq!(position_to_offset(contents, Position::new(9, 0)), 11);
+
+        assert_eq!(offset_to_position(contents, 9), Position::new(1, 3));
+        assert_eq!(offset_to_position(contents, 3), Position::new(1, 0));
+        // Inside `😀`
+        assert_eq!(offset_to_position(contents, 7), Position::new(1, 1));
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("depdencies", "dependencies"), 2);
+        assert_eq!(
+            closest("depdencies", &["dev-dependencies", "dependencies"]),
+            Some("dependencies")
+        );
+        assert_eq!(closest("xyz", &["dependencies"]), None);
+    }
+
+    #[test]
+    fn test_text_edits_changed_line() {
+        let old = "a\nb\nc\nd\n";
+        let new = "a\nB\nc\nd\n";
+        assert_eq!(
+            compute_text_edits(old, new),
+            vec![TextEdit::new(
+                Range::new(Position::new(1, 0), Position::new(2, 0)),
+                "B\n".to_string()
+            )]
+        );
+        assert!(compute_text_edits(old, old).is_empty());
+    }
+
+    #[test]
+    fn test_text_edits_insertion() {
+        let old = "fn main() {\n}\n";
+        let new = "fn main() {\n    println!(\"😀\");\n}\n";
+        assert_eq!(
+            compute_text_edits(old, new),
+            vec![TextEdit::new(
+                Range::new(Position::new(1, 0), Position::new(1, 0)),
+                "    println!(\"😀\");\n".to_string()
+            )]
+        );
+        // Last line without a newline
+        assert_eq!(
+            compute_text_edits("ä\nb", "ä\nc"),
+            vec![TextEdit::new(
+                Range::new(Position::new(1, 0), Position::new(1, 1)),
+                "c".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_apply_text_edits() {
+        let old = "ä\nb\nc\nd";
+        let new = "ä\nB\nc\nd\ne\n";
+        assert_eq!(apply_text_edits(old, &compute_text_edits(old, new)), new);
+    }
+}
@@ -0,0 +1,242 @@
+use tower_lsp::lsp_types::{
+    self, DocumentSymbol, Hover, HoverContents, HoverProviderCapability, MarkupContent, MarkupKind,
+    OneOf, Position, ServerCapabilities, SymbolKind,
+};
+use tree_sitter::{Language, Node, Parser, Tree};
+
+use super::text::offset_to_position;
+use super::{DocumentContext, Handler, HandlerError};
+
+/// Document symbols and hover from tree-sitter grammars, for filetypes
+/// without a dedicated handler. Grammars are bundled with the
+/// `treesitter-<language>` features.
+#[derive(Debug)]
+pub struct TreeSitter {}
+
+/// A bundled grammar and which of its nodes are symbols.
+struct Grammar {
+    filetypes: &'static [&'static str],
+    language: fn() -> Language,
+    /// Kinds of the nodes that are symbols when they have a body.
+    symbols: &'static [(&'static str, SymbolKind)],
+}
+
+const GRAMMARS: &[Grammar] = &[
+    #[cfg(feature = "treesitter-c")]
+    Grammar {
+        filetypes: &["c"],
+        language: tree_sitter_c::language,
+        symbols: &[
+            ("function_definition", SymbolKind::FUNCTION),
+            ("struct_specifier", SymbolKind::STRUCT),
+            ("union_specifier", SymbolKind::STRUCT),
+            ("enum_specifier", SymbolKind::ENUM),
+        ],
+    },
+    #[cfg(feature = "treesitter-python")]
+    Grammar {
+        filetypes: &["python"],
+        language: tree_sitter_python::language,
+        symbols: &[
+            ("function_definition", SymbolKind::FUNCTION),
+            ("class_definition", SymbolKind::CLASS),
+        ],
+    },
+];
+
+fn grammar(filetype: &str) -> Option<&'static Grammar> {
+    GRAMMARS
+        .iter()
+        .find(|grammar| grammar.filetypes.contains(&filetype))
+}
+
+fn parse(grammar: &Grammar, contents: &str) -> Option<Tree> {
+    let mut parser = Parser::new();
+    parser.set_language((grammar.language)()).ok()?;
+    parser.parse(contents, None)
+}
+
+/// The identifier naming `node`, from its `name` field or, like C
+/// functions, the innermost of its nested declarators.
+fn name_node(node: Node<'_>) -> Option<Node<'_>> {
+    let mut node = node;
+    loop {
+        if let Some(name) = node.child_by_field_name("name") {
+            return Some(name);
+        }
+        match node.child_by_field_name("declarator") {
+            Some(declarator) => node = declarator,
+            None => return node.kind().ends_with("identifier").then_some(node),
+        }
+    }
+}
+
+fn range(contents: &str, node: Node<'_>) -> lsp_types::Range {
+    // Tree-sitter columns are bytes, convert from offsets instead
+    lsp_types::Range::new(
+        offset_to_position(contents, node.start_byte()),
+        offset_to_position(contents, node.end_byte()),
+    )
+}
+
+/// The symbols below `node`, nested like their nodes.
+fn symbols(grammar: &Grammar, node: Node<'_>, contents: &str) -> Vec<DocumentSymbol> {
+    let mut symbols = Vec::new();
+    let mut cursor = node.walk();
+    for child in node.named_children(&mut cursor) {
+        let children = self::symbols(grammar, child, contents);
+        let kind = grammar
+            .symbols
+            .iter()
+            .find(|(kind, _)| *kind == child.kind())
+            .map(|(_, kind)| *kind);
+        // Declarations without a body only refer to a symbol
+        let name = child
+            .child_by_field_name("body")
+            .and_then(|_| name_node(child));
+        let (Some(kind), Some(name)) = (kind, name) else {
+            symbols.extend(children);
+            continue;
+        };
+        #[allow(deprecated)]
+        symbols.push(DocumentSymbol {
+            name: name
+                .utf8_text(contents.as_bytes())
+                .unwrap_or_default()
+                .to_string(),
+            detail: None,
+            kind,
+            tags: None,
+            deprecated: None,
+            range: range(contents, child),
+            selection_range: range(contents, name),
+            children: (!children.is_empty()).then_some(children),
+        });
+    }
+    symbols
+}
+
+/// The innermost symbol whose name contains `position`.
+fn symbol_at(symbols: &[DocumentSymbol], position: Position) -> Option<&DocumentSymbol> {
+    symbols.iter().find_map(|symbol| {
+        let children = symbol.children.as_deref().unwrap_or_default();
+        symbol_at(children, position).or_else(|| {
+            let name = symbol.selection_range;
+            (name.start <= position && position <= name.end).then_some(symbol)
+        })
+    })
+}
+
+impl TreeSitter {
+    pub fn new() -> Result<Self, String> {
+        if GRAMMARS.is_empty() {
+            return Err("no tree-sitter grammars are bundled".to_string());
+        }
+        Ok(Self {})
+    }
+}
+
+impl Handler for TreeSitter {
+    fn filetype_supported(&self, filetype: &str) -> bool {
+        grammar(filetype).is_some()
+    }
+
+    fn priority(&self) -> i32 {
+        // A fallback for filetypes without a dedicated handler
+        -20
+    }
+
+    fn get_capabilities(&self) -> ServerCapabilities {
+        ServerCapabilities {
+            document_symbol_provider: Some(OneOf::Left(true)),
+            hover_provider: Some(HoverProviderCapability::Simple(true)),
+            ..Default::default()
+        }
+    }
+
+    fn document_symbols(
+        &self,
+        filetype: &str,
+        contents: &str,
+    ) -> Result<Vec<DocumentSymbol>, HandlerError> {
+        let Some(grammar) = grammar(filetype) else {
+            return Ok(Vec::new());
+        };
+        let Some(tree) = parse(grammar, contents) else {
+            return Ok(Vec::new());
+        };
+        Ok(symbols(grammar, tree.root_node(), contents))
+    }
+
+    fn hover(
+        &self,
+        filetype: &str,
+        _context: &DocumentContext,
+        contents: &str,
+        position: Position,
+    ) -> Result<Option<Hover>, HandlerError> {
+        let symbols = self.document_symbols(filetype, contents)?;
+        let Some(symbol) = symbol_at(&symbols, position) else {
+            return Ok(None);
+        };
+        // The line declaring the symbol, e.g. a function's signature
+        let declaration = contents
+            .lines()
+            .nth(symbol.range.start.line as usize)
+            .unwrap_or_default()
+            .trim();
+        Ok(Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: format!("```{filetype}\n{declaration}\n```"),
+            }),
+            range: Some(symbol.selection_range),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "treesitter-c")]
+    #[test]
+    fn test_c_functions() {
+        use super::TreeSitter;
+        use crate::handlers::Handler;
+        use tower_lsp::lsp_types::{Position, SymbolKind};
+
+        let contents = r#"#include <stdio.h>
+
+struct point {
+    int x, y;
+};
+
+static int add(int a, int b) {
+    return a + b;
+}
+
+char *name(void);
+
+int main(void) {
+    printf("%d\n", add(1, 2));
+    return 0;
+}
+"#;
+        let treesitter = TreeSitter::new().unwrap();
+        let symbols = treesitter.document_symbols("c", contents).ok().unwrap();
+        let names: Vec<(&str, SymbolKind)> = symbols
+            .iter()
+            .map(|symbol| (symbol.name.as_str(), symbol.kind))
+            .collect();
+        // The prototype of `name` has no body
+        assert_eq!(
+            names,
+            vec![
+                ("point", SymbolKind::STRUCT),
+                ("add", SymbolKind::FUNCTION),
+                ("main", SymbolKind::FUNCTION),
+            ]
+        );
+        assert_eq!(symbols[1].selection_range.start, Position::new(6, 11));
+        assert_eq!(symbols[1].range.end, Position::new(8, 1));
+    }
+}
@@ -0,0 +1,126 @@
+use lazy_regex::regex;
+use std::process::Command;
+use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity, OneOf, ServerCapabilities};
+
+use super::process::{format_stdin, probe, run_and_parse, InputMode, RegexLineParser, Stream};
+use super::{Handler, HandlerError};
+
+/// Verilog and SystemVerilog linting with verible-verilog-lint, and
+/// formatting with verible-verilog-format when it is installed.
+#[derive(Debug)]
+pub struct Verible {
+    /// Whether `verible-verilog-format` is available.
+    format: bool,
+}
+
+impl Verible {
+    pub fn new() -> Result<Self, String> {
+        probe("verible-verilog-lint", &["--version"])?;
+        Ok(Self {
+            format: probe("verible-verilog-format", &["--version"]).is_ok(),
+        })
+    }
+
+    /// Parses `-:line:column: message [rule]` lints, and syntax errors
+    /// without a rule. The column may be a range, e.g. `81-120`.
+    fn parser() -> RegexLineParser {
+        RegexLineParser {
+            regex: regex!(
+                r#"(?m)^(?P<path>[^:\n]+):(?P<line>\d+):(?P<column>\d+)(?:-\d+)?: (?P<message>.*?)(?: \[Style: [^\]]*\])?(?: \[(?P<code>[\w-]+)\])?$"#
+            ),
+            source: "verible",
+            stream: Stream::Stdout,
+            first_line: 1,
+            first_column: 1,
+            severity: |_| Some(DiagnosticSeverity::ERROR),
+        }
+    }
+}
+
+impl Handler for Verible {
+    fn filetype_supported(&self, filetype: &str) -> bool {
+        matches!(filetype, "verilog" | "systemverilog")
+    }
+
+    fn get_capabilities(&self) -> ServerCapabilities {
+        ServerCapabilities {
+            document_formatting_provider: self.format.then_some(OneOf::Left(true)),
+            ..Default::default()
+        }
+    }
+
+    async fn update_diagnostics(
+        &mut self,
+        contents: &str,
+    ) -> Result<Vec<Diagnostic>, HandlerError> {
+        let messages = run_and_parse(
+            Command::new("verible-verilog-lint").arg("-"),
+            InputMode::Stdin(contents),
+            &Self::parser(),
+        )?;
+        Ok(messages
+            .into_iter()
+            .map(|(_, mut diagnostic)| {
+                // Rule violations name their rule, syntax errors don't
+                if diagnostic.code.is_some() {
+                    diagnostic.severity = Some(DiagnosticSeverity::WARNING);
+                }
+                diagnostic
+            })
+            .collect())
+    }
+
+    async fn format(
+        &mut self,
+        _filetype: &str,
+        contents: &str,
+    ) -> Result<Option<String>, HandlerError> {
+        if !self.format {
+            return Ok(None);
+        }
+        format_stdin(Command::new("verible-verilog-format").arg("-"), contents)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Verible;
+    use crate::handlers::Handler;
+    use tower_lsp::lsp_types::{NumberOrString, Position};
+
+    #[test]
+    fn test_parse_line_length() {
+        let stdout = "-:3:101-120: Line length exceeds max: 100; is: 120 [Style: line-length] [line-length]\n-:7:1: syntax error at token \"endmodule\"\n";
+        let messages = Verible::parser().parse_text(stdout);
+        assert_eq!(messages.len(), 2);
+
+        let (_, lint) = &messages[0];
+        assert_eq!(lint.range.start, Position::new(2, 100));
+        assert_eq!(lint.message, "Line length exceeds max: 100; is: 120");
+        assert_eq!(
+            lint.code,
+            Some(NumberOrString::String("line-length".to_string()))
+        );
+
+        let (_, error) = &messages[1];
+        assert_eq!(error.range.start, Position::new(6, 0));
+        assert_eq!(error.message, "syntax error at token \"endmodule\"");
+        assert_eq!(error.code, None);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs verible-verilog-format"]
+    async fn test_format() {
+        let mut verible = Verible::new().unwrap();
+        assert!(verible.format, "verible-verilog-format is not installed");
+
+        let contents = "module top(input a,output b);assign b=a;endmodule\n";
+        let formatted = verible
+            .format("verilog", contents)
+            .await
+            .ok()
+            .unwrap()
+            .unwrap();
+        assert!(formatted.contains("assign b = a;"));
+    }
+}
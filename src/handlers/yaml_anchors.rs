@@ -0,0 +1,302 @@
+use lazy_regex::regex;
+use tower_lsp::lsp_types::{
+    self, Hover, HoverContents, HoverProviderCapability, MarkupContent, MarkupKind, Position,
+    ServerCapabilities,
+};
+use yaml_rust2::parser::{Event, MarkedEventReceiver, Parser};
+use yaml_rust2::scanner::{Marker, TScalarStyle};
+use yaml_rust2::{Yaml, YamlEmitter, YamlLoader};
+
+use super::{DocumentContext, Handler, HandlerError};
+
+/// Hover of the aliases of YAML documents, e.g. `*defaults`, showing the
+/// node of their anchor with merge keys applied.
+#[derive(Debug)]
+pub struct YamlAnchors {}
+
+/// A mapping or sequence being parsed.
+enum Container {
+    Mapping {
+        key: Yaml,
+        /// Whether the next node is the value of `key`.
+        in_value: bool,
+    },
+    Sequence {
+        index: usize,
+    },
+}
+
+/// An alias of a document, with the path of its node in the document.
+struct Alias {
+    document: usize,
+    /// 0-based line and column in characters of its `*`.
+    line: usize,
+    column: usize,
+    path: Vec<Yaml>,
+}
+
+/// Collects the aliases of a stream with their paths, which are then
+/// looked up in the documents as loaded, where aliases are resolved.
+#[derive(Default)]
+struct AliasPaths {
+    document: usize,
+    containers: Vec<Container>,
+    aliases: Vec<Alias>,
+}
+
+impl AliasPaths {
+    /// The path of the node starting, `None` for keys.
+    fn path(&self) -> Option<Vec<Yaml>> {
+        let mut path = Vec::new();
+        for container in &self.containers {
+            path.push(match container {
+                Container::Mapping {
+                    in_value: false, ..
+                } => return None,
+                Container::Mapping { key, .. } => key.clone(),
+                Container::Sequence { index } => Yaml::Integer(*index as i64),
+            });
+        }
+        Some(path)
+    }
+
+    /// Records `key` when the node starting is a key.
+    fn start_node(&mut self, key: Yaml) {
+        if let Some(Container::Mapping {
+            key: current,
+            in_value: false,
+        }) = self.containers.last_mut()
+        {
+            *current = key;
+        }
+    }
+
+    /// Moves to the next node of the innermost container.
+    fn end_node(&mut self) {
+        match self.containers.last_mut() {
+            Some(Container::Mapping { in_value, .. }) => *in_value = !*in_value,
+            Some(Container::Sequence { index }) => *index += 1,
+            None => {}
+        }
+    }
+}
+
+impl MarkedEventReceiver for AliasPaths {
+    fn on_event(&mut self, event: Event, mark: Marker) {
+        match event {
+            Event::DocumentEnd => {
+                self.document += 1;
+                self.containers.clear();
+            }
+            Event::Alias(_) => {
+                if let Some(path) = self.path() {
+                    self.aliases.push(Alias {
+                        document: self.document,
+                        line: mark.line().saturating_sub(1),
+                        column: mark.col(),
+                        path,
+                    });
+                }
+                self.start_node(Yaml::BadValue);
+                self.end_node();
+            }
+            Event::Scalar(value, style, _, _) => {
+                let key = match style {
+                    TScalarStyle::Plain => Yaml::from_str(&value),
+                    _ => Yaml::String(value),
+                };
+                self.start_node(key);
+                self.end_node();
+            }
+            Event::MappingStart(_, _) => {
+                // Complex keys can't be looked up
+                self.start_node(Yaml::BadValue);
+                self.containers.push(Container::Mapping {
+                    key: Yaml::BadValue,
+                    in_value: false,
+                });
+            }
+            Event::SequenceStart(_, _) => {
+                self.start_node(Yaml::BadValue);
+                self.containers.push(Container::Sequence { index: 0 });
+            }
+            Event::MappingEnd | Event::SequenceEnd => {
+                self.containers.pop();
+                self.end_node();
+            }
+            _ => {}
+        }
+    }
+}
+
+/// `node` with the mappings of its merge keys, `<<`, merged into its own,
+/// which take precedence.
+fn merge_keys(node: &Yaml) -> Yaml {
+    match node {
+        Yaml::Hash(hash) => {
+            let merge_key = Yaml::String("<<".to_string());
+            let mut merged: yaml_rust2::yaml::Hash = hash
+                .iter()
+                .filter(|(key, _)| **key != merge_key)
+                .map(|(key, value)| (key.clone(), merge_keys(value)))
+                .collect();
+            let sources = match hash.get(&merge_key) {
+                Some(Yaml::Array(sources)) => sources.iter().collect(),
+                Some(source) => vec![source],
+                None => Vec::new(),
+            };
+            for source in sources {
+                if let Yaml::Hash(source) = merge_keys(source) {
+                    for (key, value) in source {
+                        merged.entry(key).or_insert(value);
+                    }
+                }
+            }
+            Yaml::Hash(merged)
+        }
+        Yaml::Array(items) => Yaml::Array(items.iter().map(merge_keys).collect()),
+        node => node.clone(),
+    }
+}
+
+/// The node at `path` of `document`.
+fn lookup<'a>(document: &'a Yaml, path: &[Yaml]) -> Option<&'a Yaml> {
+    path.iter()
+        .try_fold(document, |node, segment| match (node, segment) {
+            (Yaml::Hash(hash), key) => hash.get(key),
+            (Yaml::Array(items), Yaml::Integer(index)) => items.get(*index as usize),
+            _ => None,
+        })
+}
+
+/// The alias of `line` at `character`, in UTF-16 code units, with the
+/// characters of its `*` and its end.
+fn alias_at(line: &str, character: u32) -> Option<(&str, usize, u32, u32)> {
+    regex!(r#"\*([^\s,\[\]{}]+)"#)
+        .captures_iter(line)
+        .find_map(|alias| {
+            let all = alias.get(0)?;
+            let utf16 = |offset: usize| line[..offset].encode_utf16().count() as u32;
+            let (start, end) = (utf16(all.start()), utf16(all.end()));
+            (start..=end).contains(&character).then(|| {
+                let column = line[..all.start()].chars().count();
+                (
+                    alias.get(1).map_or("", |name| name.as_str()),
+                    column,
+                    start,
+                    end,
+                )
+            })
+        })
+}
+
+impl YamlAnchors {
+    pub fn new() -> Result<Self, String> {
+        Ok(Self {})
+    }
+
+    /// Markdown of the node the alias at `position` refers to.
+    fn resolve(contents: &str, position: Position) -> Option<(String, u32, u32)> {
+        let line = contents.lines().nth(position.line as usize)?;
+        let (name, column, start, end) = alias_at(line, position.character)?;
+        let documents = match YamlLoader::load_from_str(contents) {
+            Ok(documents) => documents,
+            // Loading fails on the first alias without an anchor
+            Err(_)
+                if !regex!(r#"&([^\s,\[\]{}]+)"#)
+                    .captures_iter(contents)
+                    .any(|anchor| &anchor[1] == name) =>
+            {
+                return Some((format!("Undefined alias `*{name}`"), start, end));
+            }
+            Err(_) => return None,
+        };
+        let mut paths = AliasPaths::default();
+        Parser::new_from_str(contents).load(&mut paths, true).ok()?;
+        let alias = paths
+            .aliases
+            .iter()
+            .find(|alias| alias.line == position.line as usize && alias.column == column)?;
+        let node = lookup(documents.get(alias.document)?, &alias.path)?;
+
+        let mut rendered = String::new();
+        YamlEmitter::new(&mut rendered)
+            .dump(&merge_keys(node))
+            .ok()?;
+        let rendered = rendered.trim_start_matches("---").trim_start();
+        Some((
+            format!("Alias of `&{name}`\n\n```yaml\n{rendered}\n```"),
+            start,
+            end,
+        ))
+    }
+}
+
+impl Handler for YamlAnchors {
+    fn filetype_supported(&self, filetype: &str) -> bool {
+        filetype == "yaml"
+    }
+
+    fn get_capabilities(&self) -> ServerCapabilities {
+        ServerCapabilities {
+            hover_provider: Some(HoverProviderCapability::Simple(true)),
+            ..Default::default()
+        }
+    }
+
+    fn hover(
+        &self,
+        _filetype: &str,
+        _context: &DocumentContext,
+        contents: &str,
+        position: Position,
+    ) -> Result<Option<Hover>, HandlerError> {
+        Ok(
+            Self::resolve(contents, position).map(|(value, start, end)| Hover {
+                contents: HoverContents::Markup(MarkupContent {
+                    kind: MarkupKind::Markdown,
+                    value,
+                }),
+                range: Some(lsp_types::Range::new(
+                    Position::new(position.line, start),
+                    Position::new(position.line, end),
+                )),
+            }),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::YamlAnchors;
+    use tower_lsp::lsp_types::Position;
+
+    #[test]
+    fn test_hover_mapping_alias() {
+        let contents = "defaults: &defaults\n  adapter: postgres\n  pool: 5\n\ndevelopment:\n  <<: *defaults\n  database: dev\ntest:\n  settings: [*defaults]\n";
+        let (value, start, end) = YamlAnchors::resolve(contents, Position::new(5, 8)).unwrap();
+        assert_eq!(
+            value,
+            "Alias of `&defaults`\n\n```yaml\nadapter: postgres\npool: 5\n```"
+        );
+        assert_eq!((start, end), (6, 15));
+
+        // In a flow sequence
+        let (value, _, _) = YamlAnchors::resolve(contents, Position::new(8, 13)).unwrap();
+        assert!(value.ends_with("adapter: postgres\npool: 5\n```"));
+
+        // Merge keys of the anchored node are applied
+        let contents = "base: &base\n  a: 1\nchild: &child\n  <<: *base\n  b: 2\nuse: *child\n";
+        let (value, _, _) = YamlAnchors::resolve(contents, Position::new(5, 6)).unwrap();
+        assert_eq!(value, "Alias of `&child`\n\n```yaml\nb: 2\na: 1\n```");
+    }
+
+    #[test]
+    fn test_hover_undefined_alias() {
+        let contents = "defaults: &defaults\n  pool: 5\nproduction:\n  <<: *default\n";
+        let (value, _, _) = YamlAnchors::resolve(contents, Position::new(3, 8)).unwrap();
+        assert_eq!(value, "Undefined alias `*default`");
+
+        assert_eq!(YamlAnchors::resolve(contents, Position::new(1, 3)), None);
+    }
+}
@@ -1,24 +1,176 @@
-use std::collections::HashMap;
+use serde::Serialize;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::hash::{Hash, Hasher};
 use tokio::sync::Mutex;
-use tower_lsp::jsonrpc::Result;
+use tower_lsp::jsonrpc::{self, ErrorCode, Result};
 use tower_lsp::lsp_types::*;
 use tower_lsp::{Client, LanguageServer};
 
+pub mod check;
+mod config;
 mod handlers;
+mod metrics;
+pub mod notebook;
+mod sarif;
 
-use handlers::{AnyHandler, Handler, HandlerError};
+use config::Config;
+use handlers::{apply_text_edits, AnyHandler, DocumentContext, DocumentDiagnostics, HandlerError};
+use metrics::{Metrics, RequestTimer};
+use notebook::{
+    DidChangeNotebookDocumentParams, DidCloseNotebookDocumentParams, DidOpenNotebookDocumentParams,
+    NotebookCell,
+};
+
+/// A request the server could not complete, `RequestFailed` of the LSP
+/// specification.
+const REQUEST_FAILED: i64 = -32803;
+/// A server error caused by the environment the server runs in.
+const SERVER_ERROR: i64 = -32000;
+/// A request the server gave up on, which the client may send again,
+/// `ServerCancelled` of the LSP specification.
+const SERVER_CANCELLED: i64 = -32802;
+
+/// Whether a change of the file at `url` can't change diagnostics: build
+/// outputs and dependencies, which change often and aren't read, and the
+/// temporary files of the server and editors.
+fn ignored_change(url: &Url) -> bool {
+    let Some(segments) = url.path_segments() else {
+        return true;
+    };
+    let segments: Vec<_> = segments.collect();
+    if segments
+        .iter()
+        .any(|segment| matches!(*segment, ".git" | "target" | "node_modules"))
+    {
+        return true;
+    }
+    let name = segments.last().copied().unwrap_or_default();
+    // Written by `TempFiles::write_in`, then Emacs and Vim swap, lock and
+    // backup files, and the file Vim checks it can write a directory with
+    name.starts_with(handlers::TEMP_FILE_PREFIX)
+        || name.starts_with(".#")
+        || name.starts_with("%23") && name.ends_with("%23")
+        || name.ends_with('~')
+        || [".swp", ".swo", ".swx"]
+            .iter()
+            .any(|extension| name.ends_with(extension))
+        || name == "4913"
+}
+
+/// The error a request fails with when a handler fails.
+fn handler_error_to_response(err: HandlerError) -> jsonrpc::Error {
+    let code = match &err {
+        HandlerError::Log(_) => ErrorCode::ServerError(REQUEST_FAILED),
+        HandlerError::NoSuchDocument(_) => ErrorCode::InvalidParams,
+        HandlerError::ToolNotFound(_) => ErrorCode::ServerError(SERVER_ERROR),
+        HandlerError::Parse(_) => ErrorCode::InternalError,
+        HandlerError::Timeout(_) => ErrorCode::ServerError(REQUEST_FAILED),
+        HandlerError::ToolFailed { .. } => ErrorCode::ServerError(REQUEST_FAILED),
+    };
+    let message = match err {
+        HandlerError::ToolNotFound(tool) => {
+            format!("Could not find '{tool}', install it or add it to the PATH of the server")
+        }
+        err => err.to_string(),
+    };
+    jsonrpc::Error {
+        code,
+        message: message.into(),
+        data: None,
+    }
+}
+
+/// The response of `any_ls/capabilities`, for finding out why an editor
+/// doesn't offer a feature.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CapabilitiesReport {
+    /// The capabilities sent in the response to `initialize`.
+    pub capabilities: ServerCapabilities,
+    /// The handler that set each field of `capabilities` by the path of the
+    /// field, `any_ls` for fields the server sets itself.
+    pub sources: BTreeMap<String, String>,
+}
 
 #[derive(Debug)]
 pub struct Document {
     contents: String,
     version: i32,
-    handler: Mutex<AnyHandler>,
+    filetype: String,
+    /// The `result_id` of the last diagnostics pulled by the client.
+    result_id: Option<String>,
+    /// Other files diagnostics were last published for, e.g. imported files.
+    related: Vec<Url>,
+    /// The formatting edits last sent to the client, to recognize the
+    /// change it sends back once it applied them.
+    echo: Echo,
+}
+
+/// The change of the document made by the server's own formatting.
+#[derive(Debug, Default, PartialEq)]
+enum Echo {
+    #[default]
+    None,
+    /// Hash of the contents the edits sent to the client produce.
+    Expected(u64),
+    /// The version the client sent the edits back with, whose contents
+    /// are formatted already.
+    Received(i32),
+}
+
+fn contents_hash(contents: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    contents.hash(&mut hasher);
+    hasher.finish()
+}
+
+impl Document {
+    /// Identifies the diagnostics of the document, which change with its
+    /// contents and with the files it depends on, see `Backend::generation`.
+    fn result_id(&self, generation: u64) -> String {
+        let mut hasher = DefaultHasher::new();
+        generation.hash(&mut hasher);
+        self.filetype.hash(&mut hasher);
+        self.contents.hash(&mut hasher);
+        format!("{:016x}", hasher.finish())
+    }
 }
 
 #[derive(Debug)]
 pub struct Backend {
     client: Client,
     documents: Mutex<HashMap<Url, Document>>,
+    handler: Mutex<AnyHandler>,
+    /// Creates the handlers of the settings, at `initialize` and when they
+    /// change.
+    new_handler: fn(&Config) -> AnyHandler,
+    /// Open documents no handler supports, which requests ignore.
+    unsupported: Mutex<HashSet<Url>>,
+    /// Documents saved since their diagnostics were last computed, see
+    /// `Handler::on_save_only`.
+    saved: Mutex<HashSet<Url>>,
+    workspace_folders: Mutex<Vec<WorkspaceFolder>>,
+    /// Cells of open notebooks, in order. Their text is in `documents`.
+    notebooks: Mutex<HashMap<Url, Vec<NotebookCell>>>,
+    /// Whether the client pulls diagnostics with `textDocument/diagnostic`
+    /// instead of them being published.
+    pull_diagnostics: Mutex<bool>,
+    /// Whether documents are formatted before being saved, see
+    /// `Config::format_on_save`.
+    format_on_save: Mutex<bool>,
+    /// Counts saves and changes of watched files. Handlers read other files,
+    /// e.g. imported justfiles or settings, so the diagnostics of unchanged
+    /// documents change with it.
+    generation: Mutex<u64>,
+    /// Whether the client watches the files of the workspace for the server
+    /// once it is registered to.
+    watch_files: Mutex<bool>,
+    /// `Config::root_markers`, the defaults of `DocumentContext` when unset.
+    root_markers: Mutex<Option<Vec<String>>>,
+    /// Durations of handled requests.
+    metrics: Metrics,
+    /// The capabilities sent to the client, see `Backend::capabilities`.
+    advertised: Mutex<CapabilitiesReport>,
 }
 
 impl Backend {
@@ -26,59 +178,265 @@ impl Backend {
         Self {
             client,
             documents: Mutex::new(HashMap::new()),
+            handler: Mutex::new(AnyHandler::default()),
+            new_handler: AnyHandler::new,
+            unsupported: Mutex::new(HashSet::new()),
+            saved: Mutex::new(HashSet::new()),
+            workspace_folders: Mutex::new(Vec::new()),
+            notebooks: Mutex::new(HashMap::new()),
+            pull_diagnostics: Mutex::new(false),
+            format_on_save: Mutex::new(false),
+            generation: Mutex::new(0),
+            watch_files: Mutex::new(false),
+            root_markers: Mutex::new(None),
+            metrics: Metrics::default(),
+            advertised: Mutex::new(CapabilitiesReport::default()),
         }
     }
 }
 
 impl Backend {
-    async fn init_handler(&self, url: Url, version: i32, filetype: &str) {
-        let handler = match AnyHandler::from_filetype(filetype) {
-            Some(handler) => handler,
-            None => {
+    async fn document_context(&self, url: &Url) -> DocumentContext {
+        let context =
+            DocumentContext::new(url.clone(), self.workspace_folders.lock().await.clone());
+        match self.root_markers.lock().await.clone() {
+            Some(root_markers) => context.with_root_markers(root_markers),
+            None => context,
+        }
+    }
+
+    /// Times a request of `method` for the document at `url` until the timer
+    /// is dropped.
+    async fn timer(&self, method: &'static str, url: &Url) -> RequestTimer<'_> {
+        let guard = self.documents.lock().await;
+        let (filetype, size) = guard.get(url).map_or(("unknown", 0), |document| {
+            (document.filetype.as_str(), document.contents.len())
+        });
+        self.metrics.start(method, filetype, size)
+    }
+
+    /// Logs a failed handler request to the client.
+    async fn log_error<T>(&self, handler_out: std::result::Result<T, HandlerError>) -> Option<T> {
+        match handler_out {
+            Ok(value) => Some(value),
+            Err(err) => {
                 self.client
-                    .log_message(
-                        MessageType::WARNING,
-                        format!("No handler for filetype: {filetype}"),
-                    )
+                    .log_message(MessageType::ERROR, err.to_string())
                     .await;
-                return;
+                None
             }
-        };
+        }
+    }
+
+    /// Fails the request for handler errors the client should see, `Log`
+    /// errors are left to be logged.
+    fn request_error<T>(
+        handler_out: std::result::Result<T, HandlerError>,
+    ) -> Result<std::result::Result<T, HandlerError>> {
+        match handler_out {
+            Err(err @ HandlerError::Log(_)) => Ok(Err(err)),
+            Err(err) => Err(handler_error_to_response(err)),
+            Ok(value) => Ok(Ok(value)),
+        }
+    }
+
+    /// Edits formatting the document at `url`, none for the contents the
+    /// server formatted itself, e.g. when the client saves again after
+    /// applying the edits of formatting on save.
+    async fn format_document(&self, url: &Url) -> Option<Vec<TextEdit>> {
+        let context = self.document_context(url).await;
         let mut guard = self.documents.lock().await;
-        guard.insert(
-            url,
+        let document = guard.get_mut(url)?;
+        if document.echo == Echo::Received(document.version) {
+            log::debug!(
+                "Skipped formatting version {} of {url}, formatted by the server",
+                document.version
+            );
+            return None;
+        }
+        let handler_out = self
+            .handler
+            .lock()
+            .await
+            .format(&document.filetype, &context, &document.contents)
+            .await;
+        if let Ok(Some(edits)) = &handler_out {
+            if !edits.is_empty() {
+                let formatted = apply_text_edits(&document.contents, edits);
+                document.echo = Echo::Expected(contents_hash(&formatted));
+            }
+        }
+        drop(guard);
+
+        self.log_error(handler_out).await.flatten()
+    }
+
+    async fn open_document(&self, url: Url, version: i32, filetype: &str, contents: &str) {
+        let context = self.document_context(&url).await;
+        // Kept even without a handler, the handlers change with the settings
+        self.documents.lock().await.insert(
+            url.clone(),
             Document {
                 contents: String::new(),
                 version,
-                handler: Mutex::new(handler),
+                filetype: filetype.to_string(),
+                result_id: None,
+                related: Vec::new(),
+                echo: Echo::None,
             },
         );
+        if !self
+            .handler
+            .lock()
+            .await
+            .document_supported(filetype, &context, contents)
+        {
+            self.unsupported.lock().await.insert(url);
+            self.client
+                .log_message(
+                    MessageType::WARNING,
+                    format!("No handler for filetype: {filetype}"),
+                )
+                .await;
+            return;
+        }
+        self.unsupported.lock().await.remove(&url);
+    }
+
+    /// Marks the open documents no handler supports, after the handlers
+    /// changed. Returns those that were supported before.
+    async fn update_supported(&self) -> Vec<Url> {
+        let notebooks = self.notebooks.lock().await;
+        let cells: HashSet<&Url> = notebooks
+            .values()
+            .flatten()
+            .map(|cell| &cell.document)
+            .collect();
+        // Copied, the documents aren't locked while contexts are created
+        let documents: Vec<(Url, String, String)> = self
+            .documents
+            .lock()
+            .await
+            .iter()
+            .filter(|(url, _)| !cells.contains(url))
+            .map(|(url, document)| {
+                (
+                    url.clone(),
+                    document.filetype.clone(),
+                    document.contents.clone(),
+                )
+            })
+            .collect();
+        drop(notebooks);
+
+        let mut unsupported = HashSet::new();
+        for (url, filetype, contents) in documents {
+            let context = self.document_context(&url).await;
+            if !self
+                .handler
+                .lock()
+                .await
+                .document_supported(&filetype, &context, &contents)
+            {
+                unsupported.insert(url);
+            }
+        }
+        let previous = std::mem::replace(&mut *self.unsupported.lock().await, unsupported.clone());
+        unsupported.difference(&previous).cloned().collect()
     }
 
     async fn update_document(&self, url: &Url, version: i32, contents: String) {
         let mut guard = self.documents.lock().await;
         if let Some(document) = guard.get_mut(url) {
+            // Only the first change after formatting can be its edits
+            document.echo = match document.echo {
+                Echo::Expected(hash) if hash == contents_hash(&contents) => {
+                    log::debug!("Version {version} of {url} is the server's formatting");
+                    Echo::Received(version)
+                }
+                _ => Echo::None,
+            };
             document.contents = contents;
             document.version = version;
         }
     }
 
+    /// Diagnostics of the document at `url` and the version they are of.
+    /// The documents aren't locked while they are computed, so that edits
+    /// aren't held up by slow handlers, and the document may have changed
+    /// by the time they are done.
+    async fn compute_diagnostics(
+        &self,
+        url: &Url,
+    ) -> Option<(i32, std::result::Result<DocumentDiagnostics, HandlerError>)> {
+        if self.unsupported.lock().await.contains(url) {
+            return None;
+        }
+        let mut context = self.document_context(url).await;
+        context.saved = self.saved.lock().await.remove(url);
+        let (version, filetype, contents) = {
+            let guard = self.documents.lock().await;
+            let document = guard.get(url)?;
+            (
+                document.version,
+                document.filetype.clone(),
+                document.contents.clone(),
+            )
+        };
+        let handler_out = self
+            .handler
+            .lock()
+            .await
+            .update_document_diagnostics(&filetype, &context, &contents)
+            .await;
+        Some((version, handler_out))
+    }
+
     async fn report_diagnostics(&self, url: Url) {
-        let guard = self.documents.lock().await;
-        let (version, handler_out) = if let Some(document) = guard.get(&url) {
-            let mut handler = document.handler.lock().await;
-            let handler_out = handler.update_diagnostics(&document.contents).await;
-            (document.version, handler_out)
-        } else {
+        if *self.pull_diagnostics.lock().await {
+            return;
+        }
+        let Some((version, handler_out)) = self.compute_diagnostics(&url).await else {
             // No handler
             return;
         };
+        let mut guard = self.documents.lock().await;
+        let previous = match guard.get_mut(&url) {
+            Some(document) if document.version == version => {
+                let related = match &handler_out {
+                    Ok(diagnostics) => diagnostics.related.keys().cloned().collect(),
+                    Err(_) => Vec::new(),
+                };
+                std::mem::replace(&mut document.related, related)
+            }
+            // Edited or closed since, the diagnostics of the edit replace
+            // these
+            _ => {
+                log::debug!("Dropped diagnostics of version {version} of {url}");
+                return;
+            }
+        };
         drop(guard);
 
+        // Clear diagnostics of files that no longer have any
+        for related in previous {
+            if handler_out.as_ref().map_or(true, |diagnostics| {
+                !diagnostics.related.contains_key(&related)
+            }) {
+                self.client
+                    .publish_diagnostics(related, Vec::new(), None)
+                    .await;
+            }
+        }
         match handler_out {
             Ok(diagnostics) => {
+                for (related, diagnostics) in diagnostics.related {
+                    self.client
+                        .publish_diagnostics(related, diagnostics, None)
+                        .await;
+                }
                 self.client
-                    .publish_diagnostics(url, diagnostics, Some(version))
+                    .publish_diagnostics(url, diagnostics.diagnostics, Some(version))
                     .await;
             }
             Err(err) => {
@@ -87,31 +445,271 @@ impl Backend {
                     .publish_diagnostics(url, Vec::new(), Some(version))
                     .await;
 
-                match err {
-                    HandlerError::Log(text) => {
-                        self.client.log_message(MessageType::ERROR, text).await
-                    }
-                }
+                self.client
+                    .log_message(MessageType::ERROR, err.to_string())
+                    .await;
+            }
+        }
+    }
+}
+
+/// Notebook notifications, registered as custom methods.
+impl Backend {
+    async fn report_notebook_diagnostics(&self, notebook: &Url) {
+        let mut context = self.document_context(notebook).await;
+        // Notebooks are saved without notifying the server, their cells are
+        // checked on every change instead
+        context.saved = true;
+        let cells = self
+            .notebooks
+            .lock()
+            .await
+            .get(notebook)
+            .cloned()
+            .unwrap_or_default();
+        // Copied, the documents aren't locked while handlers run
+        let guard = self.documents.lock().await;
+        let cells: Vec<(Url, String, String, i32)> = cells
+            .iter()
+            .filter(|cell| cell.kind == notebook::CODE_CELL)
+            .filter_map(|cell| {
+                let document = guard.get(&cell.document)?;
+                Some((
+                    cell.document.clone(),
+                    document.filetype.clone(),
+                    document.contents.clone(),
+                    document.version,
+                ))
+            })
+            .collect();
+        drop(guard);
+
+        // Cells of each language are checked together
+        let filetypes: BTreeSet<&str> = cells
+            .iter()
+            .map(|(_, filetype, _, _)| filetype.as_str())
+            .collect();
+        let mut published = Vec::new();
+        let mut errors = Vec::new();
+        for filetype in filetypes {
+            let cells: Vec<&(Url, String, String, i32)> = cells
+                .iter()
+                .filter(|(_, cell_filetype, _, _)| cell_filetype == filetype)
+                .collect();
+            let (source, starts) =
+                notebook::concat(cells.iter().map(|(_, _, contents, _)| contents.as_str()));
+            let diagnostics = self
+                .handler
+                .lock()
+                .await
+                .update_diagnostics(filetype, &context, &source)
+                .await
+                .unwrap_or_else(|err| {
+                    errors.push(err);
+                    Vec::new()
+                });
+            for ((uri, _, _, version), diagnostics) in
+                cells.iter().zip(notebook::split(&starts, diagnostics))
+            {
+                published.push((uri.clone(), diagnostics, *version));
+            }
+        }
+
+        for (uri, diagnostics, version) in published {
+            self.client
+                .publish_diagnostics(uri, diagnostics, Some(version))
+                .await;
+        }
+        for err in errors {
+            self.log_error::<()>(Err(err)).await;
+        }
+    }
+
+    fn open_cell(documents: &mut HashMap<Url, Document>, cell: TextDocumentItem) {
+        documents.insert(
+            cell.uri,
+            Document {
+                contents: cell.text,
+                version: cell.version,
+                filetype: cell.language_id,
+                result_id: None,
+                related: Vec::new(),
+                echo: Echo::None,
+            },
+        );
+    }
+
+    /// Handles `any_ls/capabilities`: the capabilities the server sent, with
+    /// the handler each one comes from.
+    pub async fn capabilities(&self) -> Result<CapabilitiesReport> {
+        Ok(self.advertised.lock().await.clone())
+    }
+
+    pub async fn did_open_notebook(&self, params: DidOpenNotebookDocumentParams) {
+        let mut documents = self.documents.lock().await;
+        for cell in params.cell_text_documents {
+            Self::open_cell(&mut documents, cell);
+        }
+        drop(documents);
+
+        let uri = params.notebook_document.uri;
+        self.notebooks
+            .lock()
+            .await
+            .insert(uri.clone(), params.notebook_document.cells);
+        self.report_notebook_diagnostics(&uri).await;
+    }
+
+    pub async fn did_change_notebook(&self, params: DidChangeNotebookDocumentParams) {
+        let uri = params.notebook_document.uri;
+        let Some(change) = params.change.cells else {
+            return;
+        };
+
+        let mut notebooks = self.notebooks.lock().await;
+        let mut documents = self.documents.lock().await;
+        let Some(cells) = notebooks.get_mut(&uri) else {
+            return;
+        };
+        if let Some(structure) = change.structure {
+            let array = structure.array;
+            let end = (array.start + array.delete_count).min(cells.len());
+            cells.splice(array.start.min(end)..end, array.cells.unwrap_or_default());
+            for cell in structure.did_close.unwrap_or_default() {
+                documents.remove(&cell.uri);
+            }
+            for cell in structure.did_open.unwrap_or_default() {
+                Self::open_cell(&mut documents, cell);
+            }
+        }
+        for changed in change.data.unwrap_or_default() {
+            if let Some(cell) = cells
+                .iter_mut()
+                .find(|cell| cell.document == changed.document)
+            {
+                cell.kind = changed.kind;
+            }
+        }
+        for text in change.text_content.unwrap_or_default() {
+            // Full sync, the last change has the whole cell
+            if let (Some(document), Some(change)) =
+                (documents.get_mut(&text.document.uri), text.changes.last())
+            {
+                document.contents = change.text.clone();
+                document.version = text.document.version;
             }
         }
+        drop(documents);
+        drop(notebooks);
+
+        self.report_notebook_diagnostics(&uri).await;
+    }
+
+    pub async fn did_close_notebook(&self, params: DidCloseNotebookDocumentParams) {
+        self.notebooks
+            .lock()
+            .await
+            .remove(&params.notebook_document.uri);
+        self.handler
+            .lock()
+            .await
+            .close_document(&params.notebook_document.uri);
+        let mut documents = self.documents.lock().await;
+        for cell in params.cell_text_documents {
+            documents.remove(&cell.uri);
+            // Clear diagnostics
+            self.client
+                .publish_diagnostics(cell.uri, Vec::new(), None)
+                .await;
+        }
     }
 }
 
 #[tower_lsp::async_trait]
 impl LanguageServer for Backend {
-    async fn initialize(&self, _: InitializeParams) -> Result<InitializeResult> {
+    async fn initialize(&self, params: InitializeParams) -> Result<InitializeResult> {
+        if let Some(workspace_folders) = params.workspace_folders {
+            *self.workspace_folders.lock().await = workspace_folders;
+        }
+
+        let config = match Config::from_value(params.initialization_options) {
+            Ok(config) => config,
+            Err(err) => {
+                self.client.log_message(MessageType::ERROR, err).await;
+                Config::default()
+            }
+        };
+        for err in config.validate() {
+            self.client.log_message(MessageType::ERROR, err).await;
+        }
+
+        let will_save_wait_until = params
+            .capabilities
+            .text_document
+            .as_ref()
+            .and_then(|text_document| text_document.synchronization.as_ref())
+            .and_then(|synchronization| synchronization.will_save_wait_until)
+            .unwrap_or(false);
+        let pull_diagnostics = params
+            .capabilities
+            .text_document
+            .is_some_and(|text_document| text_document.diagnostic.is_some());
+        let watch_files = params
+            .capabilities
+            .workspace
+            .as_ref()
+            .and_then(|workspace| workspace.did_change_watched_files)
+            .and_then(|watched_files| watched_files.dynamic_registration)
+            .unwrap_or(false);
+        *self.pull_diagnostics.lock().await = pull_diagnostics;
+        *self.watch_files.lock().await = watch_files;
+        *self.format_on_save.lock().await = config.format_on_save;
+        *self.root_markers.lock().await = config.root_markers.clone();
+
+        let mut handler = self.handler.lock().await;
+        *handler = (self.new_handler)(&config);
+        let capabilities = ServerCapabilities {
+            position_encoding: Some(PositionEncodingKind::UTF16),
+            text_document_sync: Some(TextDocumentSyncCapability::Options(
+                TextDocumentSyncOptions {
+                    open_close: Some(true),
+                    change: Some(TextDocumentSyncKind::FULL),
+                    will_save: None,
+                    will_save_wait_until: will_save_wait_until.then_some(true),
+                    save: Some(TextDocumentSyncSaveOptions::Supported(true)),
+                },
+            )),
+            workspace: Some(WorkspaceServerCapabilities {
+                workspace_folders: None,
+                file_operations: None,
+            }),
+            diagnostic_provider: pull_diagnostics.then(|| {
+                DiagnosticServerCapabilities::Options(DiagnosticOptions {
+                    identifier: Some("any_ls".to_string()),
+                    inter_file_dependencies: true,
+                    workspace_diagnostics: false,
+                    work_done_progress_options: Default::default(),
+                })
+            }),
+            ..handler.get_capabilities()
+        };
+
+        // The fields set above replace those of the handlers
+        let mut sources = handler.capability_sources();
+        sources.retain(|field, _| !field.starts_with("workspace"));
+        let server_fields = ["positionEncoding", "textDocumentSync", "workspace"]
+            .into_iter()
+            .chain(pull_diagnostics.then_some("diagnosticProvider"));
+        for field in server_fields {
+            sources.insert(field.to_string(), "any_ls".to_string());
+        }
+        *self.advertised.lock().await = CapabilitiesReport {
+            capabilities: capabilities.clone(),
+            sources,
+        };
+
         Ok(InitializeResult {
-            capabilities: ServerCapabilities {
-                position_encoding: Some(PositionEncodingKind::UTF16),
-                text_document_sync: Some(TextDocumentSyncCapability::Kind(
-                    TextDocumentSyncKind::FULL,
-                )),
-                workspace: Some(WorkspaceServerCapabilities {
-                    workspace_folders: None,
-                    file_operations: None,
-                }),
-                ..Default::default()
-            },
+            capabilities,
             server_info: Some(ServerInfo {
                 name: "any_ls".to_string(),
                 version: Some(env!("CARGO_PKG_VERSION").to_string()),
@@ -123,17 +721,136 @@ impl LanguageServer for Backend {
         self.client
             .log_message(MessageType::INFO, "server initialized!")
             .await;
+        if !*self.watch_files.lock().await {
+            return;
+        }
+        // Any file may be read by a handler
+        let options = DidChangeWatchedFilesRegistrationOptions {
+            watchers: vec![FileSystemWatcher {
+                glob_pattern: GlobPattern::String("**/*".to_string()),
+                kind: None,
+            }],
+        };
+        let registration = Registration {
+            id: "any_ls.watchedFiles".to_string(),
+            method: "workspace/didChangeWatchedFiles".to_string(),
+            register_options: serde_json::to_value(options).ok(),
+        };
+        if let Err(err) = self.client.register_capability(vec![registration]).await {
+            self.client
+                .log_message(
+                    MessageType::WARNING,
+                    format!("Could not watch the files of the workspace: {err}"),
+                )
+                .await;
+        }
     }
 
     async fn shutdown(&self) -> Result<()> {
+        self.metrics.log_summary();
         Ok(())
     }
 
+    async fn did_change_configuration(&self, params: DidChangeConfigurationParams) {
+        // Clients may namespace the settings by server
+        let settings = match params.settings {
+            serde_json::Value::Object(mut settings) if settings.contains_key("any_ls") => {
+                settings.remove("any_ls")
+            }
+            settings => Some(settings),
+        };
+        let config = match Config::from_value(settings) {
+            Ok(config) => config,
+            Err(err) => {
+                self.client.log_message(MessageType::ERROR, err).await;
+                return;
+            }
+        };
+        for err in config.validate() {
+            self.client.log_message(MessageType::ERROR, err).await;
+        }
+
+        *self.format_on_save.lock().await = config.format_on_save;
+        *self.root_markers.lock().await = config.root_markers.clone();
+        let mut handler = self.handler.lock().await;
+        let previous = handler.enabled().to_vec();
+        *handler = (self.new_handler)(&config);
+        let enabled: Vec<&str> = handler
+            .enabled()
+            .iter()
+            .filter(|name| !previous.contains(name))
+            .map(String::as_str)
+            .collect();
+        let disabled: Vec<&str> = previous
+            .iter()
+            .filter(|name| !handler.enabled().contains(name))
+            .map(String::as_str)
+            .collect();
+        let message = format!(
+            "Settings updated, enabled handlers: [{}], disabled handlers: [{}]",
+            enabled.join(", "),
+            disabled.join(", ")
+        );
+        drop(handler);
+        self.client.log_message(MessageType::INFO, message).await;
+
+        // Documents without a handler before may have one now, and the
+        // other way around
+        for url in self.update_supported().await {
+            let related = self
+                .documents
+                .lock()
+                .await
+                .get_mut(&url)
+                .map(|document| std::mem::take(&mut document.related))
+                .unwrap_or_default();
+            for uri in related.into_iter().chain([url]) {
+                self.client.publish_diagnostics(uri, Vec::new(), None).await;
+            }
+        }
+
+        // Capabilities are fixed at `initialize`, but diagnostics can be
+        // refreshed with the new handlers
+        if *self.pull_diagnostics.lock().await {
+            for document in self.documents.lock().await.values_mut() {
+                document.result_id = None;
+            }
+            // Fails if the client doesn't support refreshing
+            let _ = self.client.workspace_diagnostic_refresh().await;
+        }
+        // Cells are checked with the rest of their notebook
+        let notebooks: Vec<(Url, HashSet<Url>)> = self
+            .notebooks
+            .lock()
+            .await
+            .iter()
+            .map(|(notebook, cells)| {
+                let cells = cells.iter().map(|cell| cell.document.clone()).collect();
+                (notebook.clone(), cells)
+            })
+            .collect();
+        let urls: Vec<Url> = self
+            .documents
+            .lock()
+            .await
+            .keys()
+            .filter(|url| !notebooks.iter().any(|(_, cells)| cells.contains(url)))
+            .cloned()
+            .collect();
+        for url in urls {
+            self.report_diagnostics(url).await;
+        }
+        for (notebook, _) in notebooks {
+            self.report_notebook_diagnostics(&notebook).await;
+        }
+    }
+
     async fn did_open(&self, params: DidOpenTextDocumentParams) {
-        self.init_handler(
+        self.open_document(
             params.text_document.uri.clone(),
             params.text_document.version,
             &params.text_document.language_id,
+            &params.text_document.text,
         )
         .await;
         self.update_document(
@@ -146,6 +863,9 @@ impl LanguageServer for Backend {
     }
 
     async fn did_change(&self, mut params: DidChangeTextDocumentParams) {
+        // Diagnostics are computed on save and when pulled, not on changes,
+        // and the change of the server's formatting echoed by the client
+        // isn't formatted again
         self.update_document(
             &params.text_document.uri,
             params.text_document.version,
@@ -155,15 +875,1167 @@ impl LanguageServer for Backend {
     }
 
     async fn did_save(&self, params: DidSaveTextDocumentParams) {
+        // Other documents may depend on the saved one
+        *self.generation.lock().await += 1;
+        self.saved
+            .lock()
+            .await
+            .insert(params.text_document.uri.clone());
         self.report_diagnostics(params.text_document.uri).await;
     }
 
+    async fn did_change_watched_files(&self, params: DidChangeWatchedFilesParams) {
+        let relevant = params
+            .changes
+            .iter()
+            .any(|change| !ignored_change(&change.uri));
+        if !relevant {
+            return;
+        }
+        *self.generation.lock().await += 1;
+        if *self.pull_diagnostics.lock().await {
+            // Fails if the client doesn't support refreshing
+            let _ = self.client.workspace_diagnostic_refresh().await;
+        }
+    }
+
+    async fn diagnostic(
+        &self,
+        params: DocumentDiagnosticParams,
+    ) -> Result<DocumentDiagnosticReportResult> {
+        let url = params.text_document.uri;
+        let _timer = self.timer("textDocument/diagnostic", &url).await;
+        // Cells are checked with the rest of their notebook
+        let in_notebook = self
+            .notebooks
+            .lock()
+            .await
+            .values()
+            .flatten()
+            .any(|cell| cell.document == url);
+        let unsupported = self.unsupported.lock().await.contains(&url);
+        let generation = *self.generation.lock().await;
+        let guard = self.documents.lock().await;
+        let handler_out = match guard.get(&url) {
+            // No handler, or checked with the notebook
+            Some(_) if unsupported || in_notebook => Some(Ok(Default::default())),
+            Some(document) => {
+                let result_id = document.result_id(generation);
+                if params.previous_result_id.as_ref() == Some(&result_id)
+                    && document.result_id.as_ref() == Some(&result_id)
+                {
+                    return Ok(DocumentDiagnosticReportResult::Report(
+                        DocumentDiagnosticReport::Unchanged(
+                            RelatedUnchangedDocumentDiagnosticReport {
+                                related_documents: None,
+                                unchanged_document_diagnostic_report:
+                                    UnchangedDocumentDiagnosticReport { result_id },
+                            },
+                        ),
+                    ));
+                }
+                None
+            }
+            None => Some(Err(HandlerError::NoSuchDocument(url.clone()))),
+        };
+        drop(guard);
+
+        let (result_id, handler_out) = match handler_out {
+            Some(handler_out) => (None, handler_out),
+            None => {
+                let Some((version, handler_out)) = self.compute_diagnostics(&url).await else {
+                    return Err(handler_error_to_response(HandlerError::NoSuchDocument(url)));
+                };
+                let mut guard = self.documents.lock().await;
+                let Some(document) = guard
+                    .get_mut(&url)
+                    .filter(|document| document.version == version)
+                else {
+                    // Edited since, the client pulls the diagnostics of the
+                    // edit instead
+                    return Err(jsonrpc::Error {
+                        code: ErrorCode::ServerError(SERVER_CANCELLED),
+                        message: "The document changed while its diagnostics were computed".into(),
+                        data: serde_json::to_value(DiagnosticServerCancellationData {
+                            retrigger_request: true,
+                        })
+                        .ok(),
+                    });
+                };
+                let result_id = document.result_id(generation);
+                if handler_out.is_ok() {
+                    document.result_id = Some(result_id.clone());
+                }
+                (Some(result_id), handler_out)
+            }
+        };
+
+        let handler_out = Self::request_error(handler_out)?;
+        let Some(diagnostics) = self.log_error(handler_out).await else {
+            return Ok(DocumentDiagnosticReportResult::Report(
+                DocumentDiagnosticReport::Full(Default::default()),
+            ));
+        };
+        let related_documents = (!diagnostics.related.is_empty()).then(|| {
+            diagnostics
+                .related
+                .into_iter()
+                .map(|(uri, items)| {
+                    let report = FullDocumentDiagnosticReport {
+                        result_id: None,
+                        items,
+                    };
+                    (uri, DocumentDiagnosticReportKind::Full(report))
+                })
+                .collect()
+        });
+        Ok(DocumentDiagnosticReportResult::Report(
+            DocumentDiagnosticReport::Full(RelatedFullDocumentDiagnosticReport {
+                related_documents,
+                full_document_diagnostic_report: FullDocumentDiagnosticReport {
+                    result_id,
+                    items: diagnostics.diagnostics,
+                },
+            }),
+        ))
+    }
+
+    async fn hover(&self, params: HoverParams) -> Result<Option<Hover>> {
+        let url = params.text_document_position_params.text_document.uri;
+        let _timer = self.timer("textDocument/hover", &url).await;
+        let context = self.document_context(&url).await;
+        let unsupported = self.unsupported.lock().await.contains(&url);
+        let guard = self.documents.lock().await;
+        let handler_out = match guard.get(&url) {
+            // No handler
+            Some(_) if unsupported => Ok(None),
+            Some(document) => self.handler.lock().await.hover(
+                &document.filetype,
+                &context,
+                &document.contents,
+                params.text_document_position_params.position,
+            ),
+            None => Err(HandlerError::NoSuchDocument(url)),
+        };
+        drop(guard);
+
+        let handler_out = Self::request_error(handler_out)?;
+        Ok(self.log_error(handler_out).await.flatten())
+    }
+
+    async fn document_link(&self, params: DocumentLinkParams) -> Result<Option<Vec<DocumentLink>>> {
+        let url = params.text_document.uri;
+        let _timer = self.timer("textDocument/documentLink", &url).await;
+        let context = self.document_context(&url).await;
+        let guard = self.documents.lock().await;
+        let Some(document) = guard.get(&url) else {
+            return Ok(None);
+        };
+        let handler_out = self.handler.lock().await.document_links(
+            &document.filetype,
+            &context,
+            &document.contents,
+        );
+        drop(guard);
+
+        Ok(self.log_error(handler_out).await)
+    }
+
+    async fn document_color(&self, params: DocumentColorParams) -> Result<Vec<ColorInformation>> {
+        let url = params.text_document.uri;
+        let _timer = self.timer("textDocument/documentColor", &url).await;
+        let context = self.document_context(&url).await;
+        let guard = self.documents.lock().await;
+        let Some(document) = guard.get(&url) else {
+            return Ok(Vec::new());
+        };
+        let handler_out = self.handler.lock().await.document_colors(
+            &document.filetype,
+            &context,
+            &document.contents,
+        );
+        drop(guard);
+
+        Ok(self.log_error(handler_out).await.unwrap_or_default())
+    }
+
+    async fn color_presentation(
+        &self,
+        params: ColorPresentationParams,
+    ) -> Result<Vec<ColorPresentation>> {
+        let url = params.text_document.uri;
+        let context = self.document_context(&url).await;
+        let guard = self.documents.lock().await;
+        let Some(document) = guard.get(&url) else {
+            return Ok(Vec::new());
+        };
+        let handler_out = self.handler.lock().await.color_presentations(
+            &document.filetype,
+            &context,
+            params.color,
+            params.range,
+        );
+        drop(guard);
+
+        Ok(self.log_error(handler_out).await.unwrap_or_default())
+    }
+
+    async fn document_symbol(
+        &self,
+        params: DocumentSymbolParams,
+    ) -> Result<Option<DocumentSymbolResponse>> {
+        let url = params.text_document.uri;
+        let _timer = self.timer("textDocument/documentSymbol", &url).await;
+        let context = self.document_context(&url).await;
+        let guard = self.documents.lock().await;
+        let Some(document) = guard.get(&url) else {
+            return Ok(None);
+        };
+        let handler_out = self.handler.lock().await.document_symbols(
+            &document.filetype,
+            &context,
+            &document.contents,
+        );
+        drop(guard);
+
+        Ok(self
+            .log_error(handler_out)
+            .await
+            .map(DocumentSymbolResponse::Nested))
+    }
+
+    async fn completion(&self, params: CompletionParams) -> Result<Option<CompletionResponse>> {
+        let url = params.text_document_position.text_document.uri;
+        let _timer = self.timer("textDocument/completion", &url).await;
+        let context = self.document_context(&url).await;
+        let guard = self.documents.lock().await;
+        let Some(document) = guard.get(&url) else {
+            return Ok(None);
+        };
+        let handler_out = self.handler.lock().await.completions(
+            &document.filetype,
+            &context,
+            &document.contents,
+            params.text_document_position.position,
+        );
+        drop(guard);
+
+        Ok(self
+            .log_error(handler_out)
+            .await
+            .filter(|items| !items.is_empty())
+            .map(CompletionResponse::Array))
+    }
+
+    async fn code_action(&self, params: CodeActionParams) -> Result<Option<CodeActionResponse>> {
+        let url = params.text_document.uri;
+        let _timer = self.timer("textDocument/codeAction", &url).await;
+        let context = self.document_context(&url).await;
+        let guard = self.documents.lock().await;
+        let Some(document) = guard.get(&url) else {
+            return Ok(None);
+        };
+        let handler_out = self.handler.lock().await.code_actions(
+            &document.filetype,
+            &context,
+            &params.context.diagnostics,
+        );
+        drop(guard);
+
+        Ok(self
+            .log_error(handler_out)
+            .await
+            .filter(|actions| !actions.is_empty())
+            .map(|actions| {
+                actions
+                    .into_iter()
+                    .map(CodeActionOrCommand::CodeAction)
+                    .collect()
+            }))
+    }
+
+    async fn goto_definition(
+        &self,
+        params: GotoDefinitionParams,
+    ) -> Result<Option<GotoDefinitionResponse>> {
+        let url = params.text_document_position_params.text_document.uri;
+        let _timer = self.timer("textDocument/definition", &url).await;
+        let context = self.document_context(&url).await;
+        let guard = self.documents.lock().await;
+        let Some(document) = guard.get(&url) else {
+            return Ok(None);
+        };
+        let handler_out = self.handler.lock().await.definition(
+            &document.filetype,
+            &context,
+            &document.contents,
+            params.text_document_position_params.position,
+        );
+        drop(guard);
+
+        Ok(self
+            .log_error(handler_out)
+            .await
+            .flatten()
+            .map(GotoDefinitionResponse::Scalar))
+    }
+
+    async fn linked_editing_range(
+        &self,
+        params: LinkedEditingRangeParams,
+    ) -> Result<Option<LinkedEditingRanges>> {
+        let url = params.text_document_position_params.text_document.uri;
+        let _timer = self.timer("textDocument/linkedEditingRange", &url).await;
+        let context = self.document_context(&url).await;
+        let guard = self.documents.lock().await;
+        let Some(document) = guard.get(&url) else {
+            return Ok(None);
+        };
+        let handler_out = self.handler.lock().await.linked_editing_ranges(
+            &document.filetype,
+            &context,
+            &document.contents,
+            params.text_document_position_params.position,
+        );
+        drop(guard);
+
+        Ok(self.log_error(handler_out).await.flatten())
+    }
+
+    async fn execute_command(
+        &self,
+        params: ExecuteCommandParams,
+    ) -> Result<Option<serde_json::Value>> {
+        let workspace_folders = self.workspace_folders.lock().await.clone();
+        let handler_out = self
+            .handler
+            .lock()
+            .await
+            .execute_workspace_command(&params.command, &workspace_folders);
+        if let Some(summary) = self.log_error(handler_out).await.flatten() {
+            self.client
+                .show_message(MessageType::INFO, summary.clone())
+                .await;
+            return Ok(Some(serde_json::Value::String(summary)));
+        }
+
+        // Other commands run for the document whose URI is the first
+        // argument
+        let Some(url) = params
+            .arguments
+            .first()
+            .and_then(|url| serde_json::from_value::<Url>(url.clone()).ok())
+        else {
+            return Err(jsonrpc::Error::invalid_params(
+                "Expected the URI of a document as the first argument",
+            ));
+        };
+        let _timer = self.timer("workspace/executeCommand", &url).await;
+        let context = self.document_context(&url).await;
+        let guard = self.documents.lock().await;
+        let Some(document) = guard.get(&url) else {
+            return Ok(None);
+        };
+        let handler_out = self.handler.lock().await.execute_command(
+            &params.command,
+            &params.arguments[1..],
+            &document.filetype,
+            &context,
+            &document.contents,
+        );
+        drop(guard);
+
+        let Some(edit) = self.log_error(handler_out).await.flatten() else {
+            return Ok(None);
+        };
+        // Applied by the server, and returned for clients applying it
+        // themselves
+        if let Err(err) = self.client.apply_edit(edit.clone()).await {
+            self.client
+                .log_message(MessageType::ERROR, err.to_string())
+                .await;
+        }
+        Ok(serde_json::to_value(edit).ok())
+    }
+
+    async fn formatting(&self, params: DocumentFormattingParams) -> Result<Option<Vec<TextEdit>>> {
+        let _timer = self
+            .timer("textDocument/formatting", &params.text_document.uri)
+            .await;
+        Ok(self.format_document(&params.text_document.uri).await)
+    }
+
+    async fn will_save_wait_until(
+        &self,
+        params: WillSaveTextDocumentParams,
+    ) -> Result<Option<Vec<TextEdit>>> {
+        // Auto-saves while typing would move the text being edited
+        if !*self.format_on_save.lock().await
+            || params.reason == TextDocumentSaveReason::AFTER_DELAY
+        {
+            return Ok(None);
+        }
+        Ok(self.format_document(&params.text_document.uri).await)
+    }
+
     async fn did_close(&self, params: DidCloseTextDocumentParams) {
+        self.unsupported
+            .lock()
+            .await
+            .remove(&params.text_document.uri);
+        self.saved.lock().await.remove(&params.text_document.uri);
+        self.handler
+            .lock()
+            .await
+            .close_document(&params.text_document.uri);
         let mut guard = self.documents.lock().await;
-        guard.remove(&params.text_document.uri);
+        let document = guard.remove(&params.text_document.uri);
+        drop(guard);
         // Clear diagnostics
+        for related in document
+            .map(|document| document.related)
+            .unwrap_or_default()
+        {
+            self.client
+                .publish_diagnostics(related, Vec::new(), None)
+                .await;
+        }
         self.client
             .publish_diagnostics(params.text_document.uri, Vec::new(), None)
             .await;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{handler_error_to_response, Backend, Document, Echo};
+    use crate::config::Config;
+    use crate::handlers::mock::Mock;
+    use crate::handlers::{AnyHandler, HandlerError, HandlerKind};
+    use crate::notebook::DidOpenNotebookDocumentParams;
+    use serde_json::json;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex, OnceLock};
+    use tokio::sync::Notify;
+    use tower_lsp::jsonrpc::ErrorCode;
+    use tower_lsp::lsp_types::{
+        ClientCapabilities, Diagnostic, DidChangeConfigurationParams, DidChangeTextDocumentParams,
+        DidChangeWatchedFilesParams, DidOpenTextDocumentParams, DidSaveTextDocumentParams,
+        DocumentDiagnosticParams, DocumentDiagnosticReport, DocumentDiagnosticReportResult,
+        FileChangeType, FileEvent, HoverParams, InitializeParams, Position, Range,
+        TextDocumentClientCapabilities, TextDocumentContentChangeEvent, TextDocumentIdentifier,
+        TextDocumentItem, TextDocumentPositionParams, TextDocumentSaveReason, TextEdit, Url,
+        VersionedTextDocumentIdentifier, WillSaveTextDocumentParams,
+    };
+    use tower_lsp::{LanguageServer, LspService};
+
+    /// Keeps the messages logged under the `any_ls::metrics` target.
+    struct CapturingLogger {
+        lines: Mutex<Vec<String>>,
+    }
+
+    impl log::Log for CapturingLogger {
+        fn enabled(&self, metadata: &log::Metadata) -> bool {
+            metadata.target() == "any_ls::metrics"
+        }
+
+        fn log(&self, record: &log::Record) {
+            if self.enabled(record.metadata()) {
+                let mut lines = self.lines.lock().unwrap();
+                lines.push(record.args().to_string());
+            }
+        }
+
+        fn flush(&self) {}
+    }
+
+    static LOGGER: CapturingLogger = CapturingLogger {
+        lines: Mutex::new(Vec::new()),
+    };
+
+    /// An open document, not checked yet.
+    fn document(contents: &str, version: i32, filetype: &str) -> Document {
+        Document {
+            contents: contents.to_string(),
+            version,
+            filetype: filetype.to_string(),
+            result_id: None,
+            related: Vec::new(),
+            echo: Echo::None,
+        }
+    }
+
+    /// The request pulling the diagnostics of `url`, with the `result_id` of
+    /// the last ones the client has.
+    fn pull(url: &Url, previous_result_id: Option<String>) -> DocumentDiagnosticParams {
+        DocumentDiagnosticParams {
+            text_document: TextDocumentIdentifier { uri: url.clone() },
+            identifier: None,
+            previous_result_id,
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_handler_error_to_response() {
+        let url = Url::parse("file:///project/main.py").unwrap();
+        let cases = [
+            (
+                HandlerError::Log("exited with 2".to_string()),
+                ErrorCode::ServerError(-32803),
+                "exited with 2",
+            ),
+            (
+                HandlerError::NoSuchDocument(url),
+                ErrorCode::InvalidParams,
+                "Document is not open: file:///project/main.py",
+            ),
+            (
+                HandlerError::ToolNotFound("ruff".to_string()),
+                ErrorCode::ServerError(-32000),
+                "Could not find 'ruff', install it or add it to the PATH of the server",
+            ),
+            (
+                HandlerError::Parse("Invalid ruff output: EOF".to_string()),
+                ErrorCode::InternalError,
+                "Invalid ruff output: EOF",
+            ),
+            (
+                HandlerError::Timeout("ruff".to_string()),
+                ErrorCode::ServerError(-32803),
+                "'ruff' timed out",
+            ),
+            (
+                HandlerError::ToolFailed {
+                    tool: "ruff".to_string(),
+                    message: "Failed to parse main.py:1:5".to_string(),
+                },
+                ErrorCode::ServerError(-32803),
+                "'ruff' failed: Failed to parse main.py:1:5",
+            ),
+        ];
+        for (err, code, message) in cases {
+            let response = handler_error_to_response(err);
+            assert_eq!(response.code, code);
+            assert_eq!(response.message, message);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_will_save_wait_until() {
+        let (service, _) = LspService::new(Backend::new);
+        let backend = service.inner();
+        *backend.handler.lock().await =
+            AnyHandler::from_handlers(vec![HandlerKind::Mock(Box::new(Mock {
+                filetypes: vec!["text"],
+                formatted: Some("a\nb\n".to_string()),
+                ..Default::default()
+            }))]);
+        let url = Url::parse("file:///project/notes.txt").unwrap();
+        backend
+            .documents
+            .lock()
+            .await
+            .insert(url.clone(), document("a\nc\n", 1, "text"));
+        let save = |reason| WillSaveTextDocumentParams {
+            text_document: TextDocumentIdentifier { uri: url.clone() },
+            reason,
+        };
+
+        // Off by default
+        let edits = backend.will_save_wait_until(save(TextDocumentSaveReason::MANUAL));
+        assert_eq!(edits.await.unwrap(), None);
+
+        *backend.format_on_save.lock().await = true;
+        let edits = backend.will_save_wait_until(save(TextDocumentSaveReason::MANUAL));
+        assert_eq!(
+            edits.await.unwrap(),
+            Some(vec![TextEdit::new(
+                Range::new(Position::new(1, 0), Position::new(2, 0)),
+                "b\n".to_string()
+            )])
+        );
+        let edits = backend.will_save_wait_until(save(TextDocumentSaveReason::AFTER_DELAY));
+        assert_eq!(edits.await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_echoed_format_edit() {
+        let (service, _) = LspService::new(Backend::new);
+        let backend = service.inner();
+        let runs = Arc::new(AtomicUsize::new(0));
+        let format_runs = Arc::new(AtomicUsize::new(0));
+        *backend.handler.lock().await =
+            AnyHandler::from_handlers(vec![HandlerKind::Mock(Box::new(Mock {
+                filetypes: vec!["text"],
+                formatted: Some("a\nb\n".to_string()),
+                diagnostics_runs: runs.clone(),
+                format_runs: format_runs.clone(),
+                ..Default::default()
+            }))]);
+        *backend.format_on_save.lock().await = true;
+        let url = Url::parse("file:///project/notes.txt").unwrap();
+        backend
+            .documents
+            .lock()
+            .await
+            .insert(url.clone(), document("a\nc\n", 1, "text"));
+
+        let will_save = || {
+            backend.will_save_wait_until(WillSaveTextDocumentParams {
+                text_document: TextDocumentIdentifier { uri: url.clone() },
+                reason: TextDocumentSaveReason::MANUAL,
+            })
+        };
+        let change = |version, text: &str| DidChangeTextDocumentParams {
+            text_document: VersionedTextDocumentIdentifier {
+                uri: url.clone(),
+                version,
+            },
+            content_changes: vec![TextDocumentContentChangeEvent {
+                range: None,
+                range_length: None,
+                text: text.to_string(),
+            }],
+        };
+        assert!(will_save().await.unwrap().is_some());
+        // The client applies the edits and sends them back
+        backend.did_change(change(2, "a\nb\n")).await;
+        assert_eq!(runs.load(Ordering::SeqCst), 0);
+        // Saving again after applying the edits doesn't format them again
+        assert_eq!(will_save().await.unwrap(), None);
+        assert_eq!(format_runs.load(Ordering::SeqCst), 1);
+
+        backend
+            .did_save(DidSaveTextDocumentParams {
+                text_document: TextDocumentIdentifier { uri: url.clone() },
+                text: None,
+            })
+            .await;
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+        assert_eq!(backend.documents.lock().await[&url].version, 2);
+
+        // Edits of the user are formatted
+        backend.did_change(change(3, "a\nb\nc\n")).await;
+        assert!(will_save().await.unwrap().is_some());
+        assert_eq!(format_runs.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_hover_no_such_document() {
+        let (service, _) = LspService::new(Backend::new);
+        let backend = service.inner();
+        let url = Url::parse("file:///project/notes.txt").unwrap();
+        let hover = |url: &Url| HoverParams {
+            text_document_position_params: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri: url.clone() },
+                position: Position::new(0, 0),
+            },
+            work_done_progress_params: Default::default(),
+        };
+
+        let err = backend.hover(hover(&url)).await.unwrap_err();
+        assert_eq!(err.code, ErrorCode::InvalidParams);
+
+        // Open, but no handler supports it
+        backend.open_document(url.clone(), 1, "text", "").await;
+        assert!(backend.unsupported.lock().await.contains(&url));
+        assert_eq!(backend.hover(hover(&url)).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_stale_pull_diagnostics() {
+        let (service, _) = LspService::new(Backend::new);
+        let backend = service.inner();
+        let blocked = Arc::new(Notify::new());
+        *backend.handler.lock().await =
+            AnyHandler::from_handlers(vec![HandlerKind::Mock(Box::new(Mock {
+                filetypes: vec!["text"],
+                diagnostics: vec![Diagnostic::new_simple(
+                    Range::default(),
+                    "error".to_string(),
+                )],
+                blocked: Some(blocked.clone()),
+                ..Default::default()
+            }))]);
+        let url = Url::parse("file:///project/notes.txt").unwrap();
+        backend
+            .documents
+            .lock()
+            .await
+            .insert(url.clone(), document("a\n", 1, "text"));
+        // The diagnostics of version 1 finish after the edit to version 2
+        let edit = async {
+            backend
+                .did_change(DidChangeTextDocumentParams {
+                    text_document: VersionedTextDocumentIdentifier {
+                        uri: url.clone(),
+                        version: 2,
+                    },
+                    content_changes: vec![TextDocumentContentChangeEvent {
+                        range: None,
+                        range_length: None,
+                        text: "b\n".to_string(),
+                    }],
+                })
+                .await;
+            blocked.notify_one();
+        };
+        let (stale, ()) = tokio::join!(backend.diagnostic(pull(&url, None)), edit);
+        let err = stale.unwrap_err();
+        assert_eq!(err.code, ErrorCode::ServerError(-32802));
+        assert_eq!(err.data, Some(json!({ "retriggerRequest": true })));
+        assert_eq!(backend.documents.lock().await[&url].result_id, None);
+
+        blocked.notify_one();
+        let DocumentDiagnosticReportResult::Report(DocumentDiagnosticReport::Full(current)) =
+            backend.diagnostic(pull(&url, None)).await.unwrap()
+        else {
+            panic!("Expected a full report");
+        };
+        assert_eq!(current.full_document_diagnostic_report.items.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_pull_diagnostics_unchanged() {
+        let (service, _) = LspService::new(Backend::new);
+        let backend = service.inner();
+        *backend.handler.lock().await = AnyHandler::new(&Config::default());
+        let url = Url::parse("file:///project/data.jsonl").unwrap();
+        backend
+            .documents
+            .lock()
+            .await
+            .insert(url.clone(), document("{\"a\": 1}\n{bad\n", 1, "jsonl"));
+        let DocumentDiagnosticReportResult::Report(DocumentDiagnosticReport::Full(first)) =
+            backend.diagnostic(pull(&url, None)).await.unwrap()
+        else {
+            panic!("Expected a full report");
+        };
+        let first = first.full_document_diagnostic_report;
+        assert_eq!(first.items.len(), 1);
+        let result_id = first.result_id.unwrap();
+
+        let second = backend
+            .diagnostic(pull(&url, Some(result_id.clone())))
+            .await;
+        let DocumentDiagnosticReportResult::Report(DocumentDiagnosticReport::Unchanged(second)) =
+            second.unwrap()
+        else {
+            panic!("Expected an unchanged report");
+        };
+        assert_eq!(
+            second.unchanged_document_diagnostic_report.result_id,
+            result_id
+        );
+
+        // Edited since
+        backend
+            .documents
+            .lock()
+            .await
+            .get_mut(&url)
+            .unwrap()
+            .contents
+            .push_str("{}\n");
+        let third = backend
+            .diagnostic(pull(&url, Some(result_id)))
+            .await
+            .unwrap();
+        assert!(matches!(
+            third,
+            DocumentDiagnosticReportResult::Report(DocumentDiagnosticReport::Full(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_pull_diagnostics_after_file_changes() {
+        let (service, _) = LspService::new(Backend::new);
+        let backend = service.inner();
+        *backend.handler.lock().await = AnyHandler::new(&Config::default());
+        let url = Url::parse("file:///project/data.jsonl").unwrap();
+        backend
+            .documents
+            .lock()
+            .await
+            .insert(url.clone(), document("{bad\n", 1, "jsonl"));
+        let diagnostic = |previous_result_id| backend.diagnostic(pull(&url, previous_result_id));
+        let result_id = |report: DocumentDiagnosticReportResult| match report {
+            DocumentDiagnosticReportResult::Report(DocumentDiagnosticReport::Full(report)) => {
+                report.full_document_diagnostic_report.result_id
+            }
+            _ => None,
+        };
+        let changed = |path: &str| DidChangeWatchedFilesParams {
+            changes: vec![FileEvent::new(
+                Url::parse(&format!("file:///project/{path}")).unwrap(),
+                FileChangeType::CHANGED,
+            )],
+        };
+
+        let first = result_id(diagnostic(None).await.unwrap()).unwrap();
+        // Another document saved, which this one may import
+        backend
+            .did_save(DidSaveTextDocumentParams {
+                text_document: TextDocumentIdentifier {
+                    uri: Url::parse("file:///project/other.jsonl").unwrap(),
+                },
+                text: None,
+            })
+            .await;
+        let second = result_id(diagnostic(Some(first)).await.unwrap()).unwrap();
+
+        // Build outputs are ignored
+        backend
+            .did_change_watched_files(changed("target/debug/out"))
+            .await;
+        assert!(matches!(
+            diagnostic(Some(second.clone())).await.unwrap(),
+            DocumentDiagnosticReportResult::Report(DocumentDiagnosticReport::Unchanged(_))
+        ));
+        // So are temporary files, of the server or editors
+        for path in [
+            ".any_lsa1B2c3.just",
+            ".data.jsonl.swp",
+            "data.jsonl~",
+            "%23data.jsonl%23",
+        ] {
+            backend.did_change_watched_files(changed(path)).await;
+            assert!(matches!(
+                diagnostic(Some(second.clone())).await.unwrap(),
+                DocumentDiagnosticReportResult::Report(DocumentDiagnosticReport::Unchanged(_))
+            ));
+        }
+        backend.did_change_watched_files(changed(".env")).await;
+        assert!(result_id(diagnostic(Some(second)).await.unwrap()).is_some());
+    }
+
+    #[tokio::test]
+    async fn test_pull_diagnostics_on_save_only() {
+        let (service, _) = LspService::new(Backend::new);
+        let backend = service.inner();
+        let runs = Arc::new(AtomicUsize::new(0));
+        *backend.handler.lock().await =
+            AnyHandler::from_handlers(vec![HandlerKind::Mock(Box::new(Mock {
+                filetypes: vec!["elixir"],
+                on_save_only: true,
+                diagnostics: vec![Diagnostic::new_simple(
+                    Range::default(),
+                    "issue".to_string(),
+                )],
+                diagnostics_runs: runs.clone(),
+                ..Default::default()
+            }))]);
+        let url = Url::parse("file:///project/lib/app.ex").unwrap();
+        backend.documents.lock().await.insert(
+            url.clone(),
+            document("defmodule App do\nend\n", 1, "elixir"),
+        );
+        let items = || async {
+            let report = backend.diagnostic(pull(&url, None)).await.unwrap();
+            match report {
+                DocumentDiagnosticReportResult::Report(DocumentDiagnosticReport::Full(report)) => {
+                    report.full_document_diagnostic_report.items.len()
+                }
+                _ => panic!("Expected a full report"),
+            }
+        };
+
+        assert_eq!(items().await, 1);
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+        // Unsaved changes keep the diagnostics of the last check
+        backend
+            .did_change(DidChangeTextDocumentParams {
+                text_document: VersionedTextDocumentIdentifier {
+                    uri: url.clone(),
+                    version: 2,
+                },
+                content_changes: vec![TextDocumentContentChangeEvent {
+                    range: None,
+                    range_length: None,
+                    text: "defmodule App do\n  def run, do: :ok\nend\n".to_string(),
+                }],
+            })
+            .await;
+        assert_eq!(items().await, 1);
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+
+        backend
+            .did_save(DidSaveTextDocumentParams {
+                text_document: TextDocumentIdentifier { uri: url.clone() },
+                text: None,
+            })
+            .await;
+        assert_eq!(items().await, 1);
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_notebook_diagnostics() {
+        let (service, _) = LspService::new(Backend::new);
+        let backend = service.inner();
+        let runs = Arc::new(AtomicUsize::new(0));
+        let blocked = Arc::new(Notify::new());
+        *backend.handler.lock().await =
+            AnyHandler::from_handlers(vec![HandlerKind::Mock(Box::new(Mock {
+                filetypes: vec!["python", "sql"],
+                diagnostics_runs: runs.clone(),
+                blocked: Some(blocked.clone()),
+                ..Default::default()
+            }))]);
+        let notebook = "file:///project/analysis.ipynb";
+        let cells = ["python", "sql", "python"];
+        let params: DidOpenNotebookDocumentParams = serde_json::from_value(json!({
+            "notebookDocument": {
+                "uri": notebook,
+                "cells": cells
+                    .iter()
+                    .enumerate()
+                    .map(|(index, _)| json!({ "kind": 2, "document": format!("{notebook}#{index}") }))
+                    .collect::<Vec<_>>(),
+            },
+            "cellTextDocuments": cells
+                .iter()
+                .enumerate()
+                .map(|(index, language)| json!({
+                    "uri": format!("{notebook}#{index}"),
+                    "languageId": language,
+                    "version": 1,
+                    "text": "x = 1\n",
+                }))
+                .collect::<Vec<_>>(),
+        }))
+        .unwrap();
+
+        // Documents can be edited while the cells are checked
+        let other = Url::parse("file:///project/notes.txt").unwrap();
+        let edit = async {
+            backend
+                .did_change(DidChangeTextDocumentParams {
+                    text_document: VersionedTextDocumentIdentifier {
+                        uri: other,
+                        version: 2,
+                    },
+                    content_changes: vec![TextDocumentContentChangeEvent {
+                        range: None,
+                        range_length: None,
+                        text: "edited\n".to_string(),
+                    }],
+                })
+                .await;
+            blocked.notify_one();
+            // Once for the python cells together, once for the sql cell
+            blocked.notify_one();
+        };
+        tokio::join!(backend.did_open_notebook(params), edit);
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+    }
+
+    /// A Mock with an error in justfiles, unless disabled in the settings.
+    fn mock_handler(config: &Config) -> AnyHandler {
+        if config.disabled.iter().any(|name| name == "Mock") {
+            return AnyHandler::default();
+        }
+        let mock = Mock {
+            filetypes: vec!["just"],
+            diagnostics: vec![Diagnostic::new_simple(
+                Range::default(),
+                "Unknown recipe".to_string(),
+            )],
+            ..Default::default()
+        };
+        AnyHandler::from_handlers(vec![HandlerKind::Mock(Box::new(mock))])
+    }
+
+    #[tokio::test]
+    async fn test_did_change_configuration() {
+        let (service, _) = LspService::new(|client| Backend {
+            new_handler: mock_handler,
+            ..Backend::new(client)
+        });
+        let backend = service.inner();
+        *backend.pull_diagnostics.lock().await = true;
+        *backend.handler.lock().await = mock_handler(&Config::default());
+        let url = Url::parse("file:///project/justfile").unwrap();
+        backend
+            .documents
+            .lock()
+            .await
+            .insert(url.clone(), document("build:\n  just test\n", 1, "just"));
+        let diagnostic = |previous_result_id| backend.diagnostic(pull(&url, previous_result_id));
+        let full = |report: DocumentDiagnosticReportResult| match report {
+            DocumentDiagnosticReportResult::Report(DocumentDiagnosticReport::Full(report)) => {
+                let report = report.full_document_diagnostic_report;
+                Some((report.result_id, report.items.len()))
+            }
+            _ => None,
+        };
+        let configure =
+            |settings| backend.did_change_configuration(DidChangeConfigurationParams { settings });
+
+        let (first, items) = full(diagnostic(None).await.unwrap()).unwrap();
+        assert_eq!(items, 1);
+        // The diagnostics of the disabled handler are cleared
+        configure(json!({ "any_ls": { "disabled": ["Mock"] } })).await;
+        let (second, items) = full(diagnostic(first).await.unwrap()).unwrap();
+        assert_eq!(items, 0);
+        // And reported again once it is enabled
+        configure(json!({})).await;
+        let (_, items) = full(diagnostic(second).await.unwrap()).unwrap();
+        assert_eq!(items, 1);
+    }
+
+    #[tokio::test]
+    async fn test_did_change_configuration_supported() {
+        let (service, _) = LspService::new(|client| Backend {
+            new_handler: mock_handler,
+            ..Backend::new(client)
+        });
+        let backend = service.inner();
+        *backend.pull_diagnostics.lock().await = true;
+        let disabled = json!({ "any_ls": { "disabled": ["Mock"] } });
+        *backend.handler.lock().await =
+            mock_handler(&Config::from_value(Some(disabled["any_ls"].clone())).unwrap());
+        let url = Url::parse("file:///project/justfile").unwrap();
+        backend
+            .did_open(DidOpenTextDocumentParams {
+                text_document: TextDocumentItem {
+                    uri: url.clone(),
+                    language_id: "just".to_string(),
+                    version: 1,
+                    text: "build:\n  just test\n".to_string(),
+                },
+            })
+            .await;
+        let items = || async {
+            let report = backend.diagnostic(pull(&url, None)).await.unwrap();
+            match report {
+                DocumentDiagnosticReportResult::Report(DocumentDiagnosticReport::Full(report)) => {
+                    report.full_document_diagnostic_report.items.len()
+                }
+                _ => panic!("Expected a full report"),
+            }
+        };
+        let configure =
+            |settings| backend.did_change_configuration(DidChangeConfigurationParams { settings });
+
+        // Opened without a handler
+        assert!(backend.unsupported.lock().await.contains(&url));
+        assert_eq!(items().await, 0);
+        configure(json!({})).await;
+        assert!(!backend.unsupported.lock().await.contains(&url));
+        assert_eq!(items().await, 1);
+        configure(disabled).await;
+        assert!(backend.unsupported.lock().await.contains(&url));
+        assert_eq!(items().await, 0);
+    }
+
+    /// Times the Mock of `notebook_handler` computed diagnostics.
+    static NOTEBOOK_RUNS: OnceLock<Arc<AtomicUsize>> = OnceLock::new();
+
+    /// A Mock checking python and sql, counting its runs.
+    fn notebook_handler(_config: &Config) -> AnyHandler {
+        let mock = Mock {
+            filetypes: vec!["python", "sql"],
+            diagnostics_runs: NOTEBOOK_RUNS.get_or_init(Default::default).clone(),
+            ..Default::default()
+        };
+        AnyHandler::from_handlers(vec![HandlerKind::Mock(Box::new(mock))])
+    }
+
+    #[tokio::test]
+    async fn test_did_change_configuration_notebook() {
+        let (service, _) = LspService::new(|client| Backend {
+            new_handler: notebook_handler,
+            ..Backend::new(client)
+        });
+        let backend = service.inner();
+        *backend.handler.lock().await = notebook_handler(&Config::default());
+        let notebook = "file:///project/analysis.ipynb";
+        let cells = ["python", "sql", "python"];
+        let params: DidOpenNotebookDocumentParams = serde_json::from_value(json!({
+            "notebookDocument": {
+                "uri": notebook,
+                "cells": cells
+                    .iter()
+                    .enumerate()
+                    .map(|(index, _)| json!({ "kind": 2, "document": format!("{notebook}#{index}") }))
+                    .collect::<Vec<_>>(),
+            },
+            "cellTextDocuments": cells
+                .iter()
+                .enumerate()
+                .map(|(index, language)| json!({
+                    "uri": format!("{notebook}#{index}"),
+                    "languageId": language,
+                    "version": 1,
+                    "text": "x = 1\n",
+                }))
+                .collect::<Vec<_>>(),
+        }))
+        .unwrap();
+        backend.did_open_notebook(params).await;
+        let runs = NOTEBOOK_RUNS.get().unwrap();
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+
+        // The cells are checked together again, not one by one
+        backend
+            .did_change_configuration(DidChangeConfigurationParams {
+                settings: json!({}),
+            })
+            .await;
+        assert_eq!(runs.load(Ordering::SeqCst), 4);
+    }
+
+    #[tokio::test]
+    async fn test_diagnostic_timing_logged() {
+        log::set_logger(&LOGGER).unwrap();
+        log::set_max_level(log::LevelFilter::Debug);
+
+        let (service, _) = LspService::new(Backend::new);
+        let backend = service.inner();
+        *backend.handler.lock().await = AnyHandler::new(&Config::default());
+        let url = Url::parse("file:///project/data.jsonl").unwrap();
+        backend
+            .documents
+            .lock()
+            .await
+            .insert(url.clone(), document("{\"a\": 1}\n", 1, "jsonl"));
+        backend.diagnostic(pull(&url, None)).await.unwrap();
+
+        let lines = LOGGER.lines.lock().unwrap();
+        assert!(
+            lines
+                .iter()
+                .any(|line| line.starts_with("textDocument/diagnostic took ")
+                    && line.ends_with("ms for jsonl (9B)")),
+            "{lines:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_capabilities_report() {
+        let (service, _) = LspService::new(Backend::new);
+        let backend = service.inner();
+        let params = InitializeParams {
+            capabilities: ClientCapabilities {
+                text_document: Some(TextDocumentClientCapabilities {
+                    diagnostic: Some(Default::default()),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let initialized = backend.initialize(params).await.unwrap();
+
+        let report = backend.capabilities().await.unwrap();
+        assert_eq!(report.capabilities, initialized.capabilities);
+        let source = |field| report.sources.get(field).map(String::as_str);
+        // Diagnostics are pulled from the server, which runs the handlers
+        assert_eq!(source("diagnosticProvider"), Some("any_ls"));
+        assert_eq!(source("linkedEditingRangeProvider"), Some("Just"));
+        assert_eq!(source("referencesProvider"), None);
+    }
+}
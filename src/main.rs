@@ -1,12 +1,18 @@
 use std::process::ExitCode;
 
-use any_ls::Backend;
+use any_ls::{notebook, Backend};
 use flexi_logger::FileSpec;
 use tower_lsp::{LspService, Server};
 
 #[tokio::main]
 async fn main() -> ExitCode {
-    let _ = flexi_logger::Logger::try_with_env_or_str("debug")
+    let mut args = std::env::args().skip(1).peekable();
+    if args.peek().is_some_and(|arg| arg == "--check") {
+        return any_ls::check::run(args.skip(1)).await;
+    }
+
+    // Request durations are only logged when asked for, e.g. `RUST_LOG=debug`
+    let _ = flexi_logger::Logger::try_with_env_or_str("debug, any_ls::metrics=info")
         .expect("Could not create logger")
         .log_to_file(
             FileSpec::default()
@@ -20,7 +26,15 @@ async fn main() -> ExitCode {
     let stdin = tokio::io::stdin();
     let stdout = tokio::io::stdout();
 
-    let (service, socket) = LspService::new(Backend::new);
+    let (service, socket) = LspService::build(Backend::new)
+        .custom_method("notebookDocument/didOpen", Backend::did_open_notebook)
+        .custom_method("notebookDocument/didChange", Backend::did_change_notebook)
+        .custom_method("notebookDocument/didClose", Backend::did_close_notebook)
+        .custom_method("any_ls/capabilities", Backend::capabilities)
+        .finish();
+    let service = tower::ServiceBuilder::new()
+        .map_response(notebook::advertise_sync)
+        .service(service);
     Server::new(stdin, stdout, socket).serve(service).await;
     ExitCode::SUCCESS
 }
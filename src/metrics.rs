@@ -0,0 +1,129 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// The handled requests of one method.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+struct MethodStats {
+    count: u32,
+    total: Duration,
+    max: Duration,
+}
+
+/// Durations of handled requests by method, logged at debug level under the
+/// `any_ls::metrics` target so they are only written when asked for.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    methods: Mutex<BTreeMap<&'static str, MethodStats>>,
+}
+
+/// A size in bytes, e.g. `512B` or `1.2KB`.
+fn format_size(bytes: usize) -> String {
+    const KB: f64 = 1024.0;
+    let bytes_f = bytes as f64;
+    if bytes_f < KB {
+        format!("{bytes}B")
+    } else if bytes_f < KB * KB {
+        format!("{:.1}KB", bytes_f / KB)
+    } else {
+        format!("{:.1}MB", bytes_f / (KB * KB))
+    }
+}
+
+impl Metrics {
+    /// Starts timing a request of `method` for a document of `filetype` with
+    /// `size` bytes. The request is recorded when the timer is dropped.
+    pub fn start(&self, method: &'static str, filetype: &str, size: usize) -> RequestTimer<'_> {
+        RequestTimer {
+            metrics: self,
+            method,
+            filetype: filetype.to_string(),
+            size,
+            start: Instant::now(),
+        }
+    }
+
+    fn record(&self, method: &'static str, filetype: &str, size: usize, elapsed: Duration) {
+        log::debug!(
+            "{method} took {}ms for {filetype} ({})",
+            elapsed.as_millis(),
+            format_size(size)
+        );
+        let mut methods = self.methods.lock().expect("Lock is not poisoned");
+        let stats = methods.entry(method).or_default();
+        stats.count += 1;
+        stats.total += elapsed;
+        stats.max = stats.max.max(elapsed);
+    }
+
+    /// A line per method with requests, e.g.
+    /// `textDocument/hover: 3 requests, 4ms average, 9ms max`.
+    pub fn summary(&self) -> Vec<String> {
+        let methods = self.methods.lock().expect("Lock is not poisoned");
+        methods
+            .iter()
+            .map(|(method, stats)| {
+                format!(
+                    "{method}: {} requests, {}ms average, {}ms max",
+                    stats.count,
+                    (stats.total / stats.count).as_millis(),
+                    stats.max.as_millis()
+                )
+            })
+            .collect()
+    }
+
+    /// Logs the summary of the requests handled so far.
+    pub fn log_summary(&self) {
+        for line in self.summary() {
+            log::debug!("{line}");
+        }
+    }
+}
+
+/// Records a request in `Metrics` when dropped.
+#[derive(Debug)]
+pub struct RequestTimer<'a> {
+    metrics: &'a Metrics,
+    method: &'static str,
+    filetype: String,
+    size: usize,
+    start: Instant,
+}
+
+impl Drop for RequestTimer<'_> {
+    fn drop(&mut self) {
+        self.metrics
+            .record(self.method, &self.filetype, self.size, self.start.elapsed());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_size, Metrics};
+    use std::time::Duration;
+
+    #[test]
+    fn test_summary() {
+        let metrics = Metrics::default();
+        metrics.record("textDocument/hover", "just", 10, Duration::from_millis(4));
+        metrics.record("textDocument/hover", "just", 10, Duration::from_millis(8));
+        metrics.record(
+            "textDocument/diagnostic",
+            "sh",
+            10,
+            Duration::from_millis(30),
+        );
+        assert_eq!(
+            metrics.summary(),
+            vec![
+                "textDocument/diagnostic: 1 requests, 30ms average, 30ms max",
+                "textDocument/hover: 2 requests, 6ms average, 8ms max",
+            ]
+        );
+
+        assert_eq!(format_size(512), "512B");
+        assert_eq!(format_size(1229), "1.2KB");
+        assert_eq!(format_size(3 * 1024 * 1024), "3.0MB");
+    }
+}
@@ -0,0 +1,183 @@
+//! Notebook document sync, e.g. Jupyter notebooks. `lsp-types` 0.94 predates
+//! notebooks, so the protocol types needed are defined here and the
+//! notifications are registered as custom methods.
+//!
+//! Code cells of a language are checked as one document, so that names
+//! defined in earlier cells are known, and diagnostics are moved back to the
+//! cell they belong to.
+
+use serde::Deserialize;
+use serde_json::json;
+use tower_lsp::jsonrpc::Response;
+use tower_lsp::lsp_types::{
+    Diagnostic, TextDocumentContentChangeEvent, TextDocumentIdentifier, TextDocumentItem, Url,
+    VersionedTextDocumentIdentifier,
+};
+
+pub const CODE_CELL: u8 = 2;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NotebookCell {
+    /// 1 for markup, 2 for code.
+    pub kind: u8,
+    pub document: Url,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NotebookDocument {
+    pub uri: Url,
+    pub cells: Vec<NotebookCell>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DidOpenNotebookDocumentParams {
+    pub notebook_document: NotebookDocument,
+    pub cell_text_documents: Vec<TextDocumentItem>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NotebookDocumentIdentifier {
+    pub uri: Url,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DidChangeNotebookDocumentParams {
+    pub notebook_document: NotebookDocumentIdentifier,
+    pub change: NotebookDocumentChangeEvent,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NotebookDocumentChangeEvent {
+    pub cells: Option<NotebookCellsChange>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NotebookCellsChange {
+    pub structure: Option<NotebookCellsStructure>,
+    /// Cells whose kind changed.
+    pub data: Option<Vec<NotebookCell>>,
+    pub text_content: Option<Vec<NotebookCellTextChange>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NotebookCellsStructure {
+    pub array: NotebookCellArrayChange,
+    pub did_open: Option<Vec<TextDocumentItem>>,
+    pub did_close: Option<Vec<TextDocumentIdentifier>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NotebookCellArrayChange {
+    pub start: usize,
+    pub delete_count: usize,
+    pub cells: Option<Vec<NotebookCell>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NotebookCellTextChange {
+    pub document: VersionedTextDocumentIdentifier,
+    pub changes: Vec<TextDocumentContentChangeEvent>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DidCloseNotebookDocumentParams {
+    pub notebook_document: NotebookDocumentIdentifier,
+    pub cell_text_documents: Vec<TextDocumentIdentifier>,
+}
+
+/// Adds `notebookDocumentSync` to the `initialize` result, which
+/// `ServerCapabilities` of `lsp-types` 0.94 can't express. Other responses
+/// are returned unchanged.
+pub fn advertise_sync(response: Option<Response>) -> Option<Response> {
+    let (id, result) = response?.into_parts();
+    let result = result.map(|mut result| {
+        if result["serverInfo"]["name"] == "any_ls" {
+            result["capabilities"]["notebookDocumentSync"] = json!({
+                "notebookSelector": [{ "notebook": { "notebookType": "jupyter-notebook" } }],
+            });
+        }
+        result
+    });
+    Some(Response::from_parts(id, result))
+}
+
+/// Cells joined into one document, and the line each cell starts at.
+pub fn concat<'a>(cells: impl IntoIterator<Item = &'a str>) -> (String, Vec<u32>) {
+    let mut source = String::new();
+    let mut starts = Vec::new();
+    let mut line = 0;
+    for cell in cells {
+        starts.push(line);
+        source.push_str(cell);
+        if !cell.ends_with('\n') {
+            source.push('\n');
+        }
+        line += cell.matches('\n').count() as u32 + u32::from(!cell.ends_with('\n'));
+    }
+    (source, starts)
+}
+
+/// Diagnostics of the document from `concat`, moved to the cell they start
+/// in with positions relative to it.
+pub fn split(starts: &[u32], diagnostics: Vec<Diagnostic>) -> Vec<Vec<Diagnostic>> {
+    let mut cells = vec![Vec::new(); starts.len()];
+    for mut diagnostic in diagnostics {
+        let line = diagnostic.range.start.line;
+        let Some(cell) = starts
+            .partition_point(|start| *start <= line)
+            .checked_sub(1)
+        else {
+            continue;
+        };
+        diagnostic.range.start.line -= starts[cell];
+        diagnostic.range.end.line = diagnostic.range.end.line.saturating_sub(starts[cell]);
+        cells[cell].push(diagnostic);
+    }
+    cells
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{advertise_sync, concat, split};
+    use crate::config::Config;
+    use crate::handlers::{AnyHandler, DocumentContext};
+    use serde_json::json;
+    use tower_lsp::jsonrpc::{Id, Response};
+    use tower_lsp::lsp_types::Url;
+
+    #[tokio::test]
+    async fn test_cell_diagnostics() {
+        let cells = ["{\"a\": 1}\n{\"b\": 2}", "{\"c\": 3}\n{bad\n"];
+        let (source, starts) = concat(cells);
+        assert_eq!(starts, vec![0, 2]);
+
+        let uri = Url::parse("file:///project/data.ipynb").unwrap();
+        let context = DocumentContext::new(uri, Vec::new());
+        let diagnostics = AnyHandler::new(&Config::default())
+            .update_diagnostics("jsonl", &context, &source)
+            .await
+            .ok()
+            .unwrap();
+        let cells = split(&starts, diagnostics);
+        assert!(cells[0].is_empty());
+        assert_eq!(cells[1].len(), 1);
+        assert_eq!(cells[1][0].range.start.line, 1);
+    }
+
+    #[test]
+    fn test_advertise_sync() {
+        let result = json!({ "capabilities": {}, "serverInfo": { "name": "any_ls" } });
+        let response = advertise_sync(Some(Response::from_ok(Id::Number(0), result))).unwrap();
+        let (_, result) = response.into_parts();
+        assert!(result.unwrap()["capabilities"]["notebookDocumentSync"].is_object());
+
+        assert!(advertise_sync(None).is_none());
+    }
+}
@@ -0,0 +1,129 @@
+use serde_json::{json, Value};
+use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity, NumberOrString};
+
+use crate::check::FileDiagnostics;
+
+fn level(severity: Option<DiagnosticSeverity>) -> &'static str {
+    match severity {
+        Some(DiagnosticSeverity::ERROR) => "error",
+        Some(DiagnosticSeverity::INFORMATION | DiagnosticSeverity::HINT) => "note",
+        _ => "warning",
+    }
+}
+
+fn result(path: &str, diagnostic: &Diagnostic) -> Value {
+    let range = diagnostic.range;
+    let mut result = json!({
+        "level": level(diagnostic.severity),
+        "message": { "text": diagnostic.message },
+        "locations": [{
+            "physicalLocation": {
+                "artifactLocation": { "uri": path },
+                // SARIF positions are 1-based
+                "region": {
+                    "startLine": range.start.line + 1,
+                    "startColumn": range.start.character + 1,
+                    "endLine": range.end.line + 1,
+                    "endColumn": range.end.character + 1,
+                },
+            },
+        }],
+    });
+    if let Some(code) = &diagnostic.code {
+        result["ruleId"] = match code {
+            NumberOrString::Number(code) => json!(code.to_string()),
+            NumberOrString::String(code) => json!(code),
+        };
+    }
+    result
+}
+
+/// A notification of a handler failing to check the file at `path`.
+fn notification(path: &str, error: &str) -> Value {
+    json!({
+        "level": "error",
+        "message": { "text": error },
+        "locations": [{
+            "physicalLocation": {
+                "artifactLocation": { "uri": path },
+            },
+        }],
+    })
+}
+
+/// A SARIF 2.1.0 log with one result per diagnostic, and a notification per
+/// handler error.
+pub fn report(files: &[FileDiagnostics]) -> Value {
+    let results: Vec<Value> = files
+        .iter()
+        .flat_map(|file| {
+            let path = file.path.to_string_lossy().replace('\\', "/");
+            file.diagnostics
+                .iter()
+                .map(move |diagnostic| result(&path, diagnostic))
+        })
+        .collect();
+    let notifications: Vec<Value> = files
+        .iter()
+        .flat_map(|file| {
+            let path = file.path.to_string_lossy().replace('\\', "/");
+            file.errors
+                .iter()
+                .map(move |error| notification(&path, error))
+        })
+        .collect();
+
+    json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "any_ls",
+                    "version": env!("CARGO_PKG_VERSION"),
+                },
+            },
+            // Same columns as LSP positions
+            "columnKind": "utf16CodeUnits",
+            "results": results,
+            "invocations": [{
+                "executionSuccessful": notifications.is_empty(),
+                "toolExecutionNotifications": notifications,
+            }],
+        }],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::report;
+    use crate::check::FileDiagnostics;
+    use std::path::PathBuf;
+    use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity, NumberOrString, Position, Range};
+
+    #[test]
+    fn test_report() {
+        let files = vec![FileDiagnostics {
+            path: PathBuf::from("sub/justfile"),
+            diagnostics: vec![Diagnostic {
+                range: Range::new(Position::new(6, 12), Position::new(6, 13)),
+                severity: Some(DiagnosticSeverity::HINT),
+                code: Some(NumberOrString::Number(2086)),
+                message: "Unknown start of token:".to_string(),
+                ..Default::default()
+            }],
+            errors: Vec::new(),
+        }];
+
+        let report = report(&files);
+        assert_eq!(report["version"], "2.1.0");
+        let result = &report["runs"][0]["results"][0];
+        assert_eq!(result["ruleId"], "2086");
+        assert_eq!(result["level"], "note");
+        assert_eq!(result["message"]["text"], "Unknown start of token:");
+        let location = &result["locations"][0]["physicalLocation"];
+        assert_eq!(location["artifactLocation"]["uri"], "sub/justfile");
+        assert_eq!(location["region"]["startLine"], 7);
+        assert_eq!(location["region"]["startColumn"], 13);
+    }
+}